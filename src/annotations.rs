@@ -0,0 +1,394 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use lopdf::{Dictionary, Document, Object};
+use pdfium_render::prelude::*;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::utils;
+
+/// Annotation types that mark up text through quad points
+const TEXT_MARKUP_TYPES: [&str; 4] = ["Highlight", "Underline", "StrikeOut", "Squiggly"];
+
+/// Custom error type for annotation export
+#[derive(Debug)]
+pub enum AnnotationError {
+    Io(io::Error),
+    Pdf(String),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for AnnotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnnotationError::Io(e) => write!(f, "I/O error: {}", e),
+            AnnotationError::Pdf(e) => write!(f, "PDF error: {}", e),
+            AnnotationError::Csv(e) => write!(f, "CSV error: {}", e),
+            AnnotationError::Json(e) => write!(f, "JSON error: {}", e),
+            AnnotationError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for AnnotationError {}
+
+impl From<io::Error> for AnnotationError {
+    fn from(err: io::Error) -> Self {
+        AnnotationError::Io(err)
+    }
+}
+
+impl From<csv::Error> for AnnotationError {
+    fn from(err: csv::Error) -> Self {
+        AnnotationError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for AnnotationError {
+    fn from(err: serde_json::Error) -> Self {
+        AnnotationError::Json(err)
+    }
+}
+
+/// Output formats supported by the annotations command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum AnnotationFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+/// One annotation of a PDF page
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AnnotationRecord {
+    pub path: String,
+    pub page: usize,
+    pub annotation_type: String,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    /// `[left, bottom, right, top]` in PDF points
+    pub rect: [f32; 4],
+    pub subject: Option<String>,
+    pub contents: Option<String>,
+    /// Page text under the quad points of highlight/underline/strike-out annotations
+    pub highlighted_text: Option<String>,
+    #[serde(skip)]
+    quad_points: Vec<[f32; 8]>,
+}
+
+const CSV_HEADER: [&str; 12] = [
+    "path", "page", "type", "author", "date", "left", "bottom", "right", "top",
+    "subject", "contents", "highlighted_text",
+];
+
+impl AnnotationRecord {
+    /// Flatten the record into CSV columns
+    fn to_row(&self) -> Vec<String> {
+        vec![
+            self.path.clone(),
+            self.page.to_string(),
+            self.annotation_type.clone(),
+            self.author.clone().unwrap_or_default(),
+            self.date.clone().unwrap_or_default(),
+            format!("{:.1}", self.rect[0]),
+            format!("{:.1}", self.rect[1]),
+            format!("{:.1}", self.rect[2]),
+            format!("{:.1}", self.rect[3]),
+            self.subject.clone().unwrap_or_default(),
+            self.contents.clone().unwrap_or_default(),
+            self.highlighted_text.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Main function to run the annotation export functionality
+pub fn run(input_paths: &[String], format: AnnotationFormat, output_file: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(AnnotationError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    // Create progress bar
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Reading annotations");
+
+    // Read annotation dictionaries in parallel
+    let mut per_document: Vec<Vec<AnnotationRecord>> = pdf_paths
+        .par_iter()
+        .map(|path| {
+            let records = match Document::load(path) {
+                Ok(doc) => read_annotations(&doc, &path.to_string_lossy()),
+                Err(e) => {
+                    eprintln!("Error loading {}: {}", path.display(), e);
+                    Vec::new()
+                }
+            };
+            pb.inc(1);
+            records
+        })
+        .collect();
+
+    pb.finish_with_message("Done");
+
+    // Highlighted text needs character positions from Pdfium, one document at a time
+    let has_markup = per_document.iter().flatten().any(|r| !r.quad_points.is_empty());
+    if has_markup {
+        match utils::bind_pdfium() {
+            Ok(pdfium) => {
+                for (path, records) in pdf_paths.iter().zip(per_document.iter_mut()) {
+                    if let Err(e) = fill_highlighted_text(&pdfium, path, records) {
+                        eprintln!("Error reading highlighted text of {}: {}", path.display(), e);
+                    }
+                }
+            },
+            Err(e) => eprintln!("Warning: {}; highlighted text skipped", e),
+        }
+    }
+
+    let records: Vec<AnnotationRecord> = per_document.into_iter().flatten().collect();
+
+    match format {
+        AnnotationFormat::Json => utils::write_output(&serde_json::to_string_pretty(&records)?, output_file)?,
+        AnnotationFormat::Csv => write_csv(&records, output_file)?,
+        AnnotationFormat::Markdown => utils::write_output(&to_markdown(&records), output_file)?,
+    }
+
+    if let Some(output_file) = output_file {
+        println!("Successfully wrote {} annotations from {} PDFs to '{}'", records.len(), pdf_paths.len(), output_file);
+    }
+    Ok(())
+}
+
+/// Read the annotations of every page. Popup annotations are skipped: they only
+/// display the contents of their parent annotation.
+pub fn read_annotations(doc: &Document, path: &str) -> Vec<AnnotationRecord> {
+    let mut records = Vec::new();
+
+    for (page_number, page_id) in doc.get_pages() {
+        for annotation in utils::page_annotations(doc, page_id) {
+            let annotation_type = annotation.get(b"Subtype")
+                .and_then(Object::as_name_str)
+                .unwrap_or("Unknown")
+                .to_string();
+            if annotation_type == "Popup" {
+                continue;
+            }
+
+            let date = utils::dict_string(doc, annotation, b"M")
+                .or_else(|| utils::dict_string(doc, annotation, b"CreationDate"))
+                .map(|d| utils::format_pdf_date(&d));
+
+            let quad_points = if TEXT_MARKUP_TYPES.contains(&annotation_type.as_str()) {
+                quad_points(doc, annotation)
+            } else {
+                Vec::new()
+            };
+
+            records.push(AnnotationRecord {
+                path: path.to_string(),
+                page: page_number as usize,
+                author: utils::dict_string(doc, annotation, b"T"),
+                date,
+                rect: number_array(doc, annotation, b"Rect")
+                    .and_then(|r| <[f32; 4]>::try_from(r).ok())
+                    .unwrap_or_default(),
+                subject: utils::dict_string(doc, annotation, b"Subj"),
+                contents: utils::dict_string(doc, annotation, b"Contents")
+                    .filter(|c| !c.trim().is_empty()),
+                annotation_type,
+                highlighted_text: None,
+                quad_points,
+            });
+        }
+    }
+
+    records
+}
+
+/// All comment text of a PDF's annotations (contents and subjects), for searching
+pub fn annotation_text(path: &Path) -> String {
+    let doc = match Document::load(path) {
+        Ok(doc) => doc,
+        Err(_) => return String::new(),
+    };
+
+    read_annotations(&doc, &path.to_string_lossy())
+        .into_iter()
+        .flat_map(|record| [record.subject, record.contents])
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Read a numeric array such as /Rect or /QuadPoints
+fn number_array(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<Vec<f32>> {
+    let (_, array) = doc.dereference(dict.get(key).ok()?).ok()?;
+    array.as_array().ok()?
+        .iter()
+        .map(|value| doc.dereference(value).ok().and_then(|(_, v)| v.as_float().ok()))
+        .collect()
+}
+
+/// Split /QuadPoints into quadrilaterals of four (x, y) points
+fn quad_points(doc: &Document, annotation: &Dictionary) -> Vec<[f32; 8]> {
+    number_array(doc, annotation, b"QuadPoints")
+        .map(|values| {
+            values.chunks_exact(8)
+                .filter_map(|quad| <[f32; 8]>::try_from(quad).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Look up the page text under the quad points of text markup annotations
+fn fill_highlighted_text(pdfium: &Pdfium, path: &Path, records: &mut [AnnotationRecord]) -> Result<(), AnnotationError> {
+    let document = pdfium.load_pdf_from_file(path, None)
+        .map_err(|e| AnnotationError::Pdf(format!("Error loading {}: {}", path.display(), e)))?;
+
+    for record in records.iter_mut().filter(|r| !r.quad_points.is_empty()) {
+        let page = u16::try_from(record.page.saturating_sub(1)).ok()
+            .and_then(|index| document.pages().get(index).ok());
+        let text = match page.as_ref().and_then(|page| page.text().ok()) {
+            Some(text) => text,
+            None => continue,
+        };
+
+        let parts: Vec<String> = record.quad_points.iter()
+            .map(|quad| {
+                let xs = [quad[0], quad[2], quad[4], quad[6]];
+                let ys = [quad[1], quad[3], quad[5], quad[7]];
+                let left = xs.iter().copied().fold(f32::MAX, f32::min);
+                let right = xs.iter().copied().fold(f32::MIN, f32::max);
+                let bottom = ys.iter().copied().fold(f32::MAX, f32::min);
+                let top = ys.iter().copied().fold(f32::MIN, f32::max);
+
+                // Shrink vertically so glyphs of the neighbouring lines are not picked up
+                let inset = (top - bottom) * 0.2;
+                text.inside_rect(PdfRect::new_from_values(bottom + inset, left, top - inset, right))
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|part| !part.is_empty())
+            .collect();
+
+        if !parts.is_empty() {
+            record.highlighted_text = Some(parts.join(" "));
+        }
+    }
+    Ok(())
+}
+
+/// Render annotations as Markdown, grouped by document
+fn to_markdown(records: &[AnnotationRecord]) -> String {
+    let mut markdown = String::from("# Annotations\n");
+    let mut current_path: Option<&str> = None;
+
+    for record in records {
+        if current_path != Some(record.path.as_str()) {
+            markdown.push_str(&format!("\n## {}\n\n", record.path));
+            current_path = Some(record.path.as_str());
+        }
+
+        let mut heading = format!("- **Page {}** · {}", record.page, record.annotation_type);
+        if let Some(author) = &record.author {
+            heading.push_str(&format!(" · {}", author));
+        }
+        if let Some(date) = &record.date {
+            heading.push_str(&format!(" · {}", date));
+        }
+        markdown.push_str(&heading);
+        markdown.push('\n');
+
+        if let Some(highlighted) = &record.highlighted_text {
+            markdown.push_str(&format!("  > {}\n", highlighted));
+        }
+        if let Some(subject) = &record.subject {
+            markdown.push_str(&format!("  *{}*\n", subject));
+        }
+        if let Some(contents) = &record.contents {
+            for line in contents.lines() {
+                markdown.push_str(&format!("  {}\n", line));
+            }
+        }
+    }
+
+    markdown
+}
+
+/// Write annotations as CSV to a file or stdout
+fn write_csv(records: &[AnnotationRecord], output_file: Option<&str>) -> Result<(), AnnotationError> {
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    csv_writer.write_record(CSV_HEADER)?;
+    for record in records {
+        csv_writer.write_record(record.to_row())?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn annotated_document() -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let highlight = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Highlight",
+            "Rect" => vec![10.into(), 20.into(), 110.into(), 32.into()],
+            "QuadPoints" => vec![10, 32, 110, 32, 10, 20, 110, 20].into_iter().map(Object::from).collect::<Vec<_>>(),
+            "T" => Object::string_literal("Reviewer"),
+            "M" => Object::string_literal("D:20240301120000Z"),
+            "Contents" => Object::string_literal("Check this figure"),
+        };
+        let popup = dictionary! { "Type" => "Annot", "Subtype" => "Popup" };
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Annots" => vec![Object::Dictionary(highlight), Object::Dictionary(popup)],
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1,
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    #[test]
+    fn reads_markup_annotations_without_popups() {
+        let records = read_annotations(&annotated_document(), "doc.pdf");
+        assert_eq!(records.len(), 1);
+
+        let record = &records[0];
+        assert_eq!(record.page, 1);
+        assert_eq!(record.annotation_type, "Highlight");
+        assert_eq!(record.author.as_deref(), Some("Reviewer"));
+        assert_eq!(record.date.as_deref(), Some("2024-03-01 12:00:00Z"));
+        assert_eq!(record.rect, [10.0, 20.0, 110.0, 32.0]);
+        assert_eq!(record.quad_points.len(), 1);
+    }
+
+    #[test]
+    fn renders_markdown_per_document() {
+        let mut records = read_annotations(&annotated_document(), "doc.pdf");
+        records[0].highlighted_text = Some("Figure 3".to_string());
+        assert_eq!(
+            to_markdown(&records),
+            "# Annotations\n\n## doc.pdf\n\n- **Page 1** · Highlight · Reviewer · 2024-03-01 12:00:00Z\n  > Figure 3\n  Check this figure\n"
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use lopdf::{Dictionary, Document, Object};
+use serde::Serialize;
+
+use crate::utils;
+
+/// Separator between a PDF and the attachments nested in it, e.g. `invoice.pdf::data.xml`
+pub const ATTACHMENT_SEPARATOR: &str = "::";
+
+/// Maximum nesting depth when recursing into attached PDFs
+const MAX_ATTACHMENT_DEPTH: usize = 4;
+
+/// Custom error type for attachment operations
+#[derive(Debug)]
+pub enum AttachmentError {
+    Io(io::Error),
+    Pdf(String),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for AttachmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttachmentError::Io(e) => write!(f, "I/O error: {}", e),
+            AttachmentError::Pdf(e) => write!(f, "PDF error: {}", e),
+            AttachmentError::Json(e) => write!(f, "JSON error: {}", e),
+            AttachmentError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for AttachmentError {}
+
+impl From<io::Error> for AttachmentError {
+    fn from(err: io::Error) -> Self {
+        AttachmentError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for AttachmentError {
+    fn from(err: serde_json::Error) -> Self {
+        AttachmentError::Json(err)
+    }
+}
+
+/// An embedded file found in a PDF
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    /// Containing PDF, with nested PDFs joined by `::`
+    pub container: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+    pub size: usize,
+    /// Where the file is referenced: `embedded-files`, `portfolio` or `annotation (page N)`
+    pub source: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    /// Full name of the attachment, e.g. `vendor.pdf::invoice.xml`
+    pub fn qualified_name(&self) -> String {
+        format!("{}{}{}", self.container, ATTACHMENT_SEPARATOR, self.name)
+    }
+
+    /// Check whether the attachment is itself a PDF
+    pub fn is_pdf(&self) -> bool {
+        self.data.starts_with(b"%PDF") || self.name.to_lowercase().ends_with(".pdf")
+    }
+}
+
+/// Main function to list or extract the attachments of PDF files
+pub fn run(input_paths: &[String], extract_dir: Option<&str>, recursive: bool, json: bool) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(AttachmentError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    let mut attachments = Vec::new();
+    for path in &pdf_paths {
+        match fs::read(path) {
+            Ok(bytes) => {
+                let depth = if recursive { MAX_ATTACHMENT_DEPTH } else { 1 };
+                match collect_nested(&bytes, &path.to_string_lossy(), depth) {
+                    Ok(mut found) => attachments.append(&mut found),
+                    Err(e) => eprintln!("Error processing {}: {}", path.display(), e),
+                }
+            },
+            Err(e) => eprintln!("Error reading {}: {}", path.display(), e),
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&attachments)?);
+    } else {
+        for attachment in &attachments {
+            println!(
+                "{}\t{} bytes\t{}\t{}{}",
+                attachment.qualified_name(),
+                attachment.size,
+                attachment.mime_type.as_deref().unwrap_or("-"),
+                attachment.source,
+                attachment.description.as_ref().map(|d| format!("\t{}", d)).unwrap_or_default()
+            );
+        }
+        println!("\nFound {} attachments in {} PDFs", attachments.len(), pdf_paths.len());
+    }
+
+    if let Some(extract_dir) = extract_dir {
+        let written = write_attachments(&attachments, Path::new(extract_dir))?;
+        println!("Successfully extracted {} attachments to '{}'", written, extract_dir);
+    }
+    Ok(())
+}
+
+/// Collect the attachments of a PDF and, up to `depth` levels, of the PDFs attached to it
+pub fn collect_nested(bytes: &[u8], container: &str, depth: usize) -> Result<Vec<Attachment>, AttachmentError> {
+    let doc = Document::load_mem(bytes)
+        .map_err(|e| AttachmentError::Pdf(format!("Error loading {}: {}", container, e)))?;
+
+    let mut all = Vec::new();
+    for attachment in collect_attachments(&doc, container) {
+        let nested = if depth > 1 && attachment.is_pdf() {
+            collect_nested(&attachment.data, &attachment.qualified_name(), depth - 1)
+                .unwrap_or_else(|e| {
+                    eprintln!("Error processing {}: {}", attachment.qualified_name(), e);
+                    Vec::new()
+                })
+        } else {
+            Vec::new()
+        };
+        all.push(attachment);
+        all.extend(nested);
+    }
+    Ok(all)
+}
+
+/// Text of the PDFs attached to a PDF (recursively), labelled with their qualified names.
+/// Used by search and analysis to look inside attachments.
+pub fn attached_pdf_texts(path: &Path) -> Vec<(String, String)> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Vec::new(),
+    };
+    let attachments = match collect_nested(&bytes, &path.to_string_lossy(), MAX_ATTACHMENT_DEPTH) {
+        Ok(attachments) => attachments,
+        Err(_) => return Vec::new(),
+    };
+
+    attachments.into_iter()
+        .filter(Attachment::is_pdf)
+        .filter_map(|attachment| {
+            // pdf-extract panics on some malformed files
+            match panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem(&attachment.data))) {
+                Ok(Ok(text)) => Some((attachment.qualified_name(), text)),
+                Ok(Err(e)) => {
+                    eprintln!("Error extracting text from {}: {}", attachment.qualified_name(), e);
+                    None
+                },
+                Err(_) => {
+                    eprintln!("Warning: Text extraction crashed on {}; skipped", attachment.qualified_name());
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+/// The file on disk that holds a possibly nested document name
+pub fn container_path(name: &str) -> &str {
+    name.split(ATTACHMENT_SEPARATOR).next().unwrap_or(name)
+}
+
+/// Collect the embedded files of a document: the /EmbeddedFiles name tree (which also holds
+/// portfolio members) and file attachment annotations
+pub fn collect_attachments(doc: &Document, container: &str) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+
+    if let Ok(catalog) = doc.catalog() {
+        // Portfolios (collections) keep their members in the same name tree
+        let source = if catalog.has(b"Collection") { "portfolio" } else { "embedded-files" };
+
+        let tree = catalog.get(b"Names").ok()
+            .and_then(|names| dereference_dict(doc, names))
+            .and_then(|names| names.get(b"EmbeddedFiles").ok())
+            .and_then(|tree| dereference_dict(doc, tree));
+
+        if let Some(tree) = tree {
+            let mut filespecs = Vec::new();
+            walk_name_tree(doc, tree, &mut filespecs, &mut HashSet::new());
+            for (key, filespec) in filespecs {
+                if let Some(attachment) = filespec_attachment(doc, filespec, &key, container, source) {
+                    attachments.push(attachment);
+                }
+            }
+        }
+    }
+
+    for (page_number, page_id) in doc.get_pages() {
+        for annotation in utils::page_annotations(doc, page_id) {
+            if annotation.get(b"Subtype").and_then(Object::as_name_str).ok() != Some("FileAttachment") {
+                continue;
+            }
+            let filespec = annotation.get(b"FS").ok().and_then(|fs| dereference_dict(doc, fs));
+            if let Some(filespec) = filespec {
+                let source = format!("annotation (page {})", page_number);
+                if let Some(attachment) = filespec_attachment(doc, filespec, "attachment", container, &source) {
+                    attachments.push(attachment);
+                }
+            }
+        }
+    }
+
+    attachments
+}
+
+/// Collect the (key, file specification) pairs of a name tree
+fn walk_name_tree<'a>(
+    doc: &'a Document,
+    node: &'a Dictionary,
+    entries: &mut Vec<(String, &'a Dictionary)>,
+    visited: &mut HashSet<*const Dictionary>,
+) {
+    // Guard against cyclic trees
+    if !visited.insert(node as *const Dictionary) {
+        return;
+    }
+
+    if let Ok(Object::Array(names)) = node.get(b"Names").and_then(|n| doc.dereference(n)).map(|(_, n)| n) {
+        for pair in names.chunks(2) {
+            if let [key, value] = pair {
+                let key = match key {
+                    Object::String(bytes, _) => utils::decode_pdf_string(bytes),
+                    _ => continue,
+                };
+                if let Some(filespec) = dereference_dict(doc, value) {
+                    entries.push((key, filespec));
+                }
+            }
+        }
+    }
+
+    if let Ok(Object::Array(kids)) = node.get(b"Kids").and_then(|k| doc.dereference(k)).map(|(_, k)| k) {
+        for kid in kids {
+            if let Some(kid) = dereference_dict(doc, kid) {
+                walk_name_tree(doc, kid, entries, visited);
+            }
+        }
+    }
+}
+
+/// Read the embedded file stream of a file specification
+fn filespec_attachment(doc: &Document, filespec: &Dictionary, key: &str, container: &str, source: &str) -> Option<Attachment> {
+    let embedded = filespec.get(b"EF").ok().and_then(|ef| dereference_dict(doc, ef))?;
+    let stream = embedded.get(b"UF").or_else(|_| embedded.get(b"F")).ok()
+        .and_then(|s| doc.dereference(s).ok())
+        .and_then(|(_, s)| s.as_stream().ok())?;
+
+    let data = if stream.filters().map(|f| f.is_empty()).unwrap_or(true) {
+        stream.content.clone()
+    } else {
+        match stream.decompressed_content() {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Error decoding attachment '{}' in {}: {}", key, container, e);
+                return None;
+            }
+        }
+    };
+
+    let name = utils::dict_string(doc, filespec, b"UF")
+        .or_else(|| utils::dict_string(doc, filespec, b"F"))
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| key.to_string());
+
+    Some(Attachment {
+        container: container.to_string(),
+        name,
+        description: utils::dict_string(doc, filespec, b"Desc"),
+        mime_type: stream.dict.get(b"Subtype").and_then(Object::as_name_str).ok().map(str::to_string),
+        size: data.len(),
+        source: source.to_string(),
+        data,
+    })
+}
+
+fn dereference_dict<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    doc.dereference(object).ok().and_then(|(_, o)| o.as_dict().ok())
+}
+
+/// Write attachments below `output_dir`, one directory per containing PDF.
+/// Returns the number of files written.
+fn write_attachments(attachments: &[Attachment], output_dir: &Path) -> Result<usize, AttachmentError> {
+    let mut written = HashSet::new();
+
+    for attachment in attachments {
+        // Nested containers become nested directories
+        let mut dir = output_dir.to_path_buf();
+        for part in attachment.container.split(ATTACHMENT_SEPARATOR) {
+            dir.push(safe_file_name(Path::new(part).file_stem().map(|s| s.to_string_lossy()).as_deref().unwrap_or("document")));
+        }
+        fs::create_dir_all(&dir)?;
+
+        let file_name = safe_file_name(&attachment.name);
+        let mut target = dir.join(&file_name);
+        let mut counter = 1;
+        while written.contains(&target) {
+            counter += 1;
+            target = dir.join(format!("{}_{}", counter, file_name));
+        }
+
+        fs::write(&target, &attachment.data)?;
+        written.insert(target);
+    }
+    Ok(written.len())
+}
+
+/// Reduce an attachment name to a plain file name that cannot escape the output directory
+fn safe_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base.chars()
+        .map(|c| if c.is_control() || c == ':' { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if cleaned.is_empty() {
+        "attachment".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    /// A one-page PDF with the given files in its /EmbeddedFiles name tree
+    fn pdf_with_attachments(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1,
+        }));
+
+        let mut names = Vec::new();
+        for (name, data) in files {
+            let stream_id = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, data.to_vec()));
+            let filespec_id = doc.add_object(dictionary! {
+                "Type" => "Filespec",
+                "F" => Object::string_literal(*name),
+                "EF" => dictionary! { "F" => stream_id },
+            });
+            names.push(Object::string_literal(*name));
+            names.push(filespec_id.into());
+        }
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Names" => dictionary! { "EmbeddedFiles" => dictionary! { "Names" => names } },
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn collects_nested_attachments() {
+        let inner = pdf_with_attachments(&[("invoice.xml", b"<invoice/>")]);
+        let outer = pdf_with_attachments(&[("notes.txt", b"hello"), ("vendor.pdf", &inner)]);
+
+        let names: Vec<String> = collect_nested(&outer, "outer.pdf", MAX_ATTACHMENT_DEPTH).unwrap()
+            .iter().map(Attachment::qualified_name).collect();
+        assert_eq!(names, vec![
+            "outer.pdf::notes.txt",
+            "outer.pdf::vendor.pdf",
+            "outer.pdf::vendor.pdf::invoice.xml",
+        ]);
+        assert_eq!(collect_nested(&outer, "outer.pdf", 1).unwrap().len(), 2);
+        assert_eq!(container_path("outer.pdf::vendor.pdf::invoice.xml"), "outer.pdf");
+    }
+
+    #[test]
+    fn keeps_attachment_names_inside_the_output() {
+        assert_eq!(safe_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(safe_file_name("C:\\temp\\report.xlsx"), "report.xlsx");
+        assert_eq!(safe_file_name(".."), "attachment");
+        assert_eq!(safe_file_name("a:b\u{7}.txt"), "a_b_.txt");
+    }
+}
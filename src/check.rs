@@ -0,0 +1,750 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use clap::ValueEnum;
+use flate2::read::ZlibDecoder;
+use indicatif::ProgressBar;
+use lopdf::xref::XrefEntry;
+use lopdf::{Document, Object, ObjectId};
+use pdfium_render::prelude::*;
+use regex::bytes::Regex;
+use serde::Serialize;
+
+use crate::utils;
+
+/// How far from the start of the file the `%PDF-` header may appear
+const HEADER_SEARCH_WINDOW: usize = 1024;
+
+/// How far from the end of the file the `%%EOF` marker must appear
+const TRAILER_SEARCH_WINDOW: usize = 1024;
+
+/// Largest object number a PDF may use
+const MAX_OBJECT_NUMBER: u32 = 8_388_607;
+
+/// Custom error type for check operations
+#[derive(Debug)]
+pub enum CheckError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::Io(e) => write!(f, "I/O error: {}", e),
+            CheckError::Csv(e) => write!(f, "CSV error: {}", e),
+            CheckError::Json(e) => write!(f, "JSON error: {}", e),
+            CheckError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for CheckError {}
+
+impl From<io::Error> for CheckError {
+    fn from(err: io::Error) -> Self {
+        CheckError::Io(err)
+    }
+}
+
+impl From<csv::Error> for CheckError {
+    fn from(err: csv::Error) -> Self {
+        CheckError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for CheckError {
+    fn from(err: serde_json::Error) -> Self {
+        CheckError::Json(err)
+    }
+}
+
+/// Output formats supported by the check command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CheckFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+/// Severity of a problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Worth knowing, nothing is lost (e.g. encryption)
+    Info,
+    /// A spec violation readers usually work around
+    Warning,
+    /// Part of the document cannot be read
+    Error,
+    /// The document cannot be opened at all
+    Fatal,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Fatal => "fatal",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One problem found in a file
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    pub severity: Severity,
+    /// `header`, `trailer`, `xref`, `object`, `reference`, `stream`, `structure`, `pdfium`, `text` or `encryption`
+    pub code: String,
+    pub message: String,
+}
+
+/// The check result of one file
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    /// `ok` or the highest severity of its problems
+    pub status: String,
+    pub problems: Vec<Problem>,
+    /// Where the repaired copy was written
+    pub repaired: Option<String>,
+    /// Problems the repaired copy still has
+    pub remaining_problems: Option<usize>,
+}
+
+/// Machine-readable check report
+#[derive(Debug, Serialize)]
+struct CheckReport {
+    generated: String,
+    files_checked: usize,
+    files_by_status: BTreeMap<String, usize>,
+    files: Vec<FileReport>,
+}
+
+/// Main function of the check command
+pub fn run(
+    input_paths: &[String],
+    format: CheckFormat,
+    output_file: Option<&str>,
+    repair_dir: Option<&str>,
+    fail_on: Option<Severity>,
+) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(CheckError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    // Pdfium is a second opinion: files it opens are readable by most viewers
+    let pdfium = match utils::bind_pdfium() {
+        Ok(pdfium) => Some(pdfium),
+        Err(e) => {
+            eprintln!("Warning: {}; only lopdf checks are run", e);
+            None
+        },
+    };
+
+    if let Some(repair_dir) = repair_dir {
+        fs::create_dir_all(repair_dir)?;
+    }
+
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Checking PDFs");
+
+    // Repaired copies of same-named inputs get numbered names instead of overwriting each other
+    let stems = utils::unique_stems(&pdf_paths);
+    let mut files = Vec::new();
+    for (path, stem) in pdf_paths.iter().zip(&stems) {
+        let mut report = check_file(path, pdfium.as_ref());
+        if let Some(repair_dir) = repair_dir {
+            if report.problems.iter().any(|p| p.severity >= Severity::Warning) {
+                let output = Path::new(repair_dir).join(format!("{}.pdf", stem));
+                repair_and_recheck(path, &output, pdfium.as_ref(), &mut report);
+            }
+        }
+        files.push(report);
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("Done");
+
+    let mut files_by_status = BTreeMap::new();
+    for file in &files {
+        *files_by_status.entry(file.status.clone()).or_insert(0) += 1;
+    }
+    let failing = fail_on
+        .map(|threshold| files.iter().filter(|f| f.problems.iter().any(|p| p.severity >= threshold)).count())
+        .unwrap_or(0);
+
+    let report = CheckReport {
+        generated: chrono::Local::now().to_rfc3339(),
+        files_checked: files.len(),
+        files_by_status,
+        files,
+    };
+
+    match format {
+        CheckFormat::Text => utils::write_output(&to_text(&report), output_file)?,
+        CheckFormat::Json => utils::write_output(&serde_json::to_string_pretty(&report)?, output_file)?,
+        CheckFormat::Csv => write_csv(&report.files, output_file)?,
+    }
+
+    if let Some(output_file) = output_file {
+        println!("Successfully wrote check results of {} PDFs to '{}'", report.files_checked, output_file);
+    }
+
+    if failing > 0 {
+        return Err(Box::new(CheckError::Other(format!(
+            "{} files with problems at or above {} severity", failing, fail_on.unwrap_or(Severity::Info)
+        ))));
+    }
+    Ok(())
+}
+
+/// Run all checks on one file
+pub fn check_file(path: &Path, pdfium: Option<&Pdfium>) -> FileReport {
+    let mut problems = Vec::new();
+    match fs::read(path) {
+        Ok(bytes) => check_bytes(&bytes, pdfium, &mut problems),
+        Err(e) => problems.push(problem(Severity::Fatal, "file", format!("cannot be read: {}", e))),
+    }
+
+    let status = problems.iter()
+        .map(|p| p.severity)
+        .filter(|severity| *severity >= Severity::Warning)
+        .max()
+        .map(|severity| severity.to_string())
+        .unwrap_or_else(|| "ok".to_string());
+
+    FileReport {
+        path: path.to_string_lossy().into_owned(),
+        status,
+        problems,
+        repaired: None,
+        remaining_problems: None,
+    }
+}
+
+fn problem(severity: Severity, code: &str, message: String) -> Problem {
+    Problem { severity, code: code.to_string(), message }
+}
+
+fn check_bytes(bytes: &[u8], pdfium: Option<&Pdfium>, problems: &mut Vec<Problem>) {
+    if bytes.is_empty() {
+        problems.push(problem(Severity::Fatal, "file", "the file is empty".to_string()));
+        return;
+    }
+    check_header(bytes, problems);
+    check_trailer(bytes, problems);
+
+    let lopdf_result = Document::load_mem(bytes);
+    let lopdf_pages = match &lopdf_result {
+        Ok(doc) => {
+            check_structure(doc, problems);
+            Some(doc.get_pages().len())
+        },
+        Err(e) => {
+            problems.push(problem(Severity::Error, "structure", format!("lopdf cannot parse the file: {}", e)));
+            None
+        },
+    };
+    let encrypted = lopdf_result.as_ref().map(|doc| doc.is_encrypted()).unwrap_or(false);
+
+    // Pdfium repairs many broken files on the fly, so a failure there means viewers fail too
+    let pdfium_opened = match pdfium {
+        Some(pdfium) => match pdfium.load_pdf_from_byte_slice(bytes, None) {
+            Ok(document) => {
+                let pages = document.pages().len() as usize;
+                if let Some(lopdf_pages) = lopdf_pages.filter(|count| *count != pages) {
+                    problems.push(problem(Severity::Warning, "pdfium", format!(
+                        "Pdfium sees {} pages, lopdf {}", pages, lopdf_pages
+                    )));
+                }
+                Some(true)
+            },
+            Err(PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError)) => {
+                problems.push(problem(Severity::Info, "encryption", "a password is needed to open the file".to_string()));
+                Some(true)
+            },
+            Err(e) => {
+                problems.push(problem(Severity::Error, "pdfium", format!("Pdfium cannot open the file: {}", e)));
+                Some(false)
+            },
+        },
+        None => None,
+    };
+
+    // Text extraction goes through lopdf as well
+    if lopdf_pages.is_none() {
+        if pdfium_opened == Some(false) {
+            problems.push(problem(Severity::Fatal, "structure", "the file cannot be opened".to_string()));
+        }
+        return;
+    }
+
+    // The text extraction used by search, extract and analyze
+    if !encrypted {
+        let extracted = panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem(bytes)));
+        match extracted {
+            Ok(Ok(_)) => {},
+            Ok(Err(e)) => problems.push(problem(Severity::Warning, "text", format!("text extraction fails: {}", e))),
+            Err(_) => problems.push(problem(Severity::Warning, "text", "text extraction crashes".to_string())),
+        }
+    }
+}
+
+/// The file must start with `%PDF-x.y`
+fn check_header(bytes: &[u8], problems: &mut Vec<Problem>) {
+    let window = &bytes[..bytes.len().min(HEADER_SEARCH_WINDOW)];
+    match find(window, b"%PDF-") {
+        Some(0) => {
+            let version = &bytes[5..bytes.len().min(8)];
+            let valid = version.len() == 3 && version[0].is_ascii_digit() && version[1] == b'.' && version[2].is_ascii_digit();
+            if !valid {
+                problems.push(problem(Severity::Warning, "header", format!(
+                    "invalid version in header: {}", String::from_utf8_lossy(version)
+                )));
+            }
+        },
+        Some(offset) => problems.push(problem(Severity::Warning, "header", format!(
+            "{} bytes of data before the %PDF- header", offset
+        ))),
+        None => problems.push(problem(Severity::Error, "header", "no %PDF- header: this is not a PDF file".to_string())),
+    }
+}
+
+/// The file must end with `startxref`, the offset of the last cross-reference section and `%%EOF`
+fn check_trailer(bytes: &[u8], problems: &mut Vec<Problem>) {
+    let tail_start = bytes.len().saturating_sub(TRAILER_SEARCH_WINDOW);
+    if find(&bytes[tail_start..], b"%%EOF").is_none() {
+        let message = if find(bytes, b"%%EOF").is_some() {
+            "data after the last %%EOF marker"
+        } else {
+            "no %%EOF marker: the file is probably truncated"
+        };
+        let severity = if message.starts_with("data") { Severity::Warning } else { Severity::Error };
+        problems.push(problem(severity, "trailer", message.to_string()));
+    }
+
+    let startxref = match rfind(bytes, b"startxref") {
+        Some(position) => position,
+        None => {
+            problems.push(problem(Severity::Error, "xref", "no startxref entry".to_string()));
+            return;
+        },
+    };
+    let offset: Option<usize> = String::from_utf8_lossy(&bytes[startxref + 9..bytes.len().min(startxref + 40)])
+        .split_whitespace()
+        .next()
+        .and_then(|number| number.parse().ok());
+    let offset = match offset {
+        Some(offset) => offset,
+        None => {
+            problems.push(problem(Severity::Error, "xref", "startxref has no valid offset".to_string()));
+            return;
+        },
+    };
+    if offset >= bytes.len() {
+        problems.push(problem(Severity::Error, "xref", format!(
+            "startxref points to offset {}, past the end of the {}-byte file", offset, bytes.len()
+        )));
+        return;
+    }
+
+    // Either a classic `xref` table or an indirect cross-reference stream object
+    let section = &bytes[offset..bytes.len().min(offset + 64)];
+    let object_start = Regex::new(r"^\s*\d+\s+\d+\s+obj").unwrap();
+    if !section.starts_with(b"xref") && !object_start.is_match(section) {
+        problems.push(problem(Severity::Error, "xref", format!(
+            "startxref points to offset {}, which holds no cross-reference section", offset
+        )));
+    }
+}
+
+/// Objects lopdf could not read, references to missing objects, undecodable streams and the page tree
+fn check_structure(doc: &Document, problems: &mut Vec<Problem>) {
+    if doc.is_encrypted() {
+        problems.push(problem(Severity::Info, "encryption", "the file is encrypted".to_string()));
+    }
+
+    let loaded: BTreeSet<u32> = doc.objects.keys().map(|(id, _)| *id).collect();
+    let unreadable: Vec<ObjectId> = doc.reference_table.entries.iter()
+        .filter_map(|(&id, entry)| match entry {
+            XrefEntry::Normal { generation, .. } => Some((id, *generation)),
+            XrefEntry::Compressed { .. } => Some((id, 0)),
+            _ => None,
+        })
+        .filter(|(id, _)| !loaded.contains(id))
+        .collect();
+    if !unreadable.is_empty() {
+        problems.push(problem(Severity::Error, "object", format!(
+            "{} objects of the cross-reference table cannot be read ({}); the table may point to wrong offsets",
+            unreadable.len(), examples(unreadable.iter().map(|(n, g)| format!("{} {}", n, g)))
+        )));
+    }
+
+    let mut missing = BTreeSet::new();
+    for object in doc.objects.values() {
+        collect_missing_references(doc, object, &mut missing);
+    }
+    if !missing.is_empty() {
+        problems.push(problem(Severity::Warning, "reference", format!(
+            "{} references to missing objects ({})",
+            missing.len(), examples(missing.iter().map(|(n, g)| format!("{} {} R", n, g)))
+        )));
+    }
+
+    // lopdf passes over corrupt Flate data, so the first filter is decoded here
+    let mut broken_streams = Vec::new();
+    for (id, object) in &doc.objects {
+        if let Object::Stream(stream) = object {
+            let flate = stream.filters().ok()
+                .and_then(|filters| filters.first().cloned())
+                .map(|filter| filter == "FlateDecode")
+                .unwrap_or(false);
+            if flate && ZlibDecoder::new(stream.content.as_slice()).read_to_end(&mut Vec::new()).is_err() {
+                broken_streams.push(*id);
+            }
+        }
+    }
+    if !broken_streams.is_empty() {
+        problems.push(problem(Severity::Error, "stream", format!(
+            "{} streams cannot be decoded ({})",
+            broken_streams.len(), examples(broken_streams.iter().map(|(n, g)| format!("object {} {}", n, g)))
+        )));
+    }
+
+    match doc.catalog() {
+        Ok(_) => {
+            if doc.get_pages().is_empty() {
+                problems.push(problem(Severity::Error, "structure", "the page tree has no pages".to_string()));
+            }
+        },
+        Err(_) => problems.push(problem(Severity::Error, "structure", "the trailer has no document catalog".to_string())),
+    }
+}
+
+fn collect_missing_references(doc: &Document, object: &Object, missing: &mut BTreeSet<ObjectId>) {
+    match object {
+        Object::Reference(id) if !doc.objects.contains_key(id) => {
+            missing.insert(*id);
+        },
+        Object::Array(array) => array.iter().for_each(|item| collect_missing_references(doc, item, missing)),
+        Object::Dictionary(dict) => dict.iter().for_each(|(_, value)| collect_missing_references(doc, value, missing)),
+        Object::Stream(stream) => stream.dict.iter().for_each(|(_, value)| collect_missing_references(doc, value, missing)),
+        _ => {},
+    }
+}
+
+/// The first few items of a list, for messages
+fn examples(items: impl Iterator<Item = String>) -> String {
+    let items: Vec<String> = items.collect();
+    let mut text = items.iter().take(5).cloned().collect::<Vec<_>>().join(", ");
+    if items.len() > 5 {
+        text.push_str(", ...");
+    }
+    text
+}
+
+/// Write a repaired copy to `output` and check it again
+fn repair_and_recheck(path: &Path, output: &Path, pdfium: Option<&Pdfium>, report: &mut FileReport) {
+    if fs::canonicalize(output).ok() == fs::canonicalize(path).ok() {
+        eprintln!("Warning: Not repairing {} over itself", path.display());
+        return;
+    }
+    match repair(path, output) {
+        Ok(()) => {
+            let recheck = check_file(output, pdfium);
+            report.remaining_problems = Some(recheck.problems.iter().filter(|p| p.severity >= Severity::Warning).count());
+            report.repaired = Some(output.to_string_lossy().into_owned());
+        },
+        Err(e) => eprintln!("Error repairing {}: {}", path.display(), e),
+    }
+}
+
+/// Re-serialize a file with lopdf: a fresh cross-reference table, correct stream lengths and
+/// offsets, and references to missing objects dropped. Files whose cross-reference data is
+/// unusable are loaded from a table rebuilt by scanning for objects.
+pub fn repair(input: &Path, output: &Path) -> Result<(), CheckError> {
+    let bytes = fs::read(input)?;
+    let original = Document::load_mem(&bytes).ok()
+        .filter(|doc| doc.catalog().is_ok() && !doc.get_pages().is_empty());
+    let rebuilt = rebuild_xref(&bytes).and_then(|rebuilt| Document::load_mem(&rebuilt).ok())
+        .filter(|doc| doc.catalog().is_ok() && !doc.get_pages().is_empty());
+
+    // Prefer whichever version recovers more objects
+    let mut doc = match (original, rebuilt) {
+        (Some(original), Some(rebuilt)) => {
+            if rebuilt.objects.len() > original.objects.len() { rebuilt } else { original }
+        },
+        (Some(doc), None) | (None, Some(doc)) => doc,
+        (None, None) => return Err(CheckError::Other("no readable page tree could be recovered".to_string())),
+    };
+    if doc.is_encrypted() {
+        return Err(CheckError::Other("encrypted files are not repaired".to_string()));
+    }
+
+    drop_missing_references(&mut doc);
+    doc.prune_objects();
+    doc.save(output)?;
+    Ok(())
+}
+
+/// Remove dictionary entries that point to missing objects, and turn such array items into null
+fn drop_missing_references(doc: &mut Document) {
+    fn clean(object: &mut Object, existing: &BTreeSet<ObjectId>) {
+        match object {
+            Object::Array(array) => {
+                for item in array.iter_mut() {
+                    if matches!(item, Object::Reference(id) if !existing.contains(id)) {
+                        *item = Object::Null;
+                    } else {
+                        clean(item, existing);
+                    }
+                }
+            },
+            Object::Dictionary(dict) => clean_dict(dict, existing),
+            Object::Stream(stream) => clean_dict(&mut stream.dict, existing),
+            _ => {},
+        }
+    }
+
+    fn clean_dict(dict: &mut lopdf::Dictionary, existing: &BTreeSet<ObjectId>) {
+        let dangling: Vec<Vec<u8>> = dict.iter()
+            .filter(|(_, value)| matches!(value, Object::Reference(id) if !existing.contains(id)))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in dangling {
+            dict.remove(&key);
+        }
+        for (_, value) in dict.iter_mut() {
+            clean(value, existing);
+        }
+    }
+
+    let existing: BTreeSet<ObjectId> = doc.objects.keys().copied().collect();
+    for object in doc.objects.values_mut() {
+        clean(object, &existing);
+    }
+    clean_dict(&mut doc.trailer, &existing);
+}
+
+/// Rebuild the cross-reference table and trailer of a damaged file by scanning it for
+/// `N G obj` markers; later definitions win, as with incremental updates
+fn rebuild_xref(bytes: &[u8]) -> Option<Vec<u8>> {
+    if find(bytes, b"/Encrypt").is_some() {
+        return None;
+    }
+
+    // Drop anything before the header, or add one
+    let mut buffer = match find(&bytes[..bytes.len().min(HEADER_SEARCH_WINDOW)], b"%PDF-") {
+        Some(offset) => bytes[offset..].to_vec(),
+        None => {
+            let mut buffer = b"%PDF-1.7\n".to_vec();
+            buffer.extend_from_slice(bytes);
+            buffer
+        },
+    };
+
+    let object_start = Regex::new(r"(?:^|[\r\n\s])(\d{1,10})\s+(\d{1,5})\s+obj\b").unwrap();
+    let mut offsets: BTreeMap<u32, (usize, u16)> = BTreeMap::new();
+    for captures in object_start.captures_iter(&buffer) {
+        let number = captures.get(1).unwrap();
+        let id = std::str::from_utf8(number.as_bytes()).ok()?.parse::<u32>().ok();
+        let generation = std::str::from_utf8(&captures[2]).ok()?.parse::<u16>().ok();
+        if let (Some(id), Some(generation)) = (id, generation) {
+            if (1..=MAX_OBJECT_NUMBER).contains(&id) {
+                offsets.insert(id, (number.start(), generation));
+            }
+        }
+    }
+
+    // The catalog is the object declaring /Type /Catalog that comes last in the file
+    let catalog_type = Regex::new(r"/Type\s*/Catalog\b").unwrap();
+    let object_text = |offset: usize| {
+        let rest = &buffer[offset..];
+        let end = find(rest, b"endobj").unwrap_or(rest.len().min(4096));
+        &rest[..end]
+    };
+    let root = offsets.iter()
+        .filter(|(_, (offset, _))| catalog_type.is_match(object_text(*offset)))
+        .max_by_key(|(_, (offset, _))| *offset)
+        .map(|(id, (_, generation))| (*id, *generation))?;
+    let info_reference = Regex::new(r"/Info\s+(\d+)\s+(\d+)\s+R").unwrap();
+    let info = info_reference.captures_iter(&buffer)
+        .last()
+        .map(|c| format!(" /Info {} {} R", String::from_utf8_lossy(&c[1]), String::from_utf8_lossy(&c[2])));
+
+    // One subsection per run of consecutive object numbers, so gaps cost nothing
+    let size = offsets.keys().last().copied().unwrap_or(0) + 1;
+    let mut table = "\nxref\n0 1\n0000000000 65535 f \n".to_string();
+    let ids: Vec<u32> = offsets.keys().copied().collect();
+    for run in ids.chunk_by(|a, b| a + 1 == *b) {
+        table.push_str(&format!("{} {}\n", run[0], run.len()));
+        for id in run {
+            let (offset, generation) = offsets[id];
+            table.push_str(&format!("{:010} {:05} n \n", offset, generation));
+        }
+    }
+    let xref_start = buffer.len() + 1;
+    table.push_str(&format!(
+        "trailer\n<< /Size {} /Root {} {} R{} >>\nstartxref\n{}\n%%EOF\n",
+        size, root.0, root.1, info.unwrap_or_default(), xref_start
+    ));
+    buffer.extend_from_slice(table.as_bytes());
+    Some(buffer)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
+
+/// Render the report as an indented list of files and their problems
+fn to_text(report: &CheckReport) -> String {
+    let mut text = String::new();
+    for file in &report.files {
+        text.push_str(&format!("{}: {}\n", file.path, file.status));
+        for problem in &file.problems {
+            text.push_str(&format!("  [{}] {}: {}\n", problem.severity, problem.code, problem.message));
+        }
+        if let Some(repaired) = &file.repaired {
+            text.push_str(&format!(
+                "  repaired copy: {} ({} problems left)\n", repaired, file.remaining_problems.unwrap_or(0)
+            ));
+        }
+    }
+    let summary: Vec<String> = report.files_by_status.iter().map(|(status, count)| format!("{} {}", count, status)).collect();
+    text.push_str(&format!("\nChecked {} PDFs: {}", report.files_checked, summary.join(", ")));
+    text
+}
+
+/// Write one row per problem (files without problems get a row with status `ok`)
+fn write_csv(files: &[FileReport], output_file: Option<&str>) -> Result<(), CheckError> {
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    csv_writer.write_record(["path", "status", "severity", "code", "message", "repaired"])?;
+    for file in files {
+        let repaired = file.repaired.clone().unwrap_or_default();
+        if file.problems.is_empty() {
+            csv_writer.write_record([file.path.as_str(), &file.status, "", "", "", &repaired])?;
+        }
+        for problem in &file.problems {
+            csv_writer.write_record([
+                file.path.as_str(), &file.status, &problem.severity.to_string(), &problem.code, &problem.message, &repaired,
+            ])?;
+        }
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// A valid one-page PDF as bytes
+    fn valid_pdf() -> Vec<u8> {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let content_id = doc.add_object(lopdf::Stream::new(dictionary! {}, Vec::new()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {},
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1,
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn codes(problems: &[Problem]) -> Vec<&str> {
+        problems.iter().map(|p| p.code.as_str()).collect()
+    }
+
+    #[test]
+    fn accepts_a_valid_file() {
+        let mut problems = Vec::new();
+        check_bytes(&valid_pdf(), None, &mut problems);
+        assert!(problems.is_empty(), "{:?}", codes(&problems));
+    }
+
+    #[test]
+    fn reports_header_problems() {
+        let mut problems = Vec::new();
+        check_header(b"junk%PDF-1.7\n", &mut problems);
+        check_header(b"%PDF-x\n", &mut problems);
+        check_header(b"hello", &mut problems);
+        let severities: Vec<Severity> = problems.iter().map(|p| p.severity).collect();
+        assert_eq!(severities, vec![Severity::Warning, Severity::Warning, Severity::Error]);
+    }
+
+    #[test]
+    fn reports_truncated_files_and_wrong_xref_offsets() {
+        let bytes = valid_pdf();
+        let mut problems = Vec::new();
+        check_trailer(&bytes[..bytes.len() - 20], &mut problems);
+        assert_eq!(codes(&problems), vec!["trailer", "xref"]);
+
+        let startxref = rfind(&bytes, b"startxref").unwrap();
+        let mut shifted = bytes[..startxref].to_vec();
+        shifted.extend_from_slice(b"startxref\n12\n%%EOF\n");
+        let mut problems = Vec::new();
+        check_trailer(&shifted, &mut problems);
+        assert_eq!(codes(&problems), vec!["xref"]);
+        assert!(problems[0].message.contains("no cross-reference section"));
+    }
+
+    #[test]
+    fn rebuilds_the_cross_reference_table() {
+        let bytes = valid_pdf();
+        let xref = rfind(&bytes, b"xref\n").unwrap();
+        let mut broken = b"garbage\n".to_vec();
+        broken.extend_from_slice(&bytes[..xref]);
+        broken.extend_from_slice(b"%%EOF\n");
+
+        let rebuilt = rebuild_xref(&broken).unwrap();
+        assert!(rebuilt.starts_with(b"%PDF-"));
+        let doc = Document::load_mem(&rebuilt).unwrap();
+        assert_eq!(doc.get_pages().len(), 1);
+    }
+
+    #[test]
+    fn rebuilt_table_skips_gaps_and_impossible_object_numbers() {
+        let bytes = valid_pdf();
+        let xref = rfind(&bytes, b"xref\n").unwrap();
+        let mut broken = bytes[..xref].to_vec();
+        broken.extend_from_slice(b"\n9000 0 obj\n(spare)\nendobj\n4000000000 0 obj\n(stray)\nendobj\n%%EOF\n");
+
+        let rebuilt = rebuild_xref(&broken).unwrap();
+        let table = String::from_utf8_lossy(&rebuilt[rfind(&rebuilt, b"\nxref\n").unwrap()..]).into_owned();
+        assert!(table.contains("\n9000 1\n"));
+        assert!(table.contains("/Size 9001 "));
+        assert!(!table.contains("4000000000"));
+        assert!(table.lines().count() < 20);
+        let doc = Document::load_mem(&rebuilt).unwrap();
+        assert_eq!(doc.get_pages().len(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use indicatif::ProgressBar;
+use pdfium_render::prelude::*;
+
+use crate::utils;
+
+/// Minimum number of non-whitespace characters for a page or document to count as having text
+pub const MIN_TEXT_CHARS: usize = 16;
+
+/// Custom error type for text coverage operations
+#[derive(Debug)]
+pub enum CoverageError {
+    PdfError(String),
+}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoverageError::PdfError(e) => write!(f, "PDF error: {}", e),
+        }
+    }
+}
+
+impl Error for CoverageError {}
+
+/// Classification of a single page by the content it carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageKind {
+    /// Page has a text layer and no images
+    Text,
+    /// Page has images but no usable text layer (typically a scan)
+    ImageOnly,
+    /// Page has both a text layer and images
+    Mixed,
+    /// Page has neither text nor images
+    Empty,
+}
+
+impl fmt::Display for PageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PageKind::Text => write!(f, "text"),
+            PageKind::ImageOnly => write!(f, "image-only"),
+            PageKind::Mixed => write!(f, "mixed"),
+            PageKind::Empty => write!(f, "empty"),
+        }
+    }
+}
+
+/// Per-page text coverage of a document
+#[derive(Debug, Clone, Default)]
+pub struct DocumentCoverage {
+    pub pages: Vec<PageKind>,
+}
+
+impl DocumentCoverage {
+    /// Number of pages of the given kind
+    pub fn count(&self, kind: PageKind) -> usize {
+        self.pages.iter().filter(|k| **k == kind).count()
+    }
+
+    /// A document is considered scanned when it has image-only pages and no text pages
+    pub fn is_scanned(&self) -> bool {
+        self.count(PageKind::ImageOnly) > 0
+            && self.count(PageKind::Text) == 0
+            && self.count(PageKind::Mixed) == 0
+    }
+
+    /// Short human-readable summary, e.g. "3 text, 1 mixed, 2 image-only of 6 pages"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} text, {} mixed, {} image-only, {} empty of {} pages",
+            self.count(PageKind::Text),
+            self.count(PageKind::Mixed),
+            self.count(PageKind::ImageOnly),
+            self.count(PageKind::Empty),
+            self.pages.len()
+        )
+    }
+}
+
+/// Totals across several documents, used by the extract and search summaries
+#[derive(Debug, Default)]
+pub struct CoverageTotals {
+    pub documents: usize,
+    pub scanned_documents: usize,
+    pub text_pages: usize,
+    pub mixed_pages: usize,
+    pub image_only_pages: usize,
+    pub empty_pages: usize,
+}
+
+impl CoverageTotals {
+    pub fn add(&mut self, coverage: &DocumentCoverage) {
+        self.documents += 1;
+        if coverage.is_scanned() {
+            self.scanned_documents += 1;
+        }
+        self.text_pages += coverage.count(PageKind::Text);
+        self.mixed_pages += coverage.count(PageKind::Mixed);
+        self.image_only_pages += coverage.count(PageKind::ImageOnly);
+        self.empty_pages += coverage.count(PageKind::Empty);
+    }
+}
+
+impl fmt::Display for CoverageTotals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Text coverage: {} text, {} mixed, {} image-only, {} empty pages; {} of {} documents appear to be scanned",
+            self.text_pages, self.mixed_pages, self.image_only_pages, self.empty_pages,
+            self.scanned_documents, self.documents
+        )
+    }
+}
+
+/// Check whether extracted text is more than whitespace and stray glyphs
+pub fn has_text_layer(text: &str) -> bool {
+    text.chars().filter(|c| !c.is_whitespace()).take(MIN_TEXT_CHARS).count() >= MIN_TEXT_CHARS
+}
+
+/// Classify a set of PDFs one after another (Pdfium is not thread-safe).
+/// Returns `None` when the Pdfium library is not available.
+pub fn classify_all(pdf_paths: &[PathBuf]) -> Option<HashMap<PathBuf, DocumentCoverage>> {
+    let pdfium = match utils::bind_pdfium() {
+        Ok(pdfium) => pdfium,
+        Err(e) => {
+            eprintln!("Warning: {}; page classification skipped", e);
+            return None;
+        }
+    };
+
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Classifying pages");
+
+    let mut coverage = HashMap::new();
+    for path in pdf_paths {
+        match classify_pdf(&pdfium, path) {
+            Ok(doc_coverage) => {
+                coverage.insert(path.clone(), doc_coverage);
+            },
+            Err(e) => eprintln!("Error classifying {}: {}", path.display(), e),
+        }
+        pb.inc(1);
+    }
+
+    pb.finish_and_clear();
+    Some(coverage)
+}
+
+/// Classify every page of a PDF using Pdfium page objects
+pub fn classify_pdf(pdfium: &Pdfium, path: &Path) -> Result<DocumentCoverage, CoverageError> {
+    let document = pdfium.load_pdf_from_file(path, None)
+        .map_err(|e| CoverageError::PdfError(format!("Error loading {}: {}", path.display(), e)))?;
+
+    let pages = document.pages()
+        .iter()
+        .map(|page| classify_page(&page))
+        .collect();
+
+    Ok(DocumentCoverage { pages })
+}
+
+/// Classify a single page from its text layer and image objects
+pub fn classify_page(page: &PdfPage) -> PageKind {
+    let text_chars = page.text()
+        .map(|text| text.all().chars().filter(|c| !c.is_whitespace()).count())
+        .unwrap_or(0);
+    let has_text = text_chars >= MIN_TEXT_CHARS;
+    let has_images = page.objects().iter().any(|object| contains_image(&object));
+
+    match (has_text, has_images) {
+        (true, false) => PageKind::Text,
+        (true, true) => PageKind::Mixed,
+        (false, true) => PageKind::ImageOnly,
+        (false, false) => PageKind::Empty,
+    }
+}
+
+/// Check whether a page object is, or (for form XObjects) contains, an image
+fn contains_image(object: &PdfPageObject) -> bool {
+    match object.object_type() {
+        PdfPageObjectType::Image => true,
+        PdfPageObjectType::XObjectForm => object.as_x_object_form_object()
+            .map(|form| form.iter().any(|child| contains_image(&child)))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_minimum_text() {
+        assert!(!has_text_layer("  \n 1 \n"));
+        assert!(has_text_layer("A sentence with plenty of characters."));
+    }
+
+    #[test]
+    fn scanned_means_image_only_without_text() {
+        let scan = DocumentCoverage { pages: vec![PageKind::ImageOnly, PageKind::Empty] };
+        let mixed = DocumentCoverage { pages: vec![PageKind::ImageOnly, PageKind::Mixed] };
+        let blank = DocumentCoverage { pages: vec![PageKind::Empty] };
+        assert!(scan.is_scanned());
+        assert!(!mixed.is_scanned());
+        assert!(!blank.is_scanned());
+
+        let mut totals = CoverageTotals::default();
+        totals.add(&scan);
+        totals.add(&mixed);
+        assert_eq!(totals.documents, 2);
+        assert_eq!(totals.scanned_documents, 1);
+        assert_eq!(totals.image_only_pages, 2);
+        assert_eq!(totals.empty_pages, 1);
+    }
+}
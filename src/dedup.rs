@@ -0,0 +1,583 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::time::SystemTime;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::utils;
+
+/// Number of hash functions in a MinHash signature
+const MINHASH_SIZE: usize = 128;
+
+/// Fewest words a text needs to be compared; shorter ones (cover sheets, scans with a stray
+/// OCR line) would match each other by chance
+const MIN_TEXT_WORDS: usize = 20;
+
+/// Rows per LSH band; 32 bands of 4 rows make documents from about 50% similarity on candidates
+const BAND_ROWS: usize = 4;
+
+/// Custom error type for duplicate detection
+#[derive(Debug)]
+pub enum DedupError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for DedupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DedupError::Io(e) => write!(f, "I/O error: {}", e),
+            DedupError::Csv(e) => write!(f, "CSV error: {}", e),
+            DedupError::Json(e) => write!(f, "JSON error: {}", e),
+            DedupError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for DedupError {}
+
+impl From<io::Error> for DedupError {
+    fn from(err: io::Error) -> Self {
+        DedupError::Io(err)
+    }
+}
+
+impl From<csv::Error> for DedupError {
+    fn from(err: csv::Error) -> Self {
+        DedupError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for DedupError {
+    fn from(err: serde_json::Error) -> Self {
+        DedupError::Json(err)
+    }
+}
+
+/// Output formats supported by the dedup command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DedupFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+/// How the text similarity of two documents is estimated
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SimilarityMethod {
+    /// Share of common word shingles (Jaccard similarity), robust to edits anywhere in the text
+    Minhash,
+    /// Share of equal bits of a 64-bit SimHash, cheap but coarser
+    Simhash,
+}
+
+/// Options of the dedup command
+#[derive(Debug, Clone, Copy)]
+pub struct DedupOptions {
+    pub method: SimilarityMethod,
+    /// Minimum similarity, from 0 to 1, for two files to be near-duplicates
+    pub threshold: f64,
+    /// Words per shingle
+    pub shingle_size: usize,
+}
+
+/// Content fingerprints of one file
+struct Fingerprint {
+    path: String,
+    size: u64,
+    sha256: String,
+    modified: Option<SystemTime>,
+    words: usize,
+    /// Hash of the normalized text, `None` with fewer than `MIN_TEXT_WORDS` words
+    text_hash: Option<u64>,
+    minhash: Vec<u64>,
+    simhash: u64,
+}
+
+/// One file of a duplicate group
+#[derive(Debug, Serialize)]
+pub struct GroupMember {
+    pub path: String,
+    /// `canonical`, `identical` (same bytes), `same-text` or `similar`
+    pub relation: String,
+    /// Similarity to the canonical file, from 0 to 1
+    pub similarity: f64,
+    pub size: u64,
+    pub sha256: String,
+    pub words: usize,
+    pub modified: Option<String>,
+}
+
+/// Files with the same or nearly the same content
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub group: usize,
+    /// Suggested file to keep
+    pub canonical: String,
+    pub members: Vec<GroupMember>,
+}
+
+/// Machine-readable dedup report
+#[derive(Debug, Serialize)]
+struct DedupReport {
+    generated: String,
+    files_scanned: usize,
+    method: SimilarityMethod,
+    threshold: f64,
+    shingle_size: usize,
+    /// Files that could be removed, keeping one per group
+    redundant_files: usize,
+    groups: Vec<DuplicateGroup>,
+}
+
+/// Main function of the dedup command
+pub fn run(
+    input_paths: &[String],
+    options: &DedupOptions,
+    format: DedupFormat,
+    output_file: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    if !(options.threshold > 0.0 && options.threshold <= 1.0) {
+        return Err(Box::new(DedupError::Other("The threshold must be above 0 and at most 1".to_string())));
+    }
+    if options.shingle_size == 0 {
+        return Err(Box::new(DedupError::Other("The shingle size must be at least 1 word".to_string())));
+    }
+
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+    if pdf_paths.is_empty() {
+        return Err(Box::new(DedupError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Fingerprinting PDFs");
+
+    let fingerprints: Vec<Fingerprint> = pdf_paths
+        .par_iter()
+        .filter_map(|path| {
+            let fingerprint = fingerprint(path, options.shingle_size);
+            pb.inc(1);
+            fingerprint.map_err(|e| eprintln!("Error reading {}: {}", path.display(), e)).ok()
+        })
+        .collect();
+
+    pb.finish_with_message("Done");
+
+    let groups = find_groups(&fingerprints, options);
+    let report = DedupReport {
+        generated: chrono::Local::now().to_rfc3339(),
+        files_scanned: fingerprints.len(),
+        method: options.method,
+        threshold: options.threshold,
+        shingle_size: options.shingle_size,
+        redundant_files: groups.iter().map(|g| g.members.len() - 1).sum(),
+        groups,
+    };
+
+    match format {
+        DedupFormat::Text => utils::write_output(&to_text(&report), output_file)?,
+        DedupFormat::Json => utils::write_output(&serde_json::to_string_pretty(&report)?, output_file)?,
+        DedupFormat::Csv => write_csv(&report.groups, output_file)?,
+    }
+
+    if let Some(output_file) = output_file {
+        println!(
+            "Successfully wrote {} duplicate groups of {} PDFs to '{}'",
+            report.groups.len(), report.files_scanned, output_file
+        );
+    }
+    Ok(())
+}
+
+/// Hash the file and fingerprint its extracted text
+fn fingerprint(path: &Path, shingle_size: usize) -> Result<Fingerprint, DedupError> {
+    let bytes = fs::read(path)?;
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    // Files without extractable text (scans, damaged files) can still be identical copies
+    let text = panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem(&bytes)))
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or_default();
+    let words = normalized_words(&text);
+    let shingles = shingles(&words, shingle_size);
+
+    Ok(Fingerprint {
+        path: path.to_string_lossy().into_owned(),
+        size: bytes.len() as u64,
+        sha256: utils::to_hex(&Sha256::digest(&bytes)),
+        modified,
+        words: words.len(),
+        text_hash: (words.len() >= MIN_TEXT_WORDS).then(|| fnv1a(words.join(" ").as_bytes())),
+        minhash: minhash(&shingles),
+        simhash: simhash(&shingles),
+    })
+}
+
+/// Lower-case words, so that layout, punctuation and case changes do not count as edits
+fn normalized_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Hashes of all runs of `size` consecutive words (the whole text when it is shorter)
+fn shingles(words: &[String], size: usize) -> HashSet<u64> {
+    if words.is_empty() {
+        return HashSet::new();
+    }
+    words.windows(size.min(words.len()))
+        .map(|window| fnv1a(window.join(" ").as_bytes()))
+        .collect()
+}
+
+/// 64-bit FNV-1a, stable across runs and platforms
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// SplitMix64 finalizer, used to derive the independent hash functions of the MinHash signature
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Smallest hash of the shingles under each of the hash functions
+fn minhash(shingles: &HashSet<u64>) -> Vec<u64> {
+    (0..MINHASH_SIZE as u64)
+        .map(|i| {
+            let seed = mix(i);
+            shingles.iter().map(|&shingle| mix(shingle ^ seed)).min().unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+/// Each bit is set when the majority of shingle hashes have it set
+fn simhash(shingles: &HashSet<u64>) -> u64 {
+    let mut weights = [0i64; 64];
+    for &shingle in shingles {
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if shingle >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+    weights.iter().enumerate()
+        .filter(|(_, &weight)| weight > 0)
+        .fold(0, |hash, (bit, _)| hash | 1 << bit)
+}
+
+/// Estimated text similarity of two files, from 0 to 1
+fn similarity(a: &Fingerprint, b: &Fingerprint, method: SimilarityMethod) -> f64 {
+    match method {
+        SimilarityMethod::Minhash => {
+            let equal = a.minhash.iter().zip(&b.minhash).filter(|(x, y)| x == y).count();
+            equal as f64 / MINHASH_SIZE as f64
+        },
+        SimilarityMethod::Simhash => 1.0 - (a.simhash ^ b.simhash).count_ones() as f64 / 64.0,
+    }
+}
+
+/// Pairs of files (by index) worth comparing. MinHash signatures are split into bands and only
+/// files sharing a band are paired, so large archives avoid comparing every pair.
+fn candidate_pairs(fingerprints: &[Fingerprint], method: SimilarityMethod) -> BTreeSet<(usize, usize)> {
+    let with_text: Vec<usize> = (0..fingerprints.len()).filter(|&i| fingerprints[i].text_hash.is_some()).collect();
+    let mut pairs = BTreeSet::new();
+
+    match method {
+        SimilarityMethod::Minhash => {
+            let mut buckets: HashMap<(usize, &[u64]), Vec<usize>> = HashMap::new();
+            for &i in &with_text {
+                for (band, rows) in fingerprints[i].minhash.chunks(BAND_ROWS).enumerate() {
+                    buckets.entry((band, rows)).or_default().push(i);
+                }
+            }
+            for members in buckets.values() {
+                for (n, &a) in members.iter().enumerate() {
+                    pairs.extend(members[n + 1..].iter().map(|&b| (a, b)));
+                }
+            }
+        },
+        SimilarityMethod::Simhash => {
+            for (n, &a) in with_text.iter().enumerate() {
+                pairs.extend(with_text[n + 1..].iter().map(|&b| (a, b)));
+            }
+        },
+    }
+    pairs
+}
+
+/// Order of preference for the file to keep: the most complete text, among equals the oldest
+/// file, which is likely the original
+fn preference(fingerprint: &Fingerprint) -> (Reverse<usize>, SystemTime, String) {
+    (Reverse(fingerprint.words), fingerprint.modified.unwrap_or(SystemTime::UNIX_EPOCH), fingerprint.path.clone())
+}
+
+/// Group identical files and files similar to a group's canonical file. Similarity is always
+/// measured against the canonical file, so a chain of small edits does not end up in one group
+/// with files that have little in common.
+fn find_groups(fingerprints: &[Fingerprint], options: &DedupOptions) -> Vec<DuplicateGroup> {
+    // Same bytes or same normalized text make exact copies, which always share a group
+    let mut parents: Vec<usize> = (0..fingerprints.len()).collect();
+    let mut first_by_key: HashMap<String, usize> = HashMap::new();
+    for (i, fingerprint) in fingerprints.iter().enumerate() {
+        let mut keys = vec![fingerprint.sha256.clone()];
+        keys.extend(fingerprint.text_hash.map(|hash| format!("text:{:016x}", hash)));
+        for key in keys {
+            let first = *first_by_key.entry(key).or_insert(i);
+            union(&mut parents, first, i);
+        }
+    }
+
+    let mut copies: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..fingerprints.len() {
+        let root = find(&mut parents, i);
+        copies.entry(root).or_default().push(i);
+    }
+    let mut copies: Vec<Vec<usize>> = copies.into_values().collect();
+    for members in &mut copies {
+        members.sort_by_key(|&i| preference(&fingerprints[i]));
+    }
+    copies.sort_by_key(|members| preference(&fingerprints[members[0]]));
+
+    // The best file of each set of copies stands for it when comparing text
+    let mut neighbours: HashMap<usize, Vec<usize>> = HashMap::new();
+    let representatives: HashSet<usize> = copies.iter().map(|members| members[0]).collect();
+    for (a, b) in candidate_pairs(fingerprints, options.method) {
+        if representatives.contains(&a) && representatives.contains(&b) {
+            neighbours.entry(a).or_default().push(b);
+            neighbours.entry(b).or_default().push(a);
+        }
+    }
+
+    // Sets of copies are visited from the most to the least preferred, so each either starts a
+    // group as its canonical file or joins the group whose canonical file it is most similar to
+    let mut group_of_canonical: HashMap<usize, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for members in copies {
+        let representative = members[0];
+        let best = neighbours.get(&representative).into_iter().flatten()
+            .filter_map(|other| group_of_canonical.get(other).map(|&group| (group, *other)))
+            .map(|(group, canonical)| (group, similarity(&fingerprints[representative], &fingerprints[canonical], options.method)))
+            .filter(|(_, similarity)| *similarity >= options.threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)));
+        match best {
+            Some((group, _)) => groups[group].extend(members),
+            None => {
+                group_of_canonical.insert(representative, groups.len());
+                groups.push(members);
+            },
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = groups.into_iter().filter(|members| members.len() > 1).collect();
+    groups.sort_by(|a, b| fingerprints[a[0]].path.cmp(&fingerprints[b[0]].path));
+
+    groups.into_iter().enumerate()
+        .map(|(index, members)| {
+            let canonical = &fingerprints[members[0]];
+            DuplicateGroup {
+                group: index + 1,
+                canonical: canonical.path.clone(),
+                members: members.iter().map(|&i| group_member(&fingerprints[i], canonical, options.method)).collect(),
+            }
+        })
+        .collect()
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut node = i;
+    while parents[node] != root {
+        let next = parents[node];
+        parents[node] = root;
+        node = next;
+    }
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (root_a, root_b) = (find(parents, a), find(parents, b));
+    if root_a != root_b {
+        parents[root_b.max(root_a)] = root_a.min(root_b);
+    }
+}
+
+/// Describe a file of a group by how it relates to the canonical file
+fn group_member(fingerprint: &Fingerprint, canonical: &Fingerprint, method: SimilarityMethod) -> GroupMember {
+    let (relation, similarity) = if std::ptr::eq(fingerprint, canonical) {
+        ("canonical", 1.0)
+    } else if fingerprint.sha256 == canonical.sha256 {
+        ("identical", 1.0)
+    } else if fingerprint.text_hash.is_some() && fingerprint.text_hash == canonical.text_hash {
+        ("same-text", 1.0)
+    } else {
+        ("similar", similarity(fingerprint, canonical, method))
+    };
+
+    GroupMember {
+        path: fingerprint.path.clone(),
+        relation: relation.to_string(),
+        similarity,
+        size: fingerprint.size,
+        sha256: fingerprint.sha256.clone(),
+        words: fingerprint.words,
+        modified: fingerprint.modified
+            .map(|time| chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string()),
+    }
+}
+
+/// Render the groups as indented lists, the canonical file marked with `*`
+fn to_text(report: &DedupReport) -> String {
+    let mut text = String::new();
+    for group in &report.groups {
+        text.push_str(&format!("Group {} ({} files), keep {}\n", group.group, group.members.len(), group.canonical));
+        for member in &group.members {
+            let relation = match member.relation.as_str() {
+                "canonical" => format!("{} words", member.words),
+                "identical" => "identical file".to_string(),
+                "same-text" => "same text".to_string(),
+                _ => format!("{:.0}% similar", member.similarity * 100.0),
+            };
+            let marker = if member.relation == "canonical" { "*" } else { " " };
+            text.push_str(&format!(
+                "  {} {} ({}, modified {})\n", marker, member.path, relation, member.modified.as_deref().unwrap_or("unknown")
+            ));
+        }
+    }
+    text.push_str(&format!(
+        "\nFound {} duplicate groups with {} redundant files among {} PDFs",
+        report.groups.len(), report.redundant_files, report.files_scanned
+    ));
+    text
+}
+
+/// Write one row per grouped file
+fn write_csv(groups: &[DuplicateGroup], output_file: Option<&str>) -> Result<(), DedupError> {
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    csv_writer.write_record(["group", "path", "relation", "similarity", "canonical", "size", "sha256", "words", "modified"])?;
+    for group in groups {
+        for member in &group.members {
+            csv_writer.write_record([
+                group.group.to_string(),
+                member.path.clone(),
+                member.relation.clone(),
+                format!("{:.3}", member.similarity),
+                group.canonical.clone(),
+                member.size.to_string(),
+                member.sha256.clone(),
+                member.words.to_string(),
+                member.modified.clone().unwrap_or_default(),
+            ])?;
+        }
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: DedupOptions = DedupOptions { method: SimilarityMethod::Minhash, threshold: 0.75, shingle_size: 1 };
+
+    /// Fingerprint of a text made of the words `w<first>` to `w<last>`
+    fn fingerprint_of(path: &str, first: usize, last: usize) -> Fingerprint {
+        let text: Vec<String> = (first..=last).map(|n| format!("w{}", n)).collect();
+        text_fingerprint(path, &text.join(" "), path)
+    }
+
+    fn text_fingerprint(path: &str, text: &str, content: &str) -> Fingerprint {
+        let words = normalized_words(text);
+        let shingles = shingles(&words, OPTIONS.shingle_size);
+        Fingerprint {
+            path: path.to_string(),
+            size: content.len() as u64,
+            sha256: utils::to_hex(&Sha256::digest(content.as_bytes())),
+            modified: None,
+            words: words.len(),
+            text_hash: (words.len() >= MIN_TEXT_WORDS).then(|| fnv1a(words.join(" ").as_bytes())),
+            minhash: minhash(&shingles),
+            simhash: simhash(&shingles),
+        }
+    }
+
+    #[test]
+    fn estimates_similarity() {
+        let a = fingerprint_of("a.pdf", 0, 99);
+        let b = fingerprint_of("b.pdf", 10, 109);
+        let unrelated = fingerprint_of("c.pdf", 1000, 1099);
+        for method in [SimilarityMethod::Minhash, SimilarityMethod::Simhash] {
+            assert_eq!(similarity(&a, &a, method), 1.0);
+        }
+        // The Jaccard similarity of a and b is 90 / 110
+        assert!((similarity(&a, &b, SimilarityMethod::Minhash) - 0.818).abs() < 0.1);
+        assert!(similarity(&a, &unrelated, SimilarityMethod::Minhash) < 0.1);
+    }
+
+    #[test]
+    fn normalizes_words_and_shingles() {
+        assert_eq!(normalized_words("Re-saved, COPY!"), vec!["re", "saved", "copy"]);
+        let words = normalized_words("a b c d");
+        assert_eq!(shingles(&words, 3).len(), 2);
+        assert_eq!(shingles(&words, 10).len(), 1);
+        assert!(shingles(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn measures_members_against_the_canonical_file() {
+        // b is close to both a and c, but a and c share only two thirds of their words
+        let fingerprints = vec![
+            fingerprint_of("a.pdf", 0, 99),
+            fingerprint_of("b.pdf", 10, 109),
+            fingerprint_of("c.pdf", 20, 119),
+        ];
+        assert!(similarity(&fingerprints[0], &fingerprints[2], SimilarityMethod::Minhash) < OPTIONS.threshold);
+
+        let groups = find_groups(&fingerprints, &OPTIONS);
+        assert_eq!(groups.len(), 1);
+        let paths: Vec<&str> = groups[0].members.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.pdf", "b.pdf"]);
+        assert_eq!(groups[0].members[1].relation, "similar");
+        assert!(groups[0].members.iter().all(|m| m.similarity >= OPTIONS.threshold));
+    }
+
+    #[test]
+    fn groups_same_text_only_with_enough_words() {
+        let long = (0..MIN_TEXT_WORDS).map(|n| format!("word{}", n)).collect::<Vec<_>>().join(" ");
+        let fingerprints = vec![
+            text_fingerprint("cover1.pdf", "Page 1", "first cover"),
+            text_fingerprint("cover2.pdf", "page 1", "second cover"),
+            text_fingerprint("long1.pdf", &long, "first layout"),
+            text_fingerprint("long2.pdf", &long.to_uppercase(), "second layout"),
+            text_fingerprint("copy.pdf", "Page 1", "first cover"),
+        ];
+        let groups = find_groups(&fingerprints, &OPTIONS);
+        let relations: Vec<Vec<(&str, &str)>> = groups.iter()
+            .map(|g| g.members.iter().map(|m| (m.path.as_str(), m.relation.as_str())).collect())
+            .collect();
+        assert_eq!(relations, vec![
+            vec![("copy.pdf", "canonical"), ("cover1.pdf", "identical")],
+            vec![("long1.pdf", "canonical"), ("long2.pdf", "same-text")],
+        ]);
+    }
+}
@@ -0,0 +1,358 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use clap::ValueEnum;
+use serde::Serialize;
+use similar::{Algorithm, DiffTag};
+
+/// Time the word alignment may take; after it the diff is coarser (larger replaced runs)
+/// instead of taking minutes on long, very different documents
+const DIFF_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Custom error type for document comparison
+#[derive(Debug)]
+pub enum DiffError {
+    Io(io::Error),
+    Pdf(String),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::Io(e) => write!(f, "I/O error: {}", e),
+            DiffError::Pdf(e) => write!(f, "PDF error: {}", e),
+            DiffError::Json(e) => write!(f, "JSON error: {}", e),
+            DiffError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for DiffError {}
+
+impl From<io::Error> for DiffError {
+    fn from(err: io::Error) -> Self {
+        DiffError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for DiffError {
+    fn from(err: serde_json::Error) -> Self {
+        DiffError::Json(err)
+    }
+}
+
+/// Output formats supported by the diff command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DiffFormat {
+    /// Word-level unified diff, [-removed-] and {+added+} inline
+    Text,
+    Json,
+}
+
+/// A word of extracted text and the page (1-based) it is on
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub text: String,
+    pub page: usize,
+}
+
+/// What happened to a run of words between the two documents
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Equal,
+    Delete,
+    Insert,
+    Replace,
+}
+
+/// A run of words, as index ranges into the old and the new word lists
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// One difference in the JSON report
+#[derive(Debug, Serialize)]
+struct ChangeRecord {
+    kind: ChangeKind,
+    old_pages: Option<String>,
+    new_pages: Option<String>,
+    old_text: String,
+    new_text: String,
+}
+
+/// Machine-readable diff report
+#[derive(Debug, Serialize)]
+struct DiffReport {
+    old: String,
+    new: String,
+    words_removed: usize,
+    words_added: usize,
+    changes: Vec<ChangeRecord>,
+}
+
+/// Main function of the diff command
+pub fn run(old_path: &str, new_path: &str, context: usize, format: DiffFormat, output_file: Option<&str>) -> Result<(), Box<dyn Error>> {
+    for path in [old_path, new_path] {
+        if !Path::new(path).is_file() {
+            return Err(Box::new(DiffError::Other(format!("'{}' is not a file", path))));
+        }
+    }
+
+    let old = document_words(Path::new(old_path))?;
+    let new = document_words(Path::new(new_path))?;
+    let changes = diff_words(&old, &new);
+
+    let content = match format {
+        DiffFormat::Text => to_unified(old_path, new_path, &old, &new, &changes, context),
+        DiffFormat::Json => serde_json::to_string_pretty(&to_report(old_path, new_path, &old, &new, &changes))?,
+    };
+    match output_file {
+        Some(path) => {
+            fs::write(path, content)?;
+            println!("Successfully wrote the differences between '{}' and '{}' to '{}'", old_path, new_path, path);
+        },
+        None => println!("{}", content),
+    }
+    Ok(())
+}
+
+/// Split the page-wise extracted text of a PDF into words
+pub fn document_words(path: &Path) -> Result<Vec<Word>, DiffError> {
+    let bytes = fs::read(path)?;
+    let pages = panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem_by_pages(&bytes)))
+        .map_err(|_| DiffError::Pdf(format!("Text extraction crashed on {}", path.display())))?
+        .map_err(|e| DiffError::Pdf(format!("Error extracting text from {}: {}", path.display(), e)))?;
+
+    Ok(pages.iter().enumerate()
+        .flat_map(|(index, text)| {
+            text.split_whitespace().map(move |word| Word { text: word.to_string(), page: index + 1 })
+        })
+        .collect())
+}
+
+/// Align the two word lists. Line breaks and reflowed paragraphs do not show up as changes,
+/// because only the sequence of words is compared.
+pub fn diff_words(old: &[Word], new: &[Word]) -> Vec<Change> {
+    let old_texts: Vec<&str> = old.iter().map(|word| word.text.as_str()).collect();
+    let new_texts: Vec<&str> = new.iter().map(|word| word.text.as_str()).collect();
+
+    let deadline = Instant::now() + DIFF_TIMEOUT;
+    similar::capture_diff_slices_deadline(Algorithm::Myers, &old_texts, &new_texts, Some(deadline))
+        .iter()
+        .map(|op| {
+            let (tag, old, new) = op.as_tag_tuple();
+            let kind = match tag {
+                DiffTag::Equal => ChangeKind::Equal,
+                DiffTag::Delete => ChangeKind::Delete,
+                DiffTag::Insert => ChangeKind::Insert,
+                DiffTag::Replace => ChangeKind::Replace,
+            };
+            Change { kind, old, new }
+        })
+        .collect()
+}
+
+/// Split the changes into hunks, keeping up to `context` unchanged words around each change
+fn hunks(changes: &[Change], context: usize) -> Vec<Vec<Change>> {
+    let mut hunks = Vec::new();
+    let mut current: Vec<Change> = Vec::new();
+
+    for (index, change) in changes.iter().enumerate() {
+        if change.kind != ChangeKind::Equal {
+            current.push(change.clone());
+            continue;
+        }
+
+        // Unchanged runs alternate with changes, so a run is followed or preceded by a change
+        let len = change.old.len();
+        let keep = context.min(len);
+        let after_change = index > 0;
+        let before_change = index + 1 < changes.len();
+        if after_change && before_change && len <= context * 2 {
+            current.push(change.clone());
+            continue;
+        }
+        if after_change {
+            if keep > 0 {
+                current.push(equal_run(change, 0, keep));
+            }
+            hunks.push(std::mem::take(&mut current));
+        }
+        if before_change && keep > 0 {
+            current.push(equal_run(change, len - keep, len));
+        }
+    }
+    if current.iter().any(|change| change.kind != ChangeKind::Equal) {
+        hunks.push(current);
+    }
+    hunks
+}
+
+/// The words `from..to` of an unchanged run
+fn equal_run(change: &Change, from: usize, to: usize) -> Change {
+    Change {
+        kind: ChangeKind::Equal,
+        old: change.old.start + from..change.old.start + to,
+        new: change.new.start + from..change.new.start + to,
+    }
+}
+
+/// Pages covered by a word range, e.g. `3` or `3-4`. An empty range (the other side of an
+/// insertion or deletion) is placed on the page of the word after it, or of the last word.
+pub fn page_span(words: &[Word], range: &Range<usize>) -> Option<String> {
+    let first = words.get(range.start).or_else(|| range.start.checked_sub(1).and_then(|i| words.get(i)))?;
+    let last = words.get(range.end.saturating_sub(1)).filter(|_| !range.is_empty()).unwrap_or(first);
+    Some(if first.page == last.page {
+        first.page.to_string()
+    } else {
+        format!("{}-{}", first.page, last.page)
+    })
+}
+
+fn join(words: &[Word], range: &Range<usize>) -> String {
+    words[range.clone()].iter().map(|word| word.text.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+/// Render hunks of changes with `context` words around them, each headed by its pages
+fn to_unified(old_path: &str, new_path: &str, old: &[Word], new: &[Word], changes: &[Change], context: usize) -> String {
+    let (removed, added) = count_words(changes);
+    let change_count = changes.iter().filter(|change| change.kind != ChangeKind::Equal).count();
+    let mut text = format!("--- {}\n+++ {}\n", old_path, new_path);
+
+    let hunks = hunks(changes, context);
+    for hunk in &hunks {
+        let old_range = hunk[0].old.start..hunk[hunk.len() - 1].old.end;
+        let new_range = hunk[0].new.start..hunk[hunk.len() - 1].new.end;
+        text.push_str(&format!(
+            "@@ -page {} +page {} @@\n",
+            page_span(old, &old_range).unwrap_or_else(|| "-".to_string()),
+            page_span(new, &new_range).unwrap_or_else(|| "-".to_string()),
+        ));
+
+        let parts: Vec<String> = hunk.iter()
+            .map(|change| match change.kind {
+                ChangeKind::Equal => join(old, &change.old),
+                ChangeKind::Delete => format!("[-{}-]", join(old, &change.old)),
+                ChangeKind::Insert => format!("{{+{}+}}", join(new, &change.new)),
+                ChangeKind::Replace => format!("[-{}-]{{+{}+}}", join(old, &change.old), join(new, &change.new)),
+            })
+            .collect();
+        text.push_str(&parts.join(" "));
+        text.push('\n');
+    }
+
+    if hunks.is_empty() {
+        text.push_str("\nNo differences in the text");
+    } else {
+        text.push_str(&format!("\n{} changes: {} words removed, {} words added", change_count, removed, added));
+    }
+    text
+}
+
+fn to_report(old_path: &str, new_path: &str, old: &[Word], new: &[Word], changes: &[Change]) -> DiffReport {
+    let (words_removed, words_added) = count_words(changes);
+    let changes = changes.iter()
+        .filter(|change| change.kind != ChangeKind::Equal)
+        .map(|change| ChangeRecord {
+            kind: change.kind,
+            old_pages: page_span(old, &change.old),
+            new_pages: page_span(new, &change.new),
+            old_text: join(old, &change.old),
+            new_text: join(new, &change.new),
+        })
+        .collect();
+
+    DiffReport {
+        old: old_path.to_string(),
+        new: new_path.to_string(),
+        words_removed,
+        words_added,
+        changes,
+    }
+}
+
+/// Words removed from the old and added in the new document
+fn count_words(changes: &[Change]) -> (usize, usize) {
+    changes.iter()
+        .filter(|change| change.kind != ChangeKind::Equal)
+        .fold((0, 0), |(removed, added), change| (removed + change.old.len(), added + change.new.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Words of a text whose pages are separated by `|`
+    fn words(text: &str) -> Vec<Word> {
+        text.split('|').enumerate()
+            .flat_map(|(index, page)| page.split_whitespace().map(move |word| Word { text: word.to_string(), page: index + 1 }))
+            .collect()
+    }
+
+    fn kinds(changes: &[Change]) -> Vec<ChangeKind> {
+        changes.iter().map(|change| change.kind).collect()
+    }
+
+    #[test]
+    fn aligns_words_across_reflowed_lines() {
+        let old = words("the quick brown fox | jumps over the lazy dog");
+        let new = words("the quick red fox jumps | over the dog");
+        let changes = diff_words(&old, &new);
+        assert_eq!(kinds(&changes), vec![
+            ChangeKind::Equal, ChangeKind::Replace, ChangeKind::Equal, ChangeKind::Delete, ChangeKind::Equal,
+        ]);
+        assert_eq!(count_words(&changes), (2, 1));
+        assert_eq!(join(&old, &changes[1].old), "brown");
+        assert_eq!(join(&new, &changes[1].new), "red");
+    }
+
+    #[test]
+    fn spans_pages_of_word_ranges() {
+        let text = words("a b | c d | e");
+        assert_eq!(page_span(&text, &(0..2)).as_deref(), Some("1"));
+        assert_eq!(page_span(&text, &(1..4)).as_deref(), Some("1-2"));
+        // Empty ranges sit on the page of the next word, or of the last one at the end
+        assert_eq!(page_span(&text, &(2..2)).as_deref(), Some("2"));
+        assert_eq!(page_span(&text, &(5..5)).as_deref(), Some("3"));
+        assert_eq!(page_span(&[], &(0..0)), None);
+    }
+
+    #[test]
+    fn keeps_context_around_changes_in_hunks() {
+        let old = words("one two three four five six seven eight nine ten eleven twelve");
+        let new = words("one two THREE four five six seven eight nine ten ELEVEN twelve");
+        let changes = diff_words(&old, &new);
+
+        // Changes seven words apart stay separate with one word of context...
+        let separate = hunks(&changes, 1);
+        assert_eq!(separate.len(), 2);
+        assert_eq!(separate[0].first().map(|c| c.old.clone()), Some(1..2));
+        assert_eq!(separate[1].last().map(|c| c.old.clone()), Some(11..12));
+        // ...and merge when the context covers the words between them
+        assert_eq!(hunks(&changes, 4).len(), 1);
+        assert!(hunks(&diff_words(&old, &old), 3).is_empty());
+    }
+
+    #[test]
+    fn renders_unified_word_diffs() {
+        let old = words("keep this | old text");
+        let new = words("keep this | new text");
+        let changes = diff_words(&old, &new);
+        let text = to_unified("a.pdf", "b.pdf", &old, &new, &changes, 1);
+        assert_eq!(
+            text,
+            "--- a.pdf\n+++ b.pdf\n@@ -page 1-2 +page 1-2 @@\nthis [-old-]{+new+} text\n\n1 changes: 1 words removed, 1 words added"
+        );
+    }
+}
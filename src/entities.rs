@@ -0,0 +1,462 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::utils;
+
+/// Month names and abbreviations recognised in written-out dates
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+/// Email address pattern, shared with the PII scanner
+pub const EMAIL_PATTERN: &str = r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b";
+
+/// IBAN lengths by country, used to cut an IBAN off from text that follows it
+const IBAN_LENGTHS: [(&str, usize); 36] = [
+    ("AD", 24), ("AT", 20), ("BA", 20), ("BE", 16), ("BG", 22), ("CH", 21), ("CY", 28), ("CZ", 24),
+    ("DE", 22), ("DK", 18), ("EE", 20), ("ES", 24), ("FI", 18), ("FR", 27), ("GB", 22), ("GI", 23),
+    ("GR", 27), ("HR", 21), ("HU", 28), ("IE", 22), ("IS", 26), ("IT", 27), ("LI", 21), ("LT", 20),
+    ("LU", 20), ("LV", 21), ("MC", 27), ("MT", 31), ("NL", 18), ("NO", 15), ("PL", 28), ("PT", 25),
+    ("RO", 24), ("SE", 24), ("SI", 19), ("SK", 24),
+];
+
+/// Custom error type for entity extraction
+#[derive(Debug)]
+pub enum EntityError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for EntityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityError::Io(e) => write!(f, "I/O error: {}", e),
+            EntityError::Csv(e) => write!(f, "CSV error: {}", e),
+            EntityError::Json(e) => write!(f, "JSON error: {}", e),
+            EntityError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for EntityError {}
+
+impl From<io::Error> for EntityError {
+    fn from(err: io::Error) -> Self {
+        EntityError::Io(err)
+    }
+}
+
+impl From<csv::Error> for EntityError {
+    fn from(err: csv::Error) -> Self {
+        EntityError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for EntityError {
+    fn from(err: serde_json::Error) -> Self {
+        EntityError::Json(err)
+    }
+}
+
+/// Output formats supported by the entities command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum EntityFormat {
+    Csv,
+    Json,
+}
+
+/// Entity types that can be extracted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Email,
+    Phone,
+    Date,
+    Iban,
+    Doi,
+}
+
+impl EntityKind {
+    pub const ALL: [EntityKind; 5] = [EntityKind::Email, EntityKind::Phone, EntityKind::Date, EntityKind::Iban, EntityKind::Doi];
+
+    /// Display name of the entity type
+    pub fn label(&self) -> &'static str {
+        match self {
+            EntityKind::Email => "Email",
+            EntityKind::Phone => "Phone",
+            EntityKind::Date => "Date",
+            EntityKind::Iban => "IBAN",
+            EntityKind::Doi => "DOI",
+        }
+    }
+}
+
+/// A page an entity occurs on
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Location {
+    pub path: String,
+    /// 1-based page number
+    pub page: usize,
+}
+
+/// One distinct entity with its occurrences across the corpus
+#[derive(Debug, Clone, Serialize)]
+pub struct EntitySummary {
+    pub kind: EntityKind,
+    /// Normalized value: lowercase emails and DOIs, ISO dates, digits-only phone numbers,
+    /// IBANs in groups of four
+    pub value: String,
+    /// Number of occurrences
+    pub count: usize,
+    /// Number of documents it occurs in
+    pub documents: usize,
+    /// Pages it occurs on, in file and page order
+    pub locations: Vec<Location>,
+}
+
+/// Compiled detectors for the entity types
+pub struct EntityExtractor {
+    email: Regex,
+    iban: Regex,
+    doi: Regex,
+    dates: Vec<(Regex, DatePattern)>,
+    phone: Regex,
+}
+
+/// Field order of a date pattern
+#[derive(Clone, Copy)]
+enum DatePattern {
+    Iso,
+    Numeric,
+    DayMonthName,
+    MonthNameDay,
+}
+
+impl EntityExtractor {
+    pub fn new() -> Self {
+        let months = "(january|february|march|april|may|june|july|august|september|october|november|december\
+                      |jan|feb|mar|apr|jun|jul|aug|sept|sep|oct|nov|dec)\\.?";
+        let pattern = |p: &str| Regex::new(p).expect("valid entity pattern");
+
+        Self {
+            email: pattern(EMAIL_PATTERN),
+            iban: pattern(r"\b[A-Z]{2}[0-9]{2}(?: ?[A-Z0-9]){11,30}"),
+            doi: pattern(r#"\b10\.\d{4,9}/[^\s"<>]+"#),
+            dates: vec![
+                (pattern(r"\b(\d{4})-(\d{2})-(\d{2})\b"), DatePattern::Iso),
+                (pattern(r"\b(\d{1,2})([./])(\d{1,2})[./](\d{4})\b"), DatePattern::Numeric),
+                (pattern(&format!(r"(?i)\b(\d{{1,2}})(?:st|nd|rd|th)?\.? {},? (\d{{4}})\b", months)), DatePattern::DayMonthName),
+                (pattern(&format!(r"(?i)\b{} (\d{{1,2}})(?:st|nd|rd|th)?,? (\d{{4}})\b", months)), DatePattern::MonthNameDay),
+            ],
+            phone: pattern(r"(?:\+\d|\(\+?\d|\b0\d)[\d ()./-]{5,20}\d\b"),
+        }
+    }
+
+    /// Find the entities in a text, in text order. Candidates that overlap an earlier
+    /// candidate are dropped, even one that failed validation, so the digits of a date or
+    /// an IBAN are not reported as a phone number.
+    pub fn find(&self, text: &str, kinds: &[EntityKind]) -> Vec<(EntityKind, String)> {
+        let mut found: Vec<(usize, usize, EntityKind, Option<String>)> = Vec::new();
+        let mut accept = |start: usize, end: usize, kind: EntityKind, value: Option<String>| {
+            if !found.iter().any(|(s, e, _, _)| start < *e && *s < end) {
+                found.push((start, end, kind, value));
+            }
+        };
+
+        for m in self.email.find_iter(text) {
+            accept(m.start(), m.end(), EntityKind::Email, Some(m.as_str().trim_end_matches('.').to_lowercase()));
+        }
+        for m in self.iban.find_iter(text) {
+            accept(m.start(), m.end(), EntityKind::Iban, valid_iban(m.as_str()));
+        }
+        for m in self.doi.find_iter(text) {
+            accept(m.start(), m.end(), EntityKind::Doi, Some(trim_doi(m.as_str()).to_lowercase()));
+        }
+        for (regex, date_pattern) in &self.dates {
+            for captures in regex.captures_iter(text) {
+                let m = captures.get(0).expect("whole match");
+                accept(m.start(), m.end(), EntityKind::Date, parse_date(&captures, *date_pattern));
+            }
+        }
+        for m in self.phone.find_iter(text) {
+            accept(m.start(), m.end(), EntityKind::Phone, normalize_phone(m.as_str()));
+        }
+
+        found.sort_by_key(|(start, _, _, _)| *start);
+        found.into_iter()
+            .filter(|(_, _, kind, _)| kinds.contains(kind))
+            .filter_map(|(_, _, kind, value)| Some((kind, value?)))
+            .collect()
+    }
+}
+
+impl Default for EntityExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Main function to extract entities from PDF files
+pub fn run(input_paths: &[String], kinds: &[EntityKind], format: EntityFormat, output_file: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(EntityError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    let kinds = if kinds.is_empty() { EntityKind::ALL.to_vec() } else { kinds.to_vec() };
+
+    // Create progress bar
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Extracting entities");
+    let summaries = extract_entities(&pdf_paths, &kinds, &pb);
+    pb.finish_with_message("Done");
+
+    match format {
+        EntityFormat::Json => utils::write_output(&serde_json::to_string_pretty(&summaries)?, output_file)?,
+        EntityFormat::Csv => write_csv(&summaries, output_file)?,
+    }
+
+    if let Some(output_file) = output_file {
+        println!("Successfully wrote {} entities from {} PDFs to '{}'", summaries.len(), pdf_paths.len(), output_file);
+    }
+    Ok(())
+}
+
+/// Extract the entities of each page of the PDFs and aggregate them across the corpus,
+/// ordered by type and then by descending count
+pub fn extract_entities(pdf_paths: &[PathBuf], kinds: &[EntityKind], pb: &ProgressBar) -> Vec<EntitySummary> {
+    let extractor = EntityExtractor::new();
+
+    let occurrences: Vec<(EntityKind, String, Location)> = pdf_paths
+        .par_iter()
+        .map(|path| {
+            let mut found = Vec::new();
+            match fs::read(path).map_err(|e| e.to_string())
+                .and_then(|bytes| {
+                    // pdf-extract panics on some malformed files
+                    panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem_by_pages(&bytes)))
+                        .map_err(|_| "text extraction crashed".to_string())?
+                        .map_err(|e| e.to_string())
+                })
+            {
+                Ok(pages) => {
+                    let path_string = path.to_string_lossy().into_owned();
+                    for (index, text) in pages.iter().enumerate() {
+                        for (kind, value) in extractor.find(text, kinds) {
+                            found.push((kind, value, Location { path: path_string.clone(), page: index + 1 }));
+                        }
+                    }
+                },
+                Err(e) => eprintln!("Error extracting text from {}: {}", path.display(), e),
+            }
+            pb.inc(1);
+            found
+        })
+        .flatten()
+        .collect();
+
+    let mut grouped: BTreeMap<(EntityKind, String), (usize, BTreeSet<Location>)> = BTreeMap::new();
+    for (kind, value, location) in occurrences {
+        let entry = grouped.entry((kind, value)).or_default();
+        entry.0 += 1;
+        entry.1.insert(location);
+    }
+
+    let mut summaries: Vec<EntitySummary> = grouped.into_iter()
+        .map(|((kind, value), (count, locations))| EntitySummary {
+            kind,
+            value,
+            count,
+            documents: locations.iter().map(|l| &l.path).collect::<BTreeSet<_>>().len(),
+            locations: locations.into_iter().collect(),
+        })
+        .collect();
+    summaries.sort_by(|a, b| a.kind.cmp(&b.kind).then(b.count.cmp(&a.count)).then_with(|| a.value.cmp(&b.value)));
+    summaries
+}
+
+/// Validate an IBAN candidate with the mod-97 checksum. Text that follows the IBAN is cut off
+/// using the length of the country's IBANs; for other countries the whole candidate must be valid.
+pub fn valid_iban(candidate: &str) -> Option<String> {
+    let compact: String = candidate.chars().filter(|c| !c.is_whitespace()).collect();
+    if !compact.is_ascii() {
+        return None;
+    }
+    let length = IBAN_LENGTHS.iter()
+        .find(|(country, _)| compact.starts_with(country))
+        .map(|(_, length)| *length)
+        .unwrap_or(compact.len());
+    if compact.len() < length || !(15..=34).contains(&length) {
+        return None;
+    }
+    let iban = &compact[..length];
+
+    // Move the country code and check digits to the end and read letters as 10..35
+    let rearranged = iban[4..].chars().chain(iban[..4].chars());
+    let mut remainder = 0u32;
+    for c in rearranged {
+        let value = c.to_digit(36)?;
+        remainder = if value >= 10 { (remainder * 100 + value) % 97 } else { (remainder * 10 + value) % 97 };
+    }
+    if remainder != 1 {
+        return None;
+    }
+
+    let groups: Vec<String> = iban.as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect();
+    Some(groups.join(" "))
+}
+
+/// Drop punctuation that ends the sentence rather than the DOI; a closing parenthesis is
+/// kept when the DOI contains the opening one
+fn trim_doi(doi: &str) -> &str {
+    let mut doi = doi;
+    loop {
+        let trimmed = doi.trim_end_matches(['.', ',', ';', ':', ']', '}', '\'']);
+        let trimmed = if trimmed.ends_with(')') && trimmed.matches('(').count() < trimmed.matches(')').count() {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            trimmed
+        };
+        if trimmed == doi {
+            return doi;
+        }
+        doi = trimmed;
+    }
+}
+
+/// Parse a date match into ISO format; numeric dates are read as day.month.year with dots
+/// and as month/day/year with slashes, falling back to day/month/year when that is invalid
+fn parse_date(captures: &regex::Captures, pattern: DatePattern) -> Option<String> {
+    let number = |index: usize| captures.get(index)?.as_str().parse::<u32>().ok();
+    let month_name = |index: usize| {
+        let name = captures.get(index)?.as_str().trim_end_matches('.').to_lowercase();
+        MONTHS.iter().position(|month| month.starts_with(&name)).map(|index| index as u32 + 1)
+    };
+
+    let (year, month, day) = match pattern {
+        DatePattern::Iso => (number(1)?, number(2)?, number(3)?),
+        DatePattern::Numeric => {
+            let (first, second, year) = (number(1)?, number(3)?, number(4)?);
+            if captures.get(2)?.as_str() == "/" && NaiveDate::from_ymd_opt(year as i32, first, second).is_some() {
+                (year, first, second)
+            } else {
+                (year, second, first)
+            }
+        },
+        DatePattern::DayMonthName => (number(3)?, month_name(2)?, number(1)?),
+        DatePattern::MonthNameDay => (number(3)?, month_name(1)?, number(2)?),
+    };
+
+    if !(1800..=2199).contains(&year) {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(year as i32, month, day).map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+/// Reduce a phone number to `+` and digits; `(0)` after an international prefix is dropped.
+/// Numbers must have 8 to 15 digits and use single separators.
+fn normalize_phone(candidate: &str) -> Option<String> {
+    let candidate = candidate.trim();
+    if candidate.contains("  ") || candidate.contains("..") || candidate.contains("//") {
+        return None;
+    }
+    let international = candidate.trim_start_matches('(').starts_with('+');
+    let candidate = if international { candidate.replacen("(0)", "", 1) } else { candidate.to_string() };
+    let digits: String = candidate.chars().filter(char::is_ascii_digit).collect();
+    if !(8..=15).contains(&digits.len()) {
+        return None;
+    }
+    Some(if international { format!("+{}", digits) } else { digits })
+}
+
+/// Write the entity summaries as CSV, with the pages as `path:page` separated by `; `
+fn write_csv(summaries: &[EntitySummary], output_file: Option<&str>) -> Result<(), EntityError> {
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    csv_writer.write_record(["type", "value", "count", "documents", "locations"])?;
+    for summary in summaries {
+        let locations: Vec<String> = summary.locations.iter()
+            .map(|location| format!("{}:{}", location.path, location.page))
+            .collect();
+        csv_writer.write_record([
+            summary.kind.label().to_string(),
+            summary.value.clone(),
+            summary.count.to_string(),
+            summary.documents.to_string(),
+            locations.join("; "),
+        ])?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(text: &str) -> Vec<(EntityKind, String)> {
+        EntityExtractor::new().find(text, &EntityKind::ALL)
+    }
+
+    #[test]
+    fn validates_ibans() {
+        assert_eq!(valid_iban("DE89 3704 0044 0532 0130 00").as_deref(), Some("DE89 3704 0044 0532 0130 00"));
+        assert_eq!(valid_iban("GB82WEST12345698765432").as_deref(), Some("GB82 WEST 1234 5698 7654 32"));
+        assert_eq!(valid_iban("DE89 3704 0044 0532 0130 01"), None);
+        assert_eq!(valid_iban("DE89 3704"), None);
+        assert_eq!(valid_iban("DE०१ABCDEFGHIJKLMNOPQR"), None);
+    }
+
+    #[test]
+    fn finds_entities_in_text_order() {
+        let text = "Mail Jane.Doe@Example.com by 15 March 2024, call +49 (0)30 1234567 \
+                    or pay DE89 3704 0044 0532 0130 00. See doi:10.1000/xyz(12)3.";
+        assert_eq!(find(text), vec![
+            (EntityKind::Email, "jane.doe@example.com".to_string()),
+            (EntityKind::Date, "2024-03-15".to_string()),
+            (EntityKind::Phone, "+49301234567".to_string()),
+            (EntityKind::Iban, "DE89 3704 0044 0532 0130 00".to_string()),
+            (EntityKind::Doi, "10.1000/xyz(12)3".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn ignores_ibans_with_non_ascii_digits() {
+        assert!(!find("pay DE०१ABCDEFGHIJKLMNOPQR").iter().any(|(kind, _)| *kind == EntityKind::Iban));
+    }
+
+    #[test]
+    fn reads_numeric_dates_by_separator() {
+        assert_eq!(find("on 03/04/2024"), vec![(EntityKind::Date, "2024-03-04".to_string())]);
+        assert_eq!(find("on 03.04.2024"), vec![(EntityKind::Date, "2024-04-03".to_string())]);
+        assert_eq!(find("on 25/12/2024"), vec![(EntityKind::Date, "2024-12-25".to_string())]);
+        assert!(find("on 31.02.2024").is_empty());
+    }
+
+    #[test]
+    fn filters_by_kind_and_trims_dois() {
+        let kinds = [EntityKind::Doi];
+        assert_eq!(EntityExtractor::new().find("x (10.1000/abc.) y a@b.org", &kinds), vec![(EntityKind::Doi, "10.1000/abc".to_string())]);
+        assert_eq!(normalize_phone("0 1"), None);
+    }
+}
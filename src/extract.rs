@@ -1,12 +1,22 @@
+use clap::ValueEnum;
 use indicatif::ProgressBar;
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::io;
+use std::str::FromStr;
 use walkdir::WalkDir;
 use std::error::Error;
 use std::fmt;
 
+use crate::coverage::{self, CoverageTotals, DocumentCoverage, PageKind};
+use crate::layout::{self, LayoutMode};
+use crate::markup::{self, MarkupFormat};
+use crate::ocr::{OcrArgs, OcrStage};
+use crate::outline::{self, Section};
+use crate::utils;
+
 /// Custom error type for extraction operations
 #[derive(Debug)]
 pub enum ExtractError {
@@ -33,8 +43,74 @@ impl From<io::Error> for ExtractError {
     }
 }
 
+/// Output formats supported by the extract command
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum ExtractFormat {
+    /// Plain text with document boundary markers
+    #[default]
+    Text,
+    /// Markdown with inferred headings, lists, links and page anchors
+    Markdown,
+    /// HTML with inferred headings, lists, links and page anchors
+    Html,
+}
+
+impl ExtractFormat {
+    fn markup(self) -> Option<MarkupFormat> {
+        match self {
+            ExtractFormat::Text => None,
+            ExtractFormat::Markdown => Some(MarkupFormat::Markdown),
+            ExtractFormat::Html => Some(MarkupFormat::Html),
+        }
+    }
+}
+
+/// How documents are divided into separate records
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitBy {
+    /// One record per bookmark, down to the given outline level (1 = top-level bookmarks)
+    Outline(usize),
+}
+
+impl FromStr for SplitBy {
+    type Err = String;
+
+    /// Parse `outline` or `outline:LEVEL`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, level) = match s.split_once(':') {
+            Some((kind, level)) => (kind, Some(level)),
+            None => (s, None),
+        };
+        match kind {
+            "outline" => {
+                let level = match level {
+                    Some(level) => level.parse::<usize>().ok().filter(|l| *l >= 1)
+                        .ok_or_else(|| format!("Invalid outline level '{}', expected a number from 1", level))?,
+                    None => 1,
+                };
+                Ok(SplitBy::Outline(level))
+            },
+            _ => Err(format!("Unknown split mode '{}', expected outline[:LEVEL]", kind)),
+        }
+    }
+}
+
 /// Main function to run the extraction functionality
-pub fn run(output_file: &str, input_paths: &[String]) -> Result<(), Box<dyn Error>> {
+pub fn run(
+    output_file: &str,
+    input_paths: &[String],
+    layout: LayoutMode,
+    only_scanned: bool,
+    split_by: Option<SplitBy>,
+    format: ExtractFormat,
+    ocr: &OcrArgs,
+) -> Result<(), Box<dyn Error>> {
+    if split_by.is_some() && format != ExtractFormat::Text {
+        return Err(Box::new(ExtractError::OtherError(
+            "--split-by can only be combined with plain text output".to_string()
+        )));
+    }
+
     // Collect all PDF paths
     let pdf_paths = collect_pdf_paths(input_paths)?;
     
@@ -44,23 +120,255 @@ pub fn run(output_file: &str, input_paths: &[String]) -> Result<(), Box<dyn Erro
         )));
     }
 
+    // Classify pages up front so scanned documents can be reported (or selected)
+    let coverage = coverage::classify_all(&pdf_paths);
+
     // Create progress bar
     let pb = ProgressBar::new(pdf_paths.len() as u64);
     pb.set_message("Processing PDFs");
 
-    // Process PDFs in parallel
-    let extracted_texts = process_pdfs(&pdf_paths, &pb);
+    // Process PDFs in parallel, or one by one through Pdfium for layout-aware extraction.
+    // Splitting into sections needs the text of each page.
+    // Markdown and HTML are built from Pdfium font information and always read in reading order.
+    let by_pages = split_by.is_some();
+    let anchor_prefixes: HashMap<PathBuf, String> = pdf_paths.iter().cloned()
+        .zip(markup::anchor_prefixes(&pdf_paths))
+        .collect();
+    let mut documents = match (format.markup(), layout) {
+        (Some(markup_format), _) => process_pdfs_with_markup(&pdf_paths, &anchor_prefixes, markup_format, &pb)?,
+        (None, LayoutMode::Raw) => process_pdfs(&pdf_paths, by_pages, &pb),
+        (None, mode) => process_pdfs_with_layout(&pdf_paths, mode, by_pages, &pb)?,
+    };
     
     // Finish progress bar
     pb.finish_with_message("Done");
 
-    // Write to output file
-    fs::write(output_file, extracted_texts.join("\n"))?;
-    
-    println!("Successfully extracted text from {} PDFs to '{}'", pdf_paths.len(), output_file);
+    // Recognize image-only pages with the OCR engine, if one is configured
+    if let Some(stage) = OcrStage::from_args(ocr) {
+        apply_ocr(&stage, &mut documents, coverage.as_ref(), format.markup());
+    }
+
+    // Keep only documents without a text layer if requested
+    if only_scanned {
+        documents.retain(|doc| is_scanned(doc, coverage.as_ref()));
+        if documents.is_empty() {
+            println!("No scanned PDFs found in the provided paths");
+            return Ok(());
+        }
+    }
+
+    match (split_by, format.markup()) {
+        (Some(SplitBy::Outline(level)), _) => {
+            let count = write_sections(&documents, level, Path::new(output_file))?;
+            println!("Successfully extracted {} sections from {} PDFs to '{}'", count, documents.len(), output_file);
+        },
+        (None, Some(markup_format)) => {
+            let rendered: Vec<(String, String, String)> = documents.iter()
+                .filter(|doc| doc.text.is_some())
+                .map(|doc| (doc.filename.clone(), anchor_prefixes[&doc.path].clone(), doc.pages.concat()))
+                .collect();
+            fs::write(output_file, markup::wrap_documents(&rendered, markup_format))?;
+
+            println!("Successfully converted {} PDFs to '{}'", rendered.len(), output_file);
+        },
+        (None, None) => {
+            // Write to output file
+            let extracted_texts: Vec<String> = documents.iter().map(format_document).collect();
+            fs::write(output_file, extracted_texts.join("\n"))?;
+
+            println!("Successfully extracted text from {} PDFs to '{}'", documents.len(), output_file);
+        },
+    }
+    print_coverage_summary(&documents, coverage.as_ref());
     Ok(())
 }
 
+/// Text extracted from a single document
+struct ExtractedDocument {
+    path: PathBuf,
+    filename: String,
+    /// `None` when extraction failed
+    text: Option<String>,
+    /// Text of each page, only filled in when documents are split into sections
+    /// or rendered as Markdown/HTML
+    pages: Vec<String>,
+}
+
+/// Format a document block with clear boundaries
+fn format_document(doc: &ExtractedDocument) -> String {
+    match &doc.text {
+        Some(text) => format!(
+            "[Start of document: {}]\n{}\n[End of document: {}]\n",
+            doc.filename, text, doc.filename
+        ),
+        None => String::new(),
+    }
+}
+
+/// Write the sections of each document, either as blocks of one file or, when `output`
+/// is a directory, as one file per section. Returns the number of sections written.
+fn write_sections(documents: &[ExtractedDocument], level: usize, output: &Path) -> Result<usize, ExtractError> {
+    let mut blocks = Vec::new();
+    let mut count = 0;
+
+    // Same-named inputs get numbered names, so their section files do not overwrite each other
+    let documents: Vec<&ExtractedDocument> = documents.iter().filter(|doc| doc.text.is_some()).collect();
+    let paths: Vec<PathBuf> = documents.iter().map(|doc| doc.path.clone()).collect();
+    for (doc, stem) in documents.iter().zip(utils::unique_stems(&paths)) {
+        let entries = match lopdf::Document::load(&doc.path) {
+            Ok(pdf) => outline::read_outline(&pdf),
+            Err(e) => {
+                eprintln!("Error reading the outline of {}: {}", doc.filename, e);
+                Vec::new()
+            }
+        };
+
+        let mut sections = outline::sections(&entries, level, doc.pages.len());
+        let whole_document = sections.is_empty();
+        if whole_document {
+            // Without usable bookmarks the whole document is one section
+            sections.push(Section { heading_path: Vec::new(), start_page: 1, end_page: doc.pages.len().max(1) });
+            if doc.pages.len() > 1 {
+                eprintln!("Warning: {} has no outline bookmarks with page destinations; kept as one section", doc.filename);
+            }
+        }
+
+        for (index, section) in sections.iter().enumerate() {
+            let text = doc.pages.get(section.start_page - 1..section.end_page.min(doc.pages.len()))
+                .map(|pages| pages.join("\n"))
+                .unwrap_or_default();
+            let label = if whole_document {
+                doc.filename.clone()
+            } else {
+                format!("{} > {} ({})", doc.filename, section.heading(), section.page_range())
+            };
+            let block = format!("[Start of section: {}]\n{}\n[End of section: {}]\n", label, text, label);
+
+            if output.is_dir() {
+                let name = match section.heading_path.last() {
+                    Some(title) => utils::slug(title),
+                    None if whole_document => "document".to_string(),
+                    None => "front-matter".to_string(),
+                };
+                fs::write(output.join(format!("{}_{:03}_{}.txt", stem, index + 1, name)), block)?;
+            } else {
+                blocks.push(block);
+            }
+            count += 1;
+        }
+    }
+
+    if !output.is_dir() {
+        fs::write(output, blocks.join("\n"))?;
+    }
+    Ok(count)
+}
+
+/// Append OCR text for image-only pages to the extracted documents
+fn apply_ocr(
+    stage: &OcrStage,
+    documents: &mut [ExtractedDocument],
+    coverage: Option<&HashMap<PathBuf, DocumentCoverage>>,
+    markup_format: Option<MarkupFormat>,
+) {
+    // Only documents known to have image-only pages need to be rasterized
+    let targets: Vec<&mut ExtractedDocument> = documents.iter_mut()
+        .filter(|doc| doc.text.is_some())
+        .filter(|doc| coverage
+            .and_then(|c| c.get(&doc.path))
+            .map(|c| c.count(PageKind::ImageOnly) > 0)
+            .unwrap_or(true))
+        .collect();
+
+    if targets.is_empty() {
+        return;
+    }
+
+    let pb = ProgressBar::new(targets.len() as u64);
+    pb.set_message("Running OCR");
+
+    for doc in targets {
+        match stage.missing_text(&doc.path) {
+            Ok(ocr_text) if !ocr_text.is_empty() => {
+                // Recognized pages also belong to their sections
+                for (page, page_text) in ocr_pages(&ocr_text) {
+                    if let Some(existing) = doc.pages.get_mut(page - 1) {
+                        match markup_format {
+                            Some(markup_format) => existing.push_str(&markup::plain_text(page_text, markup_format)),
+                            None => existing.push_str(page_text),
+                        }
+                    }
+                }
+                if let Some(text) = doc.text.as_mut() {
+                    text.push_str(&ocr_text);
+                }
+            },
+            Ok(_) => {},
+            Err(e) => eprintln!("OCR failed for {}: {}", doc.filename, e),
+        }
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("OCR done");
+}
+
+/// Split OCR output at its `[OCR page N]` markers into (1-based page, text) pairs
+fn ocr_pages(ocr_text: &str) -> Vec<(usize, &str)> {
+    let mut pages = Vec::new();
+    let mut rest = ocr_text;
+    while let Some(start) = rest.find("[OCR page ") {
+        let after = &rest[start + "[OCR page ".len()..];
+        let close = match after.find(']') {
+            Some(close) => close,
+            None => break,
+        };
+        let body = &after[close + 1..];
+        let end = body.find("[OCR page ").unwrap_or(body.len());
+        if let Ok(page) = after[..close].parse::<usize>() {
+            if page >= 1 {
+                pages.push((page, &body[..end]));
+            }
+        }
+        rest = &body[end..];
+    }
+    pages
+}
+
+/// Decide whether a document is scanned, preferring Pdfium's page classification
+fn is_scanned(doc: &ExtractedDocument, coverage: Option<&HashMap<PathBuf, DocumentCoverage>>) -> bool {
+    match coverage.and_then(|c| c.get(&doc.path)) {
+        Some(doc_coverage) => doc_coverage.is_scanned(),
+        None => doc.text.as_deref().map(|t| !coverage::has_text_layer(t)).unwrap_or(false),
+    }
+}
+
+/// Report text coverage and list documents that will not be searchable
+fn print_coverage_summary(documents: &[ExtractedDocument], coverage: Option<&HashMap<PathBuf, DocumentCoverage>>) {
+    if let Some(coverage) = coverage {
+        let mut totals = CoverageTotals::default();
+        for doc in documents {
+            if let Some(doc_coverage) = coverage.get(&doc.path) {
+                totals.add(doc_coverage);
+            }
+        }
+        println!("{}", totals);
+    }
+
+    let without_text: Vec<&ExtractedDocument> = documents.iter()
+        .filter(|doc| doc.text.as_deref().map(|t| !coverage::has_text_layer(t)).unwrap_or(false))
+        .collect();
+
+    if !without_text.is_empty() {
+        eprintln!("Warning: {} PDF(s) have no extractable text (image-only scans need OCR):", without_text.len());
+        for doc in without_text {
+            match coverage.and_then(|c| c.get(&doc.path)) {
+                Some(doc_coverage) => eprintln!("  {} ({})", doc.path.display(), doc_coverage.summary()),
+                None => eprintln!("  {}", doc.path.display()),
+            }
+        }
+    }
+}
+
 /// Collect all PDF file paths from the provided input paths
 fn collect_pdf_paths(input_paths: &[String]) -> Result<Vec<PathBuf>, ExtractError> {
     let mut pdf_paths: Vec<PathBuf> = Vec::new();
@@ -87,27 +395,82 @@ fn collect_pdf_paths(input_paths: &[String]) -> Result<Vec<PathBuf>, ExtractErro
     Ok(pdf_paths)
 }
 
-/// Process PDFs in parallel and extract text
-fn process_pdfs(pdf_paths: &[PathBuf], pb: &ProgressBar) -> Vec<String> {
+/// Process PDFs in parallel and extract text, page by page if `by_pages` is set
+fn process_pdfs(pdf_paths: &[PathBuf], by_pages: bool, pb: &ProgressBar) -> Vec<ExtractedDocument> {
     pdf_paths
         .par_iter()
         .map(|path| {
-            let filename = path.file_name().unwrap().to_str().unwrap();
-            match extract_text_from_pdf(path) {
-                Ok(text) => format!(
-                    "[Start of document: {}]\n{}\n[End of document: {}]\n",
-                    filename, text, filename
-                ),
+            let filename = path.file_name().unwrap().to_str().unwrap().to_string();
+            let result = if by_pages {
+                extract_pages_from_pdf(path).map(|pages| (pages.join("\n"), pages))
+            } else {
+                extract_text_from_pdf(path).map(|text| (text, Vec::new()))
+            };
+            let (text, pages) = match result {
+                Ok((text, pages)) => (Some(text), pages),
                 Err(e) => {
                     eprintln!("Error processing {}: {}", filename, e);
-                    String::new()
+                    (None, Vec::new())
                 }
-            }
+            };
+            ExtractedDocument { path: path.clone(), filename, text, pages }
         })
         .inspect(|_| pb.inc(1))
         .collect()
 }
 
+/// Extract text page by page from Pdfium character positions, in reading or physical order
+fn process_pdfs_with_layout(pdf_paths: &[PathBuf], mode: LayoutMode, by_pages: bool, pb: &ProgressBar) -> Result<Vec<ExtractedDocument>, ExtractError> {
+    let pdfium = utils::bind_pdfium().map_err(ExtractError::OtherError)?;
+
+    let documents = pdf_paths
+        .iter()
+        .map(|path| {
+            let filename = path.file_name().unwrap().to_str().unwrap().to_string();
+            let (text, pages) = match layout::extract_pages(&pdfium, path, mode) {
+                Ok(pages) => (Some(pages.join("\n\n")), if by_pages { pages } else { Vec::new() }),
+                Err(e) => {
+                    eprintln!("Error processing {}: {}", filename, e);
+                    (None, Vec::new())
+                }
+            };
+            ExtractedDocument { path: path.clone(), filename, text, pages }
+        })
+        .inspect(|_| pb.inc(1))
+        .collect();
+
+    Ok(documents)
+}
+
+/// Render PDFs one by one as Markdown or HTML from Pdfium character and font information,
+/// with page anchors named after each document's prefix
+fn process_pdfs_with_markup(
+    pdf_paths: &[PathBuf],
+    anchor_prefixes: &HashMap<PathBuf, String>,
+    format: MarkupFormat,
+    pb: &ProgressBar,
+) -> Result<Vec<ExtractedDocument>, ExtractError> {
+    let pdfium = utils::bind_pdfium().map_err(ExtractError::OtherError)?;
+
+    let documents = pdf_paths
+        .iter()
+        .map(|path| {
+            let filename = path.file_name().unwrap().to_str().unwrap().to_string();
+            let (text, pages) = match markup::render_document(&pdfium, path, &anchor_prefixes[path], format) {
+                Ok(pages) => (Some(pages.concat()), pages),
+                Err(e) => {
+                    eprintln!("Error processing {}: {}", filename, e);
+                    (None, Vec::new())
+                }
+            };
+            ExtractedDocument { path: path.clone(), filename, text, pages }
+        })
+        .inspect(|_| pb.inc(1))
+        .collect();
+
+    Ok(documents)
+}
+
 /// Extract text from a single PDF file
 fn extract_text_from_pdf(path: &PathBuf) -> Result<String, ExtractError> {
     let filename = path.file_name()
@@ -121,4 +484,30 @@ fn extract_text_from_pdf(path: &PathBuf) -> Result<String, ExtractError> {
         .map_err(|e| ExtractError::PdfError(format!("Error extracting text from {}: {}", filename, e)))?;
         
     Ok(text)
-} 
\ No newline at end of file
+}
+
+/// Extract the text of each page of a single PDF file
+fn extract_pages_from_pdf(path: &PathBuf) -> Result<Vec<String>, ExtractError> {
+    let filename = path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown");
+
+    let bytes = fs::read(path)?;
+
+    pdf_extract::extract_text_from_mem_by_pages(&bytes)
+        .map_err(|e| ExtractError::PdfError(format!("Error extracting text from {}: {}", filename, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_split_modes() {
+        assert_eq!("outline".parse::<SplitBy>(), Ok(SplitBy::Outline(1)));
+        assert_eq!("outline:3".parse::<SplitBy>(), Ok(SplitBy::Outline(3)));
+        assert!("outline:0".parse::<SplitBy>().is_err());
+        assert!("outline:x".parse::<SplitBy>().is_err());
+        assert!("pages".parse::<SplitBy>().is_err());
+    }
+}
@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+use lopdf::{Dictionary, Document, Object};
+
+/// Default ascent and descent, as fractions of the font size, when the font descriptor has none
+const DEFAULT_ASCENT: f32 = 0.8;
+const DEFAULT_DESCENT: f32 = -0.2;
+
+/// Maximum number of codes of a single ToUnicode bfrange
+const MAX_RANGE_LENGTH: u32 = 0x10000;
+
+/// What is needed of a font to map the bytes of a text-showing operator to characters,
+/// advance widths and glyph boxes
+#[derive(Debug, Default)]
+pub struct PdfFont {
+    /// Two-byte character codes (Type0 fonts)
+    two_byte: bool,
+    /// Unicode text of character codes from the ToUnicode CMap
+    to_unicode: HashMap<u32, String>,
+    /// Glyph names of single-byte codes from /Differences
+    differences: HashMap<u32, String>,
+    /// StandardEncoding rather than WinAnsiEncoding for codes without ToUnicode or differences
+    standard_encoding: bool,
+    /// Advance widths in glyph units (thousandths of the font size for most fonts)
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+    /// Widths of the standard 14 fonts for fonts that come without /Widths
+    standard_widths: Option<&'static [u16; 224]>,
+    /// Scale from glyph units to text space: 1/1000, or the FontMatrix of Type3 fonts
+    width_scale: f32,
+    /// Ascent and descent as fractions of the font size
+    pub ascent: f32,
+    pub descent: f32,
+    /// Whether widths are estimated because the font has no width information
+    pub approximate_widths: bool,
+}
+
+impl PdfFont {
+    /// Read the encoding, widths and metrics of a font dictionary
+    pub fn load(doc: &Document, font: &Dictionary) -> Self {
+        let subtype = font.get(b"Subtype").and_then(Object::as_name_str).unwrap_or("");
+        let mut result = PdfFont {
+            width_scale: 0.001,
+            ascent: DEFAULT_ASCENT,
+            descent: DEFAULT_DESCENT,
+            ..Default::default()
+        };
+
+        if let Some(cmap) = font.get(b"ToUnicode").ok()
+            .and_then(|cmap| doc.dereference(cmap).ok())
+            .and_then(|(_, cmap)| cmap.as_stream().ok())
+        {
+            let data = cmap.decompressed_content().unwrap_or_else(|_| cmap.content.clone());
+            result.to_unicode = parse_to_unicode(&data);
+        }
+
+        let descriptor = if subtype == "Type0" {
+            result.two_byte = true;
+            let descendant = font.get(b"DescendantFonts").ok()
+                .and_then(|fonts| doc.dereference(fonts).ok())
+                .and_then(|(_, fonts)| fonts.as_array().ok())
+                .and_then(|fonts| fonts.first())
+                .and_then(|descendant| dereference_dict(doc, descendant));
+            if let Some(descendant) = descendant {
+                result.default_width = number(doc, descendant.get(b"DW").ok()).unwrap_or(1000.0);
+                result.widths = cid_widths(doc, descendant);
+            } else {
+                result.default_width = 1000.0;
+            }
+            descendant.and_then(|d| d.get(b"FontDescriptor").ok()).and_then(|d| dereference_dict(doc, d))
+        } else {
+            result.read_simple_encoding(doc, font);
+            result.read_simple_widths(doc, font, subtype);
+            font.get(b"FontDescriptor").ok().and_then(|d| dereference_dict(doc, d))
+        };
+
+        if let Some(descriptor) = descriptor {
+            let ascent = number(doc, descriptor.get(b"Ascent").ok()).unwrap_or(0.0) / 1000.0;
+            let descent = number(doc, descriptor.get(b"Descent").ok()).unwrap_or(0.0) / 1000.0;
+            if ascent > 0.0 {
+                result.ascent = ascent;
+            }
+            if descent < 0.0 {
+                result.descent = descent;
+            }
+        }
+        result
+    }
+
+    /// Split the bytes of a string operand into character codes: (offset, length, code)
+    pub fn codes(&self, bytes: &[u8]) -> Vec<(usize, usize, u32)> {
+        if self.two_byte {
+            bytes.chunks(2)
+                .enumerate()
+                .map(|(index, chunk)| {
+                    let code = chunk.iter().fold(0u32, |code, byte| (code << 8) | *byte as u32);
+                    (index * 2, chunk.len(), code)
+                })
+                .collect()
+        } else {
+            bytes.iter().enumerate().map(|(index, byte)| (index, 1, *byte as u32)).collect()
+        }
+    }
+
+    /// Unicode text of a character code; empty if it cannot be mapped
+    pub fn text(&self, code: u32) -> String {
+        if let Some(text) = self.to_unicode.get(&code) {
+            return text.clone();
+        }
+        if self.two_byte {
+            return String::new();
+        }
+        if let Some(name) = self.differences.get(&code) {
+            return glyph_name_to_unicode(name).unwrap_or_default();
+        }
+        match code {
+            39 if self.standard_encoding => "\u{2019}".to_string(),
+            96 if self.standard_encoding => "\u{2018}".to_string(),
+            _ => win_ansi_char(code).map(String::from).unwrap_or_default(),
+        }
+    }
+
+    /// Advance width of a character code in text space units at a font size of 1
+    pub fn width(&self, code: u32) -> f32 {
+        if let Some(width) = self.widths.get(&code) {
+            return width * self.width_scale;
+        }
+        if let Some(table) = self.standard_widths {
+            let index = match self.differences.get(&code) {
+                Some(name) => WIN_ANSI_NAMES.iter().position(|n| n == name),
+                None => (32..256).contains(&code).then(|| code as usize - 32),
+            };
+            if let Some(width) = index.map(|index| table[index]).filter(|width| *width > 0) {
+                return width as f32 * self.width_scale;
+            }
+        }
+        self.default_width * self.width_scale
+    }
+
+    fn read_simple_encoding(&mut self, doc: &Document, font: &Dictionary) {
+        let encoding = font.get(b"Encoding").ok().and_then(|e| doc.dereference(e).ok()).map(|(_, e)| e);
+        let base = match encoding {
+            Some(Object::Name(name)) => Some(name.as_slice()),
+            Some(Object::Dictionary(dict)) => {
+                if let Ok(Object::Array(differences)) = dict.get(b"Differences").and_then(|d| doc.dereference(d)).map(|(_, d)| d) {
+                    let mut code = 0u32;
+                    for item in differences {
+                        match item {
+                            Object::Integer(start) => code = *start as u32,
+                            Object::Name(name) => {
+                                self.differences.insert(code, String::from_utf8_lossy(name).into_owned());
+                                code += 1;
+                            },
+                            _ => {},
+                        }
+                    }
+                }
+                dict.get(b"BaseEncoding").and_then(Object::as_name).ok()
+            },
+            _ => None,
+        };
+        // Fonts without an encoding use their built-in one, which is StandardEncoding for Latin fonts
+        self.standard_encoding = base.is_none() || base == Some(b"StandardEncoding".as_slice());
+    }
+
+    fn read_simple_widths(&mut self, doc: &Document, font: &Dictionary, subtype: &str) {
+        let first_char = number(doc, font.get(b"FirstChar").ok()).unwrap_or(0.0) as u32;
+        let widths = font.get(b"Widths").ok()
+            .and_then(|w| doc.dereference(w).ok())
+            .and_then(|(_, w)| w.as_array().ok());
+
+        if let Some(widths) = widths {
+            for (index, width) in widths.iter().enumerate() {
+                if let Some(width) = number(doc, Some(width)) {
+                    self.widths.insert(first_char + index as u32, width);
+                }
+            }
+        }
+
+        self.default_width = font.get(b"FontDescriptor").ok()
+            .and_then(|d| dereference_dict(doc, d))
+            .and_then(|d| number(doc, d.get(b"MissingWidth").ok()))
+            .unwrap_or(0.0);
+
+        if subtype == "Type3" {
+            self.width_scale = font.get(b"FontMatrix").ok()
+                .and_then(|m| doc.dereference(m).ok())
+                .and_then(|(_, m)| m.as_array().ok())
+                .and_then(|m| number(doc, m.first()))
+                .unwrap_or(0.001);
+        }
+
+        if self.widths.is_empty() {
+            let base_font = font.get(b"BaseFont").and_then(Object::as_name_str).unwrap_or("");
+            self.standard_widths = standard_widths(base_font);
+            if base_font.contains("Courier") {
+                self.default_width = 600.0;
+            } else {
+                // Symbol, ZapfDingbats and fonts without any width information
+                self.approximate_widths = self.standard_widths.is_none();
+                self.default_width = 500.0;
+            }
+        }
+    }
+}
+
+/// Width table of a standard 14 font by its base font name; subset prefixes and the
+/// common Arial and Times New Roman aliases are recognized
+fn standard_widths(base_font: &str) -> Option<&'static [u16; 224]> {
+    let name = base_font.split_once('+').map(|(_, name)| name).unwrap_or(base_font);
+    let name = name.replace(['-', ',', ' '], "").to_lowercase();
+    let bold = name.contains("bold");
+    let italic = name.contains("italic") || name.contains("oblique");
+
+    if name.starts_with("helvetica") || name.starts_with("arial") {
+        Some(if bold { &HELVETICA_BOLD_WIDTHS } else { &HELVETICA_WIDTHS })
+    } else if name.starts_with("times") {
+        Some(match (bold, italic) {
+            (true, true) => &TIMES_BOLD_ITALIC_WIDTHS,
+            (true, false) => &TIMES_BOLD_WIDTHS,
+            (false, true) => &TIMES_ITALIC_WIDTHS,
+            (false, false) => &TIMES_ROMAN_WIDTHS,
+        })
+    } else {
+        None
+    }
+}
+
+/// Widths of a CIDFont from its /W array: `c [w1 w2 ...]` or `c_first c_last w`
+fn cid_widths(doc: &Document, descendant: &Dictionary) -> HashMap<u32, f32> {
+    let mut widths = HashMap::new();
+    let array = match descendant.get(b"W").ok().and_then(|w| doc.dereference(w).ok()).and_then(|(_, w)| w.as_array().ok()) {
+        Some(array) => array,
+        None => return widths,
+    };
+
+    let mut index = 0;
+    while index < array.len() {
+        let first = match number(doc, array.get(index)) {
+            Some(first) => first as u32,
+            None => break,
+        };
+        match array.get(index + 1).and_then(|o| doc.dereference(o).ok()).map(|(_, o)| o) {
+            Some(Object::Array(list)) => {
+                for (offset, width) in list.iter().enumerate() {
+                    if let Some(width) = number(doc, Some(width)) {
+                        widths.insert(first + offset as u32, width);
+                    }
+                }
+                index += 2;
+            },
+            Some(_) => {
+                let last = number(doc, array.get(index + 1)).map(|l| l as u32).unwrap_or(first);
+                if let Some(width) = number(doc, array.get(index + 2)) {
+                    for code in first..=last.min(first + MAX_RANGE_LENGTH) {
+                        widths.insert(code, width);
+                    }
+                }
+                index += 3;
+            },
+            None => break,
+        }
+    }
+    widths
+}
+
+/// Parse the bfchar and bfrange mappings of a ToUnicode CMap
+fn parse_to_unicode(data: &[u8]) -> HashMap<u32, String> {
+    #[derive(Debug)]
+    enum Token {
+        Hex(Vec<u8>),
+        ArrayStart,
+        ArrayEnd,
+        Keyword(String),
+    }
+
+    let text = String::from_utf8_lossy(data);
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => {
+                let hex: String = chars.by_ref().take_while(|c| *c != '>').filter(|c| c.is_ascii_hexdigit()).collect();
+                let hex = if hex.len() % 2 == 1 { format!("{}0", hex) } else { hex };
+                let bytes = (0..hex.len()).step_by(2)
+                    .filter_map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+                    .collect();
+                tokens.push(Token::Hex(bytes));
+            },
+            '[' => tokens.push(Token::ArrayStart),
+            ']' => tokens.push(Token::ArrayEnd),
+            c if c.is_alphabetic() => {
+                let mut keyword = c.to_string();
+                while let Some(next) = chars.peek().filter(|c| c.is_alphanumeric()) {
+                    keyword.push(*next);
+                    chars.next();
+                }
+                tokens.push(Token::Keyword(keyword));
+            },
+            _ => {},
+        }
+    }
+
+    let code = |bytes: &[u8]| bytes.iter().fold(0u32, |code, byte| (code << 8) | *byte as u32);
+    let unicode = |bytes: &[u8]| {
+        let units: Vec<u16> = bytes.chunks(2).map(|pair| pair.iter().fold(0u16, |u, b| (u << 8) | *b as u16)).collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    let mut map = HashMap::new();
+    let mut index = 0;
+    let mut section = "";
+    while index < tokens.len() {
+        match (&tokens[index], section) {
+            (Token::Keyword(keyword), _) => {
+                section = match keyword.as_str() {
+                    "beginbfchar" => "bfchar",
+                    "beginbfrange" => "bfrange",
+                    _ => "",
+                };
+                index += 1;
+            },
+            (Token::Hex(source), "bfchar") => {
+                if let Some(Token::Hex(target)) = tokens.get(index + 1) {
+                    map.insert(code(source), unicode(target));
+                }
+                index += 2;
+            },
+            (Token::Hex(low), "bfrange") => {
+                let (low, high) = match tokens.get(index + 1) {
+                    Some(Token::Hex(high)) => (code(low), code(high)),
+                    _ => {
+                        index += 1;
+                        continue;
+                    },
+                };
+                let high = high.min(low.saturating_add(MAX_RANGE_LENGTH));
+                match tokens.get(index + 2) {
+                    Some(Token::Hex(target)) => {
+                        // Consecutive codes map to consecutive values of the last UTF-16 unit
+                        let units: Vec<u16> = target.chunks(2).map(|p| p.iter().fold(0u16, |u, b| (u << 8) | *b as u16)).collect();
+                        if let Some((last, prefix)) = units.split_last() {
+                            for (offset, source) in (low..=high).enumerate() {
+                                let mut value = prefix.to_vec();
+                                value.push(last.wrapping_add(offset as u16));
+                                map.insert(source, String::from_utf16_lossy(&value));
+                            }
+                        }
+                        index += 3;
+                    },
+                    Some(Token::ArrayStart) => {
+                        let mut position = index + 3;
+                        let mut source = low;
+                        while let Some(Token::Hex(target)) = tokens.get(position) {
+                            if source <= high {
+                                map.insert(source, unicode(target));
+                            }
+                            source += 1;
+                            position += 1;
+                        }
+                        index = position + 1;
+                    },
+                    _ => index += 2,
+                }
+            },
+            _ => index += 1,
+        }
+    }
+    map
+}
+
+/// Unicode of a WinAnsiEncoding code
+fn win_ansi_char(code: u32) -> Option<char> {
+    const HIGH: [char; 32] = [
+        '\u{20ac}', '\u{2022}', '\u{201a}', '\u{0192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+        '\u{02c6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{2022}', '\u{017d}', '\u{2022}',
+        '\u{2022}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+        '\u{02dc}', '\u{2122}', '\u{0161}', '\u{203a}', '\u{0153}', '\u{2022}', '\u{017e}', '\u{0178}',
+    ];
+    match code {
+        32..=126 | 160..=255 => char::from_u32(code),
+        128..=159 => Some(HIGH[code as usize - 128]),
+        _ => None,
+    }
+}
+
+/// Unicode of a glyph name: WinAnsi glyph names, `uniXXXX`, `uXXXX` and common ligatures
+fn glyph_name_to_unicode(name: &str) -> Option<String> {
+    match name {
+        "fi" | "fl" | "ff" | "ffi" | "ffl" => return Some(name.to_string()),
+        "minus" => return Some("\u{2212}".to_string()),
+        "nbspace" => return Some("\u{a0}".to_string()),
+        "sfthyphen" => return Some("\u{ad}".to_string()),
+        _ => {},
+    }
+
+    if let Some(index) = WIN_ANSI_NAMES.iter().position(|n| *n == name) {
+        return win_ansi_char(index as u32 + 32).map(String::from);
+    }
+
+    let hex = name.strip_prefix("uni").or_else(|| name.strip_prefix('u'))?;
+    let hex = hex.get(..hex.len().min(6))?;
+    if hex.len() < 4 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32).map(String::from)
+}
+
+fn number(doc: &Document, object: Option<&Object>) -> Option<f32> {
+    let (_, object) = doc.dereference(object?).ok()?;
+    object.as_float().ok()
+}
+
+fn dereference_dict<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    doc.dereference(object).ok().and_then(|(_, o)| o.as_dict().ok())
+}
+
+/// Glyph names of the WinAnsi encoding for codes 32 to 255; unused codes show a bullet
+const WIN_ANSI_NAMES: [&str; 224] = [
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quotesingle",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "at", "A", "B", "C", "D", "E", "F", "G",
+    "H", "I", "J", "K", "L", "M", "N", "O",
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    "grave", "a", "b", "c", "d", "e", "f", "g",
+    "h", "i", "j", "k", "l", "m", "n", "o",
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "bullet",
+    "Euro", "bullet", "quotesinglbase", "florin", "quotedblbase", "ellipsis", "dagger", "daggerdbl",
+    "circumflex", "perthousand", "Scaron", "guilsinglleft", "OE", "bullet", "Zcaron", "bullet",
+    "bullet", "quoteleft", "quoteright", "quotedblleft", "quotedblright", "bullet", "endash", "emdash",
+    "tilde", "trademark", "scaron", "guilsinglright", "oe", "bullet", "zcaron", "Ydieresis",
+    "space", "exclamdown", "cent", "sterling", "currency", "yen", "brokenbar", "section",
+    "dieresis", "copyright", "ordfeminine", "guillemotleft", "logicalnot", "hyphen", "registered", "macron",
+    "degree", "plusminus", "twosuperior", "threesuperior", "acute", "mu", "paragraph", "periodcentered",
+    "cedilla", "onesuperior", "ordmasculine", "guillemotright", "onequarter", "onehalf", "threequarters", "questiondown",
+    "Agrave", "Aacute", "Acircumflex", "Atilde", "Adieresis", "Aring", "AE", "Ccedilla",
+    "Egrave", "Eacute", "Ecircumflex", "Edieresis", "Igrave", "Iacute", "Icircumflex", "Idieresis",
+    "Eth", "Ntilde", "Ograve", "Oacute", "Ocircumflex", "Otilde", "Odieresis", "multiply",
+    "Oslash", "Ugrave", "Uacute", "Ucircumflex", "Udieresis", "Yacute", "Thorn", "germandbls",
+    "agrave", "aacute", "acircumflex", "atilde", "adieresis", "aring", "ae", "ccedilla",
+    "egrave", "eacute", "ecircumflex", "edieresis", "igrave", "iacute", "icircumflex", "idieresis",
+    "eth", "ntilde", "ograve", "oacute", "ocircumflex", "otilde", "odieresis", "divide",
+    "oslash", "ugrave", "uacute", "ucircumflex", "udieresis", "yacute", "thorn", "ydieresis",
+];
+
+const HELVETICA_WIDTHS: [u16; 224] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, 350,
+    556, 350, 222, 556, 333, 1000, 556, 556, 333, 1000, 667, 333, 1000, 350, 611, 350,
+    350, 222, 222, 333, 333, 350, 556, 1000, 333, 1000, 500, 333, 944, 350, 500, 667,
+    278, 333, 556, 556, 556, 556, 260, 556, 333, 737, 370, 556, 584, 333, 737, 333,
+    400, 584, 333, 333, 333, 556, 537, 278, 333, 333, 365, 556, 834, 834, 834, 611,
+    667, 667, 667, 667, 667, 667, 1000, 722, 667, 667, 667, 667, 278, 278, 278, 278,
+    722, 722, 778, 778, 778, 778, 778, 584, 778, 722, 722, 722, 722, 667, 667, 611,
+    556, 556, 556, 556, 556, 556, 889, 500, 556, 556, 556, 556, 278, 278, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 584, 611, 556, 556, 556, 556, 500, 556, 500,
+];
+
+const HELVETICA_BOLD_WIDTHS: [u16; 224] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584, 350,
+    556, 350, 278, 556, 500, 1000, 556, 556, 333, 1000, 667, 333, 1000, 350, 611, 350,
+    350, 278, 278, 500, 500, 350, 556, 1000, 333, 1000, 556, 333, 944, 350, 500, 667,
+    278, 333, 556, 556, 556, 556, 280, 556, 333, 737, 370, 556, 584, 333, 737, 333,
+    400, 584, 333, 333, 333, 611, 556, 278, 333, 333, 365, 556, 834, 834, 834, 611,
+    722, 722, 722, 722, 722, 722, 1000, 722, 667, 667, 667, 667, 278, 278, 278, 278,
+    722, 722, 778, 778, 778, 778, 778, 584, 778, 722, 722, 722, 722, 667, 667, 611,
+    556, 556, 556, 556, 556, 556, 889, 556, 556, 556, 556, 556, 278, 278, 278, 278,
+    611, 611, 611, 611, 611, 611, 611, 584, 611, 611, 611, 611, 611, 556, 611, 556,
+];
+
+const TIMES_ROMAN_WIDTHS: [u16; 224] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444,
+    921, 722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889, 722, 722,
+    556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333, 278, 333, 469, 500,
+    333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500,
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541, 350,
+    500, 350, 333, 500, 444, 1000, 500, 500, 333, 1000, 556, 333, 889, 350, 611, 350,
+    350, 333, 333, 444, 444, 350, 500, 1000, 333, 980, 389, 333, 722, 350, 444, 722,
+    250, 333, 500, 500, 500, 500, 200, 500, 333, 760, 276, 500, 564, 333, 760, 333,
+    400, 564, 300, 300, 333, 500, 453, 250, 333, 300, 310, 500, 750, 750, 750, 444,
+    722, 722, 722, 722, 722, 722, 889, 667, 611, 611, 611, 611, 333, 333, 333, 333,
+    722, 722, 722, 722, 722, 722, 722, 564, 722, 722, 722, 722, 722, 722, 556, 500,
+    444, 444, 444, 444, 444, 444, 667, 444, 444, 444, 444, 444, 278, 278, 278, 278,
+    500, 500, 500, 500, 500, 500, 500, 564, 500, 500, 500, 500, 500, 500, 500, 500,
+];
+
+const TIMES_BOLD_WIDTHS: [u16; 224] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    930, 722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944, 722, 778,
+    611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667, 333, 278, 333, 581, 500,
+    333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833, 556, 500,
+    556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520, 350,
+    500, 350, 333, 500, 500, 1000, 500, 500, 333, 1000, 556, 333, 1000, 350, 667, 350,
+    350, 333, 333, 500, 500, 350, 500, 1000, 333, 1000, 389, 333, 722, 350, 444, 722,
+    250, 333, 500, 500, 500, 500, 220, 500, 333, 747, 300, 500, 570, 333, 747, 333,
+    400, 570, 300, 300, 333, 556, 540, 250, 333, 300, 330, 500, 750, 750, 750, 500,
+    722, 722, 722, 722, 722, 722, 1000, 722, 667, 667, 667, 667, 389, 389, 389, 389,
+    722, 722, 778, 778, 778, 778, 778, 570, 778, 722, 722, 722, 722, 722, 611, 556,
+    500, 500, 500, 500, 500, 500, 722, 444, 444, 444, 444, 444, 278, 278, 278, 278,
+    500, 556, 500, 500, 500, 500, 500, 570, 500, 556, 556, 556, 556, 500, 556, 500,
+];
+
+const TIMES_ITALIC_WIDTHS: [u16; 224] = [
+    250, 333, 420, 500, 500, 833, 778, 214, 333, 333, 500, 675, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 675, 675, 675, 500,
+    920, 611, 611, 667, 722, 611, 611, 722, 722, 333, 444, 667, 556, 833, 667, 722,
+    611, 722, 611, 500, 556, 722, 611, 833, 611, 556, 556, 389, 278, 389, 422, 500,
+    333, 500, 500, 444, 500, 444, 278, 500, 500, 278, 278, 444, 278, 722, 500, 500,
+    500, 500, 389, 389, 278, 500, 444, 667, 444, 444, 389, 400, 275, 400, 541, 350,
+    500, 350, 333, 500, 556, 889, 500, 500, 333, 1000, 500, 333, 944, 350, 556, 350,
+    350, 333, 333, 556, 556, 350, 500, 889, 333, 980, 389, 333, 667, 350, 389, 556,
+    250, 389, 500, 500, 500, 500, 275, 500, 333, 760, 276, 500, 675, 333, 760, 333,
+    400, 675, 300, 300, 333, 500, 523, 250, 333, 300, 310, 500, 750, 750, 750, 500,
+    611, 611, 611, 611, 611, 611, 889, 667, 611, 611, 611, 611, 333, 333, 333, 333,
+    722, 667, 722, 722, 722, 722, 722, 675, 722, 722, 722, 722, 722, 556, 611, 500,
+    500, 500, 500, 500, 500, 500, 667, 444, 444, 444, 444, 444, 278, 278, 278, 278,
+    500, 500, 500, 500, 500, 500, 500, 675, 500, 500, 500, 500, 500, 444, 500, 444,
+];
+
+const TIMES_BOLD_ITALIC_WIDTHS: [u16; 224] = [
+    250, 389, 555, 500, 500, 833, 778, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    832, 667, 667, 667, 722, 667, 667, 722, 778, 389, 500, 667, 611, 889, 722, 722,
+    611, 722, 667, 556, 611, 722, 667, 889, 667, 611, 611, 333, 278, 333, 570, 500,
+    333, 500, 500, 444, 500, 444, 333, 500, 556, 278, 278, 500, 278, 778, 556, 500,
+    500, 500, 389, 389, 278, 556, 444, 667, 500, 444, 389, 348, 220, 348, 570, 350,
+    500, 350, 333, 500, 500, 1000, 500, 500, 333, 1000, 556, 333, 944, 350, 611, 350,
+    350, 333, 333, 500, 500, 350, 500, 1000, 333, 1000, 389, 333, 722, 350, 389, 611,
+    250, 389, 500, 500, 500, 500, 220, 500, 333, 747, 266, 500, 606, 333, 747, 333,
+    400, 570, 300, 300, 333, 576, 500, 250, 333, 300, 300, 500, 750, 750, 750, 500,
+    667, 667, 667, 667, 667, 667, 944, 667, 667, 667, 667, 667, 389, 389, 389, 389,
+    722, 722, 722, 722, 722, 722, 722, 570, 722, 722, 722, 722, 722, 611, 611, 500,
+    500, 500, 500, 500, 500, 500, 722, 444, 444, 444, 444, 444, 278, 278, 278, 278,
+    500, 556, 500, 500, 500, 500, 500, 570, 500, 556, 556, 556, 556, 444, 500, 444,
+];
@@ -0,0 +1,540 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use lopdf::{Dictionary, Document, Object, Stream};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::utils;
+
+/// Field flag bits (PDF 32000-1, 12.7.3.1 and 12.7.4)
+const FLAG_READ_ONLY: i64 = 1;
+const FLAG_REQUIRED: i64 = 1 << 1;
+const FLAG_RADIO: i64 = 1 << 15;
+const FLAG_PUSHBUTTON: i64 = 1 << 16;
+
+/// Custom error type for form operations
+#[derive(Debug)]
+pub enum FormError {
+    Io(io::Error),
+    Pdf(String),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Xml(quick_xml::Error),
+    Other(String),
+}
+
+impl fmt::Display for FormError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormError::Io(e) => write!(f, "I/O error: {}", e),
+            FormError::Pdf(e) => write!(f, "PDF error: {}", e),
+            FormError::Csv(e) => write!(f, "CSV error: {}", e),
+            FormError::Json(e) => write!(f, "JSON error: {}", e),
+            FormError::Xml(e) => write!(f, "XML error: {}", e),
+            FormError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for FormError {}
+
+impl From<io::Error> for FormError {
+    fn from(err: io::Error) -> Self {
+        FormError::Io(err)
+    }
+}
+
+impl From<csv::Error> for FormError {
+    fn from(err: csv::Error) -> Self {
+        FormError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for FormError {
+    fn from(err: serde_json::Error) -> Self {
+        FormError::Json(err)
+    }
+}
+
+impl From<quick_xml::Error> for FormError {
+    fn from(err: quick_xml::Error) -> Self {
+        FormError::Xml(err)
+    }
+}
+
+/// Output formats supported by the forms command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum FormFormat {
+    Json,
+    Csv,
+}
+
+/// A form field with its value
+#[derive(Debug, Clone, Serialize)]
+pub struct FormField {
+    /// Fully qualified field name, e.g. `applicant.address.city`
+    pub name: String,
+    /// text, checkbox, radio, button, choice, signature or xfa
+    pub field_type: String,
+    pub value: Option<String>,
+    pub required: bool,
+    pub read_only: bool,
+}
+
+impl FormField {
+    /// A field counts as filled when it has a value other than the unchecked state
+    fn is_filled(&self) -> bool {
+        self.value.as_deref().map(|v| !v.trim().is_empty() && v != "Off").unwrap_or(false)
+    }
+}
+
+/// The form fields of one document
+#[derive(Debug, Default, Serialize)]
+pub struct FormRecord {
+    pub path: String,
+    /// Values were read from XFA data because the document has no AcroForm fields
+    pub xfa: bool,
+    pub fields: Vec<FormField>,
+    pub missing_required: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Main function to run the form extraction functionality
+pub fn run(
+    input_paths: &[String],
+    format: FormFormat,
+    output_file: Option<&str>,
+    check: bool,
+    schema_file: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(FormError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    // Required fields named by a schema come on top of the ones the forms flag themselves
+    let schema_required = match schema_file {
+        Some(schema_file) => load_schema(Path::new(schema_file))?,
+        None => Vec::new(),
+    };
+    let check = check || schema_file.is_some();
+
+    // Create progress bar
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Reading form fields");
+
+    // Read forms in parallel
+    let mut records: Vec<FormRecord> = pdf_paths
+        .par_iter()
+        .map(|path| {
+            let record = read_form(path);
+            pb.inc(1);
+            record
+        })
+        .collect();
+
+    pb.finish_with_message("Done");
+
+    if check {
+        for record in &mut records {
+            record.missing_required = missing_required(record, &schema_required);
+        }
+    }
+
+    match format {
+        FormFormat::Json => {
+            let json = serde_json::to_string_pretty(&records)?;
+            match output_file {
+                Some(path) => fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+        },
+        FormFormat::Csv => write_csv(&records, output_file)?,
+    }
+
+    if let Some(output_file) = output_file {
+        println!("Successfully wrote form data of {} PDFs to '{}'", records.len(), output_file);
+    }
+
+    if check {
+        let incomplete: Vec<&FormRecord> = records.iter().filter(|r| !r.missing_required.is_empty()).collect();
+        for record in &incomplete {
+            eprintln!("{}: missing {}", record.path, record.missing_required.join(", "));
+        }
+        if !incomplete.is_empty() {
+            return Err(Box::new(FormError::Other(format!(
+                "{} of {} documents are missing required fields", incomplete.len(), records.len()
+            ))));
+        }
+        eprintln!("All {} documents have their required fields filled in", records.len());
+    }
+    Ok(())
+}
+
+/// Read the form fields of a PDF, recording any failure in the `error` field
+pub fn read_form(path: &Path) -> FormRecord {
+    let mut record = FormRecord {
+        path: path.to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+
+    let doc = match Document::load(path) {
+        Ok(doc) => doc,
+        Err(e) => {
+            record.error = Some(FormError::Pdf(format!("Error parsing {}: {}", path.display(), e)).to_string());
+            return record;
+        }
+    };
+
+    let acroform = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"AcroForm").ok())
+        .and_then(|acroform| dereference_dict(&doc, acroform));
+    let acroform = match acroform {
+        Some(acroform) => acroform,
+        None => return record,
+    };
+
+    if let Ok(Object::Array(fields)) = acroform.get(b"Fields").and_then(|f| doc.dereference(f)).map(|(_, f)| f) {
+        let mut visited = HashSet::new();
+        for field in fields {
+            if let Some(field) = dereference_dict(&doc, field) {
+                collect_fields(&doc, field, &Inherited::default(), &mut record.fields, &mut visited);
+            }
+        }
+    }
+
+    // Dynamic XFA forms keep their values only in the XFA datasets packet
+    if record.fields.is_empty() {
+        if let Some(datasets) = xfa_datasets(&doc, acroform) {
+            match xfa_fields(&datasets) {
+                Ok(fields) => {
+                    record.xfa = !fields.is_empty();
+                    record.fields = fields;
+                },
+                Err(e) => record.error = Some(e.to_string()),
+            }
+        }
+    }
+
+    record
+}
+
+/// Attributes that field nodes pass down to their kids
+#[derive(Clone, Default)]
+struct Inherited {
+    name: String,
+    field_type: Option<String>,
+    flags: i64,
+    value: Option<String>,
+}
+
+/// Walk the field tree, emitting one field per terminal node
+fn collect_fields<'a>(
+    doc: &'a Document,
+    node: &'a Dictionary,
+    parent: &Inherited,
+    fields: &mut Vec<FormField>,
+    visited: &mut HashSet<*const Dictionary>,
+) {
+    // Guard against cyclic field trees
+    if !visited.insert(node as *const Dictionary) {
+        return;
+    }
+
+    let mut current = parent.clone();
+    if let Some(partial) = utils::dict_string(doc, node, b"T") {
+        current.name = if parent.name.is_empty() { partial } else { format!("{}.{}", parent.name, partial) };
+    }
+    if let Ok(field_type) = node.get(b"FT").and_then(Object::as_name_str) {
+        current.field_type = Some(field_type.to_string());
+    }
+    if let Ok(flags) = node.get(b"Ff").and_then(|f| doc.dereference(f)).and_then(|(_, f)| f.as_i64()) {
+        current.flags = flags;
+    }
+    if let Ok(value) = node.get(b"V") {
+        current.value = field_value(doc, value);
+    }
+
+    // Kids without a partial name are widget annotations of this field, not fields
+    let kids: Vec<&Dictionary> = match node.get(b"Kids").and_then(|k| doc.dereference(k)).map(|(_, k)| k) {
+        Ok(Object::Array(kids)) => kids.iter().filter_map(|kid| dereference_dict(doc, kid)).collect(),
+        _ => Vec::new(),
+    };
+    let child_fields: Vec<&Dictionary> = kids.into_iter().filter(|kid| kid.has(b"T")).collect();
+
+    if child_fields.is_empty() {
+        if current.name.is_empty() {
+            return;
+        }
+        fields.push(FormField {
+            name: current.name.clone(),
+            field_type: field_type_name(current.field_type.as_deref(), current.flags),
+            value: current.value.clone(),
+            required: current.flags & FLAG_REQUIRED != 0,
+            read_only: current.flags & FLAG_READ_ONLY != 0,
+        });
+    } else {
+        for kid in child_fields {
+            collect_fields(doc, kid, &current, fields, visited);
+        }
+    }
+}
+
+/// Map /FT and the field flags to a readable type name
+fn field_type_name(field_type: Option<&str>, flags: i64) -> String {
+    match field_type {
+        Some("Tx") => "text",
+        Some("Btn") if flags & FLAG_PUSHBUTTON != 0 => "button",
+        Some("Btn") if flags & FLAG_RADIO != 0 => "radio",
+        Some("Btn") => "checkbox",
+        Some("Ch") => "choice",
+        Some("Sig") => "signature",
+        _ => "unknown",
+    }.to_string()
+}
+
+/// Convert a /V entry into text; multiple selections are joined with `;`
+fn field_value(doc: &Document, value: &Object) -> Option<String> {
+    let (_, value) = doc.dereference(value).ok()?;
+    match value {
+        Object::String(bytes, _) => Some(utils::decode_pdf_string(bytes)),
+        Object::Name(name) => Some(String::from_utf8_lossy(name).into_owned()),
+        Object::Integer(i) => Some(i.to_string()),
+        Object::Real(r) => Some(r.to_string()),
+        Object::Boolean(b) => Some(b.to_string()),
+        Object::Array(items) => Some(
+            items.iter().filter_map(|item| field_value(doc, item)).collect::<Vec<_>>().join(";")
+        ),
+        // Signature values are dictionaries; report that the field is signed
+        Object::Dictionary(_) => Some("signed".to_string()),
+        _ => None,
+    }
+}
+
+/// Get the XML of the XFA datasets packet, if the form has one
+fn xfa_datasets(doc: &Document, acroform: &Dictionary) -> Option<String> {
+    let (_, xfa) = doc.dereference(acroform.get(b"XFA").ok()?).ok()?;
+    let stream = match xfa {
+        Object::Stream(stream) => stream,
+        // An array of (packet name, stream) pairs
+        Object::Array(packets) => packets.chunks(2).find_map(|pair| match pair {
+            [Object::String(name, _), stream] if name.as_slice() == b"datasets" => {
+                doc.dereference(stream).ok().and_then(|(_, s)| s.as_stream().ok())
+            },
+            _ => None,
+        })?,
+        _ => return None,
+    };
+    Some(String::from_utf8_lossy(&stream_content(stream)).into_owned())
+}
+
+fn stream_content(stream: &Stream) -> Vec<u8> {
+    if stream.filters().map(|f| f.is_empty()).unwrap_or(true) {
+        stream.content.clone()
+    } else {
+        stream.decompressed_content().unwrap_or_else(|_| stream.content.clone())
+    }
+}
+
+/// Read the leaf elements below `<xfa:data>` as fields named by their element path
+fn xfa_fields(xml: &str) -> Result<Vec<FormField>, FormError> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut fields = Vec::new();
+    // Element names below <data>, with a flag telling whether the element has child elements
+    let mut path: Vec<(String, bool)> = Vec::new();
+    let mut in_data = false;
+    let mut text = String::new();
+
+    let make_field = |path: &[(String, bool)], name: &str, value: String| FormField {
+        name: path.iter().map(|(n, _)| n.as_str()).chain([name]).collect::<Vec<_>>().join("."),
+        field_type: "xfa".to_string(),
+        value: Some(value).filter(|v| !v.is_empty()),
+        required: false,
+        read_only: false,
+    };
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                if !in_data {
+                    in_data = name == "data";
+                    continue;
+                }
+                if let Some(parent) = path.last_mut() {
+                    parent.1 = true;
+                }
+                path.push((name, false));
+                text.clear();
+            },
+            Event::Empty(element) if in_data => {
+                if let Some(parent) = path.last_mut() {
+                    parent.1 = true;
+                }
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                fields.push(make_field(&path, &name, String::new()));
+            },
+            Event::Text(content) if in_data => {
+                text.push_str(&content.unescape()?);
+            },
+            Event::CData(content) if in_data => {
+                text.push_str(&String::from_utf8_lossy(&content));
+            },
+            Event::End(_) if in_data => match path.pop() {
+                Some((name, has_children)) => {
+                    if !has_children {
+                        fields.push(make_field(&path, &name, std::mem::take(&mut text)));
+                    }
+                },
+                // End of <data>
+                None => in_data = false,
+            },
+            Event::Eof => break,
+            _ => {},
+        }
+    }
+
+    Ok(fields)
+}
+
+/// Load required field names from a JSON schema: either an array of names or `{"required": [...]}`
+fn load_schema(path: &Path) -> Result<Vec<String>, FormError> {
+    let schema: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let names = match &schema {
+        serde_json::Value::Array(names) => names,
+        serde_json::Value::Object(object) => match object.get("required") {
+            Some(serde_json::Value::Array(names)) => names,
+            _ => return Err(FormError::Other(format!("{}: expected a \"required\" array", path.display()))),
+        },
+        _ => return Err(FormError::Other(format!("{}: expected an array of field names", path.display()))),
+    };
+    Ok(names.iter().filter_map(|n| n.as_str().map(str::to_string)).collect())
+}
+
+/// Names of required fields (flagged in the form or listed in the schema) without a value
+fn missing_required(record: &FormRecord, schema_required: &[String]) -> Vec<String> {
+    let mut missing: Vec<String> = record.fields.iter()
+        .filter(|field| field.required && !field.is_filled())
+        .map(|field| field.name.clone())
+        .collect();
+
+    for name in schema_required {
+        let filled = record.fields.iter().any(|field| &field.name == name && field.is_filled());
+        if !filled && !missing.contains(name) {
+            missing.push(name.clone());
+        }
+    }
+    missing
+}
+
+fn dereference_dict<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    doc.dereference(object).ok().and_then(|(_, o)| o.as_dict().ok())
+}
+
+/// Write one CSV row per document with one column per field name
+fn write_csv(records: &[FormRecord], output_file: Option<&str>) -> Result<(), FormError> {
+    // Field columns in order of first appearance
+    let mut columns: Vec<&str> = Vec::new();
+    let mut seen = HashSet::new();
+    for field in records.iter().flat_map(|r| &r.fields) {
+        if seen.insert(field.name.as_str()) {
+            columns.push(field.name.as_str());
+        }
+    }
+
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    let mut header = vec!["path", "xfa", "missing_required", "error"];
+    header.extend(&columns);
+    csv_writer.write_record(&header)?;
+
+    for record in records {
+        let mut row = vec![
+            record.path.clone(),
+            record.xfa.to_string(),
+            record.missing_required.join(";"),
+            record.error.clone().unwrap_or_default(),
+        ];
+        for column in &columns {
+            let value = record.fields.iter()
+                .find(|field| field.name == *column)
+                .and_then(|field| field.value.clone())
+                .unwrap_or_default();
+            row.push(value);
+        }
+        csv_writer.write_record(&row)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, value: Option<&str>, required: bool) -> FormField {
+        FormField {
+            name: name.to_string(),
+            field_type: "text".to_string(),
+            value: value.map(str::to_string),
+            required,
+            read_only: false,
+        }
+    }
+
+    #[test]
+    fn names_field_types_from_flags() {
+        assert_eq!(field_type_name(Some("Btn"), FLAG_RADIO), "radio");
+        assert_eq!(field_type_name(Some("Btn"), FLAG_PUSHBUTTON), "button");
+        assert_eq!(field_type_name(Some("Btn"), 0), "checkbox");
+        assert_eq!(field_type_name(Some("Tx"), 0), "text");
+        assert_eq!(field_type_name(None, 0), "unknown");
+    }
+
+    #[test]
+    fn reads_xfa_data_leaves() {
+        let xml = r#"<xfa:datasets xmlns:xfa="http://www.xfa.org/schema/xfa-data/1.0/">
+            <xfa:data>
+                <form1>
+                    <applicant><name>Jane Doe</name><city/></applicant>
+                    <notes><![CDATA[a & b]]></notes>
+                </form1>
+            </xfa:data>
+        </xfa:datasets>"#;
+        let fields: Vec<(String, Option<String>)> = xfa_fields(xml).unwrap()
+            .into_iter().map(|f| (f.name, f.value)).collect();
+        assert_eq!(fields, vec![
+            ("form1.applicant.name".to_string(), Some("Jane Doe".to_string())),
+            ("form1.applicant.city".to_string(), None),
+            ("form1.notes".to_string(), Some("a & b".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn reports_missing_required_fields() {
+        let record = FormRecord {
+            fields: vec![
+                field("name", Some("Jane"), true),
+                field("agree", Some("Off"), true),
+                field("email", Some(" "), false),
+                field("phone", Some("555"), false),
+            ],
+            ..Default::default()
+        };
+        let schema = vec!["email".to_string(), "phone".to_string(), "agree".to_string()];
+        assert_eq!(missing_required(&record, &schema), vec!["agree", "email"]);
+    }
+}
@@ -1,10 +1,18 @@
-use std::path::PathBuf;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 use egui::{Context, Ui, RichText, Color32, TextEdit, Vec2};
 
+use crate::entities::{EntityKind, EntitySummary, Location};
 use super::pdf_viewer::PdfViewer;
 
+/// Number of page links shown per entity before the rest are summarized
+const MAX_ENTITY_LOCATIONS: usize = 20;
+
+/// Result slot of a background entity extraction; stays `None` while it runs
+type EntityJob = Arc<Mutex<Option<Result<Vec<EntitySummary>, String>>>>;
+
 /// Analysis panel component
 pub struct AnalysisPanel {
     keywords: String,
@@ -13,6 +21,13 @@ pub struct AnalysisPanel {
     results: Option<AnalysisResult>,
     is_analyzing: bool,
     error_message: Option<String>,
+    entity_kinds: Vec<(EntityKind, bool)>,
+    entity_filter: String,
+    entity_results: Option<Vec<EntitySummary>>,
+    /// Results of the running entity extraction, filled in by the background thread
+    entity_job: Option<EntityJob>,
+    /// Set when an entity location was opened, so the app can switch to the viewer
+    open_requested: bool,
 }
 
 /// Analysis result
@@ -40,6 +55,11 @@ impl AnalysisPanel {
             results: None,
             is_analyzing: false,
             error_message: None,
+            entity_kinds: EntityKind::ALL.iter().map(|kind| (*kind, true)).collect(),
+            entity_filter: String::new(),
+            entity_results: None,
+            entity_job: None,
+            open_requested: false,
         }
     }
     
@@ -178,7 +198,7 @@ impl AnalysisPanel {
         // Run analysis in a background thread
         std::thread::spawn(move || {
             // Use the stats module to perform analysis
-            match crate::stats::run(&input_paths_clone, &keywords_clone, &output_file, threshold) {
+            match crate::stats::run(&input_paths_clone, &keywords_clone, &output_file, threshold, false, &crate::ocr::OcrArgs::default()) {
                 Ok(_) => {
                     println!("Analysis completed and saved to {}", output_file);
                     
@@ -300,7 +320,7 @@ impl AnalysisPanel {
     }
     
     /// Show the analysis panel in the main content area
-    pub fn show(&mut self, ui: &mut Ui, _ctx: &Context, pdf_viewer: &mut PdfViewer) {
+    pub fn show(&mut self, ui: &mut Ui, ctx: &Context, pdf_viewer: &mut PdfViewer) {
         ui.vertical(|ui| {
             ui.heading("Keyword Analysis");
             
@@ -470,8 +490,142 @@ impl AnalysisPanel {
                     ui.label("Configure the analysis parameters and click Run Analysis");
                 });
             }
+            
+            ui.separator();
+            
+            self.show_entities(ui, ctx, pdf_viewer);
         });
     }
+    
+    /// Whether a document was opened from the panel since the last call
+    pub fn take_open_request(&mut self) -> bool {
+        std::mem::take(&mut self.open_requested)
+    }
+    
+    /// Extract entities from the selected sources in a background thread
+    fn extract_entities(&mut self) {
+        let input_paths: Vec<String> = self.input_paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        let pdf_paths = crate::utils::collect_pdf_paths(&input_paths);
+        if pdf_paths.is_empty() {
+            self.error_message = Some("No PDF files found in the selected sources".to_string());
+            return;
+        }
+        
+        let kinds: Vec<EntityKind> = self.entity_kinds.iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(kind, _)| *kind)
+            .collect();
+        let job = Arc::new(Mutex::new(None));
+        let job_clone = job.clone();
+        
+        std::thread::spawn(move || {
+            // A panic must still finish the job, or the panel would wait for it forever
+            let results = panic::catch_unwind(AssertUnwindSafe(|| {
+                crate::entities::extract_entities(&pdf_paths, &kinds, &indicatif::ProgressBar::hidden())
+            })).map_err(|_| "Entity extraction failed unexpectedly".to_string());
+            *job_clone.lock().unwrap() = Some(results);
+        });
+        
+        self.entity_results = None;
+        self.entity_job = Some(job);
+    }
+    
+    /// Show the entities section: type selection, extraction and results grouped by type
+    fn show_entities(&mut self, ui: &mut Ui, ctx: &Context, pdf_viewer: &mut PdfViewer) {
+        // Pick up the results of a finished extraction
+        if let Some(job) = &self.entity_job {
+            let finished = job.lock().unwrap().take();
+            match finished {
+                Some(Ok(results)) => {
+                    self.entity_results = Some(results);
+                    self.entity_job = None;
+                },
+                Some(Err(e)) => {
+                    self.error_message = Some(e);
+                    self.entity_job = None;
+                },
+                None => ctx.request_repaint(),
+            }
+        }
+        
+        let mut target: Option<Location> = None;
+        
+        ui.collapsing("Entities", |ui| {
+            ui.label("Emails, phone numbers, dates, IBANs and DOIs in the selected sources");
+            
+            ui.horizontal(|ui| {
+                for (kind, enabled) in &mut self.entity_kinds {
+                    ui.checkbox(enabled, kind.label());
+                }
+            });
+            
+            ui.horizontal(|ui| {
+                let running = self.entity_job.is_some();
+                let can_run = !running
+                    && !self.input_paths.is_empty()
+                    && self.entity_kinds.iter().any(|(_, enabled)| *enabled);
+                let button_text = if running { "Extracting..." } else { "Extract Entities" };
+                
+                if ui.add_enabled(can_run, egui::Button::new(button_text)).clicked() {
+                    self.extract_entities();
+                }
+                
+                ui.label("Filter:");
+                ui.text_edit_singleline(&mut self.entity_filter);
+            });
+            
+            let results = match &self.entity_results {
+                Some(results) => results,
+                None => return,
+            };
+            
+            if results.is_empty() {
+                ui.label("No entities found");
+                return;
+            }
+            
+            let filter = self.entity_filter.to_lowercase();
+            for kind in EntityKind::ALL {
+                let entities: Vec<&EntitySummary> = results.iter()
+                    .filter(|entity| entity.kind == kind && entity.value.to_lowercase().contains(&filter))
+                    .collect();
+                if entities.is_empty() {
+                    continue;
+                }
+                
+                let occurrences: usize = entities.iter().map(|entity| entity.count).sum();
+                ui.collapsing(format!("{} ({} distinct, {} occurrences)", kind.label(), entities.len(), occurrences), |ui| {
+                    egui::ScrollArea::vertical().id_source(kind.label()).max_height(250.0).show(ui, |ui| {
+                        for entity in entities {
+                            ui.horizontal_wrapped(|ui| {
+                                ui.label(RichText::new(&entity.value).strong());
+                                ui.label(format!("×{} in {} file(s)", entity.count, entity.documents));
+                                
+                                // Each location opens the document at that page
+                                for location in entity.locations.iter().take(MAX_ENTITY_LOCATIONS) {
+                                    let name = Path::new(&location.path).file_name().unwrap_or_default().to_string_lossy().to_string();
+                                    if ui.small_button(format!("{} p. {}", name, location.page)).on_hover_text(&location.path).clicked() {
+                                        target = Some(location.clone());
+                                    }
+                                }
+                                if entity.locations.len() > MAX_ENTITY_LOCATIONS {
+                                    ui.label(format!("and {} more pages", entity.locations.len() - MAX_ENTITY_LOCATIONS));
+                                }
+                            });
+                        }
+                    });
+                });
+            }
+        });
+        
+        if let Some(location) = target {
+            pdf_viewer.open_at_page(Path::new(&location.path), location.page.saturating_sub(1), None, ctx);
+            self.open_requested = true;
+        }
+    }
 
     fn show_heatmap(&mut self, _ui: &mut Ui, _data: &[f32]) {
         let _matrix_size = Vec2::new(100.0, 100.0);
@@ -8,6 +8,7 @@ use dirs;
 use super::pdf_viewer::PdfViewer;
 use super::search_panel::SearchPanel;
 use super::analysis_panel::AnalysisPanel;
+use super::diff_panel::DiffPanel;
 
 /// The main application state
 pub struct PdfScanApp {
@@ -25,6 +26,9 @@ pub struct PdfScanApp {
     // Analysis functionality
     analysis_panel: AnalysisPanel,
     
+    // Document comparison
+    diff_panel: DiffPanel,
+    
     // Global state
     recent_files: Vec<PathBuf>,
     theme: Theme,
@@ -35,6 +39,7 @@ enum Tab {
     Viewer,
     Search,
     Analysis,
+    Compare,
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -59,6 +64,7 @@ impl PdfScanApp {
             pdf_viewer: PdfViewer::new(),
             search_panel: SearchPanel::new(),
             analysis_panel: AnalysisPanel::new(),
+            diff_panel: DiffPanel::new(),
             recent_files,
             theme: Theme::Dark,
         }
@@ -162,6 +168,9 @@ impl PdfScanApp {
                     if ui.selectable_label(self.current_tab == Tab::Analysis, "📊 Analysis").clicked() {
                         self.current_tab = Tab::Analysis;
                     }
+                    if ui.selectable_label(self.current_tab == Tab::Compare, "🔀 Compare").clicked() {
+                        self.current_tab = Tab::Compare;
+                    }
                 });
                 
                 ui.separator();
@@ -180,6 +189,10 @@ impl PdfScanApp {
                         // Show analysis options
                         self.analysis_panel.show_options(ui, &self.pdf_viewer);
                     },
+                    Tab::Compare => {
+                        // Show the documents to compare
+                        self.diff_panel.show_options(ui, &self.pdf_viewer);
+                    },
                 }
                 
                 // Store the current sidebar width
@@ -203,7 +216,15 @@ impl PdfScanApp {
                 let output_file = save_path.to_string_lossy().to_string();
                 
                 // Use the extract module to save the text
-                match crate::extract::run(&output_file, &input_path) {
+                match crate::extract::run(
+                    &output_file,
+                    &input_path,
+                    crate::layout::LayoutMode::Raw,
+                    false,
+                    None,
+                    crate::extract::ExtractFormat::Text,
+                    &crate::ocr::OcrArgs::default(),
+                ) {
                     Ok(_) => {
                         println!("Successfully extracted text to {}", output_file);
                     },
@@ -247,6 +268,9 @@ impl eframe::App for PdfScanApp {
                         if ui.selectable_label(self.current_tab == Tab::Analysis, "📊 Analysis").clicked() {
                             self.current_tab = Tab::Analysis;
                         }
+                        if ui.selectable_label(self.current_tab == Tab::Compare, "🔀 Compare").clicked() {
+                            self.current_tab = Tab::Compare;
+                        }
                     });
                     
                     ui.separator();
@@ -265,6 +289,10 @@ impl eframe::App for PdfScanApp {
                             // Show analysis options
                             self.analysis_panel.show_options(ui, &self.pdf_viewer);
                         },
+                        Tab::Compare => {
+                            // Show the documents to compare
+                            self.diff_panel.show_options(ui, &self.pdf_viewer);
+                        },
                     }
                     
                     // Store the current sidebar width
@@ -284,6 +312,15 @@ impl eframe::App for PdfScanApp {
                 },
                 Tab::Analysis => {
                     self.analysis_panel.show(ui, ctx, &mut self.pdf_viewer);
+                    if self.analysis_panel.take_open_request() {
+                        self.current_tab = Tab::Viewer;
+                    }
+                },
+                Tab::Compare => {
+                    self.diff_panel.show(ui, ctx, &mut self.pdf_viewer);
+                    if self.diff_panel.take_open_request() {
+                        self.current_tab = Tab::Viewer;
+                    }
                 },
             }
         });
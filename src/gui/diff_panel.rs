@@ -0,0 +1,292 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use egui::{Color32, Context, Label, RichText, Sense, Ui};
+
+use crate::diff::{self, Change, ChangeKind, Word};
+use super::pdf_viewer::PdfViewer;
+
+/// Background of words removed from the old document
+const REMOVED_COLOR: Color32 = Color32::from_rgb(130, 45, 45);
+/// Background of words added in the new document
+const ADDED_COLOR: Color32 = Color32::from_rgb(40, 110, 55);
+
+/// Outcome of a comparison running in the background, filled in when it finishes
+type DiffJob = Arc<Mutex<Option<Result<DiffView, String>>>>;
+
+/// Side-by-side comparison of the text of two PDFs
+pub struct DiffPanel {
+    old_path: Option<PathBuf>,
+    new_path: Option<PathBuf>,
+    result: Option<DiffView>,
+    job: Option<DiffJob>,
+    error_message: Option<String>,
+    /// Only show pages with changes
+    changed_pages_only: bool,
+    /// Set when a page was opened from the panel, so the app can switch to the viewer
+    open_requested: bool,
+}
+
+/// The compared documents, page by page
+struct DiffView {
+    old_path: PathBuf,
+    new_path: PathBuf,
+    old: Vec<PageText>,
+    new: Vec<PageText>,
+    changes: usize,
+    words_removed: usize,
+    words_added: usize,
+}
+
+/// The words of one page, in runs of changed and unchanged text
+struct PageText {
+    page: usize,
+    runs: Vec<(String, bool)>,
+    changed: bool,
+}
+
+impl DiffPanel {
+    pub fn new() -> Self {
+        Self {
+            old_path: None,
+            new_path: None,
+            result: None,
+            job: None,
+            error_message: None,
+            changed_pages_only: false,
+            open_requested: false,
+        }
+    }
+
+    /// Show the document selection in the sidebar
+    pub fn show_options(&mut self, ui: &mut Ui, pdf_viewer: &PdfViewer) {
+        ui.heading("Compare Documents");
+
+        let current = pdf_viewer.current_pdf();
+        for (label, path) in [("Original:", &mut self.old_path), ("Revised:", &mut self.new_path)] {
+            ui.label(label);
+            ui.horizontal(|ui| {
+                let name = path.as_ref()
+                    .map(|p| p.file_name().unwrap_or_default().to_string_lossy().to_string())
+                    .unwrap_or_else(|| "none".to_string());
+                ui.label(RichText::new(name).strong());
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Choose...").clicked() {
+                    if let Some(chosen) = super::app::open_file_dialog() {
+                        *path = Some(chosen);
+                    }
+                }
+                if let Some(current) = current {
+                    if ui.button("Use Current").clicked() {
+                        *path = Some(current.clone());
+                    }
+                }
+            });
+            ui.add_space(5.0);
+        }
+
+        if ui.button("⇅ Swap").clicked() {
+            std::mem::swap(&mut self.old_path, &mut self.new_path);
+        }
+
+        ui.add_space(10.0);
+        ui.checkbox(&mut self.changed_pages_only, "Only pages with changes");
+        ui.add_space(10.0);
+
+        let running = self.job.is_some();
+        let can_run = !running && self.old_path.is_some() && self.new_path.is_some();
+        let button_text = if running { "Comparing..." } else { "Compare" };
+        if ui.add_enabled(can_run, egui::Button::new(button_text)).clicked() {
+            self.compare();
+        }
+    }
+
+    /// Whether a document was opened from the panel since the last call
+    pub fn take_open_request(&mut self) -> bool {
+        std::mem::take(&mut self.open_requested)
+    }
+
+    /// Extract and align the text of both documents in a background thread
+    fn compare(&mut self) {
+        let (old_path, new_path) = match (&self.old_path, &self.new_path) {
+            (Some(old_path), Some(new_path)) => (old_path.clone(), new_path.clone()),
+            _ => return,
+        };
+        let job = Arc::new(Mutex::new(None));
+        let job_clone = job.clone();
+
+        std::thread::spawn(move || {
+            // A panic must still finish the job, or the panel would wait for it forever
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                diff::document_words(&old_path)
+                    .and_then(|old| diff::document_words(&new_path).map(|new| (old, new)))
+                    .map(|(old, new)| {
+                        let changes = diff::diff_words(&old, &new);
+                        DiffView::new((old_path, new_path), &old, &new, &changes)
+                    })
+                    .map_err(|e| e.to_string())
+            })).unwrap_or_else(|_| Err("Comparison failed unexpectedly".to_string()));
+            *job_clone.lock().unwrap() = Some(result);
+        });
+
+        self.error_message = None;
+        self.result = None;
+        self.job = Some(job);
+    }
+
+    /// Show both documents next to each other with the changes highlighted
+    pub fn show(&mut self, ui: &mut Ui, ctx: &Context, pdf_viewer: &mut PdfViewer) {
+        // Pick up the result of a finished comparison
+        if let Some(job) = &self.job {
+            let finished = job.lock().unwrap().take();
+            match finished {
+                Some(Ok(result)) => {
+                    self.result = Some(result);
+                    self.job = None;
+                },
+                Some(Err(e)) => {
+                    self.error_message = Some(e);
+                    self.job = None;
+                },
+                None => ctx.request_repaint(),
+            }
+        }
+
+        ui.heading("Document Comparison");
+
+        if let Some(error) = &self.error_message {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("⚠ Error:").color(Color32::RED).strong());
+                ui.label(error);
+            });
+        }
+
+        if self.job.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Comparing documents...");
+            });
+            return;
+        }
+
+        let result = match &self.result {
+            Some(result) => result,
+            None => {
+                ui.label("Choose an original and a revised PDF in the sidebar, then press Compare.");
+                return;
+            },
+        };
+
+        if result.changes == 0 {
+            ui.label("No differences in the text");
+        } else {
+            ui.label(format!(
+                "{} changes: {} words removed, {} words added",
+                result.changes, result.words_removed, result.words_added
+            ));
+        }
+        ui.separator();
+
+        // Document, page and changed words to show in the viewer
+        let mut target: Option<(PathBuf, usize, Option<String>)> = None;
+        // The original is on the left with its removed words, the revision on the right with its added words
+        let sides = [(&result.old_path, &result.old, true), (&result.new_path, &result.new, false)];
+        ui.columns(2, |columns| {
+            for (side, (column, (path, pages, removed))) in columns.iter_mut().zip(sides).enumerate() {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                column.label(RichText::new(name).strong()).on_hover_text(path.to_string_lossy());
+
+                egui::ScrollArea::vertical().id_source(format!("diff_side_{}", side)).show(column, |ui| {
+                    for page in pages.iter().filter(|page| page.changed || !self.changed_pages_only) {
+                        // The page header opens the document at that page in the viewer, a
+                        // changed run opens it with the run highlighted in the text panel
+                        if ui.link(format!("Page {}", page.page)).clicked() {
+                            target = Some((path.clone(), page.page, None));
+                        }
+                        if let Some(run) = show_page_text(ui, page, removed) {
+                            target = Some((path.clone(), page.page, Some(run)));
+                        }
+                        ui.add_space(8.0);
+                    }
+                });
+            }
+        });
+
+        if let Some((path, page, run)) = target {
+            pdf_viewer.open_at_page(&path, page.saturating_sub(1), run.as_deref(), ctx);
+            self.open_requested = true;
+        }
+    }
+}
+
+impl DiffView {
+    fn new((old_path, new_path): (PathBuf, PathBuf), old: &[Word], new: &[Word], changes: &[Change]) -> Self {
+        let mut old_changed = vec![false; old.len()];
+        let mut new_changed = vec![false; new.len()];
+        for change in changes.iter().filter(|change| change.kind != ChangeKind::Equal) {
+            old_changed[change.old.clone()].iter_mut().for_each(|changed| *changed = true);
+            new_changed[change.new.clone()].iter_mut().for_each(|changed| *changed = true);
+        }
+
+        let changes_only = changes.iter().filter(|change| change.kind != ChangeKind::Equal);
+        Self {
+            old_path,
+            new_path,
+            old: page_texts(old, &old_changed),
+            new: page_texts(new, &new_changed),
+            changes: changes_only.clone().count(),
+            words_removed: changes_only.clone().map(|change| change.old.len()).sum(),
+            words_added: changes_only.map(|change| change.new.len()).sum(),
+        }
+    }
+}
+
+/// Group words by page, merging neighbouring words that are both changed or both unchanged
+fn page_texts(words: &[Word], changed: &[bool]) -> Vec<PageText> {
+    let mut pages: Vec<PageText> = Vec::new();
+    for (word, &is_changed) in words.iter().zip(changed) {
+        if pages.last().map(|page| page.page) != Some(word.page) {
+            pages.push(PageText { page: word.page, runs: Vec::new(), changed: false });
+        }
+        let page = pages.last_mut().unwrap();
+        page.changed |= is_changed;
+        match page.runs.last_mut() {
+            Some((text, run_changed)) if *run_changed == is_changed => {
+                text.push(' ');
+                text.push_str(&word.text);
+            },
+            _ => page.runs.push((word.text.clone(), is_changed)),
+        }
+    }
+    pages
+}
+
+/// Wrapped page text with the changed runs on a coloured background (struck through if
+/// removed). Returns the first word of a changed run that was clicked, as the viewer's text
+/// panel highlights single words more reliably than runs that span line breaks.
+fn show_page_text(ui: &mut Ui, page: &PageText, removed: bool) -> Option<String> {
+    let mut clicked = None;
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 4.0;
+        for (text, changed) in &page.runs {
+            if !*changed {
+                ui.label(text);
+                continue;
+            }
+            let mut run = RichText::new(text)
+                .color(Color32::WHITE)
+                .background_color(if removed { REMOVED_COLOR } else { ADDED_COLOR });
+            if removed {
+                run = run.strikethrough();
+            }
+            let response = ui.add(Label::new(run).sense(Sense::click()))
+                .on_hover_text("Show this change in the viewer");
+            if response.clicked() {
+                clicked = text.split_whitespace().next().map(str::to_string);
+            }
+        }
+    });
+    clicked
+}
@@ -2,6 +2,7 @@ mod app;
 mod pdf_viewer;
 mod search_panel;
 mod analysis_panel;
+mod diff_panel;
 mod theme;
 mod utils;
 
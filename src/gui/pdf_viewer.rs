@@ -10,6 +10,14 @@ use image::{ImageBuffer, Rgba};
 use PdfDocumentMetadataTagType::Title;
 use pdfium_render::prelude::*;
 
+use crate::layout::{self, LayoutMode};
+use crate::outline::{self, OutlineEntry};
+use crate::tables::{self, DetectedTable};
+use crate::utils;
+
+/// Resolution of the page textures (twice the nominal 72 DPI, for a sharper display)
+const VIEWER_DPI: f32 = 144.0;
+
 /// PDF viewer component that renders PDFs using Pdfium
 pub struct PdfViewer {
     current_pdf_path: Option<PathBuf>,
@@ -22,13 +30,17 @@ pub struct PdfViewer {
     pages: HashMap<usize, PageData>,
     page_textures: HashMap<usize, TextureHandle>,
     document_title: String,
-    outline: Vec<OutlineItem>,
+    outline: Vec<OutlineEntry>,
     text_data: Arc<Mutex<String>>,
     loading: bool,
+    /// Page to show once the document being loaded is ready
+    pending_page: Option<usize>,
     document_loaded: Arc<Mutex<Option<Arc<Document>>>>,
     // View mode settings
     show_text_panel: bool,
     view_mode: ViewMode,
+    text_layout: LayoutMode,
+    page_tables: HashMap<usize, Vec<DetectedTable>>,
 }
 
 /// Wrapper around PdfDocument to make it shareable between threads
@@ -53,14 +65,6 @@ struct PageData {
     size: Vec2,
 }
 
-/// Outline item
-struct OutlineItem {
-    title: String,
-    page: usize,
-    level: usize,
-    children: Vec<OutlineItem>,
-}
-
 impl PdfViewer {
     pub fn new() -> Self {
         // Try to initialize pdfium once at startup
@@ -89,16 +93,20 @@ impl PdfViewer {
             outline: Vec::new(),
             text_data: Arc::new(Mutex::new(String::new())),
             loading: false,
+            pending_page: None,
             document_loaded: Arc::new(Mutex::new(None)),
             // Initialize new fields
             show_text_panel: false,
             view_mode: ViewMode::Rendered,
+            text_layout: LayoutMode::Raw,
+            page_tables: HashMap::new(),
         }
     }
     
     /// Load a PDF file
     pub fn load_pdf(&mut self, path: &Path) {
         self.loading = true;
+        self.pending_page = None;
         self.current_pdf_path = Some(path.to_path_buf());
         
         // Create a clone for the async task
@@ -113,6 +121,8 @@ impl PdfViewer {
         self.total_pages = 0;
         self.pages.clear();
         self.page_textures.clear();
+        self.page_tables.clear();
+        self.outline.clear();
         self.document_title = path.file_name().unwrap_or_default().to_string_lossy().to_string();
         
         // Load the PDF in a separate thread
@@ -154,6 +164,7 @@ impl PdfViewer {
             if let Some(doc) = doc_option {
                 // Update state with the loaded document
                 self.document = Some(doc.clone());
+                self.outline = outline::read_outline(&doc);
                 let mut needs_render = false;
 
                 // Try to load the document with Pdfium for rendering
@@ -205,6 +216,10 @@ impl PdfViewer {
 
                 // Document loading complete
                 self.loading = false;
+
+                if let Some(page) = self.pending_page.take() {
+                    self.jump_to_page(page, None, ctx);
+                }
             }
         }
     }
@@ -236,71 +251,29 @@ impl PdfViewer {
                         // Get page dimensions (in points)
                         let width_points = page.width();
                         let height_points = page.height();
-                        
-                        // Create an image buffer at a reasonable resolution
-                        let scale = 2.0;  // Scaling factor for better resolution
-                        let width_px = (width_points.value * scale) as i32;
-                        let height_px = (height_points.value * scale) as i32;
-                        
-                        // Create a new image buffer with white background
-                        let mut img = ImageBuffer::<Rgba<u8>, Vec<u8>>::new(
-                            width_px as u32, 
-                            height_px as u32
-                        );
-                        for pixel in img.pixels_mut() {
-                            *pixel = Rgba([255, 255, 255, 255]);
-                        }
-                        
-                        // Render the page to a bitmap
-                        let config = PdfRenderConfig::new()
-                            .set_target_width(width_px)
-                            .set_target_height(height_px);
-                            
-                        match page.render_with_config(&config) {
-                            Ok(bitmap) => {
-                                // Get the bitmap data using raw_pixels() which is the correct method in pdfium-render 0.8.30
-                                let bitmap_width = bitmap.width() as u32;
-                                let bitmap_height = bitmap.height() as u32;
-                                let bitmap_data = bitmap.as_raw_bytes();
-                                
-                                // Copy bitmap data to our image buffer
-                                for y in 0..height_px as u32 {
-                                    for x in 0..width_px as u32 {
-                                        if x < bitmap_width && y < bitmap_height {
-                                            let idx = (y * bitmap_width + x) as usize * 4;
-                                            
-                                            if idx + 3 < bitmap_data.len() {
-                                                let r = bitmap_data[idx];
-                                                let g = bitmap_data[idx + 1];
-                                                let b = bitmap_data[idx + 2];
-                                                let a = bitmap_data[idx + 3];
-                                                
-                                                img.put_pixel(x, y, Rgba([r, g, b, a]));
-                                            }
-                                        }
-                                    }
-                                }
-                                
+
+                        match utils::render_page_rgba(&page, VIEWER_DPI) {
+                            Ok(img) => {
                                 // Convert to egui texture
-                                let size = [width_px as usize, height_px as usize];
+                                let size = [img.width() as usize, img.height() as usize];
                                 let pixels = img.into_raw();
-                                
+
                                 let color_image = egui::ColorImage::from_rgba_unmultiplied(
                                     size,
                                     &pixels
                                 );
-                                
+
                                 // Load as texture
                                 let texture = ctx.load_texture(
                                     format!("pdf_page_{}", page_num),
                                     color_image,
                                     egui::TextureOptions::default()
                                 );
-                                
+
                                 Some((texture, Vec2::new(width_points.value as f32, height_points.value as f32)))
                             },
                             Err(e) => {
-                                eprintln!("Error rendering page: {:?}", e);
+                                eprintln!("{}", e);
                                 None
                             }
                         }
@@ -311,13 +284,7 @@ impl PdfViewer {
                         self.insert_page_textures(page_num, texture);
                         
                         // Also extract text for this page
-                        let mut page_text = String::new();
-                        
-                        // Try to extract text from the page
-                        if let Ok(page_text_obj) = page.text() {
-                            // Get text from the page
-                            page_text = page_text_obj.to_string();
-                        }
+                        let page_text = page_text(&page, self.text_layout);
                         
                         // Store page data with text and size
                         self.pages.insert(page_num, PageData { 
@@ -413,13 +380,8 @@ impl PdfViewer {
             // Convert usize to u16 for pdfium's page index
             if let Ok(page_index) = u16::try_from(page_num) {
                 if let Ok(page) = pdfium_doc.document.pages().get(page_index) {
-                    let mut page_text = String::new();
-                    
-                    // Try to extract text from the page
-                    if let Ok(page_text_obj) = page.text() {
-                        // Get text from the page
-                        page_text = page_text_obj.to_string();
-                    }
+                    // Extract text from the page in the selected layout
+                    let page_text = page_text(&page, self.text_layout);
                     
                     let width_points = page.width();
                     let height_points = page.height();
@@ -464,6 +426,22 @@ impl PdfViewer {
         }
     }
     
+    /// Detect the tables on a page (cached per page)
+    fn page_tables(&mut self, page_num: usize) -> &[DetectedTable] {
+        if !self.page_tables.contains_key(&page_num) {
+            let mut detected = Vec::new();
+            if let Some(pdfium_doc) = &self.pdfium_document {
+                if let Ok(page_index) = u16::try_from(page_num) {
+                    if let Ok(page) = pdfium_doc.document.pages().get(page_index) {
+                        detected = tables::detect_tables(&page);
+                    }
+                }
+            }
+            self.page_tables.insert(page_num, detected);
+        }
+        &self.page_tables[&page_num]
+    }
+    
     /// Get the current PDF path
     pub fn current_pdf(&self) -> Option<&PathBuf> {
         self.current_pdf_path.as_ref()
@@ -499,6 +477,21 @@ impl PdfViewer {
         }
     }
     
+    /// Open a document at a 0-based page, loading it first unless it is already open. A search
+    /// term is highlighted in the text panel of that page.
+    pub fn open_at_page(&mut self, path: &Path, page_num: usize, search_term: Option<&str>, ctx: &Context) {
+        if let Some(term) = search_term {
+            ctx.memory_mut(|mem| mem.data.insert_temp("search_query".into(), term.to_string()));
+            self.show_text_panel = true;
+        }
+        if self.current_pdf_path.as_deref() == Some(path) && !self.loading {
+            self.jump_to_page(page_num, search_term, ctx);
+        } else {
+            self.load_pdf(path);
+            self.pending_page = Some(page_num);
+        }
+    }
+    
     /// Show the PDF viewer
     pub fn show(&mut self, ui: &mut Ui, ctx: &Context) {
         // Process any loaded document
@@ -662,6 +655,27 @@ impl PdfViewer {
                                 self.extract_page_text(self.current_page);
                             }
                         }
+                        
+                        // Copy a detected table of the current page as tab-separated text,
+                        // which spreadsheets split into cells on paste
+                        ui.separator();
+                        ui.menu_button("📋 Copy Table", |ui| {
+                            let page_tables = self.page_tables(self.current_page);
+                            if page_tables.is_empty() {
+                                ui.label("No tables found on this page");
+                            }
+                            let mut copied = None;
+                            for (index, table) in page_tables.iter().enumerate() {
+                                let label = format!("Table {} ({} × {})", index + 1, table.rows.len(), table.columns());
+                                if ui.button(label).clicked() {
+                                    copied = Some(table.to_delimited(b'\t'));
+                                }
+                            }
+                            if let Some(text) = copied {
+                                ui.output_mut(|output| output.copied_text = text);
+                                ui.close_menu();
+                            }
+                        });
                     });
                 });
             
@@ -670,13 +684,26 @@ impl PdfViewer {
                 match self.view_mode {
                     ViewMode::Rendered => {
                         if self.show_text_panel {
+                            let previous_layout = self.text_layout;
+                            
                             // Split view with rendered PDF and text
                             egui::SidePanel::right("text_panel")
                                 .resizable(true)
                                 .default_width(350.0)
                                 .width_range(200.0..=600.0)
                                 .show_inside(ui, |ui| {
-                                    ui.heading("Extracted Text");
+                                    ui.horizontal(|ui| {
+                                        ui.heading("Extracted Text");
+                                        
+                                        // Text layout selection
+                                        egui::ComboBox::from_id_source("text_layout")
+                                            .selected_text(self.text_layout.to_string())
+                                            .show_ui(ui, |ui| {
+                                                for mode in [LayoutMode::Raw, LayoutMode::ReadingOrder, LayoutMode::Physical] {
+                                                    ui.selectable_value(&mut self.text_layout, mode, mode.to_string());
+                                                }
+                                            });
+                                    });
                                     ui.separator();
                                     
                                     if let Some(page_data) = self.pages.get(&self.current_page) {
@@ -732,6 +759,12 @@ impl PdfViewer {
                                         ui.label("Loading text content...");
                                     }
                                 });
+                            
+                            // Re-extract the page text in the newly selected layout
+                            if self.text_layout != previous_layout {
+                                self.pages.clear();
+                                self.extract_page_text(self.current_page);
+                            }
                         }
                         
                         // Display the rendered PDF content
@@ -870,7 +903,7 @@ impl PdfViewer {
     }
     
     /// Show the document outline in the sidebar
-    pub fn show_outline(&self, ui: &mut Ui) {
+    pub fn show_outline(&mut self, ui: &mut Ui) {
         if self.outline.is_empty() {
             ui.label("No outline available");
             return;
@@ -878,35 +911,66 @@ impl PdfViewer {
         
         ui.heading("Document Outline");
         
-        for item in &self.outline {
-            self.show_outline_item(ui, item);
+        let mut target = None;
+        egui::ScrollArea::vertical()
+            .id_source("outline_scroll")
+            .show(ui, |ui| {
+                for (index, item) in self.outline.iter().enumerate() {
+                    Self::show_outline_item(ui, item, ui.id().with(index), self.current_page, &mut target);
+                }
+            });
+        
+        // Navigate to the clicked bookmark's page
+        if let Some(page_num) = target {
+            let ctx = ui.ctx().clone();
+            self.jump_to_page(page_num, None, &ctx);
         }
     }
     
-    /// Recursively show an outline item and its children
-    fn show_outline_item(&self, ui: &mut Ui, item: &OutlineItem) {
-        ui.horizontal(|ui| {
-            // Indent based on level
-            ui.add_space(item.level as f32 * 10.0);
-            
-            // Highlight if this is the current page
-            let text = if item.page == self.current_page {
-                RichText::new(&item.title).strong().color(ui.visuals().selection.stroke.color)
-            } else {
-                RichText::new(&item.title)
-            };
-            
-            if ui.link(text).clicked() {
-                // In a real implementation, this would scroll to the page
-                // For now, we just set it as the current page
-                // self.current_page = item.page;
+    /// Recursively show an outline item as a collapsible tree node, recording the
+    /// page index of a clicked bookmark in `target`
+    fn show_outline_item(ui: &mut Ui, item: &OutlineEntry, id: egui::Id, current_page: usize, target: &mut Option<usize>) {
+        let page_index = item.page.map(|page| page.saturating_sub(1));
+        
+        // Highlight if this is the current page
+        let title = if item.title.is_empty() { "(untitled)" } else { item.title.as_str() };
+        let text = if page_index == Some(current_page) {
+            RichText::new(title).strong().color(ui.visuals().selection.stroke.color)
+        } else {
+            RichText::new(title)
+        };
+        
+        let mut show_link = |ui: &mut Ui| {
+            match page_index {
+                Some(page_num) => {
+                    if ui.link(text.clone()).on_hover_text(format!("Page {}", page_num + 1)).clicked() {
+                        *target = Some(page_num);
+                    }
+                },
+                // Bookmarks without a resolvable destination are shown but not clickable
+                None => {
+                    ui.label(text.clone().weak());
+                },
             }
-        });
+        };
         
-        // Show children
-        for child in &item.children {
-            self.show_outline_item(ui, child);
+        if item.children.is_empty() {
+            ui.horizontal(|ui| {
+                // Line up with the titles of expandable siblings
+                ui.add_space(ui.spacing().indent);
+                show_link(ui);
+            });
+            return;
         }
+        
+        // Top-level entries start expanded, deeper levels collapsed
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, item.level == 0)
+            .show_header(ui, |ui| show_link(ui))
+            .body(|ui| {
+                for (index, child) in item.children.iter().enumerate() {
+                    Self::show_outline_item(ui, child, id.with(index), current_page, target);
+                }
+            });
     }
 
     /// Open a file dialog
@@ -917,6 +981,14 @@ impl PdfViewer {
     }
 }
 
+/// Get the text of a page in the given layout
+fn page_text(page: &PdfPage, mode: LayoutMode) -> String {
+    match mode {
+        LayoutMode::Raw => page.text().map(|text| text.all()).unwrap_or_default(),
+        mode => layout::render_layout(&layout::page_layout(page), mode),
+    }
+}
+
 /// Extract text from a PDF file using the pdf-extract library
 fn extract_text_from_pdf(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
     let bytes = std::fs::read(path)?;
@@ -2,9 +2,33 @@ use eframe::{NativeOptions, run_native};
 use egui::ViewportBuilder;
 
 mod gui;
+// Modules allowing dead code are shared with the CLI, which uses more of them
+#[allow(dead_code)]
+mod annotations;
+#[allow(dead_code)]
+mod attachments;
+mod coverage;
+#[allow(dead_code)]
+mod diff;
+#[allow(dead_code)]
+mod entities;
 mod extract;
+#[allow(dead_code)]
+mod highlight;
+mod layout;
+mod markup;
+mod ocr;
+#[allow(dead_code)]
+mod outline;
+#[allow(dead_code)]
+mod pages;
+#[allow(dead_code)]
 mod search;
 mod stats;
+#[allow(dead_code)]
+mod tables;
+#[allow(dead_code)]
+mod utils;
 
 fn main() -> Result<(), eframe::Error> {
     // Initialize logging
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use pdfium_render::prelude::*;
+
+use crate::utils;
+
+/// Highlight colour (yellow) as RGB components
+const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 0.92, 0.23];
+
+/// Custom error type for highlighted-hits export
+#[derive(Debug)]
+pub enum HighlightError {
+    Io(io::Error),
+    Pdf(String),
+    Other(String),
+}
+
+impl fmt::Display for HighlightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HighlightError::Io(e) => write!(f, "I/O error: {}", e),
+            HighlightError::Pdf(e) => write!(f, "PDF error: {}", e),
+            HighlightError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for HighlightError {}
+
+impl From<io::Error> for HighlightError {
+    fn from(err: io::Error) -> Self {
+        HighlightError::Io(err)
+    }
+}
+
+impl From<lopdf::Error> for HighlightError {
+    fn from(err: lopdf::Error) -> Self {
+        HighlightError::Pdf(err.to_string())
+    }
+}
+
+/// One occurrence of the phrase: a `[left, bottom, right, top]` box per line it spans
+type Hit = Vec<[f32; 4]>;
+
+/// Write a copy of every PDF into `output_dir` with a highlight annotation over each
+/// occurrence of the phrase. Returns the number of files written.
+pub fn export_highlighted(paths: &[String], search_phrase: &str, output_dir: &Path) -> Result<usize, HighlightError> {
+    if search_phrase.is_empty() {
+        return Err(HighlightError::Other("Highlighting hits needs a search phrase".to_string()));
+    }
+    let pdfium = utils::bind_pdfium().map_err(HighlightError::Other)?;
+    fs::create_dir_all(output_dir)?;
+
+    // Same-named inputs get numbered names, so their copies do not overwrite each other
+    let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    let stems = utils::unique_stems(&paths);
+    let mut written = 0;
+    for (path, stem) in paths.iter().zip(&stems) {
+        let hits = match find_hits(&pdfium, path, search_phrase) {
+            Ok(hits) => hits,
+            Err(e) => {
+                eprintln!("Error locating hits in {}: {}", path.display(), e);
+                continue;
+            },
+        };
+        if hits.is_empty() {
+            // Matches found only through OCR or in annotations have no text boxes to mark
+            eprintln!("Warning: No hit in the text layer of '{}' to highlight", path.display());
+            continue;
+        }
+
+        let output = output_path(stem, output_dir);
+        match write_highlighted(path, &output, &hits, search_phrase) {
+            Ok(count) => {
+                println!("Highlighted {} hits in '{}'", count, output.display());
+                written += 1;
+            },
+            Err(e) => eprintln!("Error highlighting {}: {}", path.display(), e),
+        }
+    }
+    Ok(written)
+}
+
+/// Locate every occurrence of the phrase with Pdfium, by 1-based page number
+fn find_hits(pdfium: &Pdfium, path: &Path, search_phrase: &str) -> Result<BTreeMap<usize, Vec<Hit>>, HighlightError> {
+    let document = pdfium.load_pdf_from_file(path, None)
+        .map_err(|e| HighlightError::Pdf(format!("Error loading {}: {}", path.display(), e)))?;
+    // Same semantics as the text search: case-sensitive substring matches
+    let options = PdfSearchOptions::new().match_case(true);
+
+    let mut hits = BTreeMap::new();
+    for (index, page) in document.pages().iter().enumerate() {
+        let text = match page.text() {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        let search = match text.search(search_phrase, &options) {
+            Ok(search) => search,
+            Err(_) => continue,
+        };
+
+        let mut page_hits = Vec::new();
+        while let Some(segments) = search.find_next() {
+            let boxes: Hit = segments.iter()
+                .map(|segment| {
+                    let bounds = segment.bounds();
+                    [bounds.left().value, bounds.bottom().value, bounds.right().value, bounds.top().value]
+                })
+                .filter(|rect| rect[2] > rect[0] && rect[3] > rect[1])
+                .collect();
+            if !boxes.is_empty() {
+                page_hits.push(boxes);
+            }
+        }
+        if !page_hits.is_empty() {
+            hits.insert(index + 1, page_hits);
+        }
+    }
+    Ok(hits)
+}
+
+/// `<stem>_highlighted.pdf` in the output directory
+fn output_path(stem: &str, output_dir: &Path) -> PathBuf {
+    output_dir.join(format!("{}_highlighted.pdf", stem))
+}
+
+/// Copy a PDF with highlight annotations added over the hits. Returns the number of annotations.
+fn write_highlighted(input: &Path, output: &Path, hits: &BTreeMap<usize, Vec<Hit>>, search_phrase: &str) -> Result<usize, HighlightError> {
+    let mut doc = Document::load(input)?;
+    if doc.is_encrypted() {
+        return Err(HighlightError::Other("Encrypted PDFs cannot be annotated".to_string()));
+    }
+    let page_ids = doc.get_pages();
+    let modified = utils::encode_pdf_string(&chrono::Local::now().format("D:%Y%m%d%H%M%S").to_string());
+
+    let mut count = 0;
+    for (page_number, page_hits) in hits {
+        let page_id = match page_ids.get(&(*page_number as u32)) {
+            Some(id) => *id,
+            None => continue,
+        };
+        let mut added = Vec::new();
+        for hit in page_hits {
+            added.push(add_highlight(&mut doc, page_id, hit, search_phrase, &modified));
+        }
+        count += added.len();
+        append_annotations(&mut doc, page_id, added)?;
+    }
+
+    doc.save(output)?;
+    Ok(count)
+}
+
+/// Create a highlight annotation (with an appearance stream, for readers that do not draw their own)
+fn add_highlight(doc: &mut Document, page_id: ObjectId, hit: &Hit, search_phrase: &str, modified: &Object) -> Object {
+    let left = hit.iter().map(|r| r[0]).fold(f32::MAX, f32::min);
+    let bottom = hit.iter().map(|r| r[1]).fold(f32::MAX, f32::min);
+    let right = hit.iter().map(|r| r[2]).fold(f32::MIN, f32::max);
+    let top = hit.iter().map(|r| r[3]).fold(f32::MIN, f32::max);
+
+    // Quad points go upper-left, upper-right, lower-left, lower-right, as common readers expect
+    let quad_points: Vec<Object> = hit.iter()
+        .flat_map(|r| [r[0], r[3], r[2], r[3], r[0], r[1], r[2], r[1]])
+        .map(Object::Real)
+        .collect();
+    let color: Vec<Object> = HIGHLIGHT_COLOR.iter().map(|&c| Object::Real(c)).collect();
+
+    let mut content = format!("/GS0 gs {} {} {} rg\n", HIGHLIGHT_COLOR[0], HIGHLIGHT_COLOR[1], HIGHLIGHT_COLOR[2]);
+    for r in hit {
+        content.push_str(&format!("{:.2} {:.2} {:.2} {:.2} re f\n", r[0], r[1], r[2] - r[0], r[3] - r[1]));
+    }
+    let appearance = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![left.into(), bottom.into(), right.into(), top.into()],
+            "Resources" => dictionary! {
+                "ExtGState" => dictionary! {
+                    "GS0" => dictionary! { "Type" => "ExtGState", "BM" => "Multiply" },
+                },
+            },
+        },
+        content.into_bytes(),
+    ));
+
+    let annotation = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Highlight",
+        "Rect" => vec![left.into(), bottom.into(), right.into(), top.into()],
+        "QuadPoints" => quad_points,
+        "C" => color,
+        "F" => 4,
+        "P" => page_id,
+        "T" => Object::string_literal("pdfscan"),
+        "Contents" => utils::encode_pdf_string(search_phrase),
+        "M" => modified.clone(),
+        "AP" => dictionary! { "N" => appearance },
+    });
+    Object::Reference(annotation)
+}
+
+/// Add annotation references to a page, keeping an existing /Annots array (direct or indirect)
+fn append_annotations(doc: &mut Document, page_id: ObjectId, added: Vec<Object>) -> Result<(), HighlightError> {
+    let existing = doc.get_dictionary(page_id)?.get(b"Annots").ok().cloned();
+    match existing {
+        Some(Object::Reference(id)) => {
+            if let Ok(Object::Array(array)) = doc.get_object_mut(id) {
+                array.extend(added);
+                return Ok(());
+            }
+            doc.get_dictionary_mut(page_id)?.set("Annots", added);
+        },
+        Some(Object::Array(mut array)) => {
+            array.extend(added);
+            doc.get_dictionary_mut(page_id)?.set("Annots", array);
+        },
+        _ => doc.get_dictionary_mut(page_id)?.set("Annots", added),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One page whose /Annots array is an indirect object holding one existing annotation
+    fn page_with_indirect_annots() -> (Document, ObjectId, ObjectId) {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let existing = doc.add_object(dictionary! { "Type" => "Annot", "Subtype" => "Text" });
+        let annots_id = doc.add_object(vec![Object::Reference(existing)]);
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Annots" => annots_id,
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1,
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        (doc, page_id, annots_id)
+    }
+
+    #[test]
+    fn numbers_outputs_of_same_named_inputs() {
+        let output_dir = Path::new("out");
+        let paths: Vec<PathBuf> = ["a/report.pdf", "b/report.pdf", "c/Report.pdf"].into_iter().map(PathBuf::from).collect();
+        let outputs: Vec<PathBuf> = utils::unique_stems(&paths).iter().map(|stem| output_path(stem, output_dir)).collect();
+        assert_eq!(outputs, vec![
+            output_dir.join("report_highlighted.pdf"),
+            output_dir.join("report_2_highlighted.pdf"),
+            output_dir.join("Report_3_highlighted.pdf"),
+        ]);
+    }
+
+    #[test]
+    fn highlights_span_all_lines_of_a_hit() {
+        let (mut doc, page_id, annots_id) = page_with_indirect_annots();
+        let hit: Hit = vec![[10.0, 700.0, 200.0, 712.0], [10.0, 686.0, 60.0, 698.0]];
+        let modified = Object::string_literal("D:20240101000000");
+        let added = add_highlight(&mut doc, page_id, &hit, "needle", &modified);
+        append_annotations(&mut doc, page_id, vec![added.clone()]).unwrap();
+
+        // The existing indirect array is extended rather than replaced
+        let annots = doc.get_object(annots_id).and_then(Object::as_array).unwrap();
+        assert_eq!(annots.len(), 2);
+
+        let annotation = doc.get_dictionary(added.as_reference().unwrap()).unwrap();
+        let rect: Vec<f32> = annotation.get(b"Rect").and_then(Object::as_array).unwrap()
+            .iter().map(|v| v.as_float().unwrap()).collect();
+        assert_eq!(rect, vec![10.0, 686.0, 200.0, 712.0]);
+        assert_eq!(annotation.get(b"QuadPoints").and_then(Object::as_array).unwrap().len(), 16);
+    }
+}
@@ -0,0 +1,524 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use clap::ValueEnum;
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+use indicatif::ProgressBar;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::utils;
+
+/// Quality used when images are re-encoded as JPEG
+const JPEG_QUALITY: u8 = 90;
+
+/// Custom error type for image export
+#[derive(Debug)]
+pub enum ImageExportError {
+    Io(io::Error),
+    Pdf(String),
+    Image(image::ImageError),
+    Unsupported(String),
+    Other(String),
+}
+
+impl fmt::Display for ImageExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageExportError::Io(e) => write!(f, "I/O error: {}", e),
+            ImageExportError::Pdf(e) => write!(f, "PDF error: {}", e),
+            ImageExportError::Image(e) => write!(f, "Image error: {}", e),
+            ImageExportError::Unsupported(e) => write!(f, "Unsupported image: {}", e),
+            ImageExportError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ImageExportError {}
+
+impl From<io::Error> for ImageExportError {
+    fn from(err: io::Error) -> Self {
+        ImageExportError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for ImageExportError {
+    fn from(err: image::ImageError) -> Self {
+        ImageExportError::Image(err)
+    }
+}
+
+/// Output encoding of exported images
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ImageOutputFormat {
+    /// Keep JPEG images as they are stored, write everything else as PNG
+    Auto,
+    Png,
+    Jpeg,
+}
+
+/// Options of the images command
+#[derive(Debug, Clone, Copy)]
+pub struct ImageFilter {
+    pub min_width: u32,
+    pub min_height: u32,
+}
+
+/// Counters reported at the end of an export
+#[derive(Debug, Default)]
+struct ExportStats {
+    exported: usize,
+    duplicates: usize,
+    too_small: usize,
+    unsupported: usize,
+}
+
+impl ExportStats {
+    fn add(&mut self, other: ExportStats) {
+        self.exported += other.exported;
+        self.duplicates += other.duplicates;
+        self.too_small += other.too_small;
+        self.unsupported += other.unsupported;
+    }
+}
+
+/// Main function to run the image export functionality
+pub fn run(
+    output_dir: &str,
+    input_paths: &[String],
+    format: ImageOutputFormat,
+    filter: ImageFilter,
+) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(ImageExportError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    let output_dir = Path::new(output_dir);
+    fs::create_dir_all(output_dir)?;
+
+    // Create progress bar
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Exporting images");
+
+    // Hashes of the image streams already written, shared across documents
+    let seen = Mutex::new(HashSet::new());
+
+    // Same-named inputs from different directories get numbered stems
+    let stems = utils::unique_stems(&pdf_paths);
+
+    let results: Vec<ExportStats> = pdf_paths
+        .par_iter()
+        .zip(&stems)
+        .map(|(path, stem)| {
+            let stats = export_pdf_images(path, stem, output_dir, format, filter, &seen)
+                .unwrap_or_else(|e| {
+                    eprintln!("Error processing {}: {}", path.display(), e);
+                    ExportStats::default()
+                });
+            pb.inc(1);
+            stats
+        })
+        .collect();
+
+    pb.finish_with_message("Done");
+
+    let mut totals = ExportStats::default();
+    for stats in results {
+        totals.add(stats);
+    }
+
+    println!(
+        "Exported {} images from {} PDFs to '{}' ({} duplicates, {} below minimum size, {} unsupported skipped)",
+        totals.exported, pdf_paths.len(), output_dir.display(),
+        totals.duplicates, totals.too_small, totals.unsupported
+    );
+    Ok(())
+}
+
+/// Export the image XObjects of every page of a PDF, naming the files after `stem`
+fn export_pdf_images(
+    path: &Path,
+    stem: &str,
+    output_dir: &Path,
+    format: ImageOutputFormat,
+    filter: ImageFilter,
+    seen: &Mutex<HashSet<String>>,
+) -> Result<ExportStats, ImageExportError> {
+    let doc = Document::load(path)
+        .map_err(|e| ImageExportError::Pdf(format!("Error loading {}: {}", path.display(), e)))?;
+
+    let mut stats = ExportStats::default();
+    for (page_number, page_id) in doc.get_pages() {
+        let mut images = Vec::new();
+        page_images(&doc, page_id, &mut images);
+
+        for (index, image_id) in images.into_iter().enumerate() {
+            let stream = match doc.get_object(image_id).and_then(Object::as_stream) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+
+            let (width, height) = match image_size(&stream.dict) {
+                Some(size) => size,
+                None => {
+                    eprintln!("Skipping image {} on page {} of {}: missing or invalid size", index + 1, page_number, path.display());
+                    stats.unsupported += 1;
+                    continue;
+                },
+            };
+            if width < filter.min_width || height < filter.min_height {
+                stats.too_small += 1;
+                continue;
+            }
+
+            // Identical images (e.g. logos repeated on every page) are written once
+            let hash = utils::to_hex(&Sha256::digest(&stream.content));
+            if !seen.lock().unwrap().insert(hash) {
+                stats.duplicates += 1;
+                continue;
+            }
+
+            let base_name = format!("{}_page{}_img{}", stem, page_number, index + 1);
+            match write_image(&doc, stream, width, height, format, &output_dir.join(base_name)) {
+                Ok(()) => stats.exported += 1,
+                Err(ImageExportError::Unsupported(reason)) => {
+                    eprintln!("Skipping image {} on page {} of {}: {}", index + 1, page_number, path.display(), reason);
+                    stats.unsupported += 1;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Width and height of an image XObject; `None` when either is missing, zero or out of range
+pub fn image_size(dict: &Dictionary) -> Option<(u32, u32)> {
+    let dimension = |key: &[u8]| dict.get(key).and_then(Object::as_i64).ok()
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|v| *v > 0);
+    Some((dimension(b"Width")?, dimension(b"Height")?))
+}
+
+/// Collect the image XObjects used by a page, including those inside form XObjects
+fn page_images(doc: &Document, page_id: ObjectId, images: &mut Vec<ObjectId>) {
+    let (resources, resource_ids) = doc.get_page_resources(page_id);
+    let mut visited = HashSet::new();
+
+    if let Some(resources) = resources {
+        resource_images(doc, resources, images, &mut visited);
+    }
+    for resource_id in resource_ids {
+        if let Ok(resources) = doc.get_dictionary(resource_id) {
+            resource_images(doc, resources, images, &mut visited);
+        }
+    }
+}
+
+/// Walk the /XObject entries of a resource dictionary
+fn resource_images(doc: &Document, resources: &Dictionary, images: &mut Vec<ObjectId>, visited: &mut HashSet<ObjectId>) {
+    let xobjects = match resources.get(b"XObject").and_then(|x| doc.dereference(x)) {
+        Ok((_, Object::Dictionary(xobjects))) => xobjects,
+        _ => return,
+    };
+
+    for (_, reference) in xobjects.iter() {
+        let id = match reference.as_reference() {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        if !visited.insert(id) {
+            continue;
+        }
+        let stream = match doc.get_object(id).and_then(Object::as_stream) {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+
+        match stream.dict.get(b"Subtype").and_then(Object::as_name_str) {
+            Ok("Image") => images.push(id),
+            Ok("Form") => {
+                if let Ok((_, Object::Dictionary(form_resources))) = stream.dict.get(b"Resources").and_then(|r| doc.dereference(r)) {
+                    resource_images(doc, form_resources, images, visited);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Write one image stream next to `base_path`, choosing the extension from the output format
+fn write_image(
+    doc: &Document,
+    stream: &Stream,
+    width: u32,
+    height: u32,
+    format: ImageOutputFormat,
+    base_path: &Path,
+) -> Result<(), ImageExportError> {
+    let filters = stream.filters().unwrap_or_default();
+    let is_jpeg = filters.last().map(|f| f == "DCTDecode").unwrap_or(false);
+
+    if is_jpeg && filters.len() == 1 {
+        return match format {
+            // Keep the original JPEG data untouched
+            ImageOutputFormat::Auto | ImageOutputFormat::Jpeg => {
+                fs::write(base_path.with_extension("jpg"), &stream.content)?;
+                Ok(())
+            },
+            ImageOutputFormat::Png => {
+                let image = image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg)?;
+                image.save_with_format(base_path.with_extension("png"), ImageFormat::Png)?;
+                Ok(())
+            },
+        };
+    }
+
+    let image = decode_image(doc, stream, width, height)?;
+    match format {
+        ImageOutputFormat::Auto | ImageOutputFormat::Png => {
+            image.save_with_format(base_path.with_extension("png"), ImageFormat::Png)?;
+        },
+        ImageOutputFormat::Jpeg => {
+            let mut file = fs::File::create(base_path.with_extension("jpg"))?;
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, JPEG_QUALITY)
+                .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))?;
+        },
+    }
+    Ok(())
+}
+
+/// Color space of an image, reduced to what can be converted to gray or RGB
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Indexed { base: Box<ColorSpace>, palette: Vec<u8> },
+}
+
+impl ColorSpace {
+    fn components(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Indexed { .. } => 1,
+            ColorSpace::Rgb => 3,
+            ColorSpace::Cmyk => 4,
+        }
+    }
+}
+
+/// Resolve the /ColorSpace of an image dictionary
+fn color_space(doc: &Document, object: &Object) -> Result<ColorSpace, ImageExportError> {
+    let object = doc.dereference(object).map(|(_, o)| o).unwrap_or(object);
+    match object {
+        Object::Name(name) => match name.as_slice() {
+            b"DeviceGray" | b"CalGray" | b"G" => Ok(ColorSpace::Gray),
+            b"DeviceRGB" | b"CalRGB" | b"RGB" => Ok(ColorSpace::Rgb),
+            b"DeviceCMYK" | b"CMYK" => Ok(ColorSpace::Cmyk),
+            other => Err(ImageExportError::Unsupported(format!(
+                "color space {}", String::from_utf8_lossy(other)
+            ))),
+        },
+        Object::Array(parts) => {
+            let family = parts.first().and_then(|p| p.as_name_str().ok()).unwrap_or_default();
+            match family {
+                "ICCBased" => {
+                    // The number of components decides the device space
+                    let components = parts.get(1)
+                        .and_then(|p| doc.dereference(p).ok())
+                        .and_then(|(_, p)| p.as_stream().ok())
+                        .and_then(|s| s.dict.get(b"N").and_then(Object::as_i64).ok())
+                        .unwrap_or(3);
+                    match components {
+                        1 => Ok(ColorSpace::Gray),
+                        4 => Ok(ColorSpace::Cmyk),
+                        _ => Ok(ColorSpace::Rgb),
+                    }
+                },
+                "Indexed" | "I" => {
+                    let base = parts.get(1)
+                        .ok_or_else(|| ImageExportError::Unsupported("indexed color space without base".to_string()))?;
+                    let palette = match parts.get(3).map(|p| doc.dereference(p).map(|(_, p)| p).unwrap_or(p)) {
+                        Some(Object::String(bytes, _)) => bytes.clone(),
+                        Some(Object::Stream(stream)) => stream.decompressed_content().unwrap_or_else(|_| stream.content.clone()),
+                        _ => return Err(ImageExportError::Unsupported("indexed color space without palette".to_string())),
+                    };
+                    Ok(ColorSpace::Indexed { base: Box::new(color_space(doc, base)?), palette })
+                },
+                "CalGray" => Ok(ColorSpace::Gray),
+                "CalRGB" | "Lab" => Ok(ColorSpace::Rgb),
+                other => Err(ImageExportError::Unsupported(format!("color space {}", other))),
+            }
+        },
+        _ => Err(ImageExportError::Unsupported("missing color space".to_string())),
+    }
+}
+
+/// Decode the samples of a non-JPEG image stream into a gray or RGB image
+pub fn decode_image(doc: &Document, stream: &Stream, width: u32, height: u32) -> Result<DynamicImage, ImageExportError> {
+    let filters = stream.filters().unwrap_or_default();
+    let data = if filters.is_empty() {
+        stream.content.clone()
+    } else {
+        // lopdf refuses to decompress image streams, so decode a copy without the image subtype
+        let mut plain = stream.clone();
+        plain.dict.remove(b"Subtype");
+        plain.decompressed_content()
+            .map_err(|_| ImageExportError::Unsupported(format!("filter {}", filters.join(", "))))?
+    };
+
+    let is_mask = stream.dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false);
+    let space = if is_mask {
+        ColorSpace::Gray
+    } else {
+        stream.dict.get(b"ColorSpace")
+            .map_err(|_| ImageExportError::Unsupported("missing color space".to_string()))
+            .and_then(|cs| color_space(doc, cs))?
+    };
+    let bits = if is_mask {
+        1
+    } else {
+        stream.dict.get(b"BitsPerComponent").and_then(Object::as_i64).unwrap_or(8) as usize
+    };
+    if ![1, 2, 4, 8, 16].contains(&bits) {
+        return Err(ImageExportError::Unsupported(format!("{} bits per component", bits)));
+    }
+
+    // A /Decode array of [1 0] inverts gray samples (common for scanned masks)
+    let inverted = stream.dict.get(b"Decode").and_then(Object::as_array)
+        .map(|d| d.first().and_then(|v| v.as_float().ok()) == Some(1.0))
+        .unwrap_or(false);
+
+    let is_indexed = matches!(space, ColorSpace::Indexed { .. });
+    let samples = unpack_samples(&data, width as usize, height as usize, space.components(), bits, !is_indexed)?;
+
+    match &space {
+        ColorSpace::Gray => {
+            let pixels = samples.iter().map(|v| if inverted { 255 - v } else { *v }).collect();
+            GrayImage::from_raw(width, height, pixels)
+                .map(DynamicImage::ImageLuma8)
+                .ok_or_else(|| ImageExportError::Other("Image data is truncated".to_string()))
+        },
+        _ => {
+            let pixels = to_rgb(&space, &samples);
+            RgbImage::from_raw(width, height, pixels)
+                .map(DynamicImage::ImageRgb8)
+                .ok_or_else(|| ImageExportError::Other("Image data is truncated".to_string()))
+        },
+    }
+}
+
+/// Unpack rows of 1/2/4/8/16-bit samples into one byte per sample, scaled to 0..=255
+/// unless `scale` is false (palette indices of indexed images)
+fn unpack_samples(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    components: usize,
+    bits: usize,
+    scale: bool,
+) -> Result<Vec<u8>, ImageExportError> {
+    if width == 0 || height == 0 {
+        return Err(ImageExportError::Unsupported("image without width or height".to_string()));
+    }
+    // Crafted dimensions must not wrap around and pass the truncation check
+    let too_large = || ImageExportError::Unsupported(format!("image of {} x {} pixels is too large", width, height));
+    let row_samples = width.checked_mul(components).ok_or_else(too_large)?;
+    let row_bytes = row_samples.checked_mul(bits).ok_or_else(too_large)?.div_ceil(8);
+    let sample_count = row_samples.checked_mul(height).ok_or_else(too_large)?;
+    if data.len() < row_bytes.checked_mul(height).ok_or_else(too_large)? {
+        return Err(ImageExportError::Other("Image data is truncated".to_string()));
+    }
+
+    let max = (1u32 << bits.min(8)) - 1;
+    let mut samples = Vec::with_capacity(sample_count);
+    for row in data.chunks(row_bytes).take(height) {
+        for index in 0..row_samples {
+            let value = match bits {
+                8 => row[index] as u32,
+                16 => row[index * 2] as u32,
+                _ => {
+                    let bit = index * bits;
+                    let byte = row[bit / 8] as u32;
+                    (byte >> (8 - bits - bit % 8)) & max
+                },
+            };
+            samples.push(if scale { (value * 255 / max) as u8 } else { value as u8 });
+        }
+    }
+    Ok(samples)
+}
+
+/// Convert 8-bit samples to RGB triples
+fn to_rgb(space: &ColorSpace, samples: &[u8]) -> Vec<u8> {
+    match space {
+        ColorSpace::Gray => samples.iter().flat_map(|g| [*g, *g, *g]).collect(),
+        ColorSpace::Rgb => samples.to_vec(),
+        ColorSpace::Cmyk => samples.chunks(4).flat_map(|cmyk| {
+            let k = 255 - cmyk[3] as u32;
+            let channel = |c: u8| ((255 - c as u32) * k / 255) as u8;
+            [channel(cmyk[0]), channel(cmyk[1]), channel(cmyk[2])]
+        }).collect(),
+        ColorSpace::Indexed { base, palette } => {
+            // Palette entries are 8-bit samples of the base color space
+            let entry_size = base.components();
+            samples.iter().flat_map(|index| {
+                let start = *index as usize * entry_size;
+                match palette.get(start..start + entry_size) {
+                    Some(entry) => {
+                        let rgb = to_rgb(base, entry);
+                        [rgb[0], rgb[1], rgb[2]]
+                    },
+                    None => [0, 0, 0],
+                }
+            }).collect()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_missing_or_zero_sizes() {
+        let mut dict = Dictionary::new();
+        dict.set("Width", 0);
+        dict.set("Height", 10);
+        assert_eq!(image_size(&dict), None);
+        dict.set("Width", -4);
+        assert_eq!(image_size(&dict), None);
+        dict.set("Width", 3);
+        assert_eq!(image_size(&dict), Some((3, 10)));
+        dict.remove(b"Height");
+        assert_eq!(image_size(&dict), None);
+    }
+
+    #[test]
+    fn unpacks_packed_samples() {
+        // Rows are padded to whole bytes: 3 one-bit pixels per row
+        let samples = unpack_samples(&[0b1010_0000, 0b0110_0000], 3, 2, 1, 1, true).unwrap();
+        assert_eq!(samples, vec![255, 0, 255, 0, 255, 255]);
+        let indices = unpack_samples(&[0x1F], 2, 1, 1, 4, false).unwrap();
+        assert_eq!(indices, vec![1, 15]);
+        assert!(unpack_samples(&[0xFF], 3, 2, 1, 8, true).is_err());
+        assert!(unpack_samples(&[], 0, 0, 1, 8, true).is_err());
+        let huge = u32::MAX as usize;
+        assert!(matches!(unpack_samples(&[0xFF; 16], huge, huge, 4, 16, true), Err(ImageExportError::Unsupported(_))));
+    }
+
+    #[test]
+    fn converts_to_rgb() {
+        assert_eq!(to_rgb(&ColorSpace::Cmyk, &[0, 255, 255, 0, 0, 0, 0, 255]), vec![255, 0, 0, 0, 0, 0]);
+        let indexed = ColorSpace::Indexed { base: Box::new(ColorSpace::Rgb), palette: vec![1, 2, 3, 4, 5, 6] };
+        assert_eq!(to_rgb(&indexed, &[1, 0, 7]), vec![4, 5, 6, 1, 2, 3, 0, 0, 0]);
+    }
+}
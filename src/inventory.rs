@@ -0,0 +1,334 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use lopdf::{Dictionary, Document, Object};
+use rayon::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::utils;
+
+/// Custom error type for inventory operations
+#[derive(Debug)]
+pub enum InventoryError {
+    Io(io::Error),
+    Pdf(String),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Sqlite(rusqlite::Error),
+    Other(String),
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InventoryError::Io(e) => write!(f, "I/O error: {}", e),
+            InventoryError::Pdf(e) => write!(f, "PDF error: {}", e),
+            InventoryError::Csv(e) => write!(f, "CSV error: {}", e),
+            InventoryError::Json(e) => write!(f, "JSON error: {}", e),
+            InventoryError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            InventoryError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for InventoryError {}
+
+impl From<io::Error> for InventoryError {
+    fn from(err: io::Error) -> Self {
+        InventoryError::Io(err)
+    }
+}
+
+impl From<csv::Error> for InventoryError {
+    fn from(err: csv::Error) -> Self {
+        InventoryError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for InventoryError {
+    fn from(err: serde_json::Error) -> Self {
+        InventoryError::Json(err)
+    }
+}
+
+impl From<rusqlite::Error> for InventoryError {
+    fn from(err: rusqlite::Error) -> Self {
+        InventoryError::Sqlite(err)
+    }
+}
+
+/// Output formats supported by the inventory command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum InventoryFormat {
+    Csv,
+    Json,
+    Sqlite,
+}
+
+/// One inventory row describing a single PDF file
+#[derive(Debug, Default, Serialize)]
+pub struct InventoryRecord {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub pdf_version: String,
+    pub page_count: usize,
+    pub page_sizes: Vec<String>,
+    pub producer: Option<String>,
+    pub creator: Option<String>,
+    pub creation_date: Option<String>,
+    pub modification_date: Option<String>,
+    pub encrypted: bool,
+    pub tagged: bool,
+    pub linearized: bool,
+    pub embedded_fonts: Vec<String>,
+    pub text_extractable: bool,
+    pub error: Option<String>,
+}
+
+const CSV_HEADER: [&str; 16] = [
+    "path", "size", "sha256", "pdf_version", "page_count", "page_sizes",
+    "producer", "creator", "creation_date", "modification_date",
+    "encrypted", "tagged", "linearized", "embedded_fonts", "text_extractable", "error",
+];
+
+impl InventoryRecord {
+    /// Flatten the record into CSV/SQLite columns
+    fn to_row(&self) -> Vec<String> {
+        vec![
+            self.path.clone(),
+            self.size.to_string(),
+            self.sha256.clone(),
+            self.pdf_version.clone(),
+            self.page_count.to_string(),
+            self.page_sizes.join(";"),
+            self.producer.clone().unwrap_or_default(),
+            self.creator.clone().unwrap_or_default(),
+            self.creation_date.clone().unwrap_or_default(),
+            self.modification_date.clone().unwrap_or_default(),
+            self.encrypted.to_string(),
+            self.tagged.to_string(),
+            self.linearized.to_string(),
+            self.embedded_fonts.join(";"),
+            self.text_extractable.to_string(),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Main function to run the inventory functionality
+pub fn run(input_paths: &[String], format: InventoryFormat, output_file: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(InventoryError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    if format == InventoryFormat::Sqlite && output_file.is_none() {
+        return Err(Box::new(InventoryError::Other(
+            "SQLite output requires an output file".to_string()
+        )));
+    }
+
+    // Create progress bar
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Building inventory");
+
+    // Inspect PDFs in parallel
+    let records: Vec<InventoryRecord> = pdf_paths
+        .par_iter()
+        .map(|path| inspect_pdf(path))
+        .inspect(|_| pb.inc(1))
+        .collect();
+
+    pb.finish_with_message("Done");
+
+    match format {
+        InventoryFormat::Csv => write_csv(&records, output_file)?,
+        InventoryFormat::Json => write_json(&records, output_file)?,
+        InventoryFormat::Sqlite => write_sqlite(&records, output_file.unwrap_or_default())?,
+    }
+
+    if let Some(output_file) = output_file {
+        println!("Successfully wrote inventory of {} PDFs to '{}'", records.len(), output_file);
+    }
+    Ok(())
+}
+
+/// Build the inventory record for a single PDF, recording any failure in the `error` column
+pub fn inspect_pdf(path: &Path) -> InventoryRecord {
+    let mut record = InventoryRecord {
+        path: path.to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            record.error = Some(InventoryError::Io(e).to_string());
+            return record;
+        }
+    };
+
+    record.size = bytes.len() as u64;
+    record.sha256 = utils::to_hex(&Sha256::digest(&bytes));
+    // pdf-extract panics on some malformed files
+    match panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem(&bytes))) {
+        Ok(text) => record.text_extractable = text.map(|text| !text.trim().is_empty()).unwrap_or(false),
+        Err(_) => {
+            record.error = Some(InventoryError::Pdf(
+                format!("Text extraction crashed on {}", path.display())
+            ).to_string());
+        }
+    }
+
+    match Document::load_mem(&bytes) {
+        Ok(doc) => fill_structure(&doc, &mut record),
+        Err(e) => {
+            record.error = Some(InventoryError::Pdf(
+                format!("Error parsing {}: {}", path.display(), e)
+            ).to_string());
+        }
+    }
+
+    record
+}
+
+/// Fill in the structural fields of a record from a parsed document
+fn fill_structure(doc: &Document, record: &mut InventoryRecord) {
+    let pages = doc.get_pages();
+    record.pdf_version = doc.version.clone();
+    record.page_count = pages.len();
+    record.encrypted = doc.is_encrypted();
+
+    // Unique page sizes, in points
+    let mut sizes = BTreeSet::new();
+    let mut fonts = BTreeSet::new();
+    for page_id in pages.values() {
+        if let Some([left, bottom, right, top]) = utils::page_media_box(doc, *page_id) {
+            sizes.insert(format!("{:.0}x{:.0}", (right - left).abs(), (top - bottom).abs()));
+        }
+        for font in doc.get_page_fonts(*page_id).values() {
+            if let Some(name) = embedded_font_name(doc, font) {
+                fonts.insert(name);
+            }
+        }
+    }
+    record.page_sizes = sizes.into_iter().collect();
+    record.embedded_fonts = fonts.into_iter().collect();
+
+    // Document information dictionary
+    if let Some(info) = utils::info_dictionary(doc) {
+        record.producer = utils::dict_string(doc, info, b"Producer");
+        record.creator = utils::dict_string(doc, info, b"Creator");
+        record.creation_date = utils::dict_string(doc, info, b"CreationDate")
+            .map(|d| utils::format_pdf_date(&d));
+        record.modification_date = utils::dict_string(doc, info, b"ModDate")
+            .map(|d| utils::format_pdf_date(&d));
+    }
+
+    // Tagged PDFs declare /MarkInfo << /Marked true >> or carry a structure tree
+    if let Ok(catalog) = doc.catalog() {
+        let marked = catalog.get(b"MarkInfo")
+            .ok()
+            .and_then(|m| doc.dereference(m).ok())
+            .and_then(|(_, m)| m.as_dict().ok())
+            .and_then(|m| m.get(b"Marked").and_then(Object::as_bool).ok())
+            .unwrap_or(false);
+        record.tagged = marked || catalog.has(b"StructTreeRoot");
+    }
+
+    // Linearized files carry a linearization parameter dictionary
+    record.linearized = doc.objects.values()
+        .any(|o| o.as_dict().map(|d| d.has(b"Linearized")).unwrap_or(false));
+}
+
+/// Return the base font name if the font program is embedded in the file
+fn embedded_font_name(doc: &Document, font: &Dictionary) -> Option<String> {
+    let base_font = font.get(b"BaseFont").and_then(Object::as_name_str).ok()?.to_string();
+
+    // Composite fonts keep the descriptor on their descendant font
+    let descriptor_owner = match font.get(b"DescendantFonts").ok().and_then(|d| doc.dereference(d).ok()) {
+        Some((_, Object::Array(descendants))) => descendants.first()
+            .and_then(|d| doc.dereference(d).ok())
+            .and_then(|(_, d)| d.as_dict().ok())?,
+        _ => font,
+    };
+
+    let descriptor = descriptor_owner.get(b"FontDescriptor").ok()
+        .and_then(|d| doc.dereference(d).ok())
+        .and_then(|(_, d)| d.as_dict().ok())?;
+
+    let embedded = [b"FontFile".as_slice(), b"FontFile2", b"FontFile3"]
+        .iter()
+        .any(|key| descriptor.has(key));
+
+    if embedded {
+        // Drop the subset tag (e.g. "ABCDEF+Helvetica")
+        Some(match base_font.split_once('+') {
+            Some((tag, name)) if tag.len() == 6 => name.to_string(),
+            _ => base_font,
+        })
+    } else {
+        None
+    }
+}
+
+/// Write records as CSV to a file or stdout
+fn write_csv(records: &[InventoryRecord], output_file: Option<&str>) -> Result<(), InventoryError> {
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    csv_writer.write_record(CSV_HEADER)?;
+    for record in records {
+        csv_writer.write_record(record.to_row())?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Write records as a JSON array to a file or stdout
+fn write_json(records: &[InventoryRecord], output_file: Option<&str>) -> Result<(), InventoryError> {
+    let json = serde_json::to_string_pretty(records)?;
+    match output_file {
+        Some(path) => fs::write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// Write records into an `inventory` table of a SQLite database
+fn write_sqlite(records: &[InventoryRecord], output_file: &str) -> Result<(), InventoryError> {
+    let mut conn = rusqlite::Connection::open(output_file)?;
+
+    let columns = CSV_HEADER.iter()
+        .map(|c| match *c {
+            "path" => "path TEXT PRIMARY KEY".to_string(),
+            "size" | "page_count" => format!("{} INTEGER", c),
+            _ => format!("{} TEXT", c),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    conn.execute(&format!("CREATE TABLE IF NOT EXISTS inventory ({})", columns), [])?;
+
+    let placeholders = vec!["?"; CSV_HEADER.len()].join(", ");
+    let sql = format!("INSERT OR REPLACE INTO inventory ({}) VALUES ({})", CSV_HEADER.join(", "), placeholders);
+
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(&sql)?;
+        for record in records {
+            stmt.execute(rusqlite::params_from_iter(record.to_row()))?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
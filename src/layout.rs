@@ -0,0 +1,560 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use clap::ValueEnum;
+use pdfium_render::prelude::*;
+
+/// Fraction of the page height treated as header/footer margin
+const MARGIN_FRACTION: f32 = 0.1;
+
+/// Minimum width of an empty vertical strip, in points, to count as a column gutter
+const MIN_GUTTER_WIDTH: f32 = 8.0;
+
+/// Custom error type for layout analysis
+#[derive(Debug)]
+pub enum LayoutError {
+    PdfError(String),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::PdfError(e) => write!(f, "PDF error: {}", e),
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+/// How extracted text is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LayoutMode {
+    /// Text in content-stream order, as produced by pdf-extract
+    #[default]
+    Raw,
+    /// Columns read top to bottom, left to right, without running headers/footers
+    ReadingOrder,
+    /// Text placed on a character grid that mirrors the page
+    Physical,
+}
+
+impl fmt::Display for LayoutMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutMode::Raw => write!(f, "Raw"),
+            LayoutMode::ReadingOrder => write!(f, "Reading order"),
+            LayoutMode::Physical => write!(f, "Physical"),
+        }
+    }
+}
+
+/// A positioned character
+#[derive(Debug, Clone)]
+struct Glyph {
+    ch: char,
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    font_size: f32,
+    bold: bool,
+}
+
+impl Glyph {
+    fn center_y(&self) -> f32 {
+        (self.top + self.bottom) / 2.0
+    }
+
+    fn height(&self) -> f32 {
+        (self.top - self.bottom).max(1.0)
+    }
+}
+
+/// A run of characters on one line without large horizontal gaps
+#[derive(Debug, Clone)]
+pub struct TextFragment {
+    pub text: String,
+    pub left: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub top: f32,
+    pub font_size: f32,
+    /// Byte ranges of `text` set in a bold font
+    pub bold_spans: Vec<(usize, usize)>,
+}
+
+impl TextFragment {
+    /// Check whether the whole fragment is set in a bold font
+    pub fn is_bold(&self) -> bool {
+        self.bold_spans.len() == 1 && self.bold_spans[0] == (0, self.text.len())
+    }
+}
+
+/// A line of text, made of one or more fragments (e.g. one per column)
+#[derive(Debug, Clone)]
+pub struct TextLine {
+    pub fragments: Vec<TextFragment>,
+    pub bottom: f32,
+    pub top: f32,
+}
+
+impl TextLine {
+    /// Text of the whole line, fragments separated by a gap
+    pub fn text(&self) -> String {
+        self.fragments.iter().map(|f| f.text.as_str()).collect::<Vec<_>>().join("  ")
+    }
+
+    fn center_y(&self) -> f32 {
+        (self.top + self.bottom) / 2.0
+    }
+
+    fn height(&self) -> f32 {
+        (self.top - self.bottom).max(1.0)
+    }
+}
+
+/// Text lines of a page with the page size, in PDF points (origin bottom-left)
+#[derive(Debug, Clone, Default)]
+pub struct PageLayout {
+    pub width: f32,
+    pub height: f32,
+    pub lines: Vec<TextLine>,
+}
+
+/// Build the line/fragment structure of a page from Pdfium character boxes
+pub fn page_layout(page: &PdfPage) -> PageLayout {
+    let mut glyphs = Vec::new();
+
+    if let Ok(text) = page.text() {
+        for text_char in text.chars().iter() {
+            let ch = match text_char.unicode_char() {
+                Some(ch) if !ch.is_whitespace() && !ch.is_control() => ch,
+                _ => continue,
+            };
+            if let Ok(bounds) = text_char.loose_bounds() {
+                glyphs.push(Glyph {
+                    ch,
+                    left: bounds.left().value,
+                    right: bounds.right().value,
+                    bottom: bounds.bottom().value,
+                    top: bounds.top().value,
+                    font_size: text_char.scaled_font_size().value.max(1.0),
+                    bold: is_bold(&text_char),
+                });
+            }
+        }
+    }
+
+    PageLayout {
+        width: page.width().value,
+        height: page.height().value,
+        lines: group_lines(glyphs),
+    }
+}
+
+/// Check whether a character is set in a bold font, by weight or by font name
+fn is_bold(text_char: &PdfPageTextChar) -> bool {
+    let heavy_weight = match text_char.font_weight() {
+        Some(PdfFontWeight::Weight600 | PdfFontWeight::Weight700Bold | PdfFontWeight::Weight800 | PdfFontWeight::Weight900) => true,
+        Some(PdfFontWeight::Custom(weight)) => weight >= 600,
+        _ => false,
+    };
+    // Pdfium often reports no weight for standard fonts such as Helvetica-Bold
+    heavy_weight || {
+        let name = text_char.font_name().to_lowercase();
+        ["bold", "black", "heavy", "semibold", "demi"].iter().any(|w| name.contains(w))
+    }
+}
+
+/// Group glyphs into lines by vertical position, then split lines into fragments at large gaps
+fn group_lines(mut glyphs: Vec<Glyph>) -> Vec<TextLine> {
+    glyphs.sort_by(|a, b| b.center_y().partial_cmp(&a.center_y()).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut rows: Vec<Vec<Glyph>> = Vec::new();
+    for glyph in glyphs {
+        let joins_row = rows.last().map(|row| {
+            let row_center = row.iter().map(Glyph::center_y).sum::<f32>() / row.len() as f32;
+            let row_height = row.iter().map(Glyph::height).fold(0.0, f32::max);
+            (row_center - glyph.center_y()).abs() < 0.5 * row_height.min(glyph.height())
+        }).unwrap_or(false);
+
+        if joins_row {
+            rows.last_mut().unwrap().push(glyph);
+        } else {
+            rows.push(vec![glyph]);
+        }
+    }
+
+    rows.into_iter().map(build_line).collect()
+}
+
+/// Turn a row of glyphs into a line, inserting spaces and splitting fragments by gap width
+fn build_line(mut row: Vec<Glyph>) -> TextLine {
+    row.sort_by(|a, b| a.left.partial_cmp(&b.left).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut fragments: Vec<TextFragment> = Vec::new();
+    for glyph in row {
+        match fragments.last_mut() {
+            Some(fragment) if glyph.left - fragment.right < 1.5 * glyph.font_size => {
+                let spaced = glyph.left - fragment.right > 0.2 * glyph.font_size;
+                if spaced {
+                    fragment.text.push(' ');
+                }
+                let start = fragment.text.len();
+                fragment.text.push(glyph.ch);
+                if glyph.bold {
+                    // Extend the previous bold run across the space, or start a new one
+                    match fragment.bold_spans.last_mut() {
+                        Some(span) if span.1 + usize::from(spaced) == start => span.1 = fragment.text.len(),
+                        _ => fragment.bold_spans.push((start, fragment.text.len())),
+                    }
+                }
+                fragment.right = fragment.right.max(glyph.right);
+                fragment.bottom = fragment.bottom.min(glyph.bottom);
+                fragment.top = fragment.top.max(glyph.top);
+                fragment.font_size = fragment.font_size.max(glyph.font_size);
+            },
+            _ => fragments.push(TextFragment {
+                text: glyph.ch.to_string(),
+                left: glyph.left,
+                right: glyph.right,
+                bottom: glyph.bottom,
+                top: glyph.top,
+                font_size: glyph.font_size,
+                bold_spans: if glyph.bold { vec![(0, glyph.ch.len_utf8())] } else { Vec::new() },
+            }),
+        }
+    }
+
+    let bottom = fragments.iter().map(|f| f.bottom).fold(f32::MAX, f32::min);
+    let top = fragments.iter().map(|f| f.top).fold(f32::MIN, f32::max);
+    TextLine { fragments, bottom, top }
+}
+
+/// Find the x positions of column gutters from the horizontal coverage of fragments
+pub fn column_boundaries(layout: &PageLayout) -> Vec<f32> {
+    let width = layout.width.max(1.0).ceil() as usize;
+    let mut coverage = vec![0usize; width + 1];
+    let mut min_x = f32::MAX;
+    let mut max_x = f32::MIN;
+
+    for fragment in layout.lines.iter().flat_map(|l| &l.fragments) {
+        // Full-width titles would hide every gutter
+        if fragment.right - fragment.left > layout.width * 0.6 {
+            continue;
+        }
+        min_x = min_x.min(fragment.left);
+        max_x = max_x.max(fragment.right);
+        let start = fragment.left.max(0.0) as usize;
+        let end = (fragment.right.max(0.0) as usize).min(width);
+        for count in coverage.iter_mut().take(end + 1).skip(start) {
+            *count += 1;
+        }
+    }
+
+    if min_x >= max_x {
+        return Vec::new();
+    }
+
+    // Empty runs strictly inside the text area are gutters
+    let mut boundaries = Vec::new();
+    let mut run_start: Option<usize> = None;
+    let first = min_x.max(0.0) as usize;
+    let last = (max_x.max(0.0) as usize).min(width);
+    for (x, count) in coverage.iter().enumerate().take(last + 1).skip(first) {
+        if *count == 0 {
+            run_start.get_or_insert(x);
+        } else if let Some(start) = run_start.take() {
+            if (x - start) as f32 >= MIN_GUTTER_WIDTH {
+                boundaries.push((start + x) as f32 / 2.0);
+            }
+        }
+    }
+    boundaries
+}
+
+/// A line of text in reading order: either a line spanning the columns, or the part
+/// of a line that falls into one column
+#[derive(Debug, Clone)]
+pub struct OrderedLine<'a> {
+    pub fragments: Vec<&'a TextFragment>,
+    pub spans_columns: bool,
+}
+
+impl OrderedLine<'_> {
+    pub fn text(&self) -> String {
+        let separator = if self.spans_columns { "  " } else { " " };
+        self.fragments.iter().map(|f| f.text.as_str()).collect::<Vec<_>>().join(separator)
+    }
+}
+
+/// Order the lines of a page for reading: bands of columns separated by full-width lines,
+/// each column read top to bottom
+pub fn reading_order_lines(layout: &PageLayout) -> Vec<OrderedLine<'_>> {
+    let boundaries = column_boundaries(layout);
+    let column_of = |fragment: &TextFragment| -> Option<usize> {
+        if boundaries.iter().any(|b| fragment.left < *b && fragment.right > *b) {
+            None // spans a gutter
+        } else {
+            Some(boundaries.iter().filter(|b| **b < fragment.left).count())
+        }
+    };
+
+    let mut output: Vec<OrderedLine> = Vec::new();
+    let mut band: Vec<Vec<OrderedLine>> = vec![Vec::new(); boundaries.len() + 1];
+
+    fn flush<'a>(band: &mut [Vec<OrderedLine<'a>>], output: &mut Vec<OrderedLine<'a>>) {
+        for column in band.iter_mut() {
+            output.append(column);
+        }
+    }
+
+    for line in &layout.lines {
+        if line.fragments.iter().any(|f| column_of(f).is_none()) {
+            // A spanning line ends the current band of columns
+            flush(&mut band, &mut output);
+            output.push(OrderedLine { fragments: line.fragments.iter().collect(), spans_columns: true });
+            continue;
+        }
+
+        let mut per_column: HashMap<usize, Vec<&TextFragment>> = HashMap::new();
+        for fragment in &line.fragments {
+            if let Some(column) = column_of(fragment) {
+                per_column.entry(column).or_default().push(fragment);
+            }
+        }
+        for (column, fragments) in per_column {
+            band[column].push(OrderedLine { fragments, spans_columns: false });
+        }
+    }
+    flush(&mut band, &mut output);
+
+    output
+}
+
+/// Render a page in reading order: bands of columns separated by full-width lines
+pub fn reading_order_text(layout: &PageLayout) -> String {
+    reading_order_lines(layout).iter().map(OrderedLine::text).collect::<Vec<_>>().join("\n")
+}
+
+/// Render a page on a monospace grid that follows the physical positions of the text
+pub fn physical_text(layout: &PageLayout) -> String {
+    let font_sizes: Vec<f32> = layout.lines.iter().flat_map(|l| &l.fragments).map(|f| f.font_size).collect();
+    if font_sizes.is_empty() {
+        return String::new();
+    }
+    // Assume an average glyph is about half an em wide
+    let char_width = (font_sizes.iter().sum::<f32>() / font_sizes.len() as f32 * 0.5).max(1.0);
+    let min_left = layout.lines.iter().flat_map(|l| &l.fragments).map(|f| f.left).fold(f32::MAX, f32::min);
+
+    let mut output = String::new();
+    let mut previous: Option<&TextLine> = None;
+    for line in &layout.lines {
+        // Keep larger vertical gaps as blank lines
+        if let Some(prev) = previous {
+            let gap = prev.bottom - line.top;
+            let blank_lines = ((gap / line.height()).round() as usize).min(3);
+            for _ in 0..blank_lines {
+                output.push('\n');
+            }
+        }
+
+        let mut row = String::new();
+        for fragment in &line.fragments {
+            let column = ((fragment.left - min_left) / char_width).round().max(0.0) as usize;
+            let current = row.chars().count();
+            if column > current {
+                row.push_str(&" ".repeat(column - current));
+            } else if !row.is_empty() {
+                row.push(' ');
+            }
+            row.push_str(&fragment.text);
+        }
+        output.push_str(row.trim_end());
+        output.push('\n');
+        previous = Some(line);
+    }
+
+    output
+}
+
+/// Normalize a line for header/footer comparison: lowercase, digits and words that are
+/// roman numerals collapsed
+fn running_key(text: &str) -> String {
+    let mut key = String::new();
+    let mut last_digit = false;
+    for word in text.to_lowercase().split_whitespace() {
+        // Letters are compared a run at a time, other characters one by one
+        let mut rest = word;
+        while let Some(ch) = rest.chars().next() {
+            let length = if ch.is_alphabetic() {
+                rest.find(|c: char| !c.is_alphabetic()).unwrap_or(rest.len())
+            } else {
+                ch.len_utf8()
+            };
+            let (part, tail) = rest.split_at(length);
+            rest = tail;
+
+            if ch.is_ascii_digit() || is_roman_numeral(part) {
+                if !last_digit {
+                    key.push('#');
+                }
+                last_digit = true;
+            } else {
+                key.push_str(part);
+                last_digit = false;
+            }
+        }
+    }
+    key
+}
+
+/// Check whether a lowercase word is a roman numeral below 400, as used for front matter pages
+fn is_roman_numeral(word: &str) -> bool {
+    const PLACES: [&[&str]; 3] = [
+        &["ccc", "cc", "c"],
+        &["xc", "xl", "lxxx", "lxx", "lx", "l", "xxx", "xx", "x"],
+        &["ix", "iv", "viii", "vii", "vi", "v", "iii", "ii", "i"],
+    ];
+    let mut rest = word;
+    for place in PLACES {
+        if let Some(numeral) = place.iter().find(|numeral| rest.starts_with(**numeral)) {
+            rest = &rest[numeral.len()..];
+        }
+    }
+    !word.is_empty() && rest.is_empty()
+}
+
+/// Check whether a line is a bare page number such as "12", "- 12 -", "Page 3 of 10"
+fn is_page_number(key: &str) -> bool {
+    matches!(key, "#" | "-#-" | "page#" | "page#of#" | "#/#" | "#of#" | "[#]" | "(#)")
+}
+
+/// Check whether a line sits in the top or bottom margin of its page
+fn in_margin(line: &TextLine, page_height: f32) -> bool {
+    let center = line.center_y();
+    center > page_height * (1.0 - MARGIN_FRACTION) || center < page_height * MARGIN_FRACTION
+}
+
+/// Remove running headers, footers and page numbers that repeat in the page margins
+pub fn remove_running_elements(pages: &mut [PageLayout]) {
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    for page in pages.iter() {
+        let mut seen = std::collections::HashSet::new();
+        for line in page.lines.iter().filter(|l| in_margin(l, page.height)) {
+            let key = running_key(&line.text());
+            if seen.insert(key.clone()) {
+                *occurrences.entry(key).or_insert(0) += 1;
+            }
+        }
+    }
+
+    // A margin line repeating on at least half of the pages (and at least twice) is a running element
+    let min_repeats = (pages.len() / 2).max(2);
+    for page in pages.iter_mut() {
+        let height = page.height;
+        page.lines.retain(|line| {
+            if !in_margin(line, height) {
+                return true;
+            }
+            let key = running_key(&line.text());
+            !(is_page_number(&key) || occurrences.get(&key).copied().unwrap_or(0) >= min_repeats)
+        });
+    }
+}
+
+/// Render one page's layout in the given mode
+pub fn render_layout(layout: &PageLayout, mode: LayoutMode) -> String {
+    match mode {
+        LayoutMode::Physical => physical_text(layout),
+        _ => reading_order_text(layout),
+    }
+}
+
+/// Extract the text of each page of a document in the given layout mode
+pub fn extract_pages(pdfium: &Pdfium, path: &Path, mode: LayoutMode) -> Result<Vec<String>, LayoutError> {
+    let document = pdfium.load_pdf_from_file(path, None)
+        .map_err(|e| LayoutError::PdfError(format!("Error loading {}: {}", path.display(), e)))?;
+
+    let mut pages: Vec<PageLayout> = document.pages().iter().map(|page| page_layout(&page)).collect();
+    remove_running_elements(&mut pages);
+
+    Ok(pages.iter().map(|page| render_layout(page, mode)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Glyphs for `text` set at 10pt from `left` on the baseline `bottom`, 5pt per character
+    fn word(text: &str, left: f32, bottom: f32) -> Vec<Glyph> {
+        text.chars().enumerate().map(|(i, ch)| Glyph {
+            ch,
+            left: left + i as f32 * 5.0,
+            right: left + i as f32 * 5.0 + 5.0,
+            bottom,
+            top: bottom + 10.0,
+            font_size: 10.0,
+            bold: false,
+        }).collect()
+    }
+
+    fn page(glyphs: Vec<Glyph>) -> PageLayout {
+        PageLayout { width: 600.0, height: 800.0, lines: group_lines(glyphs) }
+    }
+
+    #[test]
+    fn groups_glyphs_into_lines_and_words() {
+        let mut glyphs = word("world", 132.0, 700.0);
+        glyphs.extend(word("Hello", 100.0, 701.0));
+        glyphs.extend(word("Next", 100.0, 680.0));
+        let lines = group_lines(glyphs);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text(), "Hello world");
+        assert_eq!(lines[1].text(), "Next");
+    }
+
+    #[test]
+    fn reads_columns_top_to_bottom() {
+        let mut glyphs = word("Title spanning both columns of the page", 60.0, 760.0);
+        glyphs.extend(word("left1", 60.0, 700.0));
+        glyphs.extend(word("right1", 360.0, 700.0));
+        glyphs.extend(word("left2", 60.0, 680.0));
+        glyphs.extend(word("right2", 360.0, 680.0));
+        let layout = page(glyphs);
+
+        assert_eq!(column_boundaries(&layout).len(), 1);
+        assert_eq!(
+            reading_order_text(&layout),
+            "Title spanning both columns of the page\nleft1\nleft2\nright1\nright2"
+        );
+    }
+
+    #[test]
+    fn recognizes_page_numbers() {
+        assert!(is_page_number(&running_key("- 12 -")));
+        assert!(is_page_number(&running_key("Page 3 of 10")));
+        assert!(!is_page_number(&running_key("Chapter 3")));
+        assert!(is_page_number(&running_key("- iv -")));
+        assert!(is_page_number(&running_key("Page xii of xiv")));
+        assert!(!is_page_number(&running_key("civic")));
+        assert!(!is_page_number(&running_key("Vol. i")));
+        assert_eq!(running_key("Vol. i"), "vol.#");
+    }
+
+    #[test]
+    fn removes_repeated_margin_lines() {
+        let mut pages: Vec<PageLayout> = (1..=3).map(|n| {
+            let mut glyphs = word("ACME Corp confidential", 60.0, 780.0);
+            glyphs.extend(word(&format!("Body text {}", n), 60.0, 400.0));
+            glyphs.extend(word(&n.to_string(), 300.0, 20.0));
+            page(glyphs)
+        }).collect();
+        remove_running_elements(&mut pages);
+        for (i, page) in pages.iter().enumerate() {
+            let texts: Vec<String> = page.lines.iter().map(TextLine::text).collect();
+            assert_eq!(texts, vec![format!("Body text {}", i + 1)]);
+        }
+    }
+}
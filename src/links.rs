@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::outline;
+use crate::utils;
+
+/// Custom error type for link extraction
+#[derive(Debug)]
+pub enum LinkError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::Io(e) => write!(f, "I/O error: {}", e),
+            LinkError::Csv(e) => write!(f, "CSV error: {}", e),
+            LinkError::Json(e) => write!(f, "JSON error: {}", e),
+            LinkError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for LinkError {}
+
+impl From<io::Error> for LinkError {
+    fn from(err: io::Error) -> Self {
+        LinkError::Io(err)
+    }
+}
+
+impl From<csv::Error> for LinkError {
+    fn from(err: csv::Error) -> Self {
+        LinkError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for LinkError {
+    fn from(err: serde_json::Error) -> Self {
+        LinkError::Json(err)
+    }
+}
+
+/// Output formats supported by the links command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum LinkFormat {
+    Csv,
+    Json,
+}
+
+/// One link of a PDF page
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkRecord {
+    pub path: String,
+    pub page: usize,
+    /// `annotation` for link annotations, `text` for URLs written in the page text
+    pub source: String,
+    /// `uri`, `goto` (internal), `remote` (other PDF), `launch` (file), or the action type
+    pub kind: String,
+    /// URL, `page N` for internal links, or the file of remote and launch links
+    pub target: String,
+    /// Target page of internal links, if the destination resolves
+    pub target_page: Option<usize>,
+    /// Host name of URI links, without `www.`
+    pub domain: Option<String>,
+}
+
+const CSV_HEADER: [&str; 7] = ["path", "page", "source", "kind", "target", "target_page", "domain"];
+
+impl LinkRecord {
+    /// Flatten the record into CSV columns
+    fn to_row(&self) -> Vec<String> {
+        vec![
+            self.path.clone(),
+            self.page.to_string(),
+            self.source.clone(),
+            self.kind.clone(),
+            self.target.clone(),
+            self.target_page.map(|page| page.to_string()).unwrap_or_default(),
+            self.domain.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Links to one domain across all documents
+#[derive(Debug, Serialize)]
+struct DomainSummary {
+    domain: String,
+    links: usize,
+    documents: usize,
+    urls: Vec<String>,
+}
+
+/// Main function to list the links of PDF files
+pub fn run(input_paths: &[String], format: LinkFormat, output_file: Option<&str>, by_domain: bool) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(LinkError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    // Create progress bar
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Collecting links");
+
+    let records: Vec<LinkRecord> = pdf_paths
+        .par_iter()
+        .map(|path| {
+            let records = read_links(path);
+            pb.inc(1);
+            records
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .flatten()
+        .collect();
+
+    pb.finish_with_message("Done");
+
+    if by_domain {
+        let summaries = summarize_domains(&records);
+        match format {
+            LinkFormat::Json => utils::write_output(&serde_json::to_string_pretty(&summaries)?, output_file)?,
+            LinkFormat::Csv => write_domain_csv(&summaries, output_file)?,
+        }
+        if let Some(output_file) = output_file {
+            println!("Successfully wrote {} domains from {} PDFs to '{}'", summaries.len(), pdf_paths.len(), output_file);
+        }
+        return Ok(());
+    }
+
+    match format {
+        LinkFormat::Json => utils::write_output(&serde_json::to_string_pretty(&records)?, output_file)?,
+        LinkFormat::Csv => write_csv(&records, output_file)?,
+    }
+
+    if let Some(output_file) = output_file {
+        println!("Successfully wrote {} links from {} PDFs to '{}'", records.len(), pdf_paths.len(), output_file);
+    }
+    Ok(())
+}
+
+/// Collect the link annotations and the URLs in the text of one PDF. A URL in the text
+/// is skipped when a link annotation on the same page already points to it.
+fn read_links(path: &Path) -> Vec<LinkRecord> {
+    let path_string = path.to_string_lossy().into_owned();
+
+    let mut records = match Document::load(path) {
+        Ok(doc) => read_link_annotations(&doc, &path_string),
+        Err(e) => {
+            eprintln!("Error loading {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+
+    let pages = match fs::read(path).map_err(|e| e.to_string())
+        .and_then(|bytes| {
+            // pdf-extract panics on some malformed files
+            panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem_by_pages(&bytes)))
+                .map_err(|_| "text extraction crashed".to_string())?
+                .map_err(|e| e.to_string())
+        })
+    {
+        Ok(pages) => pages,
+        Err(e) => {
+            eprintln!("Error extracting text from {}: {}", path.display(), e);
+            return records;
+        }
+    };
+
+    for (index, text) in pages.iter().enumerate() {
+        let page = index + 1;
+        let mut seen: BTreeSet<String> = records.iter()
+            .filter(|record| record.page == page && record.kind == "uri")
+            .map(|record| normalize_url(&record.target))
+            .collect();
+
+        for (_, _, url) in utils::find_urls(text) {
+            if !seen.insert(normalize_url(&url)) {
+                continue;
+            }
+            records.push(LinkRecord {
+                path: path_string.clone(),
+                page,
+                source: "text".to_string(),
+                kind: "uri".to_string(),
+                domain: utils::url_domain(&url),
+                target: url,
+                target_page: None,
+            });
+        }
+    }
+
+    records.sort_by_key(|record| record.page);
+    records
+}
+
+/// Read the Link annotations of every page
+fn read_link_annotations(doc: &Document, path: &str) -> Vec<LinkRecord> {
+    let page_numbers = outline::page_numbers(doc);
+    let mut records = Vec::new();
+
+    for (page_number, page_id) in doc.get_pages() {
+        for annotation in utils::page_annotations(doc, page_id) {
+            if annotation.get(b"Subtype").and_then(Object::as_name_str).ok() != Some("Link") {
+                continue;
+            }
+            if let Some((kind, target, target_page)) = link_target(doc, annotation, &page_numbers) {
+                records.push(LinkRecord {
+                    path: path.to_string(),
+                    page: page_number as usize,
+                    source: "annotation".to_string(),
+                    domain: if kind == "uri" { utils::url_domain(&target) } else { None },
+                    kind,
+                    target,
+                    target_page,
+                });
+            }
+        }
+    }
+    records
+}
+
+/// The kind, target and resolved page of a link annotation's /Dest or /A action
+fn link_target(
+    doc: &Document,
+    annotation: &Dictionary,
+    page_numbers: &HashMap<ObjectId, usize>,
+) -> Option<(String, String, Option<usize>)> {
+    if let Ok(dest) = annotation.get(b"Dest") {
+        return Some(internal_target(doc, dest, page_numbers));
+    }
+
+    let action = annotation.get(b"A").ok().and_then(|action| dereference_dict(doc, action))?;
+    let action_type = action.get(b"S").and_then(Object::as_name_str).ok()?;
+    match action_type {
+        "URI" => {
+            let uri = utils::dict_string(doc, action, b"URI")?;
+            Some(("uri".to_string(), uri.trim().to_string(), None))
+        },
+        "GoTo" => Some(internal_target(doc, action.get(b"D").ok()?, page_numbers)),
+        "GoToR" | "Launch" => {
+            let kind = if action_type == "GoToR" { "remote" } else { "launch" };
+            Some((kind.to_string(), file_specification(doc, action).unwrap_or_default(), None))
+        },
+        other => Some((other.to_lowercase(), String::new(), None)),
+    }
+}
+
+fn internal_target(doc: &Document, dest: &Object, page_numbers: &HashMap<ObjectId, usize>) -> (String, String, Option<usize>) {
+    let page = outline::destination_page(doc, dest, page_numbers);
+    let target = match page {
+        Some(page) => format!("page {}", page),
+        None => "(unresolved)".to_string(),
+    };
+    ("goto".to_string(), target, page)
+}
+
+/// The file name of a /F file specification, given as a string or a dictionary
+fn file_specification(doc: &Document, action: &Dictionary) -> Option<String> {
+    let (_, file) = doc.dereference(action.get(b"F").ok()?).ok()?;
+    match file {
+        Object::Dictionary(spec) => utils::dict_string(doc, spec, b"UF")
+            .or_else(|| utils::dict_string(doc, spec, b"F")),
+        _ => utils::dict_string(doc, action, b"F"),
+    }
+}
+
+fn dereference_dict<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    doc.dereference(object).ok().and_then(|(_, o)| o.as_dict().ok())
+}
+
+/// Compare URLs regardless of case, scheme and a trailing slash
+fn normalize_url(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let url = url.split_once("://").map(|(_, rest)| rest.to_string()).unwrap_or(url);
+    let url = url.strip_prefix("www.").unwrap_or(&url);
+    url.trim_end_matches('/').to_string()
+}
+
+/// Group the URI links by domain, most linked domains first
+fn summarize_domains(records: &[LinkRecord]) -> Vec<DomainSummary> {
+    let mut domains: BTreeMap<&str, (usize, BTreeSet<&str>, BTreeSet<&str>)> = BTreeMap::new();
+    for record in records {
+        if let Some(domain) = &record.domain {
+            let entry = domains.entry(domain).or_default();
+            entry.0 += 1;
+            entry.1.insert(&record.path);
+            entry.2.insert(&record.target);
+        }
+    }
+
+    let mut summaries: Vec<DomainSummary> = domains.into_iter()
+        .map(|(domain, (links, documents, urls))| DomainSummary {
+            domain: domain.to_string(),
+            links,
+            documents: documents.len(),
+            urls: urls.into_iter().map(String::from).collect(),
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.links.cmp(&a.links).then_with(|| a.domain.cmp(&b.domain)));
+    summaries
+}
+
+/// Write link records as CSV to a file or stdout
+fn write_csv(records: &[LinkRecord], output_file: Option<&str>) -> Result<(), LinkError> {
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    csv_writer.write_record(CSV_HEADER)?;
+    for record in records {
+        csv_writer.write_record(record.to_row())?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Write the per-domain report as CSV, with the distinct URLs separated by spaces
+fn write_domain_csv(summaries: &[DomainSummary], output_file: Option<&str>) -> Result<(), LinkError> {
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    csv_writer.write_record(["domain", "links", "documents", "urls"])?;
+    for summary in summaries {
+        csv_writer.write_record([
+            summary.domain.clone(),
+            summary.links.to_string(),
+            summary.documents.to_string(),
+            summary.urls.join(" "),
+        ])?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(path: &str, url: &str) -> LinkRecord {
+        LinkRecord {
+            path: path.to_string(),
+            page: 1,
+            source: "annotation".to_string(),
+            kind: "uri".to_string(),
+            target: url.to_string(),
+            target_page: None,
+            domain: utils::url_domain(url),
+        }
+    }
+
+    #[test]
+    fn normalizes_urls_for_comparison() {
+        assert_eq!(normalize_url("HTTPS://www.Example.com/Docs/"), "example.com/docs");
+        assert_eq!(normalize_url("http://example.com/docs"), "example.com/docs");
+    }
+
+    #[test]
+    fn summarizes_links_by_domain() {
+        let records = vec![
+            uri("a.pdf", "https://example.com/1"),
+            uri("a.pdf", "https://example.com/2"),
+            uri("b.pdf", "https://www.example.com/1"),
+            uri("b.pdf", "https://other.org"),
+            LinkRecord { kind: "goto".to_string(), target: "page 2".to_string(), domain: None, ..uri("b.pdf", "") },
+        ];
+        let summary: Vec<(String, usize, usize, usize)> = summarize_domains(&records).into_iter()
+            .map(|d| (d.domain, d.links, d.documents, d.urls.len()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("example.com".to_string(), 3, 2, 3),
+            ("other.org".to_string(), 1, 1, 1),
+        ]);
+    }
+}
@@ -1,10 +1,35 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand};
 use std::path::PathBuf;
 use std::process;
 
+mod annotations;
+mod attachments;
+mod check;
+mod coverage;
+mod dedup;
+mod diff;
+mod entities;
 mod extract;
+mod fonts;
+mod forms;
+mod highlight;
+mod images;
+mod inventory;
+mod layout;
+mod links;
+mod markup;
+mod meta;
+mod ocr;
+mod outline;
+mod pages;
+mod pii;
+mod redact;
+mod render;
 mod search;
+mod stamp;
 mod stats;
+mod tables;
+mod utils;
 
 #[derive(Parser)]
 #[command(author, version, about = "PDF text extraction and search tool")]
@@ -22,21 +47,55 @@ enum Commands {
         
         /// Input paths (directories or PDF files)
         input_paths: Vec<String>,
+
+        /// Text layout: content-stream order, reading order (columns, no running headers/footers) or physical
+        #[arg(short, long, value_enum, default_value_t = layout::LayoutMode::Raw)]
+        layout: layout::LayoutMode,
+
+        /// Only extract PDFs without a text layer (image-only scans)
+        #[arg(long)]
+        only_scanned: bool,
+
+        /// Split documents into sections at their bookmarks: `outline` or `outline:LEVEL`.
+        /// Writes one file per section when OUTPUT_FILE is a directory
+        #[arg(long, value_name = "outline[:LEVEL]")]
+        split_by: Option<extract::SplitBy>,
+
+        /// Output format; Markdown and HTML infer headings, lists and links from font sizes and weights
+        #[arg(short, long, value_enum, default_value_t = extract::ExtractFormat::Text)]
+        format: extract::ExtractFormat,
+
+        #[command(flatten)]
+        ocr: ocr::OcrArgs,
     },
     
     /// Search for text in PDF files
     Search {
-        /// Text to search for
-        #[arg(short, long)]
-        search_phrase: String,
+        /// Text to search for (may be left out with --only-scanned)
+        #[arg(short, long, required_unless_present = "only_scanned")]
+        search_phrase: Option<String>,
         
         /// Directories to search in
         #[arg(short, long, required = false)]
         directories: Vec<PathBuf>,
         
-        /// Enable ZIP output of matching files
-        #[arg(short, long)]
-        zip: bool,
+        #[command(flatten)]
+        output: search::SearchOutputArgs,
+
+        /// Only report PDFs without a text layer (image-only scans), e.g. to route them to OCR
+        #[arg(long)]
+        only_scanned: bool,
+
+        /// Also search PDFs attached to the PDFs found (reported as `file.pdf::attached.pdf`)
+        #[arg(long)]
+        include_attachments: bool,
+
+        /// Also match the phrase against annotation comments and notes
+        #[arg(long)]
+        include_annotations: bool,
+
+        #[command(flatten)]
+        ocr: ocr::OcrArgs,
     },
 
     /// Analyze keyword correlations in PDF files
@@ -56,6 +115,485 @@ enum Commands {
         /// Correlation threshold (0.0 to 1.0)
         #[arg(short, long, default_value_t = 0.1)]
         threshold: f64,
+
+        /// Also analyze PDFs attached to the input PDFs, as documents of their own
+        #[arg(long)]
+        include_attachments: bool,
+
+        #[command(flatten)]
+        ocr: ocr::OcrArgs,
+    },
+
+    /// Build a metadata inventory of PDF files
+    Inventory {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = inventory::InventoryFormat::Csv)]
+        format: inventory::InventoryFormat,
+
+        /// Output file path (prints to stdout for CSV/JSON when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+    },
+
+    /// Detect tables on PDF pages and export them as CSV or JSON
+    Tables {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Output format [default: csv with --output, json on stdout without]
+        #[arg(short, long, value_enum)]
+        format: Option<tables::TableFormat>,
+
+        /// Output directory for CSV (one file per table plus index.csv), or output file for JSON
+        /// (prints JSON to stdout when omitted)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Export embedded images from PDFs as PNG/JPEG files
+    Images {
+        /// Output directory for the image files
+        output_dir: String,
+
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Image format: keep JPEGs as stored and write the rest as PNG (auto), or convert everything
+        #[arg(short, long, value_enum, default_value_t = images::ImageOutputFormat::Auto)]
+        format: images::ImageOutputFormat,
+
+        /// Skip images narrower than this many pixels
+        #[arg(long, default_value_t = 0)]
+        min_width: u32,
+
+        /// Skip images shorter than this many pixels
+        #[arg(long, default_value_t = 0)]
+        min_height: u32,
+    },
+
+    /// Render pages to PNG, JPEG or WebP images (headless, e.g. for previews on build servers)
+    Render {
+        /// Output directory for the page images
+        output_dir: String,
+
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Resolution in dots per inch
+        #[arg(short, long, default_value_t = 150.0)]
+        dpi: f32,
+
+        /// Image format
+        #[arg(short, long, value_enum, default_value_t = render::RenderFormat::Png)]
+        format: render::RenderFormat,
+
+        /// JPEG quality, from 1 to 100
+        #[arg(short, long, default_value_t = 85)]
+        quality: u8,
+
+        /// Pages to render, e.g. 1,3-5,10- (all pages when omitted)
+        #[arg(short, long, value_delimiter = ',')]
+        pages: Vec<pages::PageRange>,
+
+        /// Also write thumbnails that fit into a square of this many pixels
+        #[arg(short, long, value_name = "PIXELS")]
+        thumbnail: Option<u32>,
+    },
+
+    /// List or extract files embedded in PDFs (attachments and portfolio members)
+    Attachments {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Extract the attachments into this directory, one subdirectory per PDF
+        #[arg(short, long)]
+        extract: Option<String>,
+
+        /// Also list (and extract) the attachments of attached PDFs
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Print the listing as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Export annotations (comments, highlights, notes) of PDF files
+    Annotations {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = annotations::AnnotationFormat::Json)]
+        format: annotations::AnnotationFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+    },
+
+    /// Print the table of contents (bookmark tree) of PDF files
+    Outline {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = outline::OutlineFormat::Text)]
+        format: outline::OutlineFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+    },
+
+    /// Export AcroForm/XFA form field values, one record per document
+    Forms {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = forms::FormFormat::Json)]
+        format: forms::FormFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+
+        /// Flag documents whose required fields are empty (exits with an error if any are found)
+        #[arg(long)]
+        check: bool,
+
+        /// JSON file listing additional required field names; implies --check
+        #[arg(long)]
+        schema: Option<String>,
+    },
+
+    /// List hyperlinks: link annotations (URLs and internal jumps) and URLs written in the text
+    Links {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = links::LinkFormat::Csv)]
+        format: links::LinkFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+
+        /// Report the external links grouped by domain instead of listing every link
+        #[arg(long)]
+        by_domain: bool,
+    },
+
+    /// Extract emails, phone numbers, dates, IBANs and DOIs with counts and the pages they occur on
+    Entities {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Entity types to extract (all when omitted)
+        #[arg(short, long, value_enum, value_delimiter = ',')]
+        types: Vec<entities::EntityKind>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = entities::EntityFormat::Csv)]
+        format: entities::EntityFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+    },
+
+    /// Scan PDFs for personal data and secrets (national IDs, card numbers, API keys, emails)
+    ScanPii {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Report format
+        #[arg(short, long, value_enum, default_value_t = pii::PiiFormat::Json)]
+        format: pii::PiiFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+
+        /// Only run these detectors (all enabled detectors when omitted)
+        #[arg(short, long, value_delimiter = ',')]
+        detectors: Vec<String>,
+
+        /// JSON file to disable detectors, override severities and add pattern detectors
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// Ignore detectors below this severity
+        #[arg(long, value_enum, default_value_t = pii::Severity::Low)]
+        min_severity: pii::Severity,
+
+        /// Exit with an error if there are findings of this severity or above
+        #[arg(long, value_enum)]
+        fail_on: Option<pii::Severity>,
+    },
+
+    /// Remove matching text and images from PDFs and write redacted copies with a log
+    Redact {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Output PDF for a single input, otherwise the output directory
+        #[arg(short, long)]
+        output: String,
+
+        /// Text to remove (repeatable)
+        #[arg(short, long)]
+        query: Vec<String>,
+
+        /// Regular expression of text to remove (repeatable)
+        #[arg(short, long)]
+        regex: Vec<String>,
+
+        /// Remove personal data found by these scan-pii detectors, or `all`
+        #[arg(long, value_delimiter = ',')]
+        pii: Vec<String>,
+
+        /// Black out a page area, as PAGE:x0,y0,x1,y1 in points (PAGE may be `all`; repeatable)
+        #[arg(long)]
+        region: Vec<redact::Region>,
+
+        /// Match queries and regular expressions case-sensitively
+        #[arg(long)]
+        case_sensitive: bool,
+
+        /// Keep the document information and XMP metadata (matches in them are still replaced)
+        #[arg(long)]
+        keep_metadata: bool,
+
+        /// Write the removed text, queries and regexes to the log instead of masking them
+        #[arg(long)]
+        log_text: bool,
+    },
+
+    /// Combine PDFs into one, with a bookmark per document
+    Merge {
+        /// PDFs to combine, in order (directories add their PDFs sorted by name)
+        input_paths: Vec<String>,
+
+        /// Output PDF
+        #[arg(short, long)]
+        output: String,
+    },
+
+    /// Split a PDF into several by page ranges, page count or outline sections
+    #[command(group(ArgGroup::new("mode").required(true).args(["ranges", "every", "by_outline"])))]
+    Split {
+        /// Input PDF
+        input: String,
+
+        /// Directory for the parts
+        #[arg(short, long)]
+        output_dir: String,
+
+        /// One part per range, e.g. 1-3,4-10,11-
+        #[arg(long, value_delimiter = ',')]
+        ranges: Vec<pages::PageRange>,
+
+        /// Parts of this many pages
+        #[arg(long)]
+        every: Option<usize>,
+
+        /// One part per outline section, down to this bookmark level
+        #[arg(long, num_args = 0..=1, default_missing_value = "1")]
+        by_outline: Option<usize>,
+    },
+
+    /// Extract, reorder or delete pages of a PDF
+    Pages {
+        /// Input PDF
+        input: String,
+
+        /// Output PDF
+        #[arg(short, long)]
+        output: String,
+
+        /// Pages to keep, in this order, e.g. 3,1-2,10- (all pages when omitted)
+        #[arg(short, long, value_delimiter = ',')]
+        select: Vec<pages::PageRange>,
+
+        /// Pages to remove
+        #[arg(short, long, value_delimiter = ',')]
+        delete: Vec<pages::PageRange>,
+    },
+
+    /// Show or edit document information and XMP metadata
+    Meta {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Set a field, as FIELD=VALUE: title, author, subject, keywords, creator, producer,
+        /// creation_date, modification_date or a custom Info key; an empty value removes it (repeatable)
+        #[arg(long, value_name = "FIELD=VALUE")]
+        set: Vec<meta::FieldValue>,
+
+        /// Set fields per file from a CSV with a `path` column and a column per field
+        #[arg(long, value_name = "CSV", conflicts_with_all = ["set", "strip"])]
+        csv: Option<String>,
+
+        /// Remove all metadata (document information, XMP and private application data)
+        #[arg(long, conflicts_with = "set")]
+        strip: bool,
+
+        /// Write modified copies into this directory instead of updating the files in place
+        #[arg(long)]
+        output_dir: Option<String>,
+
+        /// Output format when showing metadata
+        #[arg(short, long, value_enum, default_value_t = meta::MetaFormat::Csv)]
+        format: meta::MetaFormat,
+
+        /// Output file path when showing metadata (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+    },
+
+    /// Diagnose broken PDFs and optionally write repaired copies
+    Check {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// Report format
+        #[arg(short, long, value_enum, default_value_t = check::CheckFormat::Text)]
+        format: check::CheckFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+
+        /// Write repaired copies of files with problems into this directory
+        #[arg(long, value_name = "DIR")]
+        repair: Option<String>,
+
+        /// Exit with an error if there are problems of this severity or above
+        #[arg(long, value_enum)]
+        fail_on: Option<check::Severity>,
+    },
+
+    /// Find identical and near-duplicate PDFs by content and suggest which copy to keep
+    Dedup {
+        /// Input paths (directories or PDF files)
+        input_paths: Vec<String>,
+
+        /// How text similarity is estimated
+        #[arg(short, long, value_enum, default_value_t = dedup::SimilarityMethod::Minhash)]
+        method: dedup::SimilarityMethod,
+
+        /// Minimum similarity, from 0 to 1, for files to count as near-duplicates
+        #[arg(short, long, default_value_t = 0.9)]
+        threshold: f64,
+
+        /// Words per shingle (shorter shingles find looser matches)
+        #[arg(long, default_value_t = 5)]
+        shingle_size: usize,
+
+        /// Report format
+        #[arg(short, long, value_enum, default_value_t = dedup::DedupFormat::Text)]
+        format: dedup::DedupFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+    },
+
+    /// Compare the text of two PDFs word by word, with page references
+    Diff {
+        /// The original PDF
+        old: String,
+
+        /// The revised PDF
+        new: String,
+
+        /// Words of unchanged text shown around each change
+        #[arg(short, long, default_value_t = 10)]
+        context: usize,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = diff::DiffFormat::Text)]
+        format: diff::DiffFormat,
+
+        /// Output file path (prints to stdout when omitted)
+        #[arg(short, long)]
+        output_file: Option<String>,
+    },
+
+    /// Overlay text or image watermarks and Bates numbers on PDF pages
+    Stamp {
+        /// Input paths (directories or PDF files), stamped in name order
+        input_paths: Vec<String>,
+
+        /// Output PDF for a single input, otherwise the output directory
+        #[arg(short, long)]
+        output: String,
+
+        /// Watermark text, e.g. CONFIDENTIAL
+        #[arg(short, long)]
+        text: Option<String>,
+
+        /// Watermark image (PNG or JPEG)
+        #[arg(short, long)]
+        image: Option<String>,
+
+        /// Where to place the watermark
+        #[arg(long, value_enum, default_value_t = stamp::Position::Center)]
+        position: stamp::Position,
+
+        /// Watermark opacity, from 0 to 1
+        #[arg(long, default_value_t = 0.3)]
+        opacity: f32,
+
+        /// Watermark rotation in degrees, counter-clockwise
+        #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+        rotation: f32,
+
+        /// Watermark font size in points
+        #[arg(long, default_value_t = 48.0)]
+        font_size: f32,
+
+        /// Watermark text colour as RRGGBB
+        #[arg(long, default_value = "FF0000")]
+        color: stamp::Color,
+
+        /// Watermark image width in points [default: a third of the page width]
+        #[arg(long)]
+        image_width: Option<f32>,
+
+        /// Pages to watermark, e.g. 1,3-5,10- (all pages when omitted)
+        #[arg(short, long, value_delimiter = ',')]
+        pages: Vec<pages::PageRange>,
+
+        /// Number all pages of all files with this Bates prefix, e.g. ABC gives ABC000001
+        #[arg(long, value_name = "PREFIX")]
+        bates: Option<String>,
+
+        /// First Bates number
+        #[arg(long, default_value_t = 1, requires = "bates")]
+        bates_start: u64,
+
+        /// Digits of the Bates number, zero-padded
+        #[arg(long, default_value_t = 6, requires = "bates")]
+        bates_digits: usize,
+
+        /// Where to place the Bates number
+        #[arg(long, value_enum, default_value_t = stamp::Position::BottomRight, requires = "bates")]
+        bates_position: stamp::Position,
+
+        /// Bates number font size in points
+        #[arg(long, default_value_t = 10.0, requires = "bates")]
+        bates_font_size: f32,
+
+        /// Distance of stamps from the page edge in points
+        #[arg(long, default_value_t = 36.0)]
+        margin: f32,
     },
 }
 
@@ -63,14 +601,100 @@ fn main() {
     let cli = Cli::parse();
 
     let result = match cli.command {
-        Commands::Extract { output_file, input_paths } => {
-            extract::run(&output_file, &input_paths)
+        Commands::Extract { output_file, input_paths, layout, only_scanned, split_by, format, ocr } => {
+            extract::run(&output_file, &input_paths, layout, only_scanned, split_by, format, &ocr)
+        },
+        Commands::Search { search_phrase, directories, output, only_scanned, include_attachments, include_annotations, ocr } => {
+            search::run(search_phrase.as_deref().unwrap_or(""), &directories, &output, only_scanned, include_attachments, include_annotations, &ocr)
+        },
+        Commands::Analyze { keywords, input_paths, output_file, threshold, include_attachments, ocr } => {
+            stats::run(&input_paths, &keywords, &output_file, threshold, include_attachments, &ocr)
+        },
+        Commands::Inventory { input_paths, format, output_file } => {
+            inventory::run(&input_paths, format, output_file.as_deref())
+        },
+        Commands::Tables { input_paths, format, output } => {
+            tables::run(&input_paths, format, output.as_deref())
+        },
+        Commands::Images { output_dir, input_paths, format, min_width, min_height } => {
+            images::run(&output_dir, &input_paths, format, images::ImageFilter { min_width, min_height })
+        },
+        Commands::Render { output_dir, input_paths, dpi, format, quality, pages, thumbnail } => {
+            render::run(&input_paths, &output_dir, &render::RenderOptions { dpi, format, quality, pages, thumbnail })
+        },
+        Commands::Attachments { input_paths, extract, recursive, json } => {
+            attachments::run(&input_paths, extract.as_deref(), recursive, json)
+        },
+        Commands::Annotations { input_paths, format, output_file } => {
+            annotations::run(&input_paths, format, output_file.as_deref())
+        },
+        Commands::Outline { input_paths, format, output_file } => {
+            outline::run(&input_paths, format, output_file.as_deref())
+        },
+        Commands::Forms { input_paths, format, output_file, check, schema } => {
+            forms::run(&input_paths, format, output_file.as_deref(), check, schema.as_deref())
+        },
+        Commands::Links { input_paths, format, output_file, by_domain } => {
+            links::run(&input_paths, format, output_file.as_deref(), by_domain)
+        },
+        Commands::Entities { input_paths, types, format, output_file } => {
+            entities::run(&input_paths, &types, format, output_file.as_deref())
+        },
+        Commands::ScanPii { input_paths, format, output_file, detectors, config, min_severity, fail_on } => {
+            pii::run(&input_paths, format, output_file.as_deref(), &detectors, config.as_deref(), min_severity, fail_on)
+        },
+        Commands::Redact { input_paths, output, query, regex, pii, region, case_sensitive, keep_metadata, log_text } => {
+            let rules = redact::RedactRules { queries: query, regexes: regex, pii, regions: region, case_sensitive };
+            redact::run(&input_paths, &output, &rules, keep_metadata, log_text)
+        },
+        Commands::Merge { input_paths, output } => {
+            pages::run_merge(&input_paths, &output)
+        },
+        Commands::Split { input, output_dir, ranges, every, by_outline } => {
+            let mode = match (every, by_outline) {
+                (Some(size), _) => pages::SplitMode::Every(size),
+                (_, Some(level)) => pages::SplitMode::Outline(level),
+                _ => pages::SplitMode::Ranges(ranges),
+            };
+            pages::run_split(&input, &output_dir, &mode)
+        },
+        Commands::Pages { input, output, select, delete } => {
+            pages::run_pages(&input, &output, &select, &delete)
+        },
+        Commands::Meta { input_paths, set, csv, strip, output_dir, format, output_file } => {
+            let action = match (csv, strip) {
+                (Some(csv), _) => meta::MetaAction::Bulk(csv),
+                (None, true) => meta::MetaAction::Strip,
+                (None, false) if !set.is_empty() => meta::MetaAction::Set(set),
+                _ => meta::MetaAction::Read { format, output_file },
+            };
+            meta::run(&input_paths, &action, output_dir.as_deref())
+        },
+        Commands::Dedup { input_paths, method, threshold, shingle_size, format, output_file } => {
+            let options = dedup::DedupOptions { method, threshold, shingle_size };
+            dedup::run(&input_paths, &options, format, output_file.as_deref())
+        },
+        Commands::Check { input_paths, format, output_file, repair, fail_on } => {
+            check::run(&input_paths, format, output_file.as_deref(), repair.as_deref(), fail_on)
         },
-        Commands::Search { search_phrase, directories, zip } => {
-            search::run(&search_phrase, &directories, zip)
+        Commands::Diff { old, new, context, format, output_file } => {
+            diff::run(&old, &new, context, format, output_file.as_deref())
         },
-        Commands::Analyze { keywords, input_paths, output_file, threshold } => {
-            stats::run(&input_paths, &keywords, &output_file, threshold)
+        Commands::Stamp {
+            input_paths, output, text, image, position, opacity, rotation, font_size, color, image_width, pages,
+            bates, bates_start, bates_digits, bates_position, bates_font_size, margin,
+        } => {
+            let watermark = (text.is_some() || image.is_some()).then_some(stamp::Watermark {
+                text, image, position, opacity, rotation, font_size, color, image_width, pages,
+            });
+            let bates = bates.map(|prefix| stamp::Bates {
+                prefix,
+                start: bates_start,
+                digits: bates_digits,
+                position: bates_position,
+                font_size: bates_font_size,
+            });
+            stamp::run(&input_paths, &output, watermark.as_ref(), bates.as_ref(), margin)
         },
     };
 
@@ -0,0 +1,633 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use pdfium_render::prelude::*;
+
+use crate::layout::{self, LayoutError, OrderedLine, PageLayout};
+use crate::utils;
+
+/// Lines at least this much larger than the body text are headings
+const HEADING_SIZE_RATIO: f32 = 1.15;
+
+/// Longest line, in words, that is still taken for a heading
+const MAX_HEADING_WORDS: usize = 20;
+
+/// Longest bold body-size line, in words, that is taken for a run-in heading
+const MAX_BOLD_HEADING_WORDS: usize = 12;
+
+/// URI schemes that are written as links; others (`javascript:`, `data:`, `file:`, ...) stay plain text
+const LINK_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Structured output formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkupFormat {
+    Markdown,
+    Html,
+}
+
+/// Kinds of text blocks inferred from the page layout
+#[derive(Debug, Clone, PartialEq)]
+enum BlockKind {
+    Heading(usize),
+    Paragraph,
+    /// A list item with its marker: `None` for bullets, the number for numbered items
+    ListItem(Option<String>),
+}
+
+/// A run of text with uniform weight
+#[derive(Debug, Clone)]
+struct Span {
+    text: String,
+    bold: bool,
+}
+
+/// A heading, paragraph or list item
+#[derive(Debug, Clone)]
+struct Block {
+    kind: BlockKind,
+    spans: Vec<Span>,
+}
+
+/// A line in reading order, with the geometry needed to group lines into blocks
+struct Line {
+    spans: Vec<Span>,
+    text: String,
+    font_size: f32,
+    left: f32,
+    bottom: f32,
+    top: f32,
+}
+
+impl Line {
+    fn is_bold(&self) -> bool {
+        self.spans.iter().all(|span| span.bold || span.text.trim().is_empty())
+    }
+
+    fn words(&self) -> usize {
+        self.text.split_whitespace().count()
+    }
+}
+
+/// Where a link on the page points
+#[derive(Debug, Clone)]
+enum LinkTarget {
+    Uri(String),
+    /// 1-based page number in the same document
+    Page(usize),
+}
+
+/// A link annotation with the text it covers
+#[derive(Debug, Clone)]
+struct PageLink {
+    anchor: String,
+    target: LinkTarget,
+}
+
+/// Render every page of a document as Markdown or HTML. Headings are inferred from font
+/// size and weight relative to the body text, paragraphs from line spacing, lists from
+/// bullet and number markers; link annotations and URLs in the text become links.
+/// Each page starts with an anchor named `<prefix>-page-<N>`.
+pub fn render_document(pdfium: &Pdfium, path: &Path, prefix: &str, format: MarkupFormat) -> Result<Vec<String>, LayoutError> {
+    let document = pdfium.load_pdf_from_file(path, None)
+        .map_err(|e| LayoutError::PdfError(format!("Error loading {}: {}", path.display(), e)))?;
+
+    let mut layouts: Vec<PageLayout> = Vec::new();
+    let mut links: Vec<Vec<PageLink>> = Vec::new();
+    for page in document.pages().iter() {
+        layouts.push(layout::page_layout(&page));
+        links.push(page_links(&page));
+    }
+    layout::remove_running_elements(&mut layouts);
+
+    let body_size = body_font_size(&layouts);
+    let pages_lines: Vec<Vec<Line>> = layouts.iter()
+        .map(|page| layout::reading_order_lines(page).iter().map(to_line).collect())
+        .collect();
+    let heading_sizes = heading_sizes(&pages_lines, body_size);
+
+    let pages = pages_lines.iter().zip(&links).enumerate()
+        .map(|(index, (lines, links))| {
+            let blocks = build_blocks(lines, body_size, &heading_sizes);
+            let mut output = page_anchor(prefix, index + 1, format);
+            output.push_str(&render_blocks(&blocks, links, prefix, format));
+            output
+        })
+        .collect();
+
+    Ok(pages)
+}
+
+/// Render plain text (such as OCR output) as paragraphs split at blank lines
+pub fn plain_text(text: &str, format: MarkupFormat) -> String {
+    let blocks: Vec<Block> = text.split("\n\n")
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| Block { kind: BlockKind::Paragraph, spans: vec![Span { text: paragraph, bold: false }] })
+        .collect();
+    render_blocks(&blocks, &[], "", format)
+}
+
+/// Wrap rendered documents, given as (file name, anchor prefix, body), into one output file
+pub fn wrap_documents(documents: &[(String, String, String)], format: MarkupFormat) -> String {
+    match format {
+        MarkupFormat::Markdown => documents.iter()
+            .map(|(name, _, body)| format!("<!-- Source: {} -->\n\n{}", name, body.trim_end()))
+            .collect::<Vec<_>>()
+            .join("\n\n---\n\n") + "\n",
+        MarkupFormat::Html => {
+            let title = match documents {
+                [(name, _, _)] => escape_html(name),
+                _ => "Extracted documents".to_string(),
+            };
+            let mut html = format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+                title
+            );
+            for (name, prefix, body) in documents {
+                html.push_str(&format!(
+                    "<article id=\"{}\" data-source=\"{}\">\n{}</article>\n",
+                    prefix, escape_html(name), body
+                ));
+            }
+            html.push_str("</body>\n</html>\n");
+            html
+        },
+    }
+}
+
+/// Anchor prefixes for documents combined into one output, derived from their file names.
+/// Documents that would share a prefix get numbered ones, so their anchors stay unique.
+pub fn anchor_prefixes(paths: &[PathBuf]) -> Vec<String> {
+    let mut used = HashSet::new();
+    utils::unique_stems(paths).iter()
+        .map(|stem| {
+            let slug = utils::slug(stem);
+            let mut candidate = slug.clone();
+            let mut counter = 2;
+            while !used.insert(candidate.clone()) {
+                candidate = format!("{}-{}", slug, counter);
+                counter += 1;
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// Collect the URI and internal links of a page with the text under their rectangles
+fn page_links(page: &PdfPage) -> Vec<PageLink> {
+    let text = match page.text() {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+
+    page.links().iter()
+        .filter_map(|link| {
+            let target = match link.action() {
+                Some(PdfAction::Uri(action)) => LinkTarget::Uri(action.uri().ok()?),
+                Some(PdfAction::LocalDestination(action)) => {
+                    LinkTarget::Page(action.destination().ok()?.page_index().ok()? as usize + 1)
+                },
+                Some(_) => return None,
+                None => LinkTarget::Page(link.destination()?.page_index().ok()? as usize + 1),
+            };
+            let anchor = text.inside_rect(link.rect().ok()?)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            Some(PageLink { anchor, target })
+        })
+        .collect()
+}
+
+/// The most common font size of the document's text, weighted by characters
+fn body_font_size(layouts: &[PageLayout]) -> f32 {
+    let mut histogram: HashMap<i32, usize> = HashMap::new();
+    for fragment in layouts.iter().flat_map(|page| &page.lines).flat_map(|line| &line.fragments) {
+        *histogram.entry(size_key(fragment.font_size)).or_insert(0) += fragment.text.chars().count();
+    }
+    histogram.into_iter()
+        .max_by_key(|(size, count)| (*count, -size))
+        .map(|(size, _)| size as f32 / 2.0)
+        .unwrap_or(10.0)
+}
+
+/// Font sizes rounded to half points
+fn size_key(size: f32) -> i32 {
+    (size * 2.0).round() as i32
+}
+
+/// Distinct heading font sizes, largest first; their rank is the heading level
+fn heading_sizes(pages: &[Vec<Line>], body_size: f32) -> Vec<i32> {
+    let mut sizes: Vec<i32> = pages.iter().flatten()
+        .filter(|line| is_large(line, body_size))
+        .map(|line| size_key(line.font_size))
+        .collect();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes.dedup();
+    // Deeper than three levels of size the differences are usually noise
+    sizes.truncate(3);
+    sizes
+}
+
+fn is_large(line: &Line, body_size: f32) -> bool {
+    line.font_size >= body_size * HEADING_SIZE_RATIO && line.words() <= MAX_HEADING_WORDS
+}
+
+/// Convert a line in reading order into styled spans
+fn to_line(ordered: &OrderedLine) -> Line {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut push = |text: &str, bold: bool| {
+        if text.is_empty() {
+            return;
+        }
+        match spans.last_mut() {
+            Some(last) if last.bold == bold => last.text.push_str(text),
+            _ => spans.push(Span { text: text.to_string(), bold }),
+        }
+    };
+
+    for (index, fragment) in ordered.fragments.iter().enumerate() {
+        if index > 0 {
+            push(" ", fragment.is_bold());
+        }
+        let mut position = 0;
+        for &(start, end) in &fragment.bold_spans {
+            push(&fragment.text[position..start], false);
+            push(&fragment.text[start..end], true);
+            position = end;
+        }
+        push(&fragment.text[position..], false);
+    }
+
+    let fragments = &ordered.fragments;
+    Line {
+        text: spans.iter().map(|span| span.text.as_str()).collect(),
+        spans,
+        font_size: fragments.iter().map(|f| f.font_size).fold(0.0, f32::max),
+        left: fragments.iter().map(|f| f.left).fold(f32::MAX, f32::min),
+        bottom: fragments.iter().map(|f| f.bottom).fold(f32::MAX, f32::min),
+        top: fragments.iter().map(|f| f.top).fold(f32::MIN, f32::max),
+    }
+}
+
+/// Split a list marker such as `•`, `-`, `3.` or `b)` off the start of a line.
+/// Returns the marker (`None` for bullets) and the byte length to strip.
+fn list_marker(text: &str) -> Option<(Option<String>, usize)> {
+    let first = text.chars().next()?;
+    if "•◦▪▫‣∙·●○■□–-*".contains(first) {
+        let rest = &text[first.len_utf8()..];
+        // A dash directly followed by text is a hyphenated word, not a bullet
+        if rest.starts_with(' ') || first == '•' {
+            return Some((None, text.len() - rest.trim_start().len()));
+        }
+        return None;
+    }
+
+    let label: String = text.chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+    let is_label = (!label.is_empty() && label.len() <= 3 && label.chars().all(|c| c.is_ascii_digit()))
+        || (label.len() == 1 && label.chars().all(|c| c.is_ascii_lowercase()));
+    let rest = &text[label.len()..];
+    if is_label && (rest.starts_with(". ") || rest.starts_with(") ")) {
+        let number = if label.chars().all(|c| c.is_ascii_digit()) { label } else { format!("{})", label) };
+        return Some((Some(number), text.len() - rest[1..].trim_start().len()));
+    }
+    None
+}
+
+/// Group the lines of a page into headings, paragraphs and list items
+fn build_blocks(lines: &[Line], body_size: f32, heading_sizes: &[i32]) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut previous: Option<&Line> = None;
+    // Left edge of the current list item's first line
+    let mut item_left = 0.0;
+
+    for line in lines {
+        if line.text.trim().is_empty() {
+            continue;
+        }
+
+        // Consecutive lines of one block are close together and in the same column
+        let close = previous.map(|prev| {
+            let gap = prev.bottom - line.top;
+            let height = (line.top - line.bottom).max(1.0);
+            gap > -0.5 * height && gap < 0.8 * height && (prev.font_size - line.font_size).abs() < 1.0
+        }).unwrap_or(false);
+
+        let kind = if is_large(line, body_size) {
+            let rank = heading_sizes.iter().position(|size| *size == size_key(line.font_size)).unwrap_or(heading_sizes.len());
+            BlockKind::Heading(rank + 1)
+        } else if line.is_bold() && line.words() <= MAX_BOLD_HEADING_WORDS && !line.text.trim_end().ends_with(['.', ':', ',']) && list_marker(&line.text).is_none() {
+            BlockKind::Heading((heading_sizes.len() + 1).min(6))
+        } else if let Some((marker, _)) = list_marker(&line.text) {
+            BlockKind::ListItem(marker)
+        } else {
+            BlockKind::Paragraph
+        };
+
+        let continues = match (blocks.last(), &kind) {
+            // Headings wrapped over several lines
+            (Some(Block { kind: BlockKind::Heading(a), .. }), BlockKind::Heading(b)) => close && a == b,
+            (Some(Block { kind: BlockKind::Paragraph, .. }), BlockKind::Paragraph) => close,
+            // Wrapped list item text is indented past the marker
+            (Some(Block { kind: BlockKind::ListItem(_), .. }), BlockKind::Paragraph) => close && line.left > item_left + 1.0,
+            _ => false,
+        };
+
+        let mut spans = line.spans.clone();
+        if let BlockKind::ListItem(_) = kind {
+            if let Some((_, strip)) = list_marker(&line.text) {
+                strip_prefix(&mut spans, strip);
+            }
+            item_left = line.left;
+        }
+
+        match blocks.last_mut() {
+            Some(block) if continues => append_line(&mut block.spans, spans),
+            _ => blocks.push(Block { kind, spans }),
+        }
+        previous = Some(line);
+    }
+
+    blocks
+}
+
+/// Remove the first `len` bytes of text from a list of spans
+fn strip_prefix(spans: &mut Vec<Span>, mut len: usize) {
+    while len > 0 && !spans.is_empty() {
+        if spans[0].text.len() <= len {
+            len -= spans[0].text.len();
+            spans.remove(0);
+        } else {
+            spans[0].text.drain(..len);
+            len = 0;
+        }
+    }
+}
+
+/// Join a wrapped line onto a block, removing end-of-line hyphenation
+fn append_line(block: &mut Vec<Span>, line: Vec<Span>) {
+    if let Some(last) = block.last_mut() {
+        let trimmed = last.text.trim_end().len();
+        last.text.truncate(trimmed);
+        let hyphenated = last.text.ends_with('-')
+            && last.text[..last.text.len() - 1].ends_with(|c: char| c.is_alphabetic())
+            && line.first().map(|span| span.text.starts_with(|c: char| c.is_lowercase())).unwrap_or(false);
+        if hyphenated {
+            last.text.pop();
+        } else {
+            last.text.push(' ');
+        }
+    }
+    for span in line {
+        match block.last_mut() {
+            Some(last) if last.bold == span.bold => last.text.push_str(&span.text),
+            _ => block.push(span),
+        }
+    }
+}
+
+fn page_anchor(prefix: &str, page: usize, format: MarkupFormat) -> String {
+    match format {
+        MarkupFormat::Markdown => format!("<a id=\"{}-page-{}\"></a>\n\n", prefix, page),
+        MarkupFormat::Html => format!("<a id=\"{}-page-{}\"></a>\n", prefix, page),
+    }
+}
+
+/// Render blocks; consecutive list items form one list
+fn render_blocks(blocks: &[Block], links: &[PageLink], prefix: &str, format: MarkupFormat) -> String {
+    let mut output = String::new();
+    let mut open_list: Option<&str> = None;
+
+    for (index, block) in blocks.iter().enumerate() {
+        // Headings are set off by their tag; bold inside them would be redundant
+        let inline = match block.kind {
+            BlockKind::Heading(_) => {
+                let plain = vec![Span { text: block.spans.iter().map(|s| s.text.as_str()).collect(), bold: false }];
+                render_inline(&plain, links, prefix, format)
+            },
+            _ => render_inline(&block.spans, links, prefix, format),
+        };
+        let inline = inline.trim();
+
+        match format {
+            MarkupFormat::Markdown => {
+                match &block.kind {
+                    BlockKind::Heading(level) => output.push_str(&format!("{} {}\n\n", "#".repeat(*level), inline)),
+                    BlockKind::Paragraph => output.push_str(&format!("{}\n\n", escape_block_start(inline))),
+                    BlockKind::ListItem(marker) => {
+                        let marker = match marker {
+                            Some(number) if !number.ends_with(')') => format!("{}.", number),
+                            Some(letter) => format!("- {}", letter),
+                            None => "-".to_string(),
+                        };
+                        output.push_str(&format!("{} {}\n", marker, inline));
+                        let next_is_item = matches!(blocks.get(index + 1), Some(Block { kind: BlockKind::ListItem(_), .. }));
+                        if !next_is_item {
+                            output.push('\n');
+                        }
+                    },
+                }
+            },
+            MarkupFormat::Html => {
+                let list_tag = match &block.kind {
+                    BlockKind::ListItem(Some(number)) if !number.ends_with(')') => Some("ol"),
+                    BlockKind::ListItem(_) => Some("ul"),
+                    _ => None,
+                };
+                if open_list != list_tag {
+                    if let Some(tag) = open_list {
+                        output.push_str(&format!("</{}>\n", tag));
+                    }
+                    if let Some(tag) = list_tag {
+                        output.push_str(&format!("<{}>\n", tag));
+                    }
+                    open_list = list_tag;
+                }
+                match &block.kind {
+                    BlockKind::Heading(level) => output.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline)),
+                    BlockKind::Paragraph => output.push_str(&format!("<p>{}</p>\n", inline)),
+                    BlockKind::ListItem(_) => output.push_str(&format!("<li>{}</li>\n", inline)),
+                }
+            },
+        }
+    }
+
+    if let Some(tag) = open_list {
+        output.push_str(&format!("</{}>\n", tag));
+    }
+    output
+}
+
+/// Render styled spans, turning link anchors and URLs into links
+fn render_inline(spans: &[Span], links: &[PageLink], prefix: &str, format: MarkupFormat) -> String {
+    let mut output = String::new();
+
+    for span in spans {
+        let mut rendered = String::new();
+        let mut position = 0;
+        for (start, end, href) in find_links(&span.text, links, prefix) {
+            rendered.push_str(&escape(&span.text[position..start], format));
+            let label = escape(&span.text[start..end], format);
+            match format {
+                MarkupFormat::Markdown => rendered.push_str(&format!("[{}]({})", label, encode_href(&href))),
+                MarkupFormat::Html => rendered.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(&encode_href(&href)), label)),
+            }
+            position = end;
+        }
+        rendered.push_str(&escape(&span.text[position..], format));
+
+        if span.bold && !rendered.trim().is_empty() {
+            // Keep surrounding spaces outside the emphasis markers
+            let leading = &rendered[..rendered.len() - rendered.trim_start().len()];
+            let trailing = &rendered[rendered.trim_end().len()..];
+            let (open, close) = match format {
+                MarkupFormat::Markdown => ("**", "**"),
+                MarkupFormat::Html => ("<strong>", "</strong>"),
+            };
+            output.push_str(&format!("{}{}{}{}{}", leading, open, rendered.trim(), close, trailing));
+        } else {
+            output.push_str(&rendered);
+        }
+    }
+    output
+}
+
+/// Find link anchors and bare URLs in a piece of text, as non-overlapping
+/// (start, end, href) byte ranges in order
+fn find_links(text: &str, links: &[PageLink], prefix: &str) -> Vec<(usize, usize, String)> {
+    let mut found: Vec<(usize, usize, String)> = Vec::new();
+
+    for link in links.iter().filter(|link| link.anchor.chars().count() >= 2) {
+        if let Some(start) = text.find(&link.anchor) {
+            let href = match &link.target {
+                LinkTarget::Uri(uri) if has_link_scheme(uri) => uri.trim().to_string(),
+                LinkTarget::Uri(_) => continue,
+                LinkTarget::Page(page) => format!("#{}-page-{}", prefix, page),
+            };
+            found.push((start, start + link.anchor.len(), href));
+        }
+    }
+
+    found.extend(utils::find_urls(text));
+
+    found.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+    let mut result: Vec<(usize, usize, String)> = Vec::new();
+    for link in found {
+        if result.last().map(|last| link.0 >= last.1).unwrap_or(true) {
+            result.push(link);
+        }
+    }
+    result
+}
+
+/// Check whether a URI uses one of the schemes in `LINK_SCHEMES`
+fn has_link_scheme(uri: &str) -> bool {
+    uri.trim().split_once(':')
+        .map(|(scheme, _)| LINK_SCHEMES.iter().any(|s| s.eq_ignore_ascii_case(scheme)))
+        .unwrap_or(false)
+}
+
+/// Percent-encode the characters of a link target that would end a Markdown link
+/// or are not allowed in a URL (spaces, parentheses, quotes, non-ASCII)
+fn encode_href(href: &str) -> String {
+    let mut encoded = String::with_capacity(href.len());
+    for byte in href.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~:/?#@!$&'*+,;=%".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn escape(text: &str, format: MarkupFormat) -> String {
+    match format {
+        MarkupFormat::Markdown => escape_markdown(text),
+        MarkupFormat::Html => escape_html(text),
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if matches!(ch, '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Keep paragraphs that start like a heading or list from being read as one
+fn escape_block_start(text: &str) -> String {
+    let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+    if text.starts_with('#') || text.starts_with("- ") || text.starts_with("+ ") {
+        format!("\\{}", text)
+    } else if digits > 0 && (text[digits..].starts_with(". ") || text[digits..].starts_with(") ")) {
+        format!("{}\\{}", &text[..digits], &text[digits..])
+    } else {
+        text.to_string()
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri_link(anchor: &str, uri: &str) -> PageLink {
+        PageLink { anchor: anchor.to_string(), target: LinkTarget::Uri(uri.to_string()) }
+    }
+
+    fn render(text: &str, links: &[PageLink], format: MarkupFormat) -> String {
+        render_inline(&[Span { text: text.to_string(), bold: false }], links, "doc", format)
+    }
+
+    #[test]
+    fn encodes_link_targets() {
+        let links = [uri_link("the spec", "https://example.com/a b(1).pdf")];
+        assert_eq!(
+            render("See the spec for details", &links, MarkupFormat::Markdown),
+            "See [the spec](https://example.com/a%20b%281%29.pdf) for details"
+        );
+        assert_eq!(
+            render("See the spec", &links, MarkupFormat::Html),
+            "See <a href=\"https://example.com/a%20b%281%29.pdf\">the spec</a>"
+        );
+        assert_eq!(encode_href("https://x.org/?q=\"<b>\"&r=ü"), "https://x.org/?q=%22%3Cb%3E%22&r=%C3%BC");
+    }
+
+    #[test]
+    fn links_only_web_and_mail_schemes() {
+        let links = [
+            uri_link("click", "javascript:alert(1)"),
+            uri_link("image", "data:text/html;base64,PHNjcmlwdD4="),
+            uri_link("write", "MAILTO:info@example.com"),
+            PageLink { anchor: "chapter 2".to_string(), target: LinkTarget::Page(7) },
+        ];
+        assert_eq!(
+            render("click the image or write about chapter 2", &links, MarkupFormat::Html),
+            "click the image or <a href=\"MAILTO:info@example.com\">write</a> about <a href=\"#doc-page-7\">chapter 2</a>"
+        );
+    }
+
+    #[test]
+    fn numbers_anchor_prefixes_of_same_named_documents() {
+        let paths: Vec<PathBuf> = ["a/Annual Report.pdf", "b/annual-report.pdf", "c/notes.pdf"]
+            .into_iter().map(PathBuf::from).collect();
+        assert_eq!(anchor_prefixes(&paths), vec!["annual-report", "annual-report-2", "notes"]);
+    }
+
+    #[test]
+    fn escapes_markdown_text() {
+        assert_eq!(render("a_b *c* [d]", &[], MarkupFormat::Markdown), "a\\_b \\*c\\* \\[d\\]");
+        assert_eq!(escape_block_start("1. not a list"), "1\\. not a list");
+        assert_eq!(escape_block_start("# not a heading"), "\\# not a heading");
+    }
+}
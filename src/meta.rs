@@ -0,0 +1,868 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use clap::ValueEnum;
+use lopdf::{dictionary, Dictionary, Document, Object, Stream};
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::{NsReader, Writer};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::utils;
+
+/// Custom error type for metadata operations
+#[derive(Debug)]
+pub enum MetaError {
+    Io(io::Error),
+    Pdf(lopdf::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for MetaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetaError::Io(e) => write!(f, "I/O error: {}", e),
+            MetaError::Pdf(e) => write!(f, "PDF error: {}", e),
+            MetaError::Csv(e) => write!(f, "CSV error: {}", e),
+            MetaError::Json(e) => write!(f, "JSON error: {}", e),
+            MetaError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for MetaError {}
+
+impl From<io::Error> for MetaError {
+    fn from(err: io::Error) -> Self {
+        MetaError::Io(err)
+    }
+}
+
+impl From<lopdf::Error> for MetaError {
+    fn from(err: lopdf::Error) -> Self {
+        MetaError::Pdf(err)
+    }
+}
+
+impl From<csv::Error> for MetaError {
+    fn from(err: csv::Error) -> Self {
+        MetaError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for MetaError {
+    fn from(err: serde_json::Error) -> Self {
+        MetaError::Json(err)
+    }
+}
+
+/// Output formats supported by the meta command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum MetaFormat {
+    Csv,
+    Json,
+}
+
+/// A standard field: CLI/CSV name, Info dictionary key and XMP property
+struct StandardField {
+    name: &'static str,
+    info_key: &'static str,
+    xmp_property: &'static str,
+}
+
+const STANDARD_FIELDS: [StandardField; 8] = [
+    StandardField { name: "title", info_key: "Title", xmp_property: "dc:title" },
+    StandardField { name: "author", info_key: "Author", xmp_property: "dc:creator" },
+    StandardField { name: "subject", info_key: "Subject", xmp_property: "dc:description" },
+    StandardField { name: "keywords", info_key: "Keywords", xmp_property: "pdf:Keywords" },
+    StandardField { name: "creator", info_key: "Creator", xmp_property: "xmp:CreatorTool" },
+    StandardField { name: "producer", info_key: "Producer", xmp_property: "pdf:Producer" },
+    StandardField { name: "creation_date", info_key: "CreationDate", xmp_property: "xmp:CreateDate" },
+    StandardField { name: "modification_date", info_key: "ModDate", xmp_property: "xmp:ModifyDate" },
+];
+
+/// Namespace of the RDF elements that hold XMP properties
+const RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// XMP namespaces of the properties above
+const XMP_NAMESPACES: [(&str, &str); 3] = [
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("pdf", "http://ns.adobe.com/pdf/1.3/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+];
+
+/// A metadata field: one of the standard fields (by index) or a custom Info key
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Standard(usize),
+    Custom(String),
+}
+
+impl Field {
+    /// Resolve a field name: a standard field by CLI/CSV name or Info key (any case), otherwise a custom Info key
+    fn parse(name: &str) -> Result<Field, MetaError> {
+        let name = name.trim();
+        if let Some(index) = STANDARD_FIELDS.iter().position(|field| {
+            field.name.eq_ignore_ascii_case(name) || field.info_key.eq_ignore_ascii_case(name)
+        }) {
+            return Ok(Field::Standard(index));
+        }
+        let valid = !name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic() && !b"/()<>[]{}%#".contains(&b));
+        if valid {
+            Ok(Field::Custom(name.to_string()))
+        } else {
+            Err(MetaError::Other(format!("Invalid metadata field name '{}'", name)))
+        }
+    }
+
+    fn info_key(&self) -> &str {
+        match self {
+            Field::Standard(index) => STANDARD_FIELDS[*index].info_key,
+            Field::Custom(key) => key,
+        }
+    }
+
+    fn is_date(&self) -> bool {
+        matches!(self, Field::Standard(index) if STANDARD_FIELDS[*index].name.ends_with("_date"))
+    }
+}
+
+/// A `FIELD=VALUE` assignment; an empty value removes the field
+#[derive(Debug, Clone)]
+pub struct FieldValue {
+    pub field: String,
+    pub value: String,
+}
+
+impl FromStr for FieldValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, value) = s.split_once('=')
+            .ok_or_else(|| format!("Expected FIELD=VALUE, got '{}'", s))?;
+        Field::parse(field).map_err(|e| e.to_string())?;
+        Ok(FieldValue { field: field.trim().to_string(), value: value.to_string() })
+    }
+}
+
+/// What the meta command does with the documents
+pub enum MetaAction {
+    /// Report the metadata
+    Read { format: MetaFormat, output_file: Option<String> },
+    /// Apply the same assignments to every document
+    Set(Vec<FieldValue>),
+    /// Apply per-document assignments from a CSV with a `path` column and a column per field
+    Bulk(String),
+    /// Remove all metadata
+    Strip,
+}
+
+/// Metadata of one PDF as reported by the meta command
+#[derive(Debug, Default, Serialize)]
+pub struct MetaRecord {
+    pub path: String,
+    /// Document information dictionary entries, dates as `YYYY-MM-DD HH:MM:SS`
+    pub info: BTreeMap<String, String>,
+    /// XMP properties of the document metadata stream
+    pub xmp: BTreeMap<String, String>,
+    pub error: Option<String>,
+}
+
+impl MetaRecord {
+    /// The standard fields as CSV columns, taking the XMP value where the Info dictionary has none
+    fn to_row(&self) -> Vec<String> {
+        let mut row = vec![self.path.clone()];
+        for field in &STANDARD_FIELDS {
+            let value = self.info.get(field.info_key)
+                .or_else(|| self.xmp.get(field.xmp_property))
+                .cloned()
+                .unwrap_or_default();
+            row.push(value);
+        }
+        row
+    }
+}
+
+/// Main function of the meta command; `output_dir` receives modified copies instead of
+/// updating the files in place
+pub fn run(input_paths: &[String], action: &MetaAction, output_dir: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let jobs: Vec<(PathBuf, Vec<FieldValue>)> = match action {
+        MetaAction::Bulk(csv_path) => {
+            if !input_paths.is_empty() {
+                return Err(Box::new(MetaError::Other(
+                    "Input paths are taken from the CSV file in bulk mode".to_string()
+                )));
+            }
+            read_bulk_csv(Path::new(csv_path))?
+        },
+        MetaAction::Set(assignments) => {
+            utils::collect_pdf_paths(input_paths).into_iter().map(|path| (path, assignments.clone())).collect()
+        },
+        _ => utils::collect_pdf_paths(input_paths).into_iter().map(|path| (path, Vec::new())).collect(),
+    };
+
+    if jobs.is_empty() {
+        return Err(Box::new(MetaError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    if let MetaAction::Read { format, output_file } = action {
+        let records: Vec<MetaRecord> = jobs.iter().map(|(path, _)| read_metadata(path)).collect();
+        match format {
+            MetaFormat::Csv => write_csv(&records, output_file.as_deref())?,
+            MetaFormat::Json => write_json(&records, output_file.as_deref())?,
+        }
+        if let Some(output_file) = output_file {
+            println!("Successfully wrote metadata of {} PDFs to '{}'", records.len(), output_file);
+        }
+        return Ok(());
+    }
+
+    if let Some(output_dir) = output_dir {
+        fs::create_dir_all(output_dir)?;
+    }
+
+    // Copies of same-named inputs get numbered names instead of overwriting each other
+    let paths: Vec<PathBuf> = jobs.iter().map(|(path, _)| path.clone()).collect();
+    let stems = utils::unique_stems(&paths);
+    let mut updated = 0;
+    for ((path, assignments), stem) in jobs.iter().zip(&stems) {
+        let output = match output_dir {
+            Some(output_dir) => Path::new(output_dir).join(format!("{}.pdf", stem)),
+            None => path.clone(),
+        };
+        let result = if matches!(action, MetaAction::Strip) {
+            strip_file(path, &output).map(|count| format!("removed {} metadata entries", count))
+        } else {
+            update_file(path, &output, assignments).map(|fields| format!("set {}", fields.join(", ")))
+        };
+        match result {
+            Ok(summary) => {
+                println!("{}: {}", output.display(), summary);
+                updated += 1;
+            },
+            Err(e) => eprintln!("Error updating {}: {}", path.display(), e),
+        }
+    }
+
+    println!("Updated {} of {} PDF files", updated, jobs.len());
+    Ok(())
+}
+
+/// Read the document information dictionary and the XMP properties of a PDF
+pub fn read_metadata(path: &Path) -> MetaRecord {
+    let mut record = MetaRecord {
+        path: path.to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+
+    let doc = match Document::load(path) {
+        Ok(doc) => doc,
+        Err(e) => {
+            record.error = Some(format!("Error parsing {}: {}", path.display(), e));
+            return record;
+        },
+    };
+
+    if let Some(info) = utils::info_dictionary(&doc) {
+        for (key, _) in info.iter() {
+            let key = String::from_utf8_lossy(key).into_owned();
+            if let Some(value) = utils::dict_string(&doc, info, key.as_bytes()) {
+                let value = if Field::parse(&key).map(|f| f.is_date()).unwrap_or(false) {
+                    utils::format_pdf_date(&value)
+                } else {
+                    value
+                };
+                record.info.insert(key, value);
+            }
+        }
+    }
+
+    if let Some(xmp) = document_xmp(&doc) {
+        for field in &STANDARD_FIELDS {
+            if let Some(value) = xmp_value(&xmp, field.xmp_property) {
+                record.xmp.insert(field.xmp_property.to_string(), value);
+            }
+        }
+    }
+
+    record
+}
+
+/// Read `path,field,...` rows into per-file assignments; empty cells leave a field unchanged
+fn read_bulk_csv(csv_path: &Path) -> Result<Vec<(PathBuf, Vec<FieldValue>)>, MetaError> {
+    let mut reader = csv::Reader::from_path(csv_path)?;
+    let headers = reader.headers()?.clone();
+    let path_column = headers.iter().position(|h| h.trim().eq_ignore_ascii_case("path"))
+        .ok_or_else(|| MetaError::Other(format!("{} has no `path` column", csv_path.display())))?;
+    for (index, header) in headers.iter().enumerate() {
+        if index != path_column {
+            Field::parse(header)?;
+        }
+    }
+
+    // Relative paths are resolved against the working directory, then the CSV's directory
+    let base = csv_path.parent().unwrap_or(Path::new(""));
+    let mut jobs = Vec::new();
+    for row in reader.records() {
+        let row = row?;
+        let path = PathBuf::from(row.get(path_column).unwrap_or_default().trim());
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let path = if path.is_relative() && !path.exists() && base.join(&path).exists() {
+            base.join(path)
+        } else {
+            path
+        };
+
+        let assignments: Vec<FieldValue> = headers.iter()
+            .zip(row.iter())
+            .enumerate()
+            .filter(|(index, (_, value))| *index != path_column && !value.is_empty())
+            .map(|(_, (field, value))| FieldValue { field: field.trim().to_string(), value: value.to_string() })
+            .collect();
+        if !assignments.is_empty() {
+            jobs.push((path, assignments));
+        }
+    }
+    Ok(jobs)
+}
+
+/// Apply assignments to the Info dictionary and the XMP metadata. Returns the fields changed.
+fn update_file(input: &Path, output: &Path, assignments: &[FieldValue]) -> Result<Vec<String>, MetaError> {
+    let mut doc = load_unencrypted(input)?;
+
+    let mut info_values: Vec<(Field, Option<Object>)> = Vec::new();
+    let mut xmp_values: Vec<(&str, Option<String>)> = Vec::new();
+    for assignment in assignments {
+        let field = Field::parse(&assignment.field)?;
+        let value = assignment.value.trim();
+        let (info_value, xmp_text) = if value.is_empty() {
+            (None, None)
+        } else if field.is_date() {
+            let date = pdf_date(value)?;
+            let iso = utils::format_pdf_date(&date).replacen(' ', "T", 1);
+            (Some(Object::string_literal(date)), Some(iso))
+        } else {
+            (Some(utils::encode_pdf_string(value)), Some(value.to_string()))
+        };
+        if let Field::Standard(index) = field {
+            xmp_values.push((STANDARD_FIELDS[index].xmp_property, xmp_text));
+        }
+        info_values.push((field, info_value));
+    }
+
+    let info = info_dictionary_mut(&mut doc)?;
+    for (field, value) in &info_values {
+        match value {
+            Some(value) => info.set(field.info_key().as_bytes().to_vec(), value.clone()),
+            None => {
+                info.remove(field.info_key().as_bytes());
+            },
+        }
+    }
+
+    if !xmp_values.is_empty() {
+        let mut xmp = document_xmp(&doc).unwrap_or_else(empty_xmp_packet);
+        for (property, value) in &xmp_values {
+            xmp = set_xmp_value(&xmp, property, value.as_deref())?;
+        }
+        set_document_xmp(&mut doc, xmp)?;
+    }
+
+    save(&mut doc, input, output)?;
+    Ok(info_values.iter().map(|(field, _)| field.info_key().to_string()).collect())
+}
+
+/// Remove the document information, XMP streams and private application data. Returns the
+/// number of entries removed.
+fn strip_file(input: &Path, output: &Path) -> Result<usize, MetaError> {
+    let mut doc = load_unencrypted(input)?;
+
+    let mut removed = usize::from(doc.trailer.remove(b"Info").is_some());
+    // Pages, images and fonts can carry metadata streams of their own
+    for object in doc.objects.values_mut() {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &mut stream.dict,
+            _ => continue,
+        };
+        for key in [b"Metadata".as_slice(), b"PieceInfo"] {
+            if dict.remove(key).is_some() {
+                removed += 1;
+            }
+        }
+    }
+    doc.prune_objects();
+
+    save(&mut doc, input, output)?;
+    Ok(removed)
+}
+
+fn load_unencrypted(path: &Path) -> Result<Document, MetaError> {
+    let doc = Document::load(path)?;
+    if doc.is_encrypted() {
+        return Err(MetaError::Other("Encrypted PDFs cannot be modified".to_string()));
+    }
+    Ok(doc)
+}
+
+/// Save a modified document; updating in place goes through a temporary file next to the input
+fn save(doc: &mut Document, input: &Path, output: &Path) -> Result<(), MetaError> {
+    let in_place = match (fs::canonicalize(input), fs::canonicalize(output)) {
+        (Ok(input), Ok(output)) => input == output,
+        _ => false,
+    };
+    if !in_place {
+        doc.save(output)?;
+        return Ok(());
+    }
+
+    let temp = output.with_extension("pdf.tmp");
+    doc.save(&temp)?;
+    fs::rename(&temp, output)?;
+    Ok(())
+}
+
+/// The Info dictionary of a document, created when missing
+fn info_dictionary_mut(doc: &mut Document) -> Result<&mut Dictionary, MetaError> {
+    let info_id = match doc.trailer.get(b"Info") {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(Object::Dictionary(_)) => None,
+        _ => {
+            let id = doc.add_object(Dictionary::new());
+            doc.trailer.set("Info", id);
+            Some(id)
+        },
+    };
+    match info_id {
+        Some(id) => Ok(doc.get_dictionary_mut(id)?),
+        None => Ok(doc.trailer.get_mut(b"Info")?.as_dict_mut()?),
+    }
+}
+
+/// Convert an ISO date (`YYYY[-MM[-DD[ HH:MM[:SS]]]]` with an optional `Z` or `±HH:MM`, `T`
+/// allowed instead of the space) or a PDF date into PDF date format
+fn pdf_date(value: &str) -> Result<String, MetaError> {
+    let value = value.trim();
+    let invalid = || MetaError::Other(format!("Invalid date '{}', expected YYYY-MM-DD HH:MM:SS", value));
+    if value.starts_with("D:") {
+        let pdf = Regex::new(r"^D:[0-9]{4}([0-9]{2}){0,5}(Z|[+-][0-9]{2}'[0-9]{2}'?)?$").unwrap();
+        return if pdf.is_match(value) { Ok(value.to_string()) } else { Err(invalid()) };
+    }
+
+    let iso = Regex::new(
+        r"^([0-9]{4})(?:-([0-9]{2})(?:-([0-9]{2})(?:[T ]([0-9]{2}):([0-9]{2})(?::([0-9]{2}))?)?)?)?(Z|[+-][0-9]{2}:[0-9]{2})?$"
+    ).unwrap();
+    let captures = iso.captures(value).ok_or_else(invalid)?;
+    let part = |index: usize| captures.get(index).map(|m| m.as_str().parse::<u32>().unwrap_or(0));
+    let year = part(1).unwrap_or(0) as i32;
+    let (month, day) = (part(2), part(3));
+    if month.is_some_and(|month| !(1..=12).contains(&month))
+        || day.is_some_and(|day| chrono::NaiveDate::from_ymd_opt(year, month.unwrap_or(1), day).is_none())
+        || part(4).is_some_and(|hour| hour > 23)
+        || part(5).is_some_and(|minute| minute > 59)
+        || part(6).is_some_and(|second| second > 59)
+    {
+        return Err(invalid());
+    }
+
+    let digits: String = value[..captures.get(7).map_or(value.len(), |m| m.start())]
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect();
+    let tz = match captures.get(7).map(|m| m.as_str()) {
+        None => String::new(),
+        Some("Z") => "Z".to_string(),
+        Some(offset) => format!("{}'{}'", &offset[..3], &offset[4..]),
+    };
+    Ok(format!("D:{}{}", digits, tz))
+}
+
+/// The XMP packet of the document catalog, if any
+fn document_xmp(doc: &Document) -> Option<String> {
+    let metadata = doc.catalog().ok()?.get(b"Metadata").ok()?;
+    let (_, metadata) = doc.dereference(metadata).ok()?;
+    let stream = metadata.as_stream().ok()?;
+    let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+    Some(String::from_utf8_lossy(&content).into_owned())
+}
+
+/// Store an XMP packet as the (uncompressed) metadata stream of the catalog
+fn set_document_xmp(doc: &mut Document, xmp: String) -> Result<(), MetaError> {
+    let existing = match doc.catalog()?.get(b"Metadata") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+    if let Some(Ok(Object::Stream(stream))) = existing.map(|id| doc.get_object_mut(id)) {
+        stream.dict.remove(b"Filter");
+        stream.dict.remove(b"DecodeParms");
+        stream.set_content(xmp.into_bytes());
+        return Ok(());
+    }
+
+    let stream = Stream::new(dictionary! { "Type" => "Metadata", "Subtype" => "XML" }, xmp.into_bytes());
+    let id = doc.add_object(stream);
+    doc.catalog_mut()?.set("Metadata", id);
+    Ok(())
+}
+
+/// A minimal XMP packet with one description to add properties to
+fn empty_xmp_packet() -> String {
+    let namespaces: String = XMP_NAMESPACES.iter()
+        .map(|(prefix, uri)| format!(" xmlns:{}=\"{}\"", prefix, uri))
+        .collect();
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"{}\">\n\
+         <rdf:Description rdf:about=\"\"{}>\n\
+         </rdf:Description>\n\
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>",
+        RDF_NAMESPACE, namespaces
+    )
+}
+
+/// Namespace URI and local name of a property such as `dc:title`
+fn xmp_name(property: &str) -> Option<(&'static str, &str)> {
+    let (prefix, local) = property.split_once(':')?;
+    XMP_NAMESPACES.iter().find(|(p, _)| *p == prefix).map(|(_, uri)| (*uri, local))
+}
+
+/// The namespace URI an element or attribute name resolved to
+fn bound_namespace(resolved: ResolveResult) -> Option<Vec<u8>> {
+    match resolved {
+        ResolveResult::Bound(Namespace(uri)) => Some(uri.to_vec()),
+        _ => None,
+    }
+}
+
+/// Whether an attribute of an element is the property `uri`/`name`
+fn is_property_attribute(reader: &NsReader<&[u8]>, attribute: &Attribute, uri: &str, name: &str) -> bool {
+    let (resolved, local) = reader.resolve_attribute(attribute.key);
+    bound_namespace(resolved).as_deref() == Some(uri.as_bytes()) && local.as_ref() == name.as_bytes()
+}
+
+/// Read an XMP property written as an element or as an attribute of a description, whatever
+/// prefix the packet binds its namespace to; list values (such as several authors) are
+/// joined with `; `
+fn xmp_value(xmp: &str, property: &str) -> Option<String> {
+    let (uri, name) = xmp_name(property)?;
+    let mut reader = NsReader::from_str(xmp);
+
+    // Nesting depth inside the property element once it is found, with its text and list items
+    let mut depth: Option<usize> = None;
+    let mut text = String::new();
+    let mut items: Vec<String> = Vec::new();
+    let mut item: Option<String> = None;
+    loop {
+        let (resolved, event) = reader.read_resolved_event().ok()?;
+        let namespace = bound_namespace(resolved);
+        match event {
+            Event::Start(element) => match depth.as_mut() {
+                Some(depth) => {
+                    *depth += 1;
+                    if namespace.as_deref() == Some(RDF_NAMESPACE.as_bytes()) && element.local_name().as_ref() == b"li" {
+                        item = Some(String::new());
+                    }
+                },
+                None => {
+                    if namespace.as_deref() == Some(uri.as_bytes()) && element.local_name().as_ref() == name.as_bytes() {
+                        depth = Some(0);
+                    } else if let Some(value) = element.attributes().flatten()
+                        .find(|attribute| is_property_attribute(&reader, attribute, uri, name))
+                    {
+                        return value.unescape_value().ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+                    }
+                },
+            },
+            Event::Empty(element) if depth.is_none() => {
+                if let Some(value) = element.attributes().flatten()
+                    .find(|attribute| is_property_attribute(&reader, attribute, uri, name))
+                {
+                    return value.unescape_value().ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+                }
+            },
+            Event::Text(content) if depth.is_some() => {
+                let content = content.unescape().ok()?;
+                item.as_mut().unwrap_or(&mut text).push_str(&content);
+            },
+            Event::CData(content) if depth.is_some() => {
+                item.as_mut().unwrap_or(&mut text).push_str(&String::from_utf8_lossy(&content));
+            },
+            Event::End(_) => match depth {
+                Some(0) => break,
+                Some(level) => {
+                    if let Some(item) = item.take() {
+                        items.push(item.trim().to_string());
+                    }
+                    depth = Some(level - 1);
+                },
+                None => {},
+            },
+            Event::Eof => return None,
+            _ => {},
+        }
+    }
+
+    let value = if items.is_empty() {
+        text.trim().to_string()
+    } else if property == "dc:creator" {
+        items.join("; ")
+    } else {
+        // Language alternatives: the first one is the default
+        items.swap_remove(0)
+    };
+    Some(value).filter(|v| !v.is_empty())
+}
+
+/// Replace (or with `None` remove) an XMP property in every form and under any prefix. The new
+/// value goes into the first description and declares its own namespaces, so it cannot clash
+/// with prefixes the packet binds differently.
+fn set_xmp_value(xmp: &str, property: &str, value: Option<&str>) -> Result<String, MetaError> {
+    let (uri, name) = xmp_name(property)
+        .ok_or_else(|| MetaError::Other(format!("Unknown XMP property '{}'", property)))?;
+    let invalid = |e: quick_xml::Error| MetaError::Other(format!("Invalid XMP metadata: {}", e));
+    let serialized = value.map(|value| xmp_property_xml(property, uri, value));
+
+    let mut reader = NsReader::from_str(xmp);
+    let mut writer = Writer::new(Vec::new());
+    let mut added = serialized.is_none();
+    // Nesting depth inside a property element being dropped
+    let mut skipping: Option<usize> = None;
+    // Whitespace before the next element, dropped together with a removed property
+    let mut space: Option<Event> = None;
+    loop {
+        let (resolved, event) = reader.read_resolved_event().map_err(invalid)?;
+        let namespace = bound_namespace(resolved);
+        if let Some(depth) = skipping {
+            skipping = match event {
+                Event::Start(_) => Some(depth + 1),
+                Event::End(_) if depth == 0 => None,
+                Event::End(_) => Some(depth - 1),
+                Event::Eof => break,
+                _ => Some(depth),
+            };
+            continue;
+        }
+
+        let is_property = |element: &BytesStart| {
+            namespace.as_deref() == Some(uri.as_bytes()) && element.local_name().as_ref() == name.as_bytes()
+        };
+        let is_description = |element: &BytesStart| {
+            namespace.as_deref() == Some(RDF_NAMESPACE.as_bytes()) && element.local_name().as_ref() == b"Description"
+        };
+        // The element without the property as an attribute
+        let without_property = |element: BytesStart<'_>| -> BytesStart<'static> {
+            let attributes: Vec<Attribute> = element.attributes().flatten().collect();
+            if !attributes.iter().any(|attribute| is_property_attribute(&reader, attribute, uri, name)) {
+                return element.into_owned();
+            }
+            let mut stripped = element.to_owned();
+            stripped.clear_attributes();
+            for attribute in attributes.into_iter().filter(|attribute| !is_property_attribute(&reader, attribute, uri, name)) {
+                stripped.push_attribute(attribute);
+            }
+            stripped.into_owned()
+        };
+
+        match event {
+            Event::Text(content) if content.iter().all(u8::is_ascii_whitespace) => {
+                if let Some(space) = space.replace(Event::Text(content)) {
+                    writer.write_event(space).map_err(invalid)?;
+                }
+                continue;
+            },
+            Event::Start(element) if is_property(&element) => {
+                space = None;
+                skipping = Some(0);
+                continue;
+            },
+            Event::Empty(element) if is_property(&element) => {
+                space = None;
+                continue;
+            },
+            _ => {},
+        }
+        if let Some(space) = space.take() {
+            writer.write_event(space).map_err(invalid)?;
+        }
+
+        match event {
+            Event::Start(element) => {
+                let description = is_description(&element);
+                writer.write_event(Event::Start(without_property(element))).map_err(invalid)?;
+                if description && !added {
+                    writer.get_mut().extend_from_slice(format!("\n{}", serialized.as_deref().unwrap_or_default()).as_bytes());
+                    added = true;
+                }
+            },
+            Event::Empty(element) => {
+                let description = is_description(&element);
+                let element = without_property(element);
+                if description && !added {
+                    let end = BytesEnd::new(String::from_utf8_lossy(element.name().as_ref()).into_owned());
+                    writer.write_event(Event::Start(element)).map_err(invalid)?;
+                    writer.get_mut().extend_from_slice(format!("\n{}\n", serialized.as_deref().unwrap_or_default()).as_bytes());
+                    writer.write_event(Event::End(end)).map_err(invalid)?;
+                    added = true;
+                } else {
+                    writer.write_event(Event::Empty(element)).map_err(invalid)?;
+                }
+            },
+            Event::Eof => break,
+            event => writer.write_event(event).map_err(invalid)?,
+        }
+    }
+
+    if !added {
+        // A packet without any description gets a fresh one
+        return set_xmp_value(&empty_xmp_packet(), property, value);
+    }
+    String::from_utf8(writer.into_inner()).map_err(|e| MetaError::Other(format!("Invalid XMP metadata: {}", e)))
+}
+
+/// A property element with its value, declaring the namespaces it uses
+fn xmp_property_xml(property: &str, uri: &str, value: &str) -> String {
+    let value = xml_escape(value);
+    let prefix = property.split(':').next().unwrap_or_default();
+    let open = format!("{} xmlns:{}=\"{}\"", property, prefix, uri);
+    let list_open = format!("{} xmlns:rdf=\"{}\"", open, RDF_NAMESPACE);
+    match property {
+        "dc:title" | "dc:description" => format!(
+            "<{0}><rdf:Alt><rdf:li xml:lang=\"x-default\">{1}</rdf:li></rdf:Alt></{2}>", list_open, value, property
+        ),
+        "dc:creator" => {
+            let items: String = value.split(';')
+                .map(str::trim)
+                .filter(|author| !author.is_empty())
+                .map(|author| format!("<rdf:li>{}</rdf:li>", author))
+                .collect();
+            format!("<{0}><rdf:Seq>{1}</rdf:Seq></{2}>", list_open, items, property)
+        },
+        _ => format!("<{0}>{1}</{2}>", open, value, property),
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Write records as CSV (the columns accepted by `--csv`) to a file or stdout
+fn write_csv(records: &[MetaRecord], output_file: Option<&str>) -> Result<(), MetaError> {
+    let mut csv_writer = utils::csv_writer(output_file)?;
+
+    let mut header = vec!["path"];
+    header.extend(STANDARD_FIELDS.iter().map(|field| field.name));
+    csv_writer.write_record(&header)?;
+    for record in records {
+        if let Some(error) = &record.error {
+            eprintln!("{}", error);
+            continue;
+        }
+        csv_writer.write_record(record.to_row())?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Write records as a JSON array to a file or stdout
+fn write_json(records: &[MetaRecord], output_file: Option<&str>) -> Result<(), MetaError> {
+    let json = serde_json::to_string_pretty(records)?;
+    match output_file {
+        Some(path) => fs::write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A packet as older Adobe software writes it: `xap` instead of `xmp`, attributes and lists
+    const XAP_PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<r:RDF xmlns:r="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<r:Description r:about="" xmlns:xap="http://ns.adobe.com/xap/1.0/" xmlns:pdf="http://ns.adobe.com/pdf/1.3/" pdf:Producer="Distiller &amp; Co">
+<xap:CreatorTool>Word</xap:CreatorTool>
+</r:Description>
+<r:Description r:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+<dc:title><r:Alt><r:li xml:lang="x-default">Annual Report</r:li><r:li xml:lang="de">Jahresbericht</r:li></r:Alt></dc:title>
+<dc:creator><r:Seq><r:li>Jane Doe</r:li><r:li>John Roe</r:li></r:Seq></dc:creator>
+</r:Description>
+</r:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn converts_iso_dates() {
+        assert_eq!(pdf_date("2024").unwrap(), "D:2024");
+        assert_eq!(pdf_date("2024-01-15").unwrap(), "D:20240115");
+        assert_eq!(pdf_date("2024-01-15 10:30").unwrap(), "D:202401151030");
+        assert_eq!(pdf_date("2024-01-15T10:30:05Z").unwrap(), "D:20240115103005Z");
+        assert_eq!(pdf_date("2024-01-15 10:30:05-05:30").unwrap(), "D:20240115103005-05'30'");
+        assert_eq!(pdf_date("D:20240115103005+01'00'").unwrap(), "D:20240115103005+01'00'");
+    }
+
+    #[test]
+    fn rejects_dates_that_are_not_iso() {
+        for value in ["15/01/2024", "15.01.2024", "2024-1-15", "2024-13-01", "2024-02-30", "2024-01-15 24:00", "D:2024-01", "yesterday",
+                      "2024-01-01+०१:00", "२०२४-01-01", "D:२०२४0101"] {
+            assert!(pdf_date(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn reads_xmp_properties_under_any_prefix() {
+        assert_eq!(xmp_value(XAP_PACKET, "xmp:CreatorTool").as_deref(), Some("Word"));
+        assert_eq!(xmp_value(XAP_PACKET, "pdf:Producer").as_deref(), Some("Distiller & Co"));
+        assert_eq!(xmp_value(XAP_PACKET, "dc:title").as_deref(), Some("Annual Report"));
+        assert_eq!(xmp_value(XAP_PACKET, "dc:creator").as_deref(), Some("Jane Doe; John Roe"));
+        assert_eq!(xmp_value(XAP_PACKET, "xmp:CreateDate"), None);
+    }
+
+    #[test]
+    fn replaces_xmp_properties_without_duplicates() {
+        let mut xmp = XAP_PACKET.to_string();
+        for (property, value) in [("xmp:CreatorTool", "pdfscan"), ("pdf:Producer", "lopdf"), ("dc:title", "Q1 <draft>")] {
+            xmp = set_xmp_value(&xmp, property, Some(value)).unwrap();
+            assert_eq!(xmp_value(&xmp, property).as_deref(), Some(value));
+        }
+        assert_eq!(xmp.matches("CreatorTool").count(), 2, "{}", xmp);
+        assert_eq!(xmp.matches("Producer").count(), 2, "{}", xmp);
+        assert!(!xmp.contains("Jahresbericht"));
+        assert_eq!(xmp_value(&xmp, "dc:creator").as_deref(), Some("Jane Doe; John Roe"));
+
+        let xmp = set_xmp_value(&xmp, "pdf:Producer", None).unwrap();
+        assert_eq!(xmp_value(&xmp, "pdf:Producer"), None);
+        assert!(!xmp.contains("Producer"));
+    }
+
+    #[test]
+    fn adds_properties_to_packets_without_a_description() {
+        let bare = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"/></x:xmpmeta>"#;
+        let xmp = set_xmp_value(bare, "dc:creator", Some("Jane Doe; John Roe")).unwrap();
+        assert_eq!(xmp_value(&xmp, "dc:creator").as_deref(), Some("Jane Doe; John Roe"));
+
+        let self_closing = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description rdf:about=""/></rdf:RDF>"#;
+        let xmp = set_xmp_value(self_closing, "xmp:CreateDate", Some("2024-01-15T10:30:00")).unwrap();
+        assert_eq!(xmp_value(&xmp, "xmp:CreateDate").as_deref(), Some("2024-01-15T10:30:00"));
+        assert!(xmp.contains("</rdf:Description>"), "{}", xmp);
+
+        assert!(set_xmp_value("<a><b></a>", "dc:title", Some("x")).is_err());
+    }
+}
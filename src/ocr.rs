@@ -0,0 +1,294 @@
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use clap::Args;
+use pdfium_render::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::coverage::{self, PageKind};
+use crate::utils;
+
+/// Environment variable that configures the OCR command when `--ocr-command` is not given
+pub const OCR_COMMAND_ENV: &str = "PDFSCAN_OCR_COMMAND";
+
+/// Command used when tesseract is found on the PATH
+const DEFAULT_OCR_COMMAND: &str = "tesseract {input} stdout";
+
+/// Custom error type for OCR operations
+#[derive(Debug)]
+pub enum OcrError {
+    Io(io::Error),
+    Pdf(String),
+    Engine(String),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::Io(e) => write!(f, "I/O error: {}", e),
+            OcrError::Pdf(e) => write!(f, "PDF error: {}", e),
+            OcrError::Engine(e) => write!(f, "OCR engine error: {}", e),
+        }
+    }
+}
+
+impl Error for OcrError {}
+
+impl From<io::Error> for OcrError {
+    fn from(err: io::Error) -> Self {
+        OcrError::Io(err)
+    }
+}
+
+/// OCR options shared by the extract, search and analyze commands
+#[derive(Args, Debug, Clone)]
+pub struct OcrArgs {
+    /// OCR command for pages without a text layer; `{input}` is replaced by the page image path
+    /// [default: $PDFSCAN_OCR_COMMAND, or tesseract if installed]
+    #[arg(long, value_name = "COMMAND")]
+    pub ocr_command: Option<String>,
+
+    /// Disable the OCR fallback for image-only pages
+    #[arg(long)]
+    pub no_ocr: bool,
+
+    /// Resolution used to rasterize pages for OCR
+    #[arg(long, default_value_t = 300)]
+    pub ocr_dpi: u32,
+}
+
+impl Default for OcrArgs {
+    fn default() -> Self {
+        Self {
+            ocr_command: None,
+            no_ocr: false,
+            ocr_dpi: 300,
+        }
+    }
+}
+
+/// A local OCR engine that turns a page image into text
+pub trait OcrEngine {
+    /// Stable identifier, used to keep cache entries of different engines apart
+    fn id(&self) -> String;
+
+    /// Recognize the text in the image at `image_path`
+    fn recognize(&self, image_path: &Path) -> Result<String, OcrError>;
+}
+
+/// OCR engine backed by an external command such as tesseract
+pub struct CommandEngine {
+    template: String,
+}
+
+impl CommandEngine {
+    /// Create an engine from a command template; the image path replaces `{input}`,
+    /// or is appended when the template has no placeholder
+    pub fn new(template: &str) -> Self {
+        Self { template: template.trim().to_string() }
+    }
+}
+
+impl OcrEngine for CommandEngine {
+    fn id(&self) -> String {
+        self.template.clone()
+    }
+
+    fn recognize(&self, image_path: &Path) -> Result<String, OcrError> {
+        let input = image_path.to_string_lossy();
+        let mut parts: Vec<String> = self.template
+            .split_whitespace()
+            .map(|part| part.replace("{input}", &input))
+            .collect();
+        if !self.template.contains("{input}") {
+            parts.push(input.into_owned());
+        }
+
+        let (program, args) = parts.split_first()
+            .ok_or_else(|| OcrError::Engine("Empty OCR command".to_string()))?;
+
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| OcrError::Engine(format!("Failed to run '{}': {}", program, e)))?;
+
+        if !output.status.success() {
+            return Err(OcrError::Engine(format!(
+                "'{}' exited with {}: {}",
+                program, output.status, String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// OCR stage: rasterizes image-only pages with Pdfium and runs them through an engine,
+/// caching the recognized text per document so later runs are cheap
+pub struct OcrStage {
+    pdfium: Pdfium,
+    engine: Box<dyn OcrEngine>,
+    dpi: f32,
+    cache_dir: Option<PathBuf>,
+}
+
+impl OcrStage {
+    /// Set up the OCR stage from command-line options.
+    /// Returns `None` when OCR is disabled or no engine/Pdfium is available.
+    pub fn from_args(args: &OcrArgs) -> Option<Self> {
+        if args.no_ocr {
+            return None;
+        }
+
+        let command = args.ocr_command.clone()
+            .or_else(|| std::env::var(OCR_COMMAND_ENV).ok().filter(|c| !c.trim().is_empty()))
+            .or_else(|| find_in_path("tesseract").map(|_| DEFAULT_OCR_COMMAND.to_string()))?;
+
+        let pdfium = match utils::bind_pdfium() {
+            Ok(pdfium) => pdfium,
+            Err(e) => {
+                eprintln!("Warning: {}; OCR fallback disabled", e);
+                return None;
+            }
+        };
+
+        let cache_dir = dirs::cache_dir().map(|dir| dir.join("pdfscan").join("ocr"));
+
+        Some(Self {
+            pdfium,
+            engine: Box::new(CommandEngine::new(&command)),
+            dpi: args.ocr_dpi.max(72) as f32,
+            cache_dir,
+        })
+    }
+
+    /// Recognize the text of every image-only page of a PDF.
+    /// Pages are separated by `[OCR page N]` markers; the result is empty when every page has text.
+    pub fn missing_text(&self, path: &Path) -> Result<String, OcrError> {
+        let bytes = fs::read(path)?;
+        let cache_file = self.cache_file(&bytes);
+
+        if let Some(cache_file) = &cache_file {
+            if let Ok(text) = fs::read_to_string(cache_file) {
+                return Ok(text);
+            }
+        }
+
+        let text = self.recognize_document(path, bytes)?;
+
+        if let Some(cache_file) = &cache_file {
+            if let Some(parent) = cache_file.parent() {
+                fs::create_dir_all(parent).ok();
+            }
+            if let Err(e) = fs::write(cache_file, &text) {
+                eprintln!("Warning: Could not write OCR cache {}: {}", cache_file.display(), e);
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Run OCR over the image-only pages of a document
+    fn recognize_document(&self, path: &Path, bytes: Vec<u8>) -> Result<String, OcrError> {
+        let document = self.pdfium.load_pdf_from_byte_vec(bytes, None)
+            .map_err(|e| OcrError::Pdf(format!("Error loading {}: {}", path.display(), e)))?;
+
+        let mut text = String::new();
+        for (index, page) in document.pages().iter().enumerate() {
+            if coverage::classify_page(&page) != PageKind::ImageOnly {
+                continue;
+            }
+
+            let image = utils::render_page_rgba(&page, self.dpi)
+                .map_err(OcrError::Pdf)?;
+
+            let (image_path, file) = create_page_image()?;
+            if let Err(e) = image.write_to(&mut BufWriter::new(file), image::ImageOutputFormat::Png) {
+                fs::remove_file(&image_path).ok();
+                return Err(OcrError::Engine(format!("Error writing page image: {}", e)));
+            }
+
+            let page_text = self.engine.recognize(&image_path);
+            fs::remove_file(&image_path).ok();
+
+            text.push_str(&format!("[OCR page {}]\n{}\n", index + 1, page_text?.trim_end()));
+        }
+
+        Ok(text)
+    }
+
+    /// Cache location for a document, keyed by its content and the engine configuration
+    fn cache_file(&self, bytes: &[u8]) -> Option<PathBuf> {
+        let document_hash = utils::to_hex(&Sha256::digest(bytes));
+        let engine_hash = utils::to_hex(&Sha256::digest(format!("{}@{}", self.engine.id(), self.dpi).as_bytes()));
+        self.cache_dir.as_ref()
+            .map(|dir| dir.join(&engine_hash[..12]).join(format!("{}.txt", document_hash)))
+    }
+}
+
+/// Create a new file with a random name in the temporary directory for a page image. The
+/// file must not exist yet, so a file or symlink planted under that name is never written
+/// through, and concurrent OCR jobs never share an image.
+fn create_page_image() -> io::Result<(PathBuf, fs::File)> {
+    let state = RandomState::new();
+    for attempt in 0..16u32 {
+        let mut hasher = state.build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u32(attempt);
+        let path = std::env::temp_dir().join(format!("pdfscan-ocr-{:016x}.png", hasher.finish()));
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "Could not create a temporary page image"))
+}
+
+/// Locate an executable on the PATH
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_engine_substitutes_or_appends_input() {
+        let image = Path::new("/tmp/page 1.png");
+        let appended = CommandEngine::new("echo").recognize(image).unwrap();
+        assert_eq!(appended.trim(), "/tmp/page 1.png");
+        let placed = CommandEngine::new("echo {input} done").recognize(image).unwrap();
+        assert_eq!(placed.trim(), "/tmp/page 1.png done");
+    }
+
+    #[test]
+    fn page_images_get_new_files() {
+        let (first, _) = create_page_image().unwrap();
+        let (second, _) = create_page_image().unwrap();
+        assert_ne!(first, second);
+        assert!(first.is_file() && second.is_file());
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+    }
+
+    #[test]
+    fn command_engine_reports_failures() {
+        assert!(matches!(CommandEngine::new("  ").recognize(Path::new("x.png")), Err(OcrError::Engine(_))));
+        assert!(matches!(CommandEngine::new("false").recognize(Path::new("x.png")), Err(OcrError::Engine(_))));
+    }
+}
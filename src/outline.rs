@@ -0,0 +1,456 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use clap::ValueEnum;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::utils;
+
+/// Maximum nesting depth of outline items and name trees
+const MAX_OUTLINE_DEPTH: usize = 64;
+
+/// Custom error type for outline operations
+#[derive(Debug)]
+pub enum OutlineError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for OutlineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutlineError::Io(e) => write!(f, "I/O error: {}", e),
+            OutlineError::Json(e) => write!(f, "JSON error: {}", e),
+            OutlineError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for OutlineError {}
+
+impl From<io::Error> for OutlineError {
+    fn from(err: io::Error) -> Self {
+        OutlineError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for OutlineError {
+    fn from(err: serde_json::Error) -> Self {
+        OutlineError::Json(err)
+    }
+}
+
+/// Output formats supported by the outline command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutlineFormat {
+    Text,
+    Json,
+}
+
+/// A bookmark of the document outline
+#[derive(Debug, Clone, Serialize)]
+pub struct OutlineEntry {
+    pub title: String,
+    /// 1-based page number the bookmark points to, if its destination resolves to a page
+    pub page: Option<usize>,
+    /// Nesting depth, 0 for top-level bookmarks
+    pub level: usize,
+    pub children: Vec<OutlineEntry>,
+}
+
+/// A run of pages that belongs to one outline heading. Sections are page-granular:
+/// a page is assigned to the last bookmark that starts on or before it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Section {
+    /// Titles from the top-level bookmark down to this section; empty for front matter
+    pub heading_path: Vec<String>,
+    pub start_page: usize,
+    pub end_page: usize,
+}
+
+impl Section {
+    /// Heading path joined as `Chapter > Section`
+    pub fn heading(&self) -> String {
+        if self.heading_path.is_empty() {
+            "(front matter)".to_string()
+        } else {
+            self.heading_path.join(" > ")
+        }
+    }
+
+    /// Page range as `page 3` or `pages 3-7`
+    pub fn page_range(&self) -> String {
+        if self.start_page == self.end_page {
+            format!("page {}", self.start_page)
+        } else {
+            format!("pages {}-{}", self.start_page, self.end_page)
+        }
+    }
+}
+
+/// The outline of one document
+#[derive(Debug, Serialize)]
+struct OutlineRecord {
+    path: String,
+    outline: Vec<OutlineEntry>,
+}
+
+/// Main function to print the table of contents of PDF files
+pub fn run(input_paths: &[String], format: OutlineFormat, output_file: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(OutlineError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    let records: Vec<OutlineRecord> = pdf_paths
+        .par_iter()
+        .filter_map(|path| match Document::load(path) {
+            Ok(doc) => Some(OutlineRecord {
+                path: path.to_string_lossy().into_owned(),
+                outline: read_outline(&doc),
+            }),
+            Err(e) => {
+                eprintln!("Error loading {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+
+    let output = match format {
+        OutlineFormat::Text => records.iter().map(to_text).collect::<Vec<_>>().join("\n"),
+        OutlineFormat::Json => serde_json::to_string_pretty(&records)?,
+    };
+
+    match output_file {
+        Some(path) => {
+            fs::write(path, output)?;
+            println!("Successfully wrote the outlines of {} PDFs to '{}'", records.len(), path);
+        },
+        None => println!("{}", output),
+    }
+    Ok(())
+}
+
+/// Read the bookmark tree of a document, resolving destinations to page numbers
+pub fn read_outline(doc: &Document) -> Vec<OutlineEntry> {
+    let root = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|outlines| dereference_dict(doc, outlines));
+
+    let root = match root {
+        Some(root) => root,
+        None => return Vec::new(),
+    };
+
+    let mut visited = HashSet::new();
+    read_items(doc, root, 0, &page_numbers(doc), &mut visited)
+}
+
+/// Map page object ids to 1-based page numbers, for resolving destinations
+pub fn page_numbers(doc: &Document) -> HashMap<ObjectId, usize> {
+    doc.get_pages()
+        .into_iter()
+        .map(|(number, id)| (id, number as usize))
+        .collect()
+}
+
+/// Flatten an outline into a list in document order
+pub fn flatten(entries: &[OutlineEntry]) -> Vec<&OutlineEntry> {
+    let mut flat = Vec::new();
+    for entry in entries {
+        flat.push(entry);
+        flat.extend(flatten(&entry.children));
+    }
+    flat
+}
+
+/// Divide a document of `page_count` pages into sections at the bookmarks of the first
+/// `max_level` outline levels. Returns no sections when no bookmark resolves to a page.
+pub fn sections(entries: &[OutlineEntry], max_level: usize, page_count: usize) -> Vec<Section> {
+    let mut starts = Vec::new();
+    collect_starts(entries, max_level, &mut Vec::new(), &mut starts);
+    starts.retain(|(_, page)| *page >= 1 && *page <= page_count);
+    // Bookmarks are usually in page order already; the sort is stable for those that are
+    starts.sort_by_key(|(_, page)| *page);
+
+    // Of several bookmarks starting on the same page, the last one gets the page
+    let mut starts: Vec<(Vec<String>, usize)> = starts.into_iter().rev().collect();
+    starts.dedup_by_key(|(_, page)| *page);
+    starts.reverse();
+
+    let mut sections = Vec::new();
+    if let Some((_, first_page)) = starts.first() {
+        if *first_page > 1 {
+            sections.push(Section { heading_path: Vec::new(), start_page: 1, end_page: first_page - 1 });
+        }
+    }
+    for (index, (heading_path, start_page)) in starts.iter().enumerate() {
+        let end_page = starts.get(index + 1).map(|(_, next)| next - 1).unwrap_or(page_count);
+        sections.push(Section { heading_path: heading_path.clone(), start_page: *start_page, end_page });
+    }
+    sections
+}
+
+/// Find the section a 1-based page belongs to
+pub fn section_for_page(sections: &[Section], page: usize) -> Option<&Section> {
+    sections.iter().find(|section| section.start_page <= page && page <= section.end_page)
+}
+
+fn collect_starts(entries: &[OutlineEntry], max_level: usize, path: &mut Vec<String>, starts: &mut Vec<(Vec<String>, usize)>) {
+    for entry in entries.iter().filter(|entry| entry.level < max_level) {
+        path.push(entry.title.clone());
+        if let Some(page) = entry.page {
+            starts.push((path.clone(), page));
+        }
+        collect_starts(&entry.children, max_level, path, starts);
+        path.pop();
+    }
+}
+
+/// Read the children of an outline node by following /First and /Next
+fn read_items(
+    doc: &Document,
+    parent: &Dictionary,
+    level: usize,
+    page_numbers: &HashMap<ObjectId, usize>,
+    visited: &mut HashSet<ObjectId>,
+) -> Vec<OutlineEntry> {
+    let mut entries = Vec::new();
+    if level >= MAX_OUTLINE_DEPTH {
+        return entries;
+    }
+
+    let mut next = parent.get(b"First").and_then(Object::as_reference).ok();
+    while let Some(id) = next {
+        // Guard against cyclic sibling chains
+        if !visited.insert(id) {
+            break;
+        }
+        let item = match doc.get_dictionary(id) {
+            Ok(item) => item,
+            Err(_) => break,
+        };
+
+        entries.push(OutlineEntry {
+            title: utils::dict_string(doc, item, b"Title")
+                .map(|title| title.split_whitespace().collect::<Vec<_>>().join(" "))
+                .unwrap_or_default(),
+            page: item_destination(doc, item).and_then(|dest| destination_page(doc, dest, page_numbers)),
+            level,
+            children: read_items(doc, item, level + 1, page_numbers, visited),
+        });
+
+        next = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    entries
+}
+
+/// The destination of an outline item: /Dest, or /D of a GoTo action
+fn item_destination<'a>(doc: &'a Document, item: &'a Dictionary) -> Option<&'a Object> {
+    if let Ok(dest) = item.get(b"Dest") {
+        return Some(dest);
+    }
+    let action = item.get(b"A").ok().and_then(|action| dereference_dict(doc, action))?;
+    if action.get(b"S").and_then(Object::as_name_str).ok() != Some("GoTo") {
+        return None;
+    }
+    action.get(b"D").ok()
+}
+
+/// Resolve an explicit or named destination to a 1-based page number
+pub fn destination_page(doc: &Document, dest: &Object, page_numbers: &HashMap<ObjectId, usize>) -> Option<usize> {
+    let (_, dest) = doc.dereference(dest).ok()?;
+    match dest {
+        Object::Array(array) => match array.first()? {
+            Object::Reference(id) => page_numbers.get(id).copied(),
+            // Some producers write a 0-based page index instead of a page reference
+            Object::Integer(index) => usize::try_from(*index).ok()
+                .map(|index| index + 1)
+                .filter(|page| *page <= page_numbers.len()),
+            _ => None,
+        },
+        // Destination dictionaries as stored in /Dests: << /D [...] >>
+        Object::Dictionary(dict) => destination_page(doc, dict.get(b"D").ok()?, page_numbers),
+        Object::Name(name) | Object::String(name, _) => {
+            let (_, target) = doc.dereference(named_destination(doc, name)?).ok()?;
+            // A name that maps to another name would recurse forever on cyclic files
+            if matches!(target, Object::Name(_) | Object::String(..)) {
+                return None;
+            }
+            destination_page(doc, target, page_numbers)
+        },
+        _ => None,
+    }
+}
+
+/// Look up a named destination in the catalog's /Dests dictionary or the /Names /Dests tree
+fn named_destination<'a>(doc: &'a Document, name: &[u8]) -> Option<&'a Object> {
+    let catalog = doc.catalog().ok()?;
+
+    if let Some(dests) = catalog.get(b"Dests").ok().and_then(|dests| dereference_dict(doc, dests)) {
+        if let Ok(dest) = dests.get(name) {
+            return Some(dest);
+        }
+    }
+
+    let tree = catalog.get(b"Names").ok()
+        .and_then(|names| dereference_dict(doc, names))
+        .and_then(|names| names.get(b"Dests").ok())
+        .and_then(|tree| dereference_dict(doc, tree))?;
+    lookup_name_tree(doc, tree, name, 0)
+}
+
+fn lookup_name_tree<'a>(doc: &'a Document, node: &'a Dictionary, key: &[u8], depth: usize) -> Option<&'a Object> {
+    if depth >= MAX_OUTLINE_DEPTH {
+        return None;
+    }
+
+    if let Ok(Object::Array(names)) = node.get(b"Names").and_then(|n| doc.dereference(n)).map(|(_, n)| n) {
+        for pair in names.chunks(2) {
+            if let [Object::String(name, _), value] = pair {
+                if name.as_slice() == key {
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    if let Ok(Object::Array(kids)) = node.get(b"Kids").and_then(|k| doc.dereference(k)).map(|(_, k)| k) {
+        for kid in kids {
+            if let Some(found) = dereference_dict(doc, kid).and_then(|kid| lookup_name_tree(doc, kid, key, depth + 1)) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+fn dereference_dict<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    doc.dereference(object).ok().and_then(|(_, o)| o.as_dict().ok())
+}
+
+/// Render an outline as an indented table of contents
+fn to_text(record: &OutlineRecord) -> String {
+    let mut text = format!("{}\n", record.path);
+    if record.outline.is_empty() {
+        text.push_str("  (no outline)\n");
+    }
+    for entry in flatten(&record.outline) {
+        let page = entry.page.map(|page| page.to_string()).unwrap_or_else(|| "-".to_string());
+        text.push_str(&format!("{}{} ... {}\n", "  ".repeat(entry.level + 1), entry.title, page));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// A three-page document with the bookmarks
+    /// `Intro` (page 1), `Setup` (page 2) > `Requirements` (named destination on page 3)
+    fn outlined_document() -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page_ids: Vec<ObjectId> = (0..3)
+            .map(|_| doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id }))
+            .collect();
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => page_ids.iter().map(|id| Object::Reference(*id)).collect::<Vec<_>>(),
+            "Count" => 3,
+        }));
+
+        let outlines_id = doc.new_object_id();
+        let intro_id = doc.new_object_id();
+        let setup_id = doc.new_object_id();
+        let requirements_id = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Requirements"),
+            "Parent" => setup_id,
+            "A" => dictionary! { "S" => "GoTo", "D" => Object::string_literal("reqs") },
+        });
+        doc.objects.insert(intro_id, Object::Dictionary(dictionary! {
+            "Title" => Object::string_literal("  Intro\n"),
+            "Parent" => outlines_id,
+            "Next" => setup_id,
+            "Dest" => vec![page_ids[0].into(), "Fit".into()],
+        }));
+        doc.objects.insert(setup_id, Object::Dictionary(dictionary! {
+            "Title" => Object::string_literal("Setup"),
+            "Parent" => outlines_id,
+            "Prev" => intro_id,
+            "First" => requirements_id,
+            "Last" => requirements_id,
+            // 0-based page index, as some producers write it
+            "Dest" => vec![1.into(), "Fit".into()],
+        }));
+        doc.objects.insert(outlines_id, Object::Dictionary(dictionary! {
+            "Type" => "Outlines", "First" => intro_id, "Last" => setup_id,
+        }));
+
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Outlines" => outlines_id,
+            "Dests" => dictionary! { "reqs" => dictionary! { "D" => vec![page_ids[2].into(), "Fit".into()] } },
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    #[test]
+    fn reads_bookmark_tree() {
+        let outline = read_outline(&outlined_document());
+        let flat: Vec<(&str, Option<usize>, usize)> = flatten(&outline).iter()
+            .map(|entry| (entry.title.as_str(), entry.page, entry.level))
+            .collect();
+        assert_eq!(flat, vec![("Intro", Some(1), 0), ("Setup", Some(2), 0), ("Requirements", Some(3), 1)]);
+    }
+
+    #[test]
+    fn stops_at_cyclic_siblings() {
+        let mut doc = outlined_document();
+        let outlines_id = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        let first = doc.get_dictionary(outlines_id).unwrap().get(b"First").unwrap().as_reference().unwrap();
+        doc.get_dictionary_mut(first).unwrap().set("Next", first);
+        assert_eq!(flatten(&read_outline(&doc)).len(), 1);
+    }
+
+    #[test]
+    fn divides_pages_into_sections() {
+        let outline = read_outline(&outlined_document());
+        let top = sections(&outline, 1, 4);
+        let summary: Vec<(String, String)> = top.iter().map(|s| (s.heading(), s.page_range())).collect();
+        assert_eq!(summary, vec![
+            ("Intro".to_string(), "page 1".to_string()),
+            ("Setup".to_string(), "pages 2-4".to_string()),
+        ]);
+
+        let nested = sections(&outline, 2, 4);
+        assert_eq!(section_for_page(&nested, 4).unwrap().heading(), "Setup > Requirements");
+        assert_eq!(section_for_page(&nested, 5).map(Section::heading), None);
+    }
+
+    #[test]
+    fn adds_front_matter_and_keeps_last_bookmark_per_page() {
+        let entry = |title: &str, page| OutlineEntry { title: title.to_string(), page: Some(page), level: 0, children: Vec::new() };
+        let outline = vec![entry("Cover note", 3), entry("Chapter 1", 3), entry("Chapter 2", 6), entry("Beyond", 40)];
+        let ranges: Vec<(String, usize, usize)> = sections(&outline, 1, 8).into_iter()
+            .map(|s| (s.heading(), s.start_page, s.end_page))
+            .collect();
+        assert_eq!(ranges, vec![
+            ("(front matter)".to_string(), 1, 2),
+            ("Chapter 1".to_string(), 3, 5),
+            ("Chapter 2".to_string(), 6, 8),
+        ]);
+        assert!(sections(&[], 1, 8).is_empty());
+    }
+}
@@ -0,0 +1,704 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId};
+
+use crate::outline;
+use crate::utils;
+
+/// Page attributes a page inherits from its page tree nodes
+const INHERITED_ATTRIBUTES: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Catalog entries carried over from the first source document
+const CATALOG_ENTRIES: [&[u8]; 5] = [b"Metadata", b"Lang", b"ViewerPreferences", b"PageLayout", b"PageMode"];
+
+/// Nesting limit when filtering the form field tree
+const MAX_FIELD_DEPTH: usize = 32;
+
+/// Custom error type for page manipulation
+#[derive(Debug)]
+pub enum PageError {
+    Io(io::Error),
+    Pdf(lopdf::Error),
+    Other(String),
+}
+
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PageError::Io(e) => write!(f, "I/O error: {}", e),
+            PageError::Pdf(e) => write!(f, "PDF error: {}", e),
+            PageError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for PageError {}
+
+impl From<io::Error> for PageError {
+    fn from(err: io::Error) -> Self {
+        PageError::Io(err)
+    }
+}
+
+impl From<lopdf::Error> for PageError {
+    fn from(err: lopdf::Error) -> Self {
+        PageError::Pdf(err)
+    }
+}
+
+/// A page or page range: `5`, `3-7`, `7-3` (descending), `10-` (to the last page) or `-4`
+#[derive(Debug, Clone, Copy)]
+pub struct PageRange {
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+impl FromStr for PageRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |part: &str| -> Result<Option<usize>, String> {
+            let part = part.trim();
+            if part.is_empty() {
+                return Ok(None);
+            }
+            match part.parse::<usize>() {
+                Ok(page) if page > 0 => Ok(Some(page)),
+                _ => Err(format!("invalid page number '{}'", part)),
+            }
+        };
+        let range = match s.split_once('-') {
+            Some((start, end)) => PageRange { start: number(start)?, end: number(end)? },
+            None => {
+                let page = number(s)?.ok_or_else(|| "empty page range".to_string())?;
+                PageRange { start: Some(page), end: Some(page) }
+            },
+        };
+        if range.start.is_none() && range.end.is_none() {
+            return Err(format!("invalid page range '{}'", s));
+        }
+        Ok(range)
+    }
+}
+
+impl PageRange {
+    /// The 1-based pages of the range in a document of `page_count` pages
+    pub fn resolve(&self, page_count: usize) -> Result<Vec<usize>, PageError> {
+        let start = self.start.unwrap_or(1);
+        let end = self.end.unwrap_or(page_count);
+        if let Some(page) = [start, end].into_iter().find(|page| *page > page_count) {
+            return Err(PageError::Other(format!(
+                "Page {} is out of range (the document has {} pages)", page, page_count
+            )));
+        }
+        Ok(if start <= end { (start..=end).collect() } else { (end..=start).rev().collect() })
+    }
+
+    /// Whether the range includes a page, clipping an open or too large end to the document.
+    /// A range that starts after the last page includes none of its pages.
+    pub fn contains(&self, page: usize, page_count: usize) -> bool {
+        let start = self.start.unwrap_or(1);
+        let end = self.end.unwrap_or(page_count.max(start));
+        (start.min(end)..=start.max(end).min(page_count)).contains(&page)
+    }
+
+    fn label(&self, page_count: usize) -> String {
+        let start = self.start.unwrap_or(1);
+        let end = self.end.unwrap_or(page_count);
+        if start == end { format!("page_{}", start) } else { format!("pages_{}-{}", start, end) }
+    }
+}
+
+/// How `split` divides a document
+#[derive(Debug, Clone)]
+pub enum SplitMode {
+    /// One part per page range
+    Ranges(Vec<PageRange>),
+    /// Parts of N pages
+    Every(usize),
+    /// One part per outline section down to this level
+    Outline(usize),
+}
+
+/// A bookmark of an assembled document, pointing to a 1-based page of its source
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub title: String,
+    pub page: usize,
+    pub children: Vec<Bookmark>,
+}
+
+/// Pages taken from one document, in output order, with the bookmarks to create for them
+pub struct PageSource {
+    pub doc: Document,
+    pub pages: Vec<usize>,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+/// Main function of the merge command
+pub fn run_merge(input_paths: &[String], output: &str) -> Result<(), Box<dyn Error>> {
+    // Keep the order of the arguments; only directories are expanded (sorted)
+    let pdf_paths: Vec<PathBuf> = input_paths.iter()
+        .flat_map(|path| utils::collect_pdf_paths(std::slice::from_ref(path)))
+        .collect();
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(PageError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+    refuse_overwrite(&pdf_paths, Path::new(output))?;
+
+    let mut sources = Vec::new();
+    for path in &pdf_paths {
+        let doc = load(path)?;
+        let page_count = doc.get_pages().len();
+        if page_count == 0 {
+            eprintln!("Warning: Skipping {}: it has no pages", path.display());
+            continue;
+        }
+        // Each document gets a bookmark with its own outline below it
+        let title = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let bookmarks = vec![Bookmark { title, page: 1, children: source_outline(&doc) }];
+        sources.push(PageSource { doc, pages: (1..=page_count).collect(), bookmarks });
+    }
+
+    let page_count: usize = sources.iter().map(|source| source.pages.len()).sum();
+    let document_count = sources.len();
+    let mut merged = assemble(sources)?;
+    merged.save(output)?;
+
+    println!("Merged {} PDFs ({} pages) into '{}'", document_count, page_count, output);
+    Ok(())
+}
+
+/// Main function of the split command
+pub fn run_split(input: &str, output_dir: &str, mode: &SplitMode) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(input);
+    let doc = load(path)?;
+    let page_count = doc.get_pages().len();
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_else(|| "document".to_string());
+
+    let parts: Vec<(String, Vec<usize>)> = match mode {
+        SplitMode::Ranges(ranges) => ranges.iter()
+            .map(|range| Ok((range.label(page_count), range.resolve(page_count)?)))
+            .collect::<Result<_, PageError>>()?,
+        SplitMode::Every(size) => {
+            if *size == 0 {
+                return Err(Box::new(PageError::Other("--every needs at least 1 page".to_string())));
+            }
+            (1..=page_count).step_by(*size)
+                .map(|start| {
+                    let end = (start + size - 1).min(page_count);
+                    let range = PageRange { start: Some(start), end: Some(end) };
+                    (range.label(page_count), (start..=end).collect())
+                })
+                .collect()
+        },
+        SplitMode::Outline(level) => {
+            let sections = outline::sections(&outline::read_outline(&doc), (*level).max(1), page_count);
+            if sections.is_empty() {
+                return Err(Box::new(PageError::Other(format!(
+                    "{} has no bookmarks to split at", path.display()
+                ))));
+            }
+            sections.iter()
+                .enumerate()
+                .map(|(index, section)| {
+                    let title = section.heading_path.last().map(|title| utils::slug(title)).unwrap_or_else(|| "front-matter".to_string());
+                    (format!("{:02}_{}", index + 1, title), (section.start_page..=section.end_page).collect())
+                })
+                .collect()
+        },
+    };
+
+    fs::create_dir_all(output_dir)?;
+    let bookmarks = source_outline(&doc);
+    for (label, pages) in &parts {
+        let target = Path::new(output_dir).join(format!("{}_{}.pdf", stem, label));
+        refuse_overwrite(&[path.to_path_buf()], &target)?;
+        let source = PageSource { doc: doc.clone(), pages: pages.clone(), bookmarks: bookmarks.clone() };
+        assemble(vec![source])?.save(&target)?;
+    }
+
+    println!("Split '{}' into {} PDFs in '{}'", input, parts.len(), output_dir);
+    Ok(())
+}
+
+/// Main function of the pages command: keep the selected pages in the given order, minus
+/// the deleted ones
+pub fn run_pages(input: &str, output: &str, select: &[PageRange], delete: &[PageRange]) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(input);
+    refuse_overwrite(&[path.to_path_buf()], Path::new(output))?;
+    let doc = load(path)?;
+    let page_count = doc.get_pages().len();
+
+    let mut pages = Vec::new();
+    if select.is_empty() {
+        pages.extend(1..=page_count);
+    }
+    for range in select {
+        pages.extend(range.resolve(page_count)?);
+    }
+    let mut deleted = HashSet::new();
+    for range in delete {
+        deleted.extend(range.resolve(page_count)?);
+    }
+    pages.retain(|page| !deleted.contains(page));
+
+    if pages.is_empty() {
+        return Err(Box::new(PageError::Other("No pages left to write".to_string())));
+    }
+
+    let bookmarks = source_outline(&doc);
+    let written = pages.len();
+    assemble(vec![PageSource { doc, pages, bookmarks }])?.save(output)?;
+
+    println!("Wrote {} of {} pages to '{}'", written, page_count, output);
+    Ok(())
+}
+
+fn load(path: &Path) -> Result<Document, PageError> {
+    let doc = Document::load(path)
+        .map_err(|e| PageError::Other(format!("Error loading {}: {}", path.display(), e)))?;
+    if doc.is_encrypted() {
+        return Err(PageError::Other(format!("{} is encrypted", path.display())));
+    }
+    Ok(doc)
+}
+
+pub fn refuse_overwrite(inputs: &[PathBuf], output: &Path) -> Result<(), PageError> {
+    let output = match fs::canonicalize(output) {
+        Ok(output) => output,
+        Err(_) => return Ok(()),
+    };
+    match inputs.iter().find(|input| fs::canonicalize(input).ok().as_ref() == Some(&output)) {
+        Some(input) => Err(PageError::Other(format!("Refusing to overwrite the input file '{}'", input.display()))),
+        None => Ok(()),
+    }
+}
+
+/// The outline of a document as bookmarks; entries without a page make way for their children
+pub fn source_outline(doc: &Document) -> Vec<Bookmark> {
+    fn convert(entries: &[outline::OutlineEntry]) -> Vec<Bookmark> {
+        let mut bookmarks = Vec::new();
+        for entry in entries {
+            let children = convert(&entry.children);
+            match entry.page {
+                Some(page) => bookmarks.push(Bookmark { title: entry.title.clone(), page, children }),
+                None => bookmarks.extend(children),
+            }
+        }
+        bookmarks
+    }
+    convert(&outline::read_outline(doc))
+}
+
+/// Build one document from pages of several documents. Bookmarks, links between the copied
+/// pages, form fields and the metadata of the first document are kept; everything that is
+/// only reachable from pages that were left out is dropped.
+pub fn assemble(sources: Vec<PageSource>) -> Result<Document, PageError> {
+    let mut output = Document::with_version("1.5");
+    let mut next_id = 1;
+    let mut kids: Vec<ObjectId> = Vec::new();
+    let mut bookmarks: Vec<PlacedBookmark> = Vec::new();
+    let mut widgets: HashSet<ObjectId> = HashSet::new();
+    let mut fields: Vec<Object> = Vec::new();
+    let mut acro_form: Option<Dictionary> = None;
+    let mut catalog_entries = Dictionary::new();
+    let mut info: Option<Object> = None;
+
+    for (index, source) in sources.into_iter().enumerate() {
+        let mut doc = source.doc;
+        let page_ids = doc.get_pages();
+        if let Some(page) = source.pages.iter().find(|page| !page_ids.contains_key(&(**page as u32))) {
+            return Err(PageError::Other(format!(
+                "Page {} is out of range (the document has {} pages)", page, page_ids.len()
+            )));
+        }
+        if doc.version > output.version {
+            output.version = doc.version.clone();
+        }
+
+        let selected: HashSet<ObjectId> = source.pages.iter().map(|page| page_ids[&(*page as u32)]).collect();
+        prepare_pages(&mut doc, &selected);
+
+        let offset = next_id - 1;
+        shift_ids(&mut doc, offset);
+        next_id = doc.max_id + 1;
+
+        // Pages selected twice are copied, as a page object can only appear once in the tree
+        let mut used = HashSet::new();
+        let mut placed_pages: HashMap<usize, ObjectId> = HashMap::new();
+        for page in &source.pages {
+            let (number, generation) = page_ids[&(*page as u32)];
+            let mut id = (number + offset, generation);
+            if !used.insert(id) {
+                let copy = doc.get_dictionary(id)?.clone();
+                id = (next_id, 0);
+                next_id += 1;
+                doc.objects.insert(id, Object::Dictionary(copy));
+            }
+            placed_pages.entry(*page).or_insert(id);
+            kids.push(id);
+
+            for annotation in page_annotation_entries(&doc, id) {
+                if let Ok((Some(annotation_id), Object::Dictionary(annotation))) = doc.dereference(&annotation) {
+                    if annotation.get(b"Subtype").and_then(Object::as_name_str).ok() == Some("Widget") {
+                        widgets.insert(annotation_id);
+                    }
+                }
+            }
+        }
+
+        bookmarks.extend(place_bookmarks(&source.bookmarks, &placed_pages));
+
+        if let Ok(catalog) = doc.catalog() {
+            if index == 0 {
+                for key in CATALOG_ENTRIES {
+                    if let Ok(value) = catalog.get(key) {
+                        catalog_entries.set(key.to_vec(), value.clone());
+                    }
+                }
+                info = doc.trailer.get(b"Info").ok().cloned();
+            }
+            if let Some(form) = catalog.get(b"AcroForm").ok().and_then(|form| doc.dereference(form).ok()).and_then(|(_, form)| form.as_dict().ok()) {
+                fields.extend(form.get(b"Fields").and_then(Object::as_array).cloned().unwrap_or_default());
+                if acro_form.is_none() {
+                    acro_form = Some(form.clone());
+                }
+            }
+        }
+
+        output.objects.extend(doc.objects);
+    }
+    output.max_id = next_id - 1;
+
+    let pages_id = output.new_object_id();
+    for kid in &kids {
+        output.get_dictionary_mut(*kid)?.set("Parent", Object::Reference(pages_id));
+    }
+    output.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => kids.iter().map(|kid| Object::Reference(*kid)).collect::<Vec<_>>(),
+        "Count" => kids.len() as i64,
+    }));
+
+    let mut catalog = dictionary! {
+        "Type" => "Catalog",
+        "Pages" => Object::Reference(pages_id),
+    };
+    for (key, value) in catalog_entries.iter() {
+        catalog.set(key.clone(), value.clone());
+    }
+
+    if let Some(outline_id) = write_outline(&mut output, &bookmarks) {
+        catalog.set("Outlines", Object::Reference(outline_id));
+    }
+
+    if let Some(mut form) = acro_form {
+        let kept: Vec<Object> = fields.into_iter()
+            .filter(|field| field.as_reference().map(|id| filter_field(&mut output, id, &widgets, 0)).unwrap_or(false))
+            .collect();
+        if !kept.is_empty() {
+            form.set("Fields", Object::Array(kept));
+            catalog.set("AcroForm", Object::Dictionary(form));
+        }
+    }
+
+    let catalog_id = output.add_object(catalog);
+    output.trailer.set("Root", Object::Reference(catalog_id));
+    if let Some(info) = info {
+        output.trailer.set("Info", info);
+    }
+
+    // Drop the old page trees and everything only the left-out pages used
+    output.prune_objects();
+    output.renumber_objects();
+    output.compress();
+    Ok(output)
+}
+
+/// Make the selected pages self-contained before they move to a new page tree: copy
+/// inherited attributes, drop links to pages that are left out and article beads
+fn prepare_pages(doc: &mut Document, selected: &HashSet<ObjectId>) {
+    let page_numbers = outline::page_numbers(doc);
+    let selected_numbers: HashSet<usize> = selected.iter().filter_map(|id| page_numbers.get(id).copied()).collect();
+
+    for page_id in selected {
+        let inherited: Vec<(&[u8], Object)> = INHERITED_ATTRIBUTES.iter()
+            .filter_map(|key| utils::inherited_page_attribute(doc, *page_id, key).map(|value| (*key, value.clone())))
+            .collect();
+
+        // Internal links: keep those to copied pages, as explicit destinations
+        let annotations = page_annotation_entries(doc, *page_id);
+        let mut removed = HashSet::new();
+        let mut retargeted = Vec::new();
+        for (index, annotation) in annotations.iter().enumerate() {
+            let (id, dict) = match doc.dereference(annotation) {
+                Ok((id, Object::Dictionary(dict))) => (id, dict),
+                _ => continue,
+            };
+            let destination = dict.get(b"Dest").ok().or_else(|| {
+                let action = dict.get(b"A").ok().and_then(|a| doc.dereference(a).ok()).and_then(|(_, a)| a.as_dict().ok())?;
+                (action.get(b"S").and_then(Object::as_name_str).ok() == Some("GoTo")).then(|| action.get(b"D").ok()).flatten()
+            });
+            let target = match destination {
+                Some(destination) => outline::destination_page(doc, destination, &page_numbers),
+                None => continue,
+            };
+            match (target, id) {
+                (Some(page), Some(id)) if selected_numbers.contains(&page) => {
+                    let page_id = page_numbers.iter().find(|(_, number)| **number == page).map(|(id, _)| *id);
+                    retargeted.extend(page_id.map(|page_id| (id, page_id)));
+                },
+                _ => {
+                    removed.insert(index);
+                },
+            }
+        }
+
+        for (id, target) in retargeted {
+            if let Ok(annotation) = doc.get_dictionary_mut(id) {
+                annotation.remove(b"A");
+                annotation.set("Dest", vec![Object::Reference(target), Object::Name(b"Fit".to_vec())]);
+            }
+        }
+
+        if let Ok(page) = doc.get_dictionary_mut(*page_id) {
+            for (key, value) in inherited {
+                if !page.has(key) {
+                    page.set(key.to_vec(), value);
+                }
+            }
+            page.remove(b"B");
+            if !removed.is_empty() {
+                let kept: Vec<Object> = annotations.into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| !removed.contains(index))
+                    .map(|(_, annotation)| annotation)
+                    .collect();
+                page.set("Annots", Object::Array(kept));
+            }
+        }
+    }
+}
+
+/// The entries of a page's /Annots array, which may itself be an indirect object
+fn page_annotation_entries(doc: &Document, page_id: ObjectId) -> Vec<Object> {
+    doc.get_dictionary(page_id).ok()
+        .and_then(|page| page.get(b"Annots").ok())
+        .and_then(|annots| doc.dereference(annots).ok())
+        .and_then(|(_, annots)| annots.as_array().ok())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Add `offset` to every object number of a document, so its objects can join another one
+fn shift_ids(doc: &mut Document, offset: u32) {
+    fn shift(object: &mut Object, offset: u32) {
+        match object {
+            Object::Reference(id) => id.0 += offset,
+            Object::Array(items) => items.iter_mut().for_each(|item| shift(item, offset)),
+            Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, value)| shift(value, offset)),
+            Object::Stream(stream) => stream.dict.iter_mut().for_each(|(_, value)| shift(value, offset)),
+            _ => {},
+        }
+    }
+
+    if offset == 0 {
+        return;
+    }
+    let objects = std::mem::take(&mut doc.objects);
+    doc.objects = objects.into_iter()
+        .map(|((number, generation), mut object)| {
+            shift(&mut object, offset);
+            ((number + offset, generation), object)
+        })
+        .collect();
+    doc.trailer.iter_mut().for_each(|(_, value)| shift(value, offset));
+    doc.max_id += offset;
+}
+
+/// A bookmark whose page is in the output
+struct PlacedBookmark {
+    title: String,
+    page: ObjectId,
+    children: Vec<PlacedBookmark>,
+}
+
+/// Attach bookmarks to the copied pages; bookmarks to pages that were left out make way
+/// for their children
+fn place_bookmarks(bookmarks: &[Bookmark], pages: &HashMap<usize, ObjectId>) -> Vec<PlacedBookmark> {
+    let mut placed = Vec::new();
+    for bookmark in bookmarks {
+        let children = place_bookmarks(&bookmark.children, pages);
+        match pages.get(&bookmark.page) {
+            Some(page) => placed.push(PlacedBookmark { title: bookmark.title.clone(), page: *page, children }),
+            None => placed.extend(children),
+        }
+    }
+    placed
+}
+
+/// Write the outline dictionaries; returns the outline root. Items below the top level
+/// start collapsed.
+fn write_outline(doc: &mut Document, bookmarks: &[PlacedBookmark]) -> Option<ObjectId> {
+    fn write_items(doc: &mut Document, parent: ObjectId, bookmarks: &[PlacedBookmark]) -> (ObjectId, ObjectId) {
+        let ids: Vec<ObjectId> = bookmarks.iter().map(|_| doc.new_object_id()).collect();
+        for (index, bookmark) in bookmarks.iter().enumerate() {
+            let mut item = dictionary! {
+                "Title" => utils::encode_pdf_string(&bookmark.title),
+                "Parent" => Object::Reference(parent),
+                "Dest" => vec![Object::Reference(bookmark.page), Object::Name(b"Fit".to_vec())],
+            };
+            if index > 0 {
+                item.set("Prev", Object::Reference(ids[index - 1]));
+            }
+            if let Some(next) = ids.get(index + 1) {
+                item.set("Next", Object::Reference(*next));
+            }
+            if !bookmark.children.is_empty() {
+                let (first, last) = write_items(doc, ids[index], &bookmark.children);
+                item.set("First", Object::Reference(first));
+                item.set("Last", Object::Reference(last));
+                item.set("Count", -(bookmark.children.len() as i64));
+            }
+            doc.objects.insert(ids[index], Object::Dictionary(item));
+        }
+        (ids[0], ids[ids.len() - 1])
+    }
+
+    if bookmarks.is_empty() {
+        return None;
+    }
+    let root = doc.new_object_id();
+    let (first, last) = write_items(doc, root, bookmarks);
+    doc.objects.insert(root, Object::Dictionary(dictionary! {
+        "Type" => "Outlines",
+        "First" => Object::Reference(first),
+        "Last" => Object::Reference(last),
+        "Count" => bookmarks.len() as i64,
+    }));
+    Some(root)
+}
+
+/// Keep a form field if one of its widgets is on a copied page, dropping the kids that are not
+fn filter_field(doc: &mut Document, id: ObjectId, widgets: &HashSet<ObjectId>, depth: usize) -> bool {
+    let kids = doc.get_dictionary(id).ok()
+        .and_then(|field| field.get(b"Kids").ok())
+        .and_then(|kids| kids.as_array().ok())
+        .cloned();
+
+    match kids {
+        Some(kids) if depth < MAX_FIELD_DEPTH => {
+            let kept: Vec<Object> = kids.into_iter()
+                .filter(|kid| kid.as_reference().map(|kid| filter_field(doc, kid, widgets, depth + 1)).unwrap_or(false))
+                .collect();
+            if kept.is_empty() {
+                return false;
+            }
+            if let Ok(field) = doc.get_dictionary_mut(id) {
+                field.set("Kids", Object::Array(kept));
+            }
+            true
+        },
+        _ => widgets.contains(&id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(s: &str) -> PageRange {
+        s.parse().unwrap()
+    }
+
+    /// Document whose pages carry their number as MediaBox width, to tell them apart
+    fn numbered_document(page_count: usize) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = (1..=page_count)
+            .map(|number| doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), (number as i64).into(), 100.into()],
+            }).into())
+            .collect();
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => page_count as i64,
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn page_widths(doc: &Document) -> Vec<i64> {
+        doc.get_pages().values()
+            .map(|id| {
+                let page = doc.get_dictionary(*id).unwrap();
+                page.get(b"MediaBox").and_then(Object::as_array).unwrap()[2].as_i64().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_page_ranges() {
+        assert_eq!(range("5").resolve(10).unwrap(), vec![5]);
+        assert_eq!(range("3-5").resolve(10).unwrap(), vec![3, 4, 5]);
+        assert_eq!(range("5-3").resolve(10).unwrap(), vec![5, 4, 3]);
+        assert_eq!(range("8-").resolve(10).unwrap(), vec![8, 9, 10]);
+        assert_eq!(range("-2").resolve(10).unwrap(), vec![1, 2]);
+
+        for invalid in ["", "-", "0", "a-3", "2-x"] {
+            assert!(invalid.parse::<PageRange>().is_err(), "{}", invalid);
+        }
+        assert!(range("9-12").resolve(10).is_err());
+    }
+
+    #[test]
+    fn contains_clips_to_the_document() {
+        assert!(range("3-5").contains(4, 10));
+        assert!(!range("3-5").contains(6, 10));
+        assert!(range("5-3").contains(3, 10));
+        assert!(range("8-20").contains(10, 10));
+        assert!(range("8-").contains(10, 10));
+        assert!(!range("-2").contains(3, 10));
+        assert!(range("20-8").contains(9, 10));
+        assert!(!range("12").contains(10, 10));
+        assert!(!range("12-").contains(10, 10));
+        assert!(!range("3-5").contains(2, 2));
+    }
+
+    #[test]
+    fn labels_single_pages_and_ranges() {
+        assert_eq!(range("4").label(10), "page_4");
+        assert_eq!(range("4-").label(10), "pages_4-10");
+    }
+
+    #[test]
+    fn assembles_pages_in_order_with_bookmarks() {
+        let first = PageSource {
+            doc: numbered_document(3),
+            pages: vec![3, 1],
+            bookmarks: vec![Bookmark { title: "Start".to_string(), page: 1, children: Vec::new() }],
+        };
+        let second = PageSource { doc: numbered_document(2), pages: vec![2], bookmarks: Vec::new() };
+
+        let assembled = assemble(vec![first, second]).unwrap();
+        assert_eq!(page_widths(&assembled), vec![3, 1, 2]);
+
+        let entries = outline::read_outline(&assembled);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "Start");
+        assert_eq!(entries[0].page, Some(2));
+    }
+}
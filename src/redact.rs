@@ -0,0 +1,1626 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+use chrono::Local;
+use image::ImageFormat;
+use indicatif::ProgressBar;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::fonts::PdfFont;
+use crate::images;
+use crate::pages;
+use crate::pii;
+use crate::utils;
+
+/// Nesting limit for Form XObjects that draw other forms
+const MAX_FORM_DEPTH: usize = 12;
+
+/// Replacement for matches in outline titles and kept metadata
+const REDACTED_TEXT: &str = "[redacted]";
+
+/// Gap between glyphs, as a fraction of the font size, that counts as a word break
+const WORD_GAP: f32 = 0.15;
+
+/// Custom error type for redaction
+#[derive(Debug)]
+pub enum RedactError {
+    Io(io::Error),
+    Pdf(lopdf::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for RedactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedactError::Io(e) => write!(f, "I/O error: {}", e),
+            RedactError::Pdf(e) => write!(f, "PDF error: {}", e),
+            RedactError::Json(e) => write!(f, "JSON error: {}", e),
+            RedactError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RedactError {}
+
+impl From<io::Error> for RedactError {
+    fn from(err: io::Error) -> Self {
+        RedactError::Io(err)
+    }
+}
+
+impl From<lopdf::Error> for RedactError {
+    fn from(err: lopdf::Error) -> Self {
+        RedactError::Pdf(err)
+    }
+}
+
+impl From<serde_json::Error> for RedactError {
+    fn from(err: serde_json::Error) -> Self {
+        RedactError::Json(err)
+    }
+}
+
+/// Rectangle in user space: `[x0, y0, x1, y1]` with x0 <= x1 and y0 <= y1
+type Rect = [f32; 4];
+
+/// A page area to black out, given as `PAGE:x0,y0,x1,y1` in PDF points with PAGE a page
+/// number or `all`
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub page: Option<usize>,
+    pub rect: Rect,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (page, coordinates) = s.split_once(':')
+            .ok_or_else(|| format!("expected PAGE:x0,y0,x1,y1, got '{}'", s))?;
+        let page = match page.trim() {
+            "all" | "*" => None,
+            number => Some(number.parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("invalid page number '{}'", number))?),
+        };
+        let values = coordinates.split(',')
+            .map(|v| v.trim().parse::<f32>().map_err(|_| format!("invalid coordinate '{}'", v)))
+            .collect::<Result<Vec<f32>, String>>()?;
+        if values.len() != 4 {
+            return Err(format!("expected four coordinates, got {}", values.len()));
+        }
+        Ok(Region { page, rect: normalize_rect([values[0], values[1], values[2], values[3]]) })
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [x0, y0, x1, y1] = self.rect;
+        match self.page {
+            Some(page) => write!(f, "{}:{},{},{},{}", page, x0, y0, x1, y1),
+            None => write!(f, "all:{},{},{},{}", x0, y0, x1, y1),
+        }
+    }
+}
+
+/// What to remove: literal queries, regular expressions, personal data detectors and page regions
+#[derive(Debug, Default)]
+pub struct RedactRules {
+    pub queries: Vec<String>,
+    pub regexes: Vec<String>,
+    /// Names of the scan-pii detectors to apply; `all` for every built-in detector
+    pub pii: Vec<String>,
+    pub regions: Vec<Region>,
+    pub case_sensitive: bool,
+}
+
+/// One removal in the redaction log
+#[derive(Debug, Serialize)]
+struct Removal {
+    page: Option<usize>,
+    /// `text`, `image`, `annotation`, `outline` or `metadata`
+    kind: String,
+    rule: String,
+    /// The removed text, masked unless the log is asked to keep it
+    text: String,
+    rect: Option<Rect>,
+}
+
+/// The rules as recorded in the log; queries and regexes are masked like removed text
+#[derive(Debug, Serialize)]
+struct RuleSummary {
+    queries: Vec<String>,
+    regexes: Vec<String>,
+    pii: Vec<String>,
+    regions: Vec<String>,
+    case_sensitive: bool,
+}
+
+/// Redaction log written next to each output PDF
+#[derive(Debug, Serialize)]
+struct RedactionLog {
+    source: String,
+    output: String,
+    generated: String,
+    rules: RuleSummary,
+    removals: Vec<Removal>,
+    warnings: Vec<String>,
+}
+
+/// Outcome of redacting one document
+struct DocumentResult {
+    source: PathBuf,
+    removals: usize,
+    /// Matches still found in the text of the output
+    remaining: usize,
+    warnings: Vec<String>,
+}
+
+/// Main function to redact PDF files
+pub fn run(
+    input_paths: &[String],
+    output: &str,
+    rules: &RedactRules,
+    keep_metadata: bool,
+    log_text: bool,
+) -> Result<(), Box<dyn Error>> {
+    let matcher = Matcher::new(rules)?;
+    if matcher.is_empty() && rules.regions.is_empty() {
+        return Err(Box::new(RedactError::Other(
+            "Nothing to redact: give at least one --query, --regex, --pii or --region".to_string()
+        )));
+    }
+
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+    if pdf_paths.is_empty() {
+        return Err(Box::new(RedactError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    // A single PDF is written to the output file, several into the output directory
+    let output = PathBuf::from(output);
+    let single_file = pdf_paths.len() == 1 && !output.is_dir();
+    if !single_file {
+        fs::create_dir_all(&output)?;
+    }
+    // Same-named inputs from different directories get numbered names, so neither their
+    // PDFs nor their logs overwrite each other
+    let targets: Vec<(PathBuf, PathBuf)> = pdf_paths.iter()
+        .zip(utils::unique_stems(&pdf_paths))
+        .map(|(path, stem)| {
+            let target = if single_file {
+                output.clone()
+            } else {
+                output.join(format!("{}.pdf", stem))
+            };
+            (path.clone(), target)
+        })
+        .collect();
+
+    // A target may not replace any input, as the inputs are still being read in parallel
+    for (_, target) in &targets {
+        pages::refuse_overwrite(&pdf_paths, target).map_err(|e| RedactError::Other(e.to_string()))?;
+    }
+
+    let pb = ProgressBar::new(targets.len() as u64);
+    pb.set_message("Redacting");
+
+    let results: Vec<Result<DocumentResult, (PathBuf, RedactError)>> = targets
+        .par_iter()
+        .map(|(source, target)| {
+            let result = redact_file(source, target, rules, &matcher, keep_metadata, log_text)
+                .map_err(|e| (source.clone(), e));
+            pb.inc(1);
+            result
+        })
+        .collect();
+
+    pb.finish_with_message("Done");
+
+    let mut failed = 0;
+    let mut removals = 0;
+    let mut incomplete = 0;
+    for result in &results {
+        match result {
+            Ok(result) => {
+                removals += result.removals;
+                for warning in &result.warnings {
+                    eprintln!("Warning: {}: {}", result.source.display(), warning);
+                }
+                if result.remaining > 0 {
+                    incomplete += 1;
+                }
+            },
+            Err((path, e)) => {
+                eprintln!("Error redacting {}: {}", path.display(), e);
+                failed += 1;
+            },
+        }
+    }
+
+    println!(
+        "Redacted {} items in {} PDFs to '{}'",
+        removals, results.len() - failed, output.display()
+    );
+
+    if failed > 0 || incomplete > 0 {
+        return Err(Box::new(RedactError::Other(format!(
+            "{} PDFs could not be redacted and {} still contain matching text; see the redaction logs",
+            failed, incomplete
+        ))));
+    }
+    Ok(())
+}
+
+/// The text rules compiled into regular expressions plus the personal data detectors
+struct Matcher {
+    patterns: Vec<(String, Regex)>,
+    detectors: Vec<pii::Detector>,
+}
+
+impl Matcher {
+    fn new(rules: &RedactRules) -> Result<Self, RedactError> {
+        let mut patterns = Vec::new();
+
+        for (index, query) in rules.queries.iter().enumerate() {
+            // Any run of whitespace in a query matches any whitespace in the text
+            let words: Vec<String> = query.split_whitespace().map(regex::escape).collect();
+            if words.is_empty() {
+                continue;
+            }
+            let regex = RegexBuilder::new(&words.join(r"\s+"))
+                .case_insensitive(!rules.case_sensitive)
+                .build()
+                .map_err(|e| RedactError::Other(format!("Invalid query '{}': {}", query, e)))?;
+            patterns.push((format!("query {}", index + 1), regex));
+        }
+
+        for (index, regex) in rules.regexes.iter().enumerate() {
+            let compiled = RegexBuilder::new(regex)
+                .case_insensitive(!rules.case_sensitive)
+                .build()
+                .map_err(|e| RedactError::Other(format!("Invalid regex '{}': {}", regex, e)))?;
+            patterns.push((format!("regex {}", index + 1), compiled));
+        }
+
+        let detectors = if rules.pii.is_empty() {
+            Vec::new()
+        } else {
+            let names: Vec<String> = rules.pii.iter().filter(|name| *name != "all").cloned().collect();
+            pii::builtin_detectors(&names).map_err(|e| RedactError::Other(e.to_string()))?
+        };
+
+        Ok(Matcher { patterns, detectors })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && self.detectors.is_empty()
+    }
+
+    /// Byte ranges of all matches in a text with the rule that found them; overlapping
+    /// matches are merged
+    fn find(&self, text: &str) -> Vec<(usize, usize, String)> {
+        let mut matches: Vec<(usize, usize, String)> = Vec::new();
+        for (label, regex) in &self.patterns {
+            matches.extend(regex.find_iter(text)
+                .filter(|m| !m.as_str().trim().is_empty())
+                .map(|m| (m.start(), m.end(), label.clone())));
+        }
+        for (start, end, detector) in pii::scan_text(text, &self.detectors) {
+            matches.push((start, end, format!("pii:{}", detector.name)));
+        }
+
+        matches.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+        let mut merged: Vec<(usize, usize, String)> = Vec::new();
+        for (start, end, label) in matches {
+            match merged.last_mut() {
+                Some(last) if start < last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end, label)),
+            }
+        }
+        merged
+    }
+
+    /// Replace every match in a text; None if nothing matches
+    fn replace(&self, text: &str) -> Option<String> {
+        let matches = self.find(text);
+        if matches.is_empty() {
+            return None;
+        }
+        let mut result = String::new();
+        let mut position = 0;
+        for (start, end, _) in matches {
+            result.push_str(&text[position..start]);
+            result.push_str(REDACTED_TEXT);
+            position = end;
+        }
+        result.push_str(&text[position..]);
+        Some(result)
+    }
+}
+
+/// Where the operations of a parsed content stream come from
+enum UnitSource {
+    Page,
+    Form(ObjectId),
+}
+
+/// A parsed content stream: the page content or a Form XObject
+struct ContentUnit {
+    source: UnitSource,
+    content: Content,
+}
+
+/// One glyph drawn by a text-showing operator
+struct Glyph {
+    unit: usize,
+    op: usize,
+    /// Index of the string in a TJ array; 0 for the other operators
+    element: usize,
+    /// Byte range of the character code in the string
+    start: usize,
+    len: usize,
+    /// TJ adjustment that moves the text position as far as the glyph would
+    adjust: f32,
+    text: String,
+    bbox: Rect,
+    /// Text position before and after the glyph in user space
+    origin: (f32, f32),
+    end: (f32, f32),
+    /// Font size in user space
+    size: f32,
+}
+
+/// An image XObject drawn on a page; the unit square of image space maps to user space by `ctm`
+struct ImagePlacement {
+    id: ObjectId,
+    ctm: [f32; 6],
+}
+
+/// The glyphs and images of one page
+struct PageScan {
+    number: usize,
+    page_id: ObjectId,
+    unit: Option<usize>,
+    glyphs: Vec<Glyph>,
+    images: Vec<ImagePlacement>,
+}
+
+/// Graphics state, including the text state parameters that q and Q save and restore
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: [f32; 6],
+    font: Option<Rc<PdfFont>>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            ctm: IDENTITY,
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        }
+    }
+}
+
+/// Walks the content streams of a document and records where every glyph and image is drawn
+struct Walker<'a> {
+    doc: &'a Document,
+    units: Vec<ContentUnit>,
+    form_units: HashMap<ObjectId, usize>,
+    fonts: HashMap<ObjectId, Rc<PdfFont>>,
+    approximate_fonts: Vec<String>,
+    warnings: Vec<String>,
+    /// Content that could not be parsed and therefore cannot be redacted
+    failures: Vec<String>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(doc: &'a Document) -> Self {
+        Walker {
+            doc,
+            units: Vec::new(),
+            form_units: HashMap::new(),
+            fonts: HashMap::new(),
+            approximate_fonts: Vec::new(),
+            warnings: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+
+    fn scan_page(&mut self, number: usize, page_id: ObjectId) -> PageScan {
+        let mut scan = PageScan { number, page_id, unit: None, glyphs: Vec::new(), images: Vec::new() };
+
+        let content = self.doc.get_page_content(page_id).map_err(|e| e.to_string())
+            .and_then(|data| parse_content(&data));
+        let content = match content {
+            Ok(content) => content,
+            Err(e) => {
+                self.failures.push(format!("page {}: content could not be parsed ({})", number, e));
+                return scan;
+            },
+        };
+
+        let (inline, ids) = self.doc.get_page_resources(page_id);
+        let mut resources: Vec<&'a Dictionary> = inline.into_iter().collect();
+        resources.extend(ids.iter().filter_map(|id| self.doc.get_dictionary(*id).ok()));
+
+        self.units.push(ContentUnit { source: UnitSource::Page, content });
+        let unit = self.units.len() - 1;
+        scan.unit = Some(unit);
+        self.walk(unit, &resources, IDENTITY, &mut Vec::new(), &mut scan);
+        scan
+    }
+
+    /// Interpret the operations of a content unit, following Form XObjects
+    fn walk(&mut self, unit: usize, resources: &[&'a Dictionary], ctm: [f32; 6], forms: &mut Vec<ObjectId>, scan: &mut PageScan) {
+        let operations = self.units[unit].content.operations.clone();
+        let mut state = GraphicsState { ctm, ..Default::default() };
+        let mut stack: Vec<GraphicsState> = Vec::new();
+        let mut text_matrix = IDENTITY;
+        let mut line_matrix = IDENTITY;
+
+        for (index, operation) in operations.iter().enumerate() {
+            let operands = &operation.operands;
+            let number = |i: usize| operands.get(i).and_then(|o| o.as_float().ok()).unwrap_or(0.0);
+
+            match operation.operator.as_str() {
+                "q" => stack.push(state.clone()),
+                "Q" => {
+                    if let Some(saved) = stack.pop() {
+                        state = saved;
+                    }
+                },
+                "cm" => {
+                    if let Some(matrix) = matrix_operands(operands) {
+                        state.ctm = multiply(&matrix, &state.ctm);
+                    }
+                },
+                "BT" => {
+                    text_matrix = IDENTITY;
+                    line_matrix = IDENTITY;
+                },
+                "Tf" => {
+                    state.font = operands.first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| self.font(resources, name));
+                    state.font_size = number(1);
+                },
+                "Tc" => state.char_spacing = number(0),
+                "Tw" => state.word_spacing = number(0),
+                "Tz" => state.horizontal_scale = number(0) / 100.0,
+                "TL" => state.leading = number(0),
+                "Ts" => state.rise = number(0),
+                "Td" | "TD" => {
+                    if operation.operator == "TD" {
+                        state.leading = -number(1);
+                    }
+                    line_matrix = multiply(&translate(number(0), number(1)), &line_matrix);
+                    text_matrix = line_matrix;
+                },
+                "Tm" => {
+                    if let Some(matrix) = matrix_operands(operands) {
+                        line_matrix = matrix;
+                        text_matrix = matrix;
+                    }
+                },
+                "T*" => {
+                    line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                    text_matrix = line_matrix;
+                },
+                "Tj" | "'" | "\"" => {
+                    if operation.operator == "\"" {
+                        state.word_spacing = number(0);
+                        state.char_spacing = number(1);
+                    }
+                    if operation.operator != "Tj" {
+                        line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                        text_matrix = line_matrix;
+                    }
+                    if let Some(Object::String(bytes, _)) = operands.last() {
+                        self.show(bytes, (unit, index, 0), &state, &mut text_matrix, scan);
+                    }
+                },
+                "TJ" => {
+                    if let Some(Object::Array(elements)) = operands.first() {
+                        for (element, item) in elements.iter().enumerate() {
+                            match item {
+                                Object::String(bytes, _) => self.show(bytes, (unit, index, element), &state, &mut text_matrix, scan),
+                                other => {
+                                    let adjustment = other.as_float().unwrap_or(0.0);
+                                    let tx = -adjustment / 1000.0 * state.font_size * state.horizontal_scale;
+                                    text_matrix = multiply(&translate(tx, 0.0), &text_matrix);
+                                },
+                            }
+                        }
+                    }
+                },
+                "Do" => {
+                    if let Some(name) = operands.first().and_then(|name| name.as_name().ok()) {
+                        self.draw_xobject(name, resources, state.ctm, forms, scan);
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Record the glyphs of one string operand and advance the text matrix past them
+    fn show(&mut self, bytes: &[u8], position: (usize, usize, usize), state: &GraphicsState, text_matrix: &mut [f32; 6], scan: &mut PageScan) {
+        let font = match &state.font {
+            Some(font) => font.clone(),
+            None => return,
+        };
+        let (unit, op, element) = position;
+        let scale = state.font_size * state.horizontal_scale;
+
+        for (start, len, code) in font.codes(bytes) {
+            let width = font.width(code);
+            let mut tx = width * state.font_size + state.char_spacing;
+            if len == 1 && code == 32 {
+                tx += state.word_spacing;
+            }
+            tx *= state.horizontal_scale;
+
+            let render = multiply(
+                &[scale, 0.0, 0.0, state.font_size, 0.0, state.rise],
+                &multiply(text_matrix, &state.ctm),
+            );
+            let advance = if scale != 0.0 { tx / scale } else { 0.0 };
+            let corners = [
+                apply(&render, 0.0, font.descent),
+                apply(&render, width, font.descent),
+                apply(&render, 0.0, font.ascent),
+                apply(&render, width, font.ascent),
+            ];
+
+            scan.glyphs.push(Glyph {
+                unit,
+                op,
+                element,
+                start,
+                len,
+                adjust: -advance * 1000.0,
+                text: font.text(code),
+                bbox: bounding_box(&corners),
+                origin: apply(&render, 0.0, 0.0),
+                end: apply(&render, advance, 0.0),
+                size: render[2].hypot(render[3]),
+            });
+
+            *text_matrix = multiply(&translate(tx, 0.0), text_matrix);
+        }
+    }
+
+    fn draw_xobject(&mut self, name: &[u8], resources: &[&'a Dictionary], ctm: [f32; 6], forms: &mut Vec<ObjectId>, scan: &mut PageScan) {
+        let (id, stream) = match resource(self.doc, resources, b"XObject", name) {
+            Some((Some(id), Object::Stream(stream))) => (id, stream),
+            _ => return,
+        };
+
+        match stream.dict.get(b"Subtype").and_then(Object::as_name_str).unwrap_or("") {
+            "Image" => scan.images.push(ImagePlacement { id, ctm }),
+            "Form" => {
+                if forms.contains(&id) || forms.len() >= MAX_FORM_DEPTH {
+                    return;
+                }
+                let unit = match self.form_unit(id, stream) {
+                    Some(unit) => unit,
+                    None => return,
+                };
+                let matrix = stream.dict.get(b"Matrix").ok()
+                    .and_then(|m| m.as_array().ok())
+                    .and_then(|m| matrix_operands(m))
+                    .unwrap_or(IDENTITY);
+                let form_resources: Vec<&'a Dictionary> = match stream.dict.get(b"Resources").ok()
+                    .and_then(|r| self.doc.dereference(r).ok())
+                    .and_then(|(_, r)| r.as_dict().ok())
+                {
+                    Some(own) => vec![own],
+                    None => resources.to_vec(),
+                };
+
+                forms.push(id);
+                self.walk(unit, &form_resources, multiply(&matrix, &ctm), forms, scan);
+                forms.pop();
+            },
+            _ => {},
+        }
+    }
+
+    /// Parse a Form XObject once; later placements reuse its unit
+    fn form_unit(&mut self, id: ObjectId, stream: &Stream) -> Option<usize> {
+        if let Some(unit) = self.form_units.get(&id) {
+            return Some(*unit);
+        }
+        let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+        match parse_content(&data) {
+            Ok(content) => {
+                self.units.push(ContentUnit { source: UnitSource::Form(id), content });
+                self.form_units.insert(id, self.units.len() - 1);
+                Some(self.units.len() - 1)
+            },
+            Err(e) => {
+                self.failures.push(format!("form XObject {} {}: content could not be parsed ({})", id.0, id.1, e));
+                None
+            },
+        }
+    }
+
+    fn font(&mut self, resources: &[&'a Dictionary], name: &[u8]) -> Option<Rc<PdfFont>> {
+        let (id, font) = resource(self.doc, resources, b"Font", name)?;
+        let dict = font.as_dict().ok()?;
+        if let Some(font) = id.and_then(|id| self.fonts.get(&id)) {
+            return Some(font.clone());
+        }
+
+        let font = Rc::new(PdfFont::load(self.doc, dict));
+        if font.approximate_widths {
+            let base_font = dict.get(b"BaseFont").and_then(Object::as_name_str).unwrap_or("(unnamed)").to_string();
+            if !self.approximate_fonts.contains(&base_font) {
+                self.approximate_fonts.push(base_font);
+            }
+        }
+        if let Some(id) = id {
+            self.fonts.insert(id, font.clone());
+        }
+        Some(font)
+    }
+}
+
+/// Parse a content stream, failing where lopdf would silently drop the rest of the stream:
+/// it cannot read inline images and stops at the first operation it does not understand
+fn parse_content(data: &[u8]) -> Result<Content, String> {
+    let content = Content::decode(data).map_err(|e| e.to_string())?;
+    if content.operations.iter().any(|operation| matches!(operation.operator.as_str(), "BI" | "ID" | "EI")) {
+        return Err("inline images are not supported".to_string());
+    }
+    let end = data.iter().rposition(|byte| !byte.is_ascii_whitespace()).map_or(0, |last| last + 1);
+    let complete = match content.operations.last() {
+        Some(last) => {
+            let operator = last.operator.as_bytes();
+            data[..end].ends_with(operator)
+                && (end == operator.len() || !data[end - operator.len() - 1].is_ascii_alphabetic())
+        },
+        None => end == 0,
+    };
+    if !complete {
+        return Err("the stream ends in data that is not a valid operation".to_string());
+    }
+    Ok(content)
+}
+
+/// Look up a named resource of a category (Font, XObject) in the resource dictionaries
+fn resource<'a>(doc: &'a Document, resources: &[&'a Dictionary], category: &[u8], name: &[u8]) -> Option<(Option<ObjectId>, &'a Object)> {
+    resources.iter()
+        .filter_map(|dict| dict.get(category).ok())
+        .filter_map(|group| doc.dereference(group).ok())
+        .filter_map(|(_, group)| group.as_dict().ok())
+        .find_map(|group| group.get(name).ok())
+        .and_then(|entry| doc.dereference(entry).ok())
+}
+
+/// The text of a page in drawing order, with spaces and line breaks inferred from the
+/// glyph positions; returns the text, the byte range of each glyph and each glyph's line
+fn page_text(glyphs: &[Glyph]) -> (String, Vec<(usize, usize)>, Vec<usize>) {
+    let mut text = String::new();
+    let mut ranges = Vec::with_capacity(glyphs.len());
+    let mut lines = Vec::with_capacity(glyphs.len());
+    let mut line = 0;
+
+    for (index, glyph) in glyphs.iter().enumerate() {
+        if let Some(previous) = index.checked_sub(1).map(|i| &glyphs[i]) {
+            // Measure the step from the previous glyph along and across its baseline
+            let (dx, dy) = (glyph.origin.0 - previous.end.0, glyph.origin.1 - previous.end.1);
+            let (bx, by) = (previous.end.0 - previous.origin.0, previous.end.1 - previous.origin.1);
+            let length = bx.hypot(by);
+            let (bx, by) = if length > 0.0 { (bx / length, by / length) } else { (1.0, 0.0) };
+            let along = dx * bx + dy * by;
+            let across = dy * bx - dx * by;
+            let size = glyph.size.max(previous.size).max(1.0);
+
+            if across.abs() > size * 0.5 || along < -size {
+                text.push('\n');
+                line += 1;
+            } else if along > size * WORD_GAP {
+                text.push(' ');
+            }
+        }
+        let start = text.len();
+        text.push_str(&glyph.text);
+        ranges.push((start, text.len()));
+        lines.push(line);
+    }
+    (text, ranges, lines)
+}
+
+/// Removed character codes of one operation per string element: (start, len, adjust)
+type RemovedCodes = BTreeMap<usize, Vec<(usize, usize, f32)>>;
+
+/// Removals, black boxes and image edits collected for a document before any change is made
+#[derive(Default)]
+struct Plan {
+    /// Removed character codes per (unit, operation)
+    glyphs: HashMap<(usize, usize), RemovedCodes>,
+    /// Black boxes per page
+    boxes: HashMap<ObjectId, Vec<Rect>>,
+    /// Areas to black out per image, in user space with the placement matrix
+    images: BTreeMap<ObjectId, Vec<([f32; 6], Rect)>>,
+    removals: Vec<Removal>,
+}
+
+impl Plan {
+    fn remove_glyph(&mut self, glyph: &Glyph) {
+        let ranges = self.glyphs.entry((glyph.unit, glyph.op)).or_default().entry(glyph.element).or_default();
+        if !ranges.iter().any(|(start, _, _)| *start == glyph.start) {
+            ranges.push((glyph.start, glyph.len, glyph.adjust));
+        }
+    }
+}
+
+/// Redact one PDF into the target path and write its log
+fn redact_file(
+    source: &Path,
+    target: &Path,
+    rules: &RedactRules,
+    matcher: &Matcher,
+    keep_metadata: bool,
+    log_text: bool,
+) -> Result<DocumentResult, RedactError> {
+    let mut doc = Document::load(source)?;
+    if doc.is_encrypted() {
+        return Err(RedactError::Other("encrypted PDFs are not supported".to_string()));
+    }
+    let shown = |text: &str| if log_text { text.to_string() } else { pii::mask(text) };
+
+    let mut plan = Plan::default();
+    let (scans, mut units, mut warnings) = {
+        let mut walker = Walker::new(&doc);
+        let scans: Vec<PageScan> = doc.get_pages().into_iter()
+            .map(|(number, page_id)| walker.scan_page(number as usize, page_id))
+            .collect();
+        // Content that cannot be rewritten would keep its text and images under the boxes
+        // (lopdf cannot parse inline images, for example), so the document is not written at all
+        if !walker.failures.is_empty() {
+            return Err(RedactError::Other(format!(
+                "{}; refusing to write a partially redacted copy", walker.failures.join("; ")
+            )));
+        }
+        for font in &walker.approximate_fonts {
+            walker.warnings.push(format!("font {} has no width information; black boxes may be misplaced", font));
+        }
+        (scans, walker.units, walker.warnings)
+    };
+
+    for scan in &scans {
+        plan_page(scan, rules, matcher, &mut plan, &shown);
+    }
+
+    // Rewrite the content streams
+    let mut edits: HashMap<usize, HashMap<usize, RemovedCodes>> = HashMap::new();
+    for ((unit, op), elements) in std::mem::take(&mut plan.glyphs) {
+        edits.entry(unit).or_default().insert(op, elements);
+    }
+    for scan in &scans {
+        let unit = match scan.unit {
+            Some(unit) => unit,
+            None => continue,
+        };
+        let boxes = plan.boxes.get(&scan.page_id).map(Vec::as_slice).unwrap_or_default();
+        let unit_edits = edits.remove(&unit).unwrap_or_default();
+        if unit_edits.is_empty() && boxes.is_empty() {
+            continue;
+        }
+        let content = rewrite_operations(&units[unit].content, &unit_edits);
+        let mut data = b"q\n".to_vec();
+        data.extend(content.encode()?);
+        data.extend(b"\nQ\n");
+        for [x0, y0, x1, y1] in boxes {
+            data.extend(format!("q 0 g {:.2} {:.2} {:.2} {:.2} re f Q\n", x0, y0, x1 - x0, y1 - y0).into_bytes());
+        }
+        let mut stream = Stream::new(dictionary! {}, data);
+        stream.compress()?;
+        let stream_id = doc.add_object(stream);
+        doc.get_dictionary_mut(scan.page_id)?.set("Contents", Object::Reference(stream_id));
+    }
+    for (unit, unit_edits) in edits {
+        if let UnitSource::Form(id) = units[unit].source {
+            let content = rewrite_operations(&units[unit].content, &unit_edits);
+            units[unit].content = content;
+            if let Ok(Object::Stream(stream)) = doc.get_object_mut(id) {
+                stream.set_plain_content(units[unit].content.encode()?);
+                stream.compress()?;
+            }
+        }
+    }
+
+    for (id, areas) in std::mem::take(&mut plan.images) {
+        match black_out_image(&mut doc, id, &areas) {
+            Ok(None) => {},
+            Ok(Some(reason)) => warnings.push(format!("image {} {}: {}; the whole image was replaced with black", id.0, id.1, reason)),
+            Err(e) => return Err(RedactError::Other(format!("image {} {}: {}", id.0, id.1, e))),
+        }
+    }
+
+    for scan in &scans {
+        let boxes = plan.boxes.get(&scan.page_id).cloned().unwrap_or_default();
+        redact_annotations(&mut doc, scan, matcher, &boxes, &mut plan.removals, &shown);
+    }
+    redact_outline(&mut doc, matcher, &mut plan.removals, &shown);
+
+    if keep_metadata {
+        redact_info(&mut doc, matcher, &mut plan.removals, &shown);
+    } else {
+        scrub_metadata(&mut doc, &scans, &mut plan.removals);
+    }
+
+    let has_attachments = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"Names").ok())
+        .and_then(|names| doc.dereference(names).ok())
+        .and_then(|(_, names)| names.as_dict().ok())
+        .map(|names| names.has(b"EmbeddedFiles"))
+        .unwrap_or(false);
+    if has_attachments {
+        warnings.push("the document has embedded files, which are not redacted".to_string());
+    }
+
+    // Drop every object that is no longer referenced, such as the original content streams
+    doc.prune_objects();
+    doc.save(target)?;
+
+    let remaining = verify(target, matcher, &mut warnings);
+    let log = RedactionLog {
+        source: source.to_string_lossy().into_owned(),
+        output: target.to_string_lossy().into_owned(),
+        generated: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        rules: RuleSummary {
+            queries: rules.queries.iter().map(|query| shown(query)).collect(),
+            regexes: rules.regexes.iter().map(|regex| shown(regex)).collect(),
+            pii: rules.pii.clone(),
+            regions: rules.regions.iter().map(Region::to_string).collect(),
+            case_sensitive: rules.case_sensitive,
+        },
+        removals: plan.removals,
+        warnings,
+    };
+    fs::write(target.with_extension("redaction.json"), serde_json::to_string_pretty(&log)?)?;
+
+    Ok(DocumentResult {
+        source: source.to_path_buf(),
+        removals: log.removals.len(),
+        remaining,
+        warnings: log.warnings,
+    })
+}
+
+/// Find the matches and region hits of one page and add them to the plan
+fn plan_page(scan: &PageScan, rules: &RedactRules, matcher: &Matcher, plan: &mut Plan, shown: &dyn Fn(&str) -> String) {
+    let mut boxes: Vec<Rect> = Vec::new();
+    let (text, ranges, lines) = page_text(&scan.glyphs);
+
+    for (start, end, rule) in matcher.find(&text) {
+        let matched: Vec<usize> = (0..scan.glyphs.len())
+            .filter(|i| ranges[*i].0 < end && start < ranges[*i].1)
+            .collect();
+
+        // One box per line of the match
+        let mut rect: Option<Rect> = None;
+        for (position, glyph) in matched.iter().enumerate() {
+            plan.remove_glyph(&scan.glyphs[*glyph]);
+            let bbox = scan.glyphs[*glyph].bbox;
+            rect = Some(rect.map(|r| union(&r, &bbox)).unwrap_or(bbox));
+            let line_ends = matched.get(position + 1).map(|next| lines[*next] != lines[*glyph]).unwrap_or(true);
+            if line_ends {
+                boxes.extend(rect.take());
+            }
+        }
+
+        let bounds = matched.iter().map(|i| scan.glyphs[*i].bbox).reduce(|a, b| union(&a, &b));
+        plan.removals.push(Removal {
+            page: Some(scan.number),
+            kind: "text".to_string(),
+            rule,
+            text: shown(&text[start..end]),
+            rect: bounds,
+        });
+    }
+
+    for (index, region) in rules.regions.iter().enumerate() {
+        if region.page.is_some_and(|page| page != scan.number) {
+            continue;
+        }
+        let inside: Vec<&Glyph> = scan.glyphs.iter()
+            .filter(|glyph| {
+                let center = ((glyph.bbox[0] + glyph.bbox[2]) / 2.0, (glyph.bbox[1] + glyph.bbox[3]) / 2.0);
+                contains(&region.rect, center)
+            })
+            .collect();
+        for glyph in &inside {
+            plan.remove_glyph(glyph);
+        }
+        let removed_text: String = inside.iter().map(|glyph| glyph.text.as_str()).collect();
+        plan.removals.push(Removal {
+            page: Some(scan.number),
+            kind: "text".to_string(),
+            rule: format!("region {}", index + 1),
+            text: shown(&removed_text),
+            rect: Some(region.rect),
+        });
+        boxes.push(region.rect);
+    }
+
+    // Images under a black box lose the covered pixels too, which also covers scanned
+    // pages whose invisible text layer matched; images that cannot be decoded are
+    // replaced as a whole
+    for placement in &scan.images {
+        let corners = [
+            apply(&placement.ctm, 0.0, 0.0),
+            apply(&placement.ctm, 1.0, 0.0),
+            apply(&placement.ctm, 0.0, 1.0),
+            apply(&placement.ctm, 1.0, 1.0),
+        ];
+        let image_rect = bounding_box(&corners);
+        for rect in &boxes {
+            if let Some(area) = intersection(&image_rect, rect) {
+                plan.images.entry(placement.id).or_default().push((placement.ctm, area));
+                plan.removals.push(Removal {
+                    page: Some(scan.number),
+                    kind: "image".to_string(),
+                    rule: "black box".to_string(),
+                    text: String::new(),
+                    rect: Some(area),
+                });
+            }
+        }
+    }
+
+    if !boxes.is_empty() {
+        plan.boxes.entry(scan.page_id).or_default().extend(boxes);
+    }
+}
+
+/// Rebuild the operations of a content unit with the removed character codes replaced by
+/// TJ adjustments, so the remaining glyphs keep their positions
+fn rewrite_operations(content: &Content, edits: &HashMap<usize, RemovedCodes>) -> Content {
+    let mut operations = Vec::with_capacity(content.operations.len());
+    for (index, operation) in content.operations.iter().enumerate() {
+        let removed = match edits.get(&index) {
+            Some(removed) => removed,
+            None => {
+                operations.push(operation.clone());
+                continue;
+            },
+        };
+
+        let operands = &operation.operands;
+        let single = |string: Option<&Object>| match string {
+            Some(Object::String(bytes, format)) => split_string(bytes, format, removed.get(&0)),
+            _ => Vec::new(),
+        };
+        match operation.operator.as_str() {
+            "Tj" => operations.push(Operation::new("TJ", vec![Object::Array(single(operands.first()))])),
+            "'" => {
+                operations.push(Operation::new("T*", vec![]));
+                operations.push(Operation::new("TJ", vec![Object::Array(single(operands.first()))]));
+            },
+            "\"" => {
+                operations.push(Operation::new("Tw", vec![operands.first().cloned().unwrap_or(Object::Integer(0))]));
+                operations.push(Operation::new("Tc", vec![operands.get(1).cloned().unwrap_or(Object::Integer(0))]));
+                operations.push(Operation::new("T*", vec![]));
+                operations.push(Operation::new("TJ", vec![Object::Array(single(operands.get(2)))]));
+            },
+            "TJ" => {
+                let elements = match operands.first() {
+                    Some(Object::Array(elements)) => elements,
+                    _ => {
+                        operations.push(operation.clone());
+                        continue;
+                    },
+                };
+                let mut array = Vec::new();
+                for (element, item) in elements.iter().enumerate() {
+                    match item {
+                        Object::String(bytes, format) => array.extend(split_string(bytes, format, removed.get(&element))),
+                        other => array.push(other.clone()),
+                    }
+                }
+                operations.push(Operation::new("TJ", vec![Object::Array(array)]));
+            },
+            _ => operations.push(operation.clone()),
+        }
+    }
+    Content { operations }
+}
+
+/// Split a string operand around removed character codes, replacing each removed run with
+/// the adjustment that skips its width
+fn split_string(bytes: &[u8], format: &StringFormat, removed: Option<&Vec<(usize, usize, f32)>>) -> Vec<Object> {
+    let mut removed = removed.cloned().unwrap_or_default();
+    removed.sort_by_key(|(start, _, _)| *start);
+
+    let string = |part: &[u8]| Object::String(part.to_vec(), format.clone());
+    let mut parts = Vec::new();
+    let mut position = 0;
+    let mut adjustment = 0.0;
+    for (start, len, adjust) in removed {
+        if start > position {
+            if adjustment != 0.0 {
+                parts.push(Object::Real(adjustment));
+                adjustment = 0.0;
+            }
+            parts.push(string(&bytes[position..start]));
+        }
+        adjustment += adjust;
+        position = start + len;
+    }
+    if adjustment != 0.0 {
+        parts.push(Object::Real(adjustment));
+    }
+    if position < bytes.len() {
+        parts.push(string(&bytes[position..]));
+    }
+    parts
+}
+
+/// Paint the covered parts of an image black. Images that are fully covered, drawn rotated
+/// or skewed, or that cannot be decoded (CCITT, JBIG2, JPX, ...) are replaced by a single
+/// black pixel, so the original pixels never survive; returns the reason in the last case.
+fn black_out_image(doc: &mut Document, id: ObjectId, areas: &[([f32; 6], Rect)]) -> Result<Option<String>, String> {
+    let stream = doc.get_object(id).and_then(Object::as_stream).map_err(|e| e.to_string())?;
+    let (width, height) = images::image_size(&stream.dict).unwrap_or((0, 0));
+    let is_mask = stream.dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false);
+
+    // Pixel rectangles (left, top, right, bottom) of the covered areas
+    let mut pixels = Vec::new();
+    let mut whole = is_mask || width == 0 || height == 0;
+    for (ctm, area) in areas {
+        let [a, b, c, d, e, f] = *ctm;
+        if b.abs() > 1e-3 || c.abs() > 1e-3 || a == 0.0 || d == 0.0 {
+            whole = true;
+            break;
+        }
+        let (u0, u1) = ordered((area[0] - e) / a, (area[2] - e) / a);
+        let (v0, v1) = ordered((area[1] - f) / d, (area[3] - f) / d);
+        let left = (u0.clamp(0.0, 1.0) * width as f32).floor() as u32;
+        let right = (u1.clamp(0.0, 1.0) * width as f32).ceil() as u32;
+        let top = ((1.0 - v1.clamp(0.0, 1.0)) * height as f32).floor() as u32;
+        let bottom = ((1.0 - v0.clamp(0.0, 1.0)) * height as f32).ceil() as u32;
+        if left == 0 && top == 0 && right >= width && bottom >= height {
+            whole = true;
+            break;
+        }
+        pixels.push((left, top, right, bottom));
+    }
+
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "ColorSpace" => "DeviceRGB",
+        "BitsPerComponent" => 8,
+    };
+    if let Ok(smask) = stream.dict.get(b"SMask") {
+        dict.set("SMask", smask.clone());
+    }
+
+    let decoded = if whole {
+        None
+    } else {
+        let filters = stream.filters().unwrap_or_default();
+        let decoded = if filters.len() == 1 && filters[0] == "DCTDecode" {
+            image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg).map_err(|e| e.to_string())
+        } else {
+            images::decode_image(doc, stream, width, height).map_err(|e| e.to_string())
+        };
+        Some(decoded.map(|decoded| decoded.to_rgb8()).and_then(|image| {
+            if image.dimensions() == (width, height) {
+                Ok(image)
+            } else {
+                Err(format!("decoded size {}x{} does not match /Width and /Height", image.width(), image.height()))
+            }
+        }))
+    };
+
+    let mut fallback = None;
+    let (width, height, data) = match decoded {
+        Some(Ok(mut image)) => {
+            for (left, top, right, bottom) in pixels {
+                for y in top..bottom.min(height) {
+                    for x in left..right.min(width) {
+                        image.put_pixel(x, y, image::Rgb([0, 0, 0]));
+                    }
+                }
+            }
+            (width, height, image.into_raw())
+        },
+        Some(Err(e)) => {
+            fallback = Some(format!("it could not be decoded ({})", e));
+            (1, 1, vec![0, 0, 0])
+        },
+        None => (1, 1, vec![0, 0, 0]),
+    };
+
+    dict.set("Width", width as i64);
+    dict.set("Height", height as i64);
+    let mut replacement = Stream::new(dict, data);
+    replacement.compress().map_err(|e| e.to_string())?;
+    doc.objects.insert(id, Object::Stream(replacement));
+    Ok(fallback)
+}
+
+/// Remove annotations whose text matches or that lie under a black box, together with their
+/// popups and replies. Form fields stay in place but lose their value and appearance.
+fn redact_annotations(
+    doc: &mut Document,
+    scan: &PageScan,
+    matcher: &Matcher,
+    boxes: &[Rect],
+    removals: &mut Vec<Removal>,
+    shown: &dyn Fn(&str) -> String,
+) {
+    let entries: Vec<Object> = match doc.get_dictionary(scan.page_id).ok()
+        .and_then(|page| page.get(b"Annots").ok())
+        .and_then(|annots| doc.dereference(annots).ok())
+        .and_then(|(_, annots)| annots.as_array().ok())
+    {
+        Some(entries) => entries.clone(),
+        None => return,
+    };
+
+    let mut removed: HashSet<usize> = HashSet::new();
+    let mut removed_ids: HashSet<ObjectId> = HashSet::new();
+    let mut scrubbed: Vec<ObjectId> = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let (id, annotation) = match doc.dereference(entry) {
+            Ok((id, Object::Dictionary(annotation))) => (id, annotation),
+            _ => continue,
+        };
+        let subtype = annotation.get(b"Subtype").and_then(Object::as_name_str).unwrap_or("").to_string();
+
+        let mut matched_text = Vec::new();
+        for key in [b"Contents".as_slice(), b"T", b"Subj", b"RC", b"V"] {
+            if let Some(value) = utils::dict_string(doc, annotation, key) {
+                matched_text.extend(matcher.find(&value).into_iter().map(|(start, end, rule)| (rule, value[start..end].to_string())));
+            }
+        }
+        let rect = annotation.get(b"Rect").ok()
+            .and_then(|r| doc.dereference(r).ok())
+            .and_then(|(_, r)| r.as_array().ok())
+            .and_then(|r| rect_values(doc, r));
+        let covered = rect.map(|rect| boxes.iter().any(|b| intersection(&rect, b).is_some())).unwrap_or(false);
+        if matched_text.is_empty() && !covered {
+            continue;
+        }
+
+        let (rule, text) = matched_text.into_iter().next().unwrap_or_else(|| ("black box".to_string(), String::new()));
+        removals.push(Removal {
+            page: Some(scan.number),
+            kind: "annotation".to_string(),
+            rule,
+            text: format!("{}: {}", subtype, shown(&text)),
+            rect,
+        });
+
+        if subtype == "Widget" {
+            scrubbed.extend(id);
+            let parent = annotation.get(b"Parent").and_then(Object::as_reference).ok();
+            scrubbed.extend(parent);
+        } else {
+            removed.insert(index);
+            removed_ids.extend(id);
+        }
+    }
+
+    // Popups and replies of removed annotations go as well
+    loop {
+        let before = removed.len();
+        for (index, entry) in entries.iter().enumerate() {
+            if removed.contains(&index) {
+                continue;
+            }
+            let (id, annotation) = match doc.dereference(entry) {
+                Ok((id, Object::Dictionary(annotation))) => (id, annotation),
+                _ => continue,
+            };
+            let linked = [b"Parent".as_slice(), b"IRT"].iter()
+                .filter_map(|key| annotation.get(key).and_then(Object::as_reference).ok())
+                .any(|target| removed_ids.contains(&target));
+            if linked {
+                removed.insert(index);
+                removed_ids.extend(id);
+            }
+        }
+        if removed.len() == before {
+            break;
+        }
+    }
+
+    for id in scrubbed {
+        if let Ok(field) = doc.get_dictionary_mut(id) {
+            for key in [b"V".as_slice(), b"DV", b"AP", b"RV"] {
+                field.remove(key);
+            }
+        }
+    }
+
+    if !removed.is_empty() {
+        let kept: Vec<Object> = entries.into_iter()
+            .enumerate()
+            .filter(|(index, _)| !removed.contains(index))
+            .map(|(_, entry)| entry)
+            .collect();
+        if let Ok(page) = doc.get_dictionary_mut(scan.page_id) {
+            page.set("Annots", Object::Array(kept));
+        }
+    }
+}
+
+/// Replace matches in the titles of the document outline
+fn redact_outline(doc: &mut Document, matcher: &Matcher, removals: &mut Vec<Removal>, shown: &dyn Fn(&str) -> String) {
+    let root = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|outlines| doc.dereference(outlines).ok())
+        .and_then(|(_, outlines)| outlines.as_dict().ok())
+        .and_then(|outlines| outlines.get(b"First").and_then(Object::as_reference).ok());
+
+    let mut pending: Vec<ObjectId> = root.into_iter().collect();
+    let mut visited = HashSet::new();
+    let mut titles = Vec::new();
+    while let Some(id) = pending.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        if let Ok(item) = doc.get_dictionary(id) {
+            if let Some(title) = utils::dict_string(doc, item, b"Title") {
+                if let Some(replaced) = matcher.replace(&title) {
+                    titles.push((id, title, replaced));
+                }
+            }
+            for key in [b"First".as_slice(), b"Next"] {
+                pending.extend(item.get(key).and_then(Object::as_reference).ok());
+            }
+        }
+    }
+
+    for (id, title, replaced) in titles {
+        if let Ok(item) = doc.get_dictionary_mut(id) {
+            item.set("Title", utils::encode_pdf_string(&replaced));
+        }
+        removals.push(Removal {
+            page: None,
+            kind: "outline".to_string(),
+            rule: "title".to_string(),
+            text: shown(&title),
+            rect: None,
+        });
+    }
+}
+
+/// Replace matches in the document information strings
+fn redact_info(doc: &mut Document, matcher: &Matcher, removals: &mut Vec<Removal>, shown: &dyn Fn(&str) -> String) {
+    let info_id = match doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(id) => id,
+        Err(_) => return,
+    };
+    let mut changes = Vec::new();
+    if let Ok(info) = doc.get_dictionary(info_id) {
+        for (key, value) in info.iter() {
+            if let Object::String(bytes, _) = value {
+                let text = utils::decode_pdf_string(bytes);
+                if let Some(replaced) = matcher.replace(&text) {
+                    changes.push((key.clone(), text, replaced));
+                }
+            }
+        }
+    }
+    for (key, text, replaced) in changes {
+        if let Ok(info) = doc.get_dictionary_mut(info_id) {
+            info.set(key.clone(), utils::encode_pdf_string(&replaced));
+        }
+        removals.push(Removal {
+            page: None,
+            kind: "metadata".to_string(),
+            rule: String::from_utf8_lossy(&key).into_owned(),
+            text: shown(&text),
+            rect: None,
+        });
+    }
+}
+
+/// Remove the document information dictionary and the XMP metadata of the document and its pages
+fn scrub_metadata(doc: &mut Document, scans: &[PageScan], removals: &mut Vec<Removal>) {
+    let mut scrub = |page: Option<usize>, what: &str| removals.push(Removal {
+        page,
+        kind: "metadata".to_string(),
+        rule: "scrub".to_string(),
+        text: what.to_string(),
+        rect: None,
+    });
+
+    if doc.trailer.remove(b"Info").is_some() {
+        scrub(None, "document information");
+    }
+    if let Ok(catalog) = doc.catalog_mut() {
+        if catalog.remove(b"Metadata").is_some() {
+            scrub(None, "XMP metadata");
+        }
+        catalog.remove(b"PieceInfo");
+    }
+    for scan in scans {
+        if let Ok(page) = doc.get_dictionary_mut(scan.page_id) {
+            let metadata = page.remove(b"Metadata").is_some();
+            let piece_info = page.remove(b"PieceInfo").is_some();
+            if metadata || piece_info {
+                scrub(Some(scan.number), "page metadata");
+            }
+        }
+    }
+}
+
+/// Extract the text of the written PDF again and count what the rules still match
+fn verify(path: &Path, matcher: &Matcher, warnings: &mut Vec<String>) -> usize {
+    let pages = match fs::read(path).map_err(|e| e.to_string()).and_then(|bytes| {
+        panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem_by_pages(&bytes)))
+            .map_err(|_| "text extraction crashed".to_string())?
+            .map_err(|e| e.to_string())
+    }) {
+        Ok(pages) => pages,
+        Err(e) => {
+            warnings.push(format!("the output could not be checked: {}", e));
+            return 0;
+        },
+    };
+
+    let mut remaining = 0;
+    for (index, text) in pages.iter().enumerate() {
+        let matches = matcher.find(text);
+        if !matches.is_empty() {
+            warnings.push(format!("page {}: {} matches are still present in the output text", index + 1, matches.len()));
+            remaining += matches.len();
+        }
+    }
+    remaining
+}
+
+const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+fn translate(x: f32, y: f32) -> [f32; 6] {
+    [1.0, 0.0, 0.0, 1.0, x, y]
+}
+
+/// Product of two PDF matrices: `a` applied first, then `b`
+fn multiply(a: &[f32; 6], b: &[f32; 6]) -> [f32; 6] {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+fn apply(m: &[f32; 6], x: f32, y: f32) -> (f32, f32) {
+    (x * m[0] + y * m[2] + m[4], x * m[1] + y * m[3] + m[5])
+}
+
+fn matrix_operands(operands: &[Object]) -> Option<[f32; 6]> {
+    if operands.len() != 6 {
+        return None;
+    }
+    let mut matrix = [0.0; 6];
+    for (value, operand) in matrix.iter_mut().zip(operands) {
+        *value = operand.as_float().ok()?;
+    }
+    Some(matrix)
+}
+
+/// A /Rect array, whose numbers may be indirect
+fn rect_values(doc: &Document, values: &[Object]) -> Option<Rect> {
+    if values.len() != 4 {
+        return None;
+    }
+    let mut rect = [0.0; 4];
+    for (value, object) in rect.iter_mut().zip(values) {
+        *value = doc.dereference(object).ok()?.1.as_float().ok()?;
+    }
+    Some(normalize_rect(rect))
+}
+
+fn bounding_box(points: &[(f32, f32)]) -> Rect {
+    points.iter().fold(
+        [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+        |[x0, y0, x1, y1], (x, y)| [x0.min(*x), y0.min(*y), x1.max(*x), y1.max(*y)],
+    )
+}
+
+fn normalize_rect(rect: Rect) -> Rect {
+    let (x0, x1) = ordered(rect[0], rect[2]);
+    let (y0, y1) = ordered(rect[1], rect[3]);
+    [x0, y0, x1, y1]
+}
+
+fn ordered(a: f32, b: f32) -> (f32, f32) {
+    if a <= b { (a, b) } else { (b, a) }
+}
+
+fn union(a: &Rect, b: &Rect) -> Rect {
+    [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]
+}
+
+fn intersection(a: &Rect, b: &Rect) -> Option<Rect> {
+    let rect = [a[0].max(b[0]), a[1].max(b[1]), a[2].min(b[2]), a[3].min(b[3])];
+    (rect[0] < rect[2] && rect[1] < rect[3]).then_some(rect)
+}
+
+fn contains(rect: &Rect, (x, y): (f32, f32)) -> bool {
+    rect[0] <= x && x <= rect[2] && rect[1] <= y && y <= rect[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(queries: &[&str], regexes: &[&str]) -> RedactRules {
+        RedactRules {
+            queries: queries.iter().map(|q| q.to_string()).collect(),
+            regexes: regexes.iter().map(|r| r.to_string()).collect(),
+            ..RedactRules::default()
+        }
+    }
+
+    /// One-page document with the given content stream and an optional image resource `Im1`
+    fn document_with_content(content: &[u8], image: Option<Stream>) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.to_vec()));
+        let mut resources = dictionary! {};
+        if let Some(image) = image {
+            let image_id = doc.add_object(image);
+            resources.set("XObject", dictionary! { "Im1" => image_id });
+        }
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 200.into(), 200.into()],
+            "Contents" => content_id,
+            "Resources" => resources,
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    #[test]
+    fn parses_and_prints_regions() {
+        let region: Region = "3:100,200,50,20".parse().unwrap();
+        assert_eq!(region.page, Some(3));
+        assert_eq!(region.rect, [50.0, 20.0, 100.0, 200.0]);
+        assert_eq!(region.to_string(), "3:50,20,100,200");
+
+        let all: Region = "all:0,0,10,10".parse().unwrap();
+        assert_eq!(all.page, None);
+        assert_eq!(all.to_string(), "all:0,0,10,10");
+
+        assert!("0:0,0,1,1".parse::<Region>().is_err());
+        assert!("1:0,0,1".parse::<Region>().is_err());
+        assert!("0,0,1,1".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn queries_match_across_whitespace_and_merge_overlaps() {
+        let matcher = Matcher::new(&rules(&["John  Smith"], &[r"Smith \w+"])).unwrap();
+        let text = "Signed by John\nSmith Jr. today";
+        let matches = matcher.find(text);
+        assert_eq!(matches.len(), 1);
+        let (start, end, label) = &matches[0];
+        assert_eq!(&text[*start..*end], "John\nSmith Jr");
+        assert_eq!(label, "query 1");
+        assert_eq!(matcher.replace(text).unwrap(), "Signed by [redacted]. today");
+        assert_eq!(matcher.replace("nothing here"), None);
+    }
+
+    #[test]
+    fn regex_labels_do_not_reveal_the_pattern() {
+        let matcher = Matcher::new(&rules(&[], &[r"secret-\d+"])).unwrap();
+        let matches = matcher.find("code secret-42");
+        assert_eq!(matches[0].2, "regex 1");
+        assert!(Matcher::new(&rules(&[], &["("])).is_err());
+    }
+
+    #[test]
+    fn splits_strings_around_removed_codes() {
+        let format = StringFormat::Literal;
+        let parts = split_string(b"abcdef", &format, Some(&vec![(2, 2, -500.0)]));
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].as_str().unwrap(), b"ab");
+        assert_eq!(parts[1].as_float().unwrap(), -500.0);
+        assert_eq!(parts[2].as_str().unwrap(), b"ef");
+
+        let untouched = split_string(b"abc", &format, None);
+        assert_eq!(untouched.len(), 1);
+        assert_eq!(untouched[0].as_str().unwrap(), b"abc");
+    }
+
+    #[test]
+    fn undecodable_images_are_replaced_whole() {
+        let image = Stream::new(dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 8,
+            "Height" => 8,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 1,
+            "Filter" => "JBIG2Decode",
+        }, vec![1, 2, 3, 4]);
+        let mut doc = document_with_content(b"q 100 0 0 100 0 0 cm /Im1 Do Q", Some(image));
+        let image_id = doc.objects.iter()
+            .find(|(_, object)| object.as_stream().map(|s| s.dict.has(b"Filter") && s.dict.has(b"Width")).unwrap_or(false))
+            .map(|(id, _)| *id)
+            .unwrap();
+
+        let area = ([100.0, 0.0, 0.0, 100.0, 0.0, 0.0], [0.0, 0.0, 50.0, 50.0]);
+        let reason = black_out_image(&mut doc, image_id, &[area]).unwrap();
+        assert!(reason.unwrap().contains("could not be decoded"));
+
+        let replaced = doc.get_object(image_id).and_then(Object::as_stream).unwrap();
+        assert_eq!(images::image_size(&replaced.dict), Some((1, 1)));
+        let data = replaced.decompressed_content().unwrap_or_else(|_| replaced.content.clone());
+        assert_eq!(data, vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_content_that_lopdf_would_truncate() {
+        assert_eq!(parse_content(b"BT /F1 12 Tf (a) Tj ET\n").unwrap().operations.len(), 4);
+        assert!(parse_content(b"  \n").unwrap().operations.is_empty());
+        assert!(parse_content(b"q BI /W 1 /H 1 ID \x00 EI Q").is_err());
+        assert!(parse_content(b"BT (a) Tj ET ) garbage").is_err());
+    }
+
+    #[test]
+    fn unparseable_content_fails_the_document() {
+        let directory = std::env::temp_dir().join(format!("pdfscan-redact-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let source = directory.join("inline.pdf");
+        let target = directory.join("inline_redacted.pdf");
+        let mut doc = document_with_content(b"BT /F1 12 Tf (secret) Tj ET\nBI /W 2 /H 2 /BPC 8 /CS /G ID \x00\xff)\x00 EI", None);
+        doc.save(&source).unwrap();
+
+        let rules = rules(&["secret"], &[]);
+        let matcher = Matcher::new(&rules).unwrap();
+        let result = redact_file(&source, &target, &rules, &matcher, false, false);
+        assert!(matches!(result, Err(RedactError::Other(ref e)) if e.contains("could not be parsed")));
+        assert!(!target.exists());
+        assert!(!target.with_extension("redaction.json").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use clap::ValueEnum;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageFormat};
+use indicatif::ProgressBar;
+use pdfium_render::prelude::*;
+
+use crate::pages::PageRange;
+use crate::utils;
+
+/// Highest accepted resolution; an A4 page at this DPI is about 19843 x 28063 pixels
+const MAX_DPI: f32 = 2400.0;
+
+/// Largest page bitmap rendered, in pixels (1 GB as RGBA); larger pages are skipped
+const MAX_PAGE_PIXELS: u64 = 250_000_000;
+
+/// Custom error type for page rendering
+#[derive(Debug)]
+pub enum RenderError {
+    Io(io::Error),
+    Pdf(String),
+    Image(image::ImageError),
+    Other(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Io(e) => write!(f, "I/O error: {}", e),
+            RenderError::Pdf(e) => write!(f, "PDF error: {}", e),
+            RenderError::Image(e) => write!(f, "Image error: {}", e),
+            RenderError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+impl From<io::Error> for RenderError {
+    fn from(err: io::Error) -> Self {
+        RenderError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for RenderError {
+    fn from(err: image::ImageError) -> Self {
+        RenderError::Image(err)
+    }
+}
+
+/// Image format of rendered pages
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum RenderFormat {
+    Png,
+    Jpeg,
+    /// Lossless WebP
+    Webp,
+}
+
+impl RenderFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            RenderFormat::Png => "png",
+            RenderFormat::Jpeg => "jpg",
+            RenderFormat::Webp => "webp",
+        }
+    }
+
+    /// Longest image side the encoder can write
+    fn max_side(self) -> u32 {
+        match self {
+            RenderFormat::Png => u32::MAX,
+            RenderFormat::Jpeg => 65_535,
+            RenderFormat::Webp => 16_383,
+        }
+    }
+}
+
+/// Options of the render command
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub dpi: f32,
+    pub format: RenderFormat,
+    /// JPEG quality, from 1 to 100
+    pub quality: u8,
+    /// Pages to render (all pages when empty)
+    pub pages: Vec<PageRange>,
+    /// Also write a thumbnail fitting into a square of this many pixels
+    pub thumbnail: Option<u32>,
+}
+
+/// Main function to rasterize PDF pages into image files
+pub fn run(input_paths: &[String], output_dir: &str, options: &RenderOptions) -> Result<(), Box<dyn Error>> {
+    if !(1.0..=MAX_DPI).contains(&options.dpi) {
+        return Err(Box::new(RenderError::Other(format!("DPI must be between 1 and {}", MAX_DPI))));
+    }
+    if !(1..=100).contains(&options.quality) {
+        return Err(Box::new(RenderError::Other("Quality must be between 1 and 100".to_string())));
+    }
+    if options.thumbnail == Some(0) {
+        return Err(Box::new(RenderError::Other("Thumbnail size must be at least 1 pixel".to_string())));
+    }
+
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+    if pdf_paths.is_empty() {
+        return Err(Box::new(RenderError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    // Rendering runs headless, so build servers only need the Pdfium library
+    let pdfium = utils::bind_pdfium().map_err(RenderError::Other)?;
+    let output_dir = Path::new(output_dir);
+    fs::create_dir_all(output_dir)?;
+
+    // Pdfium is not thread-safe, so documents are rendered one after another
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Rendering pages");
+
+    // Same-named inputs get numbered names, so their pages do not overwrite each other
+    let stems = utils::unique_stems(&pdf_paths);
+    let mut rendered = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+    for (path, stem) in pdf_paths.iter().zip(&stems) {
+        match render_document(&pdfium, path, stem, output_dir, options) {
+            Ok((count, skipped_pages)) => {
+                rendered += count;
+                skipped += skipped_pages;
+            },
+            Err(e) => {
+                eprintln!("Error rendering {}: {}", path.display(), e);
+                failed += 1;
+            },
+        }
+        pb.inc(1);
+    }
+    pb.finish_with_message("Done");
+
+    println!(
+        "Rendered {} pages from {} PDFs to '{}' at {} DPI{}",
+        rendered, pdf_paths.len() - failed, output_dir.display(), options.dpi,
+        if options.thumbnail.is_some() { " (with thumbnails)" } else { "" }
+    );
+    if skipped > 0 {
+        println!("Skipped {} pages that could not be rendered", skipped);
+    }
+
+    if failed > 0 {
+        return Err(Box::new(RenderError::Other(format!("{} PDFs could not be rendered", failed))));
+    }
+    Ok(())
+}
+
+/// Render the selected pages of a document into `<stem>_page<N>` files. A page that is too
+/// large or fails to render or encode is skipped with a message. Returns the number of pages
+/// written and skipped.
+fn render_document(pdfium: &Pdfium, path: &Path, stem: &str, output_dir: &Path, options: &RenderOptions) -> Result<(usize, usize), RenderError> {
+    let document = pdfium.load_pdf_from_file(path, None)
+        .map_err(|e| RenderError::Pdf(format!("Error loading {}: {}", path.display(), e)))?;
+    let page_count = document.pages().len() as usize;
+
+    let mut count = 0;
+    let mut skipped = 0;
+    for (index, page) in document.pages().iter().enumerate() {
+        let number = index + 1;
+        if !options.pages.is_empty() && !options.pages.iter().any(|r| r.contains(number, page_count)) {
+            continue;
+        }
+
+        let base_name = format!("{}_page{}", stem, number);
+        let result = pixel_size(page.width().value, page.height().value, options)
+            .and_then(|_| render_page(&page, &base_name, output_dir, options));
+        match result {
+            Ok(()) => count += 1,
+            Err(e) => {
+                eprintln!("Warning: Skipping page {} of '{}': {}", number, path.display(), e);
+                skipped += 1;
+            },
+        }
+    }
+
+    if count == 0 && skipped == 0 {
+        eprintln!("Warning: No selected page in '{}' ({} pages)", path.display(), page_count);
+    }
+    Ok((count, skipped))
+}
+
+/// Pixel size of a page at the requested resolution, if it can be rendered and encoded
+fn pixel_size(width_points: f32, height_points: f32, options: &RenderOptions) -> Result<(u32, u32), RenderError> {
+    let scale = options.dpi / 72.0;
+    let width = (width_points.abs() * scale).round() as u64;
+    let height = (height_points.abs() * scale).round() as u64;
+    let max_side = options.format.max_side() as u64;
+    if width * height > MAX_PAGE_PIXELS {
+        return Err(RenderError::Other(format!(
+            "{} x {} pixels exceed the limit of {} megapixels; use a lower --dpi", width, height, MAX_PAGE_PIXELS / 1_000_000
+        )));
+    }
+    if width.max(height) > max_side {
+        return Err(RenderError::Other(format!(
+            "{} x {} pixels exceed the {}-pixel limit of {}; use a lower --dpi or another format",
+            width, height, max_side, options.format.extension()
+        )));
+    }
+    Ok((width as u32, height as u32))
+}
+
+/// Render one page, and its thumbnail if asked, into `output_dir`
+fn render_page(page: &PdfPage, base_name: &str, output_dir: &Path, options: &RenderOptions) -> Result<(), RenderError> {
+    let image = DynamicImage::ImageRgba8(
+        utils::render_page_rgba(page, options.dpi).map_err(RenderError::Pdf)?
+    );
+    if let Some(size) = options.thumbnail {
+        let thumbnail = image.thumbnail(size, size);
+        let file_name = format!("{}_thumb.{}", base_name, options.format.extension());
+        write_image(&thumbnail, &output_dir.join(file_name), options)?;
+    }
+    let file_name = format!("{}.{}", base_name, options.format.extension());
+    write_image(&image, &output_dir.join(file_name), options)
+}
+
+/// Encode a rendered page. Pages are opaque, so the alpha channel is dropped.
+fn write_image(image: &DynamicImage, output: &Path, options: &RenderOptions) -> Result<(), RenderError> {
+    let rgb = image.to_rgb8();
+    match options.format {
+        RenderFormat::Png => rgb.save_with_format(output, ImageFormat::Png)?,
+        RenderFormat::Jpeg => {
+            let mut file = BufWriter::new(fs::File::create(output)?);
+            JpegEncoder::new_with_quality(&mut file, options.quality).encode_image(&rgb)?;
+        },
+        RenderFormat::Webp => {
+            let file = BufWriter::new(fs::File::create(output)?);
+            WebPEncoder::new_lossless(file).encode(rgb.as_raw(), rgb.width(), rgb.height(), image::ColorType::Rgb8)?;
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(dpi: f32, format: RenderFormat) -> RenderOptions {
+        RenderOptions { dpi, format, quality: 85, pages: Vec::new(), thumbnail: None }
+    }
+
+    #[test]
+    fn computes_page_sizes_in_pixels() {
+        // A4 is 595 x 842 points
+        let size = pixel_size(595.0, 842.0, &options(300.0, RenderFormat::Png)).unwrap();
+        assert_eq!(size, (2479, 3508));
+    }
+
+    #[test]
+    fn skips_pages_too_large_to_render_or_encode() {
+        // About 19833 x 28067 pixels: over the area limit in any format
+        assert!(pixel_size(595.0, 842.0, &options(MAX_DPI, RenderFormat::Png)).is_err());
+        // A 10 x 0.5 m banner at 150 DPI stays under the area limit but is too wide for WebP
+        let banner = options(150.0, RenderFormat::Webp);
+        assert!(pixel_size(28346.0, 1417.0, &banner).is_err());
+        assert!(pixel_size(28346.0, 1417.0, &options(150.0, RenderFormat::Jpeg)).is_ok());
+    }
+}
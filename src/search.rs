@@ -1,15 +1,27 @@
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::{self, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::error::Error;
 use std::fmt;
+use indicatif::ProgressBar;
 use walkdir::WalkDir;
 use zip::write::FileOptions;
 use chrono;
+use clap::Args;
 use dirs;
 
+use crate::annotations;
+use crate::attachments;
+use crate::coverage::{self, CoverageTotals};
+use crate::highlight;
+use crate::ocr::{OcrArgs, OcrStage};
+use crate::outline;
+use crate::pages::{self, Bookmark, PageSource};
+
 /// Custom error type for search operations
 #[derive(Debug)]
 pub enum SearchError {
@@ -44,8 +56,36 @@ impl From<zip::result::ZipError> for SearchError {
     }
 }
 
+/// What to produce from the matching files besides the listing
+#[derive(Args, Debug, Clone, Default)]
+pub struct SearchOutputArgs {
+    /// Enable ZIP output of matching files
+    #[arg(short, long)]
+    pub zip: bool,
+
+    /// Assemble every page with a hit from all matching PDFs into one PDF
+    #[arg(long, value_name = "OUTPUT")]
+    pub export_pages: Option<PathBuf>,
+
+    /// Pages before and after every hit to include in the exported PDF
+    #[arg(long, default_value_t = 0, requires = "export_pages")]
+    pub context: usize,
+
+    /// Write a copy of every matching PDF with its hits highlighted into this directory
+    #[arg(long, value_name = "DIR")]
+    pub export_highlights: Option<PathBuf>,
+}
+
 /// Main function to run the search functionality
-pub fn run(search_phrase: &str, directories: &[PathBuf], zip_output: bool) -> Result<(), Box<dyn Error>> {
+pub fn run(
+    search_phrase: &str,
+    directories: &[PathBuf],
+    output: &SearchOutputArgs,
+    only_scanned: bool,
+    include_attachments: bool,
+    include_annotations: bool,
+    ocr: &OcrArgs,
+) -> Result<(), Box<dyn Error>> {
     let search_dirs = if directories.is_empty() {
         // Use home directory as default if no directories provided
         match dirs::home_dir() {
@@ -68,40 +108,279 @@ pub fn run(search_phrase: &str, directories: &[PathBuf], zip_output: bool) -> Re
     }
 
     // Search for PDF files
-    let results = search_pdf_files(search_phrase, &search_dirs)?;
+    let mut outcome = search_pdf_files(search_phrase, &search_dirs, only_scanned, include_attachments, include_annotations)?;
+
+    // Give image-only pages a second chance through OCR
+    let recovered = match OcrStage::from_args(ocr) {
+        Some(stage) => search_with_ocr(&stage, search_phrase, only_scanned, &mut outcome),
+        None => HashSet::new(),
+    };
+
+    // Classify the PDFs that had no text layer to tell scans apart from blank or broken files
+    let no_text_paths: Vec<PathBuf> = outcome.no_text.iter().map(PathBuf::from).collect();
+    let coverage = if no_text_paths.is_empty() {
+        None
+    } else {
+        coverage::classify_all(&no_text_paths)
+    };
+
+    let mut results = outcome.matches;
+    if only_scanned {
+        if let Some(coverage) = &coverage {
+            results.retain(|path| coverage.get(Path::new(path)).map(|c| c.is_scanned()).unwrap_or(true));
+        }
+    }
     
     // Output results
     println!("\nFound {} matching PDF files:", results.len());
     for result in &results {
         println!("{}", result);
+        // Sections of the document outline that contain the phrase
+        if let Some(sections) = outcome.sections.get(result) {
+            for section in sections {
+                println!("    in {}", section);
+            }
+        }
+    }
+
+    // Report documents that could not be searched
+    let unsearchable: Vec<&String> = outcome.no_text.iter()
+        .filter(|path| !recovered.contains(*path))
+        .collect();
+    if !unsearchable.is_empty() && !only_scanned {
+        eprintln!("\nWarning: {} PDF(s) have no text layer and could not be searched (image-only scans need OCR):", unsearchable.len());
+        let mut totals = CoverageTotals::default();
+        for path in unsearchable {
+            match coverage.as_ref().and_then(|c| c.get(Path::new(path))) {
+                Some(doc_coverage) => {
+                    totals.add(doc_coverage);
+                    eprintln!("  {} ({})", path, doc_coverage.summary());
+                },
+                None => eprintln!("  {}", path),
+            }
+        }
+        if totals.documents > 0 {
+            eprintln!("{}", totals);
+        }
     }
 
     // Create zip file if requested
-    if zip_output && !results.is_empty() {
+    if output.zip && !results.is_empty() {
         let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S").to_string();
         let zip_file_name = format!("search_results_{}.zip", timestamp);
+
+        // Matches inside attachments are zipped as the PDF file that carries them
+        let mut files: Vec<String> = Vec::new();
+        for result in &results {
+            let file = attachments::container_path(result).to_string();
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
         
-        zip_files(&zip_file_name, &results)?;
+        zip_files(&zip_file_name, &files)?;
         println!("Search results have been zipped to: {}", zip_file_name);
     }
 
+    if let Some(export_path) = &output.export_pages {
+        export_hit_pages(&results, search_phrase, include_annotations, output.context, export_path)?;
+    }
+
+    if let Some(highlight_dir) = &output.export_highlights {
+        // Matches inside attachments have no file of their own to annotate
+        let files: Vec<String> = results.iter()
+            .filter(|result| attachments::container_path(result) == result.as_str())
+            .cloned()
+            .collect();
+        let written = highlight::export_highlighted(&files, search_phrase, highlight_dir)
+            .map_err(|e| SearchError::OtherError(e.to_string()))?;
+        println!("Wrote {} highlighted PDF files to '{}'", written, highlight_dir.display());
+    }
+
+    Ok(())
+}
+
+/// Assemble the pages with a hit (plus `context` pages around each) from the matching PDFs into
+/// one PDF, with a bookmark per source file and a child bookmark per page
+fn export_hit_pages(
+    results: &[String],
+    search_phrase: &str,
+    include_annotations: bool,
+    context: usize,
+    export_path: &Path,
+) -> Result<(), SearchError> {
+    let inputs: Vec<PathBuf> = results.iter().map(PathBuf::from).collect();
+    pages::refuse_overwrite(&inputs, export_path).map_err(|e| SearchError::OtherError(e.to_string()))?;
+
+    let mut sources = Vec::new();
+    let mut hit_count = 0;
+    for result in results {
+        // Matches inside attachments have no pages in a file of their own
+        if attachments::container_path(result) != result {
+            eprintln!("Warning: Not exporting pages of attached file '{}'", result);
+            continue;
+        }
+        let doc = match lopdf::Document::load(result) {
+            Ok(doc) if !doc.is_encrypted() => doc,
+            _ => {
+                eprintln!("Warning: Skipping '{}': its pages cannot be copied", result);
+                continue;
+            },
+        };
+
+        let hits = hit_pages(Path::new(result), &doc, search_phrase, include_annotations);
+        if hits.is_empty() {
+            // Matches found only through OCR have no text layer to locate them in
+            eprintln!("Warning: No page of '{}' has a hit in its text layer", result);
+            continue;
+        }
+        let page_count = doc.get_pages().len();
+        let mut selected: Vec<usize> = hits.iter()
+            .flat_map(|&page| page.saturating_sub(context).max(1)..=(page + context).min(page_count))
+            .collect();
+        selected.sort_unstable();
+        selected.dedup();
+        hit_count += hits.len();
+
+        let file_name = Path::new(result).file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| result.clone());
+        let children = selected.iter()
+            .map(|&page| Bookmark {
+                title: if hits.contains(&page) {
+                    format!("{}, page {}", file_name, page)
+                } else {
+                    format!("{}, page {} (context)", file_name, page)
+                },
+                page,
+                children: Vec::new(),
+            })
+            .collect();
+        sources.push(PageSource {
+            doc,
+            bookmarks: vec![Bookmark { title: file_name, page: selected[0], children }],
+            pages: selected,
+        });
+    }
+
+    if sources.is_empty() {
+        println!("No pages to export");
+        return Ok(());
+    }
+
+    let document_count = sources.len();
+    let mut assembled = pages::assemble(sources).map_err(|e| SearchError::PdfError(e.to_string()))?;
+    let page_count = assembled.get_pages().len();
+    assembled.save(export_path)?;
+    println!(
+        "Exported {} pages ({} with hits) from {} PDF files to '{}'",
+        page_count, hit_count, document_count, export_path.display()
+    );
     Ok(())
 }
 
+/// 1-based pages whose text (or, if enabled, annotation comments) contains the phrase
+fn hit_pages(path: &Path, doc: &lopdf::Document, search_phrase: &str, include_annotations: bool) -> Vec<usize> {
+    let mut hits: Vec<usize> = extract_page_texts(path).ok()
+        .map(|pages| {
+            pages.iter()
+                .enumerate()
+                .filter(|(_, text)| text.contains(search_phrase))
+                .map(|(index, _)| index + 1)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if include_annotations {
+        for record in annotations::read_annotations(doc, &path.to_string_lossy()) {
+            let matched = [&record.subject, &record.contents].into_iter()
+                .flatten()
+                .any(|text| text.contains(search_phrase));
+            if matched {
+                hits.push(record.page);
+            }
+        }
+    }
+
+    hits.sort_unstable();
+    hits.dedup();
+    hits
+}
+
+/// Matching files plus the files that had no text layer to search
+#[derive(Default)]
+struct SearchOutcome {
+    matches: Vec<String>,
+    no_text: Vec<String>,
+    /// Searched files with a text layer that did not match
+    unmatched: Vec<String>,
+    /// Outline sections containing the phrase, by matching file
+    sections: HashMap<String, Vec<String>>,
+}
+
+/// OCR the image-only pages of unmatched and text-less PDFs and match the phrase against them.
+/// Returns the text-less PDFs for which OCR produced text.
+fn search_with_ocr(stage: &OcrStage, search_phrase: &str, only_scanned: bool, outcome: &mut SearchOutcome) -> HashSet<String> {
+    let mut candidates = outcome.no_text.clone();
+    if !only_scanned {
+        candidates.extend(outcome.unmatched.iter().cloned());
+    }
+
+    let mut recovered = HashSet::new();
+    if candidates.is_empty() {
+        return recovered;
+    }
+
+    let pb = ProgressBar::new(candidates.len() as u64);
+    pb.set_message("Running OCR");
+
+    for path in candidates {
+        match stage.missing_text(Path::new(&path)) {
+            Ok(text) if !text.is_empty() => {
+                if outcome.no_text.contains(&path) {
+                    recovered.insert(path.clone());
+                }
+                if text.contains(search_phrase) && !outcome.matches.contains(&path) {
+                    outcome.matches.push(path);
+                }
+            },
+            Ok(_) => {},
+            Err(e) => eprintln!("OCR failed for {}: {}", path, e),
+        }
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("OCR done");
+    recovered
+}
+
 /// Search for PDF files containing the given phrase
-fn search_pdf_files(search_phrase: &str, directories: &[PathBuf]) -> Result<Vec<String>, SearchError> {
-    // Using Arc<Mutex<Vec<String>>> to safely share results between threads
-    let results: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+fn search_pdf_files(
+    search_phrase: &str,
+    directories: &[PathBuf],
+    only_scanned: bool,
+    include_attachments: bool,
+    include_annotations: bool,
+) -> Result<SearchOutcome, SearchError> {
+    // Using Arc<Mutex<SearchOutcome>> to safely share results between threads
+    let outcome: Arc<Mutex<SearchOutcome>> = Arc::new(Mutex::new(SearchOutcome::default()));
     let mut handles = Vec::new();
 
     for directory in directories {
-        let results_clone = results.clone();
+        let outcome_clone = outcome.clone();
         let search_phrase_clone = search_phrase.to_string();
         let directory_clone = directory.clone();
 
         handles.push(thread::spawn(move || {
             println!("Searching in: {}", directory_clone.display());
-            search_directory(&directory_clone, &search_phrase_clone, results_clone);
+            search_directory(
+                &directory_clone,
+                &search_phrase_clone,
+                only_scanned,
+                include_attachments,
+                include_annotations,
+                outcome_clone,
+            );
         }));
     }
 
@@ -113,14 +392,21 @@ fn search_pdf_files(search_phrase: &str, directories: &[PathBuf]) -> Result<Vec<
     }
 
     // Return the final results
-    let locked_results = results.lock()
+    let mut locked_outcome = outcome.lock()
         .map_err(|_| SearchError::OtherError("Failed to lock results".to_string()))?;
     
-    Ok(locked_results.clone())
+    Ok(std::mem::take(&mut *locked_outcome))
 }
 
 /// Search for PDFs in a single directory
-fn search_directory(dir: &PathBuf, search_phrase: &str, results: Arc<Mutex<Vec<String>>>) {
+fn search_directory(
+    dir: &PathBuf,
+    search_phrase: &str,
+    only_scanned: bool,
+    include_attachments: bool,
+    include_annotations: bool,
+    outcome: Arc<Mutex<SearchOutcome>>,
+) {
     for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
         let path = entry.path();
 
@@ -130,37 +416,105 @@ fn search_directory(dir: &PathBuf, search_phrase: &str, results: Arc<Mutex<Vec<S
                     let path_str = path.to_string_lossy().into_owned();
                     
                     // If no search phrase specified, include all PDFs
-                    if search_phrase.is_empty() {
-                        let mut locked_results = results.lock().unwrap();
-                        locked_results.push(path_str);
+                    if search_phrase.is_empty() && !only_scanned {
+                        let mut locked_outcome = outcome.lock().unwrap();
+                        locked_outcome.matches.push(path_str);
                         continue;
                     }
 
                     // Check if PDF contains the search phrase
-                    match search_phrase_in_pdf(path, search_phrase) {
-                        Ok(true) => {
-                            let mut locked_results = results.lock().unwrap();
-                            locked_results.push(path_str);
+                    match extract_page_texts(path) {
+                        Ok(pages) => {
+                            let text = pages.concat();
+                            let searchable = coverage::has_text_layer(&text);
+                            // Comments and notes count as part of the document
+                            let matched = text.contains(search_phrase)
+                                || (include_annotations && annotations::annotation_text(path).contains(search_phrase));
+                            let sections = if matched && !search_phrase.is_empty() && text.contains(search_phrase) {
+                                hit_sections(path, &pages, search_phrase)
+                            } else {
+                                Vec::new()
+                            };
+                            let mut locked_outcome = outcome.lock().unwrap();
+                            if !sections.is_empty() {
+                                locked_outcome.sections.insert(path_str.clone(), sections);
+                            }
+                            if !searchable {
+                                locked_outcome.no_text.push(path_str.clone());
+                            }
+                            // With --only-scanned, documents with a text layer are skipped
+                            if only_scanned && searchable {
+                                continue;
+                            }
+                            if matched {
+                                locked_outcome.matches.push(path_str);
+                            } else if searchable {
+                                locked_outcome.unmatched.push(path_str);
+                            }
                         },
-                        Ok(false) => {}, // Phrase not found
                         Err(e) => eprintln!("Error processing {}: {}", path.display(), e),
                     }
+
+                    // Search the PDFs attached to this one, reported as `file.pdf::attached.pdf`
+                    if include_attachments && !only_scanned {
+                        for (name, text) in attachments::attached_pdf_texts(path) {
+                            if text.contains(search_phrase) {
+                                outcome.lock().unwrap().matches.push(name);
+                            }
+                        }
+                    }
                 }
             }
         }
     }
 }
 
-/// Check if a PDF file contains the search phrase
-fn search_phrase_in_pdf(file_path: &Path, search_phrase: &str) -> Result<bool, SearchError> {
+/// Describe the outline sections whose pages contain the phrase, e.g.
+/// `2 Methods > 2.1 Sampling (p. 4, 5)`. Empty for documents without bookmarks.
+fn hit_sections(path: &Path, pages: &[String], search_phrase: &str) -> Vec<String> {
+    let entries = match lopdf::Document::load(path) {
+        Ok(doc) => outline::read_outline(&doc),
+        Err(_) => return Vec::new(),
+    };
+    if entries.is_empty() {
+        return Vec::new();
+    }
+
+    // Report the most specific section of every page with a hit
+    let sections = outline::sections(&entries, usize::MAX, pages.len());
+    let mut hits: Vec<(String, Vec<usize>)> = Vec::new();
+    for (index, page_text) in pages.iter().enumerate() {
+        if !page_text.contains(search_phrase) {
+            continue;
+        }
+        let page = index + 1;
+        let heading = outline::section_for_page(&sections, page)
+            .map(|section| section.heading())
+            .unwrap_or_default();
+        match hits.last_mut() {
+            Some((last, pages)) if *last == heading => pages.push(page),
+            _ => hits.push((heading, vec![page])),
+        }
+    }
+
+    hits.into_iter()
+        .map(|(heading, pages)| {
+            let pages: Vec<String> = pages.iter().map(|p| p.to_string()).collect();
+            format!("{} (p. {})", heading, pages.join(", "))
+        })
+        .collect()
+}
+
+/// Extract the text of each page of a PDF file for searching
+fn extract_page_texts(file_path: &Path) -> Result<Vec<String>, SearchError> {
     let bytes = std::fs::read(file_path)?;
-    
-    let text = pdf_extract::extract_text_from_mem(&bytes)
+
+    // pdf-extract panics on some malformed files
+    panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem_by_pages(&bytes)))
+        .map_err(|_| SearchError::PdfError(format!("Text extraction crashed on {}", file_path.display())))?
         .map_err(|e| SearchError::PdfError(
             format!("Error extracting text from {}: {}", file_path.display(), e)
-        ))?;
-
-    Ok(text.contains(search_phrase))
+        ))
 }
 
 /// Create a zip file containing the specified PDF files
@@ -191,4 +545,60 @@ pub fn zip_files(zip_file_name: &str, file_paths: &[String]) -> Result<(), Searc
 
     zip.finish()?;
     Ok(())
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    /// Two pages: the phrase is in the text of page 1 and in a note on page 2
+    fn document_with_hits() -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let content_id = doc.add_object(Stream::new(dictionary! {}, b"BT /F1 12 Tf 72 700 Td (the needle is here) Tj ET".to_vec()));
+        let first = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+        });
+        let note = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "Rect" => vec![10.into(), 10.into(), 30.into(), 30.into()],
+            "Contents" => Object::string_literal("another needle"),
+        };
+        let second = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Annots" => vec![Object::Dictionary(note)],
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![first.into(), second.into()],
+            "Count" => 2,
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    #[test]
+    fn hit_pages_include_annotations_on_request() {
+        let path = std::env::temp_dir().join(format!("pdfscan-search-{}.pdf", std::process::id()));
+        let mut doc = document_with_hits();
+        doc.save(&path).unwrap();
+
+        assert_eq!(hit_pages(&path, &doc, "needle", false), vec![1]);
+        assert_eq!(hit_pages(&path, &doc, "needle", true), vec![1, 2]);
+        assert!(hit_pages(&path, &doc, "haystack", true).is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
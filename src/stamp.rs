@@ -0,0 +1,514 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::fonts::PdfFont;
+use crate::pages::{self, PageRange};
+use crate::utils;
+
+/// Resource names used for the stamp; unusual enough not to clash with a page's own
+const FONT_NAME: &str = "PdfscanStampFont";
+const STATE_NAME: &str = "PdfscanStampState";
+const IMAGE_NAME: &str = "PdfscanStampImage";
+
+/// Custom error type for stamp operations
+#[derive(Debug)]
+pub enum StampError {
+    Io(io::Error),
+    Pdf(lopdf::Error),
+    Image(image::ImageError),
+    Other(String),
+}
+
+impl fmt::Display for StampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StampError::Io(e) => write!(f, "I/O error: {}", e),
+            StampError::Pdf(e) => write!(f, "PDF error: {}", e),
+            StampError::Image(e) => write!(f, "Image error: {}", e),
+            StampError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for StampError {}
+
+impl From<io::Error> for StampError {
+    fn from(err: io::Error) -> Self {
+        StampError::Io(err)
+    }
+}
+
+impl From<lopdf::Error> for StampError {
+    fn from(err: lopdf::Error) -> Self {
+        StampError::Pdf(err)
+    }
+}
+
+impl From<image::ImageError> for StampError {
+    fn from(err: image::ImageError) -> Self {
+        StampError::Image(err)
+    }
+}
+
+/// Where a stamp is placed on the page as displayed
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Position {
+    Center,
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+/// An RGB colour given as `RRGGBB` or `#RRGGBB`
+#[derive(Debug, Clone, Copy)]
+pub struct Color([f32; 3]);
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid colour '{}', expected RRGGBB", s));
+        }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map(|v| v as f32 / 255.0).unwrap_or(0.0);
+        Ok(Color([component(0), component(2), component(4)]))
+    }
+}
+
+/// The watermark to overlay on the selected pages
+#[derive(Debug, Clone)]
+pub struct Watermark {
+    pub text: Option<String>,
+    pub image: Option<String>,
+    pub position: Position,
+    /// 0 (invisible) to 1 (opaque)
+    pub opacity: f32,
+    /// Counter-clockwise, in degrees
+    pub rotation: f32,
+    pub font_size: f32,
+    pub color: Color,
+    /// Image width in points; a third of the page width when not given
+    pub image_width: Option<f32>,
+    /// Pages to stamp (all pages when empty)
+    pub pages: Vec<PageRange>,
+}
+
+/// Sequential Bates numbers (`PREFIX000001`) over all pages of all documents
+#[derive(Debug, Clone)]
+pub struct Bates {
+    pub prefix: String,
+    pub start: u64,
+    pub digits: usize,
+    pub position: Position,
+    pub font_size: f32,
+}
+
+/// A decoded image, ready to embed
+struct StampImage {
+    width: u32,
+    height: u32,
+    rgb: Vec<u8>,
+    alpha: Option<Vec<u8>>,
+}
+
+/// Main function of the stamp command
+pub fn run(
+    input_paths: &[String],
+    output: &str,
+    watermark: Option<&Watermark>,
+    bates: Option<&Bates>,
+    margin: f32,
+) -> Result<(), Box<dyn Error>> {
+    if watermark.is_none() && bates.is_none() {
+        return Err(Box::new(StampError::Other(
+            "Nothing to stamp: give --text, --image or --bates".to_string()
+        )));
+    }
+    if let Some(watermark) = watermark {
+        if !(0.0..=1.0).contains(&watermark.opacity) {
+            return Err(Box::new(StampError::Other("The opacity must be between 0 and 1".to_string())));
+        }
+    }
+
+    // Bates numbers run on in input order, so the files are stamped in that order
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+    if pdf_paths.is_empty() {
+        return Err(Box::new(StampError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    let image = match watermark.and_then(|w| w.image.as_deref()) {
+        Some(path) => Some(load_image(Path::new(path))?),
+        None => None,
+    };
+
+    // A single PDF is written to the output file, several into the output directory
+    let output = PathBuf::from(output);
+    let single_file = pdf_paths.len() == 1 && !output.is_dir();
+    if !single_file {
+        std::fs::create_dir_all(&output)?;
+    }
+    // Same-named inputs get numbered names, so no stamped copy (and its printed Bates range)
+    // is overwritten by a later one
+    let targets: Vec<(PathBuf, PathBuf)> = pdf_paths.iter()
+        .zip(utils::unique_stems(&pdf_paths))
+        .map(|(path, stem)| {
+            let target = if single_file { output.clone() } else { output.join(format!("{}.pdf", stem)) };
+            (path.clone(), target)
+        })
+        .collect();
+    // A target may not replace any input, as later files would then be stamped twice
+    for (_, target) in &targets {
+        pages::refuse_overwrite(&pdf_paths, target)?;
+    }
+
+    let pb = ProgressBar::new(targets.len() as u64);
+    pb.set_message("Stamping");
+
+    let mut next_number = bates.map(|b| b.start).unwrap_or(0);
+    let mut stamped = 0;
+    for (source, target) in &targets {
+        match stamp_file(source, target, watermark, image.as_ref(), bates, next_number, margin) {
+            Ok(page_count) => {
+                if let Some(bates) = bates {
+                    if page_count > 0 {
+                        println!(
+                            "{}: {} - {}",
+                            target.display(),
+                            bates_number(bates, next_number),
+                            bates_number(bates, next_number + page_count as u64 - 1)
+                        );
+                    }
+                    next_number += page_count as u64;
+                }
+                stamped += 1;
+            },
+            Err(e) => eprintln!("Error stamping {}: {}", source.display(), e),
+        }
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("Done");
+    println!("Stamped {} of {} PDF files", stamped, targets.len());
+    if let Some(bates) = bates {
+        if next_number > bates.start {
+            println!("Bates numbers {} to {}", bates_number(bates, bates.start), bates_number(bates, next_number - 1));
+        }
+    }
+
+    if stamped < targets.len() {
+        return Err(Box::new(StampError::Other(format!(
+            "{} PDFs could not be stamped", targets.len() - stamped
+        ))));
+    }
+    Ok(())
+}
+
+fn bates_number(bates: &Bates, number: u64) -> String {
+    format!("{}{:0width$}", bates.prefix, number, width = bates.digits)
+}
+
+fn load_image(path: &Path) -> Result<StampImage, StampError> {
+    let rgba = image::open(path)?.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+    let mut alpha = Vec::with_capacity((width * height) as usize);
+    for pixel in rgba.pixels() {
+        rgb.extend_from_slice(&pixel.0[..3]);
+        alpha.push(pixel.0[3]);
+    }
+    let alpha = if alpha.iter().all(|&a| a == 255) { None } else { Some(alpha) };
+    Ok(StampImage { width, height, rgb, alpha })
+}
+
+/// Stamp one document. Returns its page count.
+fn stamp_file(
+    source: &Path,
+    target: &Path,
+    watermark: Option<&Watermark>,
+    image: Option<&StampImage>,
+    bates: Option<&Bates>,
+    first_number: u64,
+    margin: f32,
+) -> Result<usize, StampError> {
+    let mut doc = Document::load(source)?;
+    if doc.is_encrypted() {
+        return Err(StampError::Other("Encrypted PDFs cannot be stamped".to_string()));
+    }
+
+    let font_dict = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    };
+    let font = PdfFont::load(&doc, &font_dict);
+    let font_id = doc.add_object(font_dict);
+    let state_id = doc.add_object(dictionary! {
+        "Type" => "ExtGState",
+        "ca" => watermark.map(|w| w.opacity).unwrap_or(1.0),
+        "CA" => watermark.map(|w| w.opacity).unwrap_or(1.0),
+    });
+    let image_id = image.map(|image| add_image(&mut doc, image));
+
+    let page_ids: Vec<(u32, ObjectId)> = doc.get_pages().into_iter().collect();
+    let page_count = page_ids.len();
+    for (index, (number, page_id)) in page_ids.into_iter().enumerate() {
+        let (area, rotate) = displayed_area(&doc, page_id);
+        let (width, height) = if rotate % 180 == 0 { (area[2] - area[0], area[3] - area[1]) } else { (area[3] - area[1], area[2] - area[0]) };
+
+        let mut content = String::new();
+        let mut uses_image = false;
+        if let Some(watermark) = watermark.filter(|w| w.pages.is_empty() || w.pages.iter().any(|r| r.contains(number as usize, page_count))) {
+            if let Some(text) = &watermark.text {
+                let (encoded, text_width) = encode_text(&font, text, watermark.font_size);
+                let box_size = (text_width, watermark.font_size);
+                let (x, y) = anchor(watermark.position, box_size, (width, height), margin);
+                content.push_str(&format!(
+                    "q /{} gs {} {} {} rg\n{} cm\nBT /{} {} Tf {:.2} {:.2} Td {} Tj ET\nQ\n",
+                    STATE_NAME, watermark.color.0[0], watermark.color.0[1], watermark.color.0[2],
+                    placement(x, y, watermark.rotation), FONT_NAME, watermark.font_size,
+                    -text_width / 2.0, -watermark.font_size * 0.35, encoded
+                ));
+            }
+            if let Some(image) = image {
+                let image_width = watermark.image_width.unwrap_or(width / 3.0);
+                let image_height = image_width * image.height as f32 / image.width.max(1) as f32;
+                let (x, y) = anchor(watermark.position, (image_width, image_height), (width, height), margin);
+                content.push_str(&format!(
+                    "q /{} gs\n{} cm\n{:.2} 0 0 {:.2} {:.2} {:.2} cm /{} Do\nQ\n",
+                    STATE_NAME, placement(x, y, watermark.rotation),
+                    image_width, image_height, -image_width / 2.0, -image_height / 2.0, IMAGE_NAME
+                ));
+                uses_image = true;
+            }
+        }
+        if let Some(bates) = bates {
+            let label = bates_number(bates, first_number + index as u64);
+            let (encoded, text_width) = encode_text(&font, &label, bates.font_size);
+            let (x, y) = anchor(bates.position, (text_width, bates.font_size), (width, height), margin);
+            content.push_str(&format!(
+                "q 0 g BT /{} {} Tf {:.2} {:.2} Td {} Tj ET Q\n",
+                FONT_NAME, bates.font_size, x - text_width / 2.0, y - bates.font_size * 0.35, encoded
+            ));
+        }
+        if content.is_empty() {
+            continue;
+        }
+
+        // Map the displayed page (origin bottom left, after /Rotate) onto user space
+        let content = format!("q {} cm\n{}Q\n", display_matrix(area, rotate), content);
+        add_resources(&mut doc, page_id, font_id, state_id, image_id.filter(|_| uses_image))?;
+        wrap_contents(&mut doc, page_id, content.into_bytes())?;
+    }
+
+    doc.compress();
+    doc.save(target)?;
+    Ok(page_count)
+}
+
+/// The visible box of a page (crop box, else media box) and its rotation in degrees
+fn displayed_area(doc: &Document, page_id: ObjectId) -> ([f32; 4], i64) {
+    let crop_box = utils::inherited_page_attribute(doc, page_id, b"CropBox")
+        .and_then(|value| value.as_array().ok())
+        .filter(|array| array.len() == 4)
+        .and_then(|array| {
+            let values: Vec<f32> = array.iter()
+                .filter_map(|v| doc.dereference(v).ok().and_then(|(_, v)| v.as_float().ok()))
+                .collect();
+            <[f32; 4]>::try_from(values).ok()
+        });
+    let area = crop_box.or_else(|| utils::page_media_box(doc, page_id)).unwrap_or([0.0, 0.0, 612.0, 792.0]);
+    let area = [area[0].min(area[2]), area[1].min(area[3]), area[0].max(area[2]), area[1].max(area[3])];
+    let rotate = utils::inherited_page_attribute(doc, page_id, b"Rotate")
+        .and_then(|value| value.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360);
+    (area, rotate - rotate % 90)
+}
+
+/// Matrix from displayed-page coordinates to user space for a page box and rotation
+fn display_matrix(area: [f32; 4], rotate: i64) -> String {
+    let [x0, y0, x1, y1] = area;
+    match rotate {
+        90 => format!("0 1 -1 0 {:.2} {:.2}", x1, y0),
+        180 => format!("-1 0 0 -1 {:.2} {:.2}", x1, y1),
+        270 => format!("0 -1 1 0 {:.2} {:.2}", x0, y1),
+        _ => format!("1 0 0 1 {:.2} {:.2}", x0, y0),
+    }
+}
+
+/// Centre of a box of the given size placed at a position of the displayed page
+fn anchor(position: Position, size: (f32, f32), page: (f32, f32), margin: f32) -> (f32, f32) {
+    let (width, height) = size;
+    let (page_width, page_height) = page;
+    let left = margin + width / 2.0;
+    let right = page_width - margin - width / 2.0;
+    let bottom = margin + height / 2.0;
+    let top = page_height - margin - height / 2.0;
+    match position {
+        Position::Center => (page_width / 2.0, page_height / 2.0),
+        Position::TopLeft => (left, top),
+        Position::TopCenter => (page_width / 2.0, top),
+        Position::TopRight => (right, top),
+        Position::BottomLeft => (left, bottom),
+        Position::BottomCenter => (page_width / 2.0, bottom),
+        Position::BottomRight => (right, bottom),
+    }
+}
+
+/// Matrix that moves the origin to (x, y) and rotates around it
+fn placement(x: f32, y: f32, rotation: f32) -> String {
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    format!("{:.4} {:.4} {:.4} {:.4} {:.2} {:.2}", cos, sin, -sin, cos, x, y)
+}
+
+/// Encode text for Helvetica with WinAnsiEncoding as a PDF string, with its width at the size
+fn encode_text(font: &PdfFont, text: &str, size: f32) -> (String, f32) {
+    let bytes: Vec<u8> = text.chars()
+        .map(|c| if (c as u32) < 0x80 || (0xA0..0x100).contains(&(c as u32)) { c as u8 } else { b'?' })
+        .collect();
+    let width: f32 = bytes.iter().map(|&b| font.width(b as u32)).sum::<f32>() * size;
+
+    let mut encoded = String::from("(");
+    for &b in &bytes {
+        match b {
+            b'(' | b')' | b'\\' => {
+                encoded.push('\\');
+                encoded.push(b as char);
+            },
+            0x20..=0x7E => encoded.push(b as char),
+            _ => encoded.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    encoded.push(')');
+    (encoded, width)
+}
+
+/// Embed an image (with its transparency as a soft mask) as an image XObject
+fn add_image(doc: &mut Document, image: &StampImage) -> ObjectId {
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => image.width as i64,
+        "Height" => image.height as i64,
+        "ColorSpace" => "DeviceRGB",
+        "BitsPerComponent" => 8,
+    };
+    if let Some(alpha) = &image.alpha {
+        let mut mask = Stream::new(dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => image.width as i64,
+            "Height" => image.height as i64,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        }, alpha.clone());
+        let _ = mask.compress();
+        dict.set("SMask", doc.add_object(mask));
+    }
+    let mut stream = Stream::new(dict, image.rgb.clone());
+    let _ = stream.compress();
+    doc.add_object(stream)
+}
+
+/// Give the page its own resources (copied from the inherited ones) with the stamp's font,
+/// graphics state and image added
+fn add_resources(doc: &mut Document, page_id: ObjectId, font_id: ObjectId, state_id: ObjectId, image_id: Option<ObjectId>) -> Result<(), StampError> {
+    let mut resources = utils::inherited_page_attribute(doc, page_id, b"Resources")
+        .and_then(|resources| resources.as_dict().ok())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut entries: Vec<(&[u8], &str, ObjectId)> = vec![(b"Font", FONT_NAME, font_id), (b"ExtGState", STATE_NAME, state_id)];
+    if let Some(image_id) = image_id {
+        entries.push((b"XObject", IMAGE_NAME, image_id));
+    }
+    for (category, name, id) in entries {
+        // Shared sub-dictionaries are copied, so other pages keep theirs unchanged
+        let mut dict: Dictionary = resources.get(category).ok()
+            .and_then(|value| doc.dereference(value).ok())
+            .and_then(|(_, value)| value.as_dict().ok())
+            .cloned()
+            .unwrap_or_default();
+        dict.set(name, id);
+        resources.set(category, dict);
+    }
+
+    doc.get_dictionary_mut(page_id)?.set("Resources", resources);
+    Ok(())
+}
+
+/// Enclose the existing page content in q/Q and draw the stamp after it
+fn wrap_contents(doc: &mut Document, page_id: ObjectId, stamp: Vec<u8>) -> Result<(), StampError> {
+    let existing: Vec<Object> = match doc.get_dictionary(page_id)?.get(b"Contents") {
+        Ok(Object::Reference(id)) => match doc.get_object(*id) {
+            Ok(Object::Array(array)) => array.clone(),
+            _ => vec![Object::Reference(*id)],
+        },
+        Ok(Object::Array(array)) => array.clone(),
+        _ => Vec::new(),
+    };
+
+    let save = doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+    let mut restore_and_stamp = b"\nQ\n".to_vec();
+    restore_and_stamp.extend(stamp);
+    let stamp = doc.add_object(Stream::new(Dictionary::new(), restore_and_stamp));
+
+    let mut contents = vec![Object::Reference(save)];
+    contents.extend(existing);
+    contents.push(Object::Reference(stamp));
+    doc.get_dictionary_mut(page_id)?.set("Contents", contents);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bates(prefix: &str, digits: usize) -> Bates {
+        Bates { prefix: prefix.to_string(), start: 1, digits, position: Position::BottomRight, font_size: 9.0 }
+    }
+
+    #[test]
+    fn pads_bates_numbers() {
+        assert_eq!(bates_number(&bates("ACME", 6), 42), "ACME000042");
+        assert_eq!(bates_number(&bates("", 3), 1234), "1234");
+    }
+
+    #[test]
+    fn parses_colors() {
+        let Color(rgb) = "#FF8000".parse().unwrap();
+        assert_eq!(rgb, [1.0, 128.0 / 255.0, 0.0]);
+        assert!("ff80".parse::<Color>().is_err());
+        assert!("gg0000".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn anchors_boxes_inside_the_margins() {
+        let page = (600.0, 800.0);
+        assert_eq!(anchor(Position::Center, (100.0, 20.0), page, 36.0), (300.0, 400.0));
+        assert_eq!(anchor(Position::TopLeft, (100.0, 20.0), page, 36.0), (86.0, 754.0));
+        assert_eq!(anchor(Position::BottomRight, (100.0, 20.0), page, 36.0), (514.0, 46.0));
+    }
+
+    #[test]
+    fn maps_displayed_coordinates_for_rotated_pages() {
+        let area = [10.0, 20.0, 610.0, 820.0];
+        assert_eq!(display_matrix(area, 0), "1 0 0 1 10.00 20.00");
+        assert_eq!(display_matrix(area, 90), "0 1 -1 0 610.00 20.00");
+        assert_eq!(display_matrix(area, 180), "-1 0 0 -1 610.00 820.00");
+        assert_eq!(display_matrix(area, 270), "0 -1 1 0 10.00 820.00");
+    }
+}
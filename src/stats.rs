@@ -7,6 +7,9 @@ use rayon::prelude::*;
 use walkdir::WalkDir;
 use indicatif::ProgressBar;
 
+use crate::attachments;
+use crate::ocr::{OcrArgs, OcrStage};
+
 /// Custom error type for statistical analysis operations
 #[derive(Debug)]
 pub enum StatsError {
@@ -170,6 +173,8 @@ pub fn run(
     keywords: &[String],
     output_file: &str,
     correlation_threshold: f64,
+    include_attachments: bool,
+    ocr: &OcrArgs,
 ) -> Result<(), Box<dyn Error>> {
     if keywords.is_empty() {
         return Err(Box::new(StatsError::OtherError(
@@ -220,6 +225,17 @@ pub fn run(
         .collect();
     
     pb.finish_with_message("Analysis complete");
+
+    // Count keywords in the OCR text of image-only pages as well
+    let mut documents = documents;
+    if let Some(stage) = OcrStage::from_args(ocr) {
+        add_ocr_counts(&stage, &pdf_paths, &mut documents, keywords);
+    }
+
+    // PDFs attached to the analyzed files count as documents of their own
+    if include_attachments {
+        documents.extend(attachment_documents(&pdf_paths, keywords));
+    }
     
     // Update analysis with documents
     analysis.documents = documents;
@@ -259,6 +275,53 @@ pub fn run(
     Ok(())
 }
 
+/// Add keyword counts found in the OCR text of each document's image-only pages
+fn add_ocr_counts(stage: &OcrStage, pdf_paths: &[PathBuf], documents: &mut [Document], keywords: &[String]) {
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Running OCR");
+
+    // Documents are collected in the same order as their paths
+    for (path, doc) in pdf_paths.iter().zip(documents.iter_mut()) {
+        match stage.missing_text(path) {
+            Ok(text) if !text.is_empty() => {
+                for keyword in keywords {
+                    *doc.keyword_counts.entry(keyword.to_string()).or_insert(0) += text.matches(keyword.as_str()).count();
+                }
+            },
+            Ok(_) => {},
+            Err(e) => eprintln!("OCR failed for {}: {}", doc.filename, e),
+        }
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("OCR done");
+}
+
+/// Build documents for the PDFs attached to the given files, named `file.pdf::attached.pdf`
+fn attachment_documents(pdf_paths: &[PathBuf], keywords: &[String]) -> Vec<Document> {
+    pdf_paths.par_iter()
+        .flat_map(|path| attachments::attached_pdf_texts(path))
+        .map(|(name, text)| {
+            // Keep the file name of the container, like the other documents in the report
+            let container = attachments::container_path(&name);
+            let filename = format!(
+                "{}{}",
+                Path::new(container).file_name().unwrap_or_default().to_string_lossy(),
+                &name[container.len()..]
+            );
+            let keyword_counts = keywords.iter()
+                .map(|keyword| (keyword.to_string(), text.matches(keyword.as_str()).count()))
+                .collect();
+
+            Document {
+                filename,
+                keyword_counts,
+                correlation_score: 0.0,
+            }
+        })
+        .collect()
+}
+
 /// Collect all PDF file paths from the provided input paths
 fn collect_pdf_paths(input_paths: &[String]) -> Result<Vec<PathBuf>, StatsError> {
     let mut pdf_paths: Vec<PathBuf> = Vec::new();
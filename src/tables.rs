@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use pdfium_render::prelude::*;
+use serde::Serialize;
+
+use crate::layout::{self, PageLayout, TextLine};
+use crate::utils;
+
+/// Maximum thickness, in points, of a path object treated as a ruling line
+const MAX_RULING_THICKNESS: f32 = 2.0;
+
+/// Minimum length, in points, of a ruling line
+const MIN_RULING_LENGTH: f32 = 10.0;
+
+/// Distance, in points, under which ruling lines are considered touching or equal
+const RULING_TOLERANCE: f32 = 3.0;
+
+/// Minimum number of rows for a table found from text alignment alone
+const MIN_TEXT_TABLE_ROWS: usize = 3;
+
+/// Tables cells are short; fragments longer than this (in words, median) are prose columns
+const MAX_CELL_WORDS: usize = 4;
+
+/// Custom error type for table extraction
+#[derive(Debug)]
+pub enum TableError {
+    Io(io::Error),
+    Pdf(String),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Other(String),
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Io(e) => write!(f, "I/O error: {}", e),
+            TableError::Pdf(e) => write!(f, "PDF error: {}", e),
+            TableError::Csv(e) => write!(f, "CSV error: {}", e),
+            TableError::Json(e) => write!(f, "JSON error: {}", e),
+            TableError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for TableError {}
+
+impl From<io::Error> for TableError {
+    fn from(err: io::Error) -> Self {
+        TableError::Io(err)
+    }
+}
+
+impl From<csv::Error> for TableError {
+    fn from(err: csv::Error) -> Self {
+        TableError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for TableError {
+    fn from(err: serde_json::Error) -> Self {
+        TableError::Json(err)
+    }
+}
+
+/// Output formats supported by the tables command
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TableFormat {
+    Csv,
+    Json,
+}
+
+/// Bounding box in PDF points (origin bottom-left)
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct BoundingBox {
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32,
+    pub top: f32,
+}
+
+impl BoundingBox {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.left && x <= self.right && y >= self.bottom && y <= self.top
+    }
+
+    fn grow(&mut self, other: &BoundingBox) {
+        self.left = self.left.min(other.left);
+        self.bottom = self.bottom.min(other.bottom);
+        self.right = self.right.max(other.right);
+        self.top = self.top.max(other.top);
+    }
+
+    fn touches(&self, other: &BoundingBox) -> bool {
+        self.left - RULING_TOLERANCE <= other.right
+            && other.left - RULING_TOLERANCE <= self.right
+            && self.bottom - RULING_TOLERANCE <= other.top
+            && other.bottom - RULING_TOLERANCE <= self.top
+    }
+}
+
+/// A table detected on a page
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectedTable {
+    pub bbox: BoundingBox,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl DetectedTable {
+    /// Number of columns of the widest row
+    pub fn columns(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Format the table with the given field delimiter (`b','` for CSV, `b'\t'` for spreadsheets)
+    pub fn to_delimited(&self, delimiter: u8) -> String {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(Vec::new());
+        for row in &self.rows {
+            writer.write_record(row).ok();
+        }
+        writer.into_inner()
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// A detected table together with where it was found
+#[derive(Debug, Serialize)]
+pub struct TableRecord {
+    pub path: String,
+    pub page: usize,
+    pub table: usize,
+    pub bbox: BoundingBox,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Ruling lines of a page, split by orientation
+#[derive(Debug, Default)]
+struct Rulings {
+    horizontal: Vec<BoundingBox>,
+    vertical: Vec<BoundingBox>,
+}
+
+/// Main function to run the table extraction functionality
+/// Without an explicit format, tables are written as CSV files when an output directory is
+/// given and as JSON on stdout otherwise.
+pub fn run(input_paths: &[String], format: Option<TableFormat>, output: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let pdf_paths = utils::collect_pdf_paths(input_paths);
+
+    if pdf_paths.is_empty() {
+        return Err(Box::new(TableError::Other(
+            "No PDF files found in the provided paths".to_string()
+        )));
+    }
+
+    let format = format.unwrap_or(if output.is_some() { TableFormat::Csv } else { TableFormat::Json });
+    if format == TableFormat::Csv && output.is_none() {
+        return Err(Box::new(TableError::Other(
+            "CSV output requires an output directory".to_string()
+        )));
+    }
+
+    let pdfium = utils::bind_pdfium().map_err(TableError::Other)?;
+
+    // Pdfium is not thread-safe, so documents are processed one after another
+    let pb = ProgressBar::new(pdf_paths.len() as u64);
+    pb.set_message("Detecting tables");
+
+    let mut records = Vec::new();
+    for path in &pdf_paths {
+        match extract_tables(&pdfium, path) {
+            Ok(mut tables) => records.append(&mut tables),
+            Err(e) => eprintln!("Error processing {}: {}", path.display(), e),
+        }
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("Done");
+
+    match format {
+        TableFormat::Csv => {
+            let stems: HashMap<String, String> = pdf_paths.iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .zip(utils::unique_stems(&pdf_paths))
+                .collect();
+            write_csv(&records, &stems, Path::new(output.unwrap_or_default()))?
+        },
+        TableFormat::Json => write_json(&records, output)?,
+    }
+
+    if let Some(output) = output {
+        println!("Successfully wrote {} tables from {} PDFs to '{}'", records.len(), pdf_paths.len(), output);
+    }
+    Ok(())
+}
+
+/// Detect the tables on every page of a PDF
+pub fn extract_tables(pdfium: &Pdfium, path: &Path) -> Result<Vec<TableRecord>, TableError> {
+    let document = pdfium.load_pdf_from_file(path, None)
+        .map_err(|e| TableError::Pdf(format!("Error loading {}: {}", path.display(), e)))?;
+
+    let mut records = Vec::new();
+    for (index, page) in document.pages().iter().enumerate() {
+        for (table_index, table) in detect_tables(&page).into_iter().enumerate() {
+            records.push(TableRecord {
+                path: path.to_string_lossy().into_owned(),
+                page: index + 1,
+                table: table_index + 1,
+                bbox: table.bbox,
+                rows: table.rows,
+            });
+        }
+    }
+    Ok(records)
+}
+
+/// Detect the tables on a page, top to bottom.
+/// Ruled tables are found from path objects; the remaining text is scanned for aligned columns.
+pub fn detect_tables(page: &PdfPage) -> Vec<DetectedTable> {
+    let layout = layout::page_layout(page);
+    let rulings = page_rulings(page);
+
+    let mut tables = ruled_tables(&layout, &rulings);
+
+    // Lines inside a ruled table are not considered again
+    let remaining: Vec<&TextLine> = layout.lines.iter()
+        .filter(|line| !tables.iter().any(|t| t.bbox.contains(line_center_x(line), line_center_y(line))))
+        .collect();
+    tables.extend(text_tables(&layout, &remaining));
+
+    tables.sort_by(|a, b| b.bbox.top.partial_cmp(&a.bbox.top).unwrap_or(std::cmp::Ordering::Equal));
+    tables
+}
+
+/// Collect thin path objects (and the edges of stroked rectangles) as ruling lines
+fn page_rulings(page: &PdfPage) -> Rulings {
+    let mut rulings = Rulings::default();
+    let page_width = page.width().value;
+    let page_height = page.height().value;
+
+    for object in page.objects().iter() {
+        let path = match object.as_path_object() {
+            Some(path) => path,
+            None => continue,
+        };
+        let bounds = match object.bounds() {
+            Ok(bounds) => BoundingBox {
+                left: bounds.left().value,
+                bottom: bounds.bottom().value,
+                right: bounds.right().value,
+                top: bounds.top().value,
+            },
+            Err(_) => continue,
+        };
+        let width = bounds.right - bounds.left;
+        let height = bounds.top - bounds.bottom;
+
+        if height <= MAX_RULING_THICKNESS && width >= MIN_RULING_LENGTH {
+            rulings.horizontal.push(bounds);
+        } else if width <= MAX_RULING_THICKNESS && height >= MIN_RULING_LENGTH {
+            rulings.vertical.push(bounds);
+        } else if path.is_stroked().unwrap_or(false)
+            && width < page_width * 0.9
+            && height < page_height * 0.9
+            && width >= MIN_RULING_LENGTH
+            && height >= MIN_RULING_LENGTH
+        {
+            // A stroked rectangle is a cell (or table) border
+            let edge = |left, bottom, right, top| BoundingBox { left, bottom, right, top };
+            rulings.horizontal.push(edge(bounds.left, bounds.top, bounds.right, bounds.top));
+            rulings.horizontal.push(edge(bounds.left, bounds.bottom, bounds.right, bounds.bottom));
+            rulings.vertical.push(edge(bounds.left, bounds.bottom, bounds.left, bounds.top));
+            rulings.vertical.push(edge(bounds.right, bounds.bottom, bounds.right, bounds.top));
+        }
+    }
+
+    rulings
+}
+
+/// Build tables from connected groups of horizontal and vertical ruling lines
+fn ruled_tables(layout: &PageLayout, rulings: &Rulings) -> Vec<DetectedTable> {
+    // Group touching rulings into regions
+    let mut groups: Vec<(BoundingBox, Vec<f32>, Vec<f32>)> = Vec::new();
+    let all = rulings.horizontal.iter().map(|r| (r, true))
+        .chain(rulings.vertical.iter().map(|r| (r, false)));
+
+    for (ruling, horizontal) in all {
+        let position = if horizontal { (ruling.top + ruling.bottom) / 2.0 } else { (ruling.left + ruling.right) / 2.0 };
+        let mut merged = (*ruling, Vec::new(), Vec::new());
+        if horizontal { merged.1.push(position) } else { merged.2.push(position) }
+
+        // Absorb every group this ruling touches
+        let mut index = 0;
+        while index < groups.len() {
+            if groups[index].0.touches(&merged.0) {
+                let (bbox, ys, xs) = groups.remove(index);
+                merged.0.grow(&bbox);
+                merged.1.extend(ys);
+                merged.2.extend(xs);
+                index = 0;
+            } else {
+                index += 1;
+            }
+        }
+        groups.push(merged);
+    }
+
+    let mut tables = Vec::new();
+    for (bbox, ys, xs) in groups {
+        let row_edges = merge_positions(ys);
+        let column_edges = merge_positions(xs);
+        if row_edges.len() < 2 || column_edges.len() < 2 {
+            continue;
+        }
+
+        let lines: Vec<&TextLine> = layout.lines.iter()
+            .filter(|line| bbox.contains(line_center_x(line), line_center_y(line)))
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+
+        // Full grid: rows and columns come from the rulings
+        let rows: Vec<f32> = row_edges[1..row_edges.len() - 1].to_vec();
+        let columns: Vec<f32> = column_edges[1..column_edges.len() - 1].to_vec();
+        let table = fill_grid(&lines, bbox, &columns, Some(&rows));
+
+        if let Some(table) = table {
+            tables.push(table);
+        }
+    }
+    tables
+}
+
+/// Find tables among lines that are not ruled: runs of multi-fragment lines sharing column gutters
+fn text_tables(layout: &PageLayout, lines: &[&TextLine]) -> Vec<DetectedTable> {
+    let mut blocks: Vec<Vec<&TextLine>> = Vec::new();
+    let mut current: Vec<&TextLine> = Vec::new();
+
+    for line in lines {
+        let continues = current.last().map(|previous| {
+            previous.bottom - line.top < 2.5 * (previous.top - previous.bottom).max(1.0)
+        }).unwrap_or(true);
+
+        if line.fragments.len() >= 2 && continues {
+            current.push(line);
+        } else {
+            if current.len() >= MIN_TEXT_TABLE_ROWS {
+                blocks.push(std::mem::take(&mut current));
+            }
+            current.clear();
+            if line.fragments.len() >= 2 {
+                current.push(line);
+            }
+        }
+    }
+    if current.len() >= MIN_TEXT_TABLE_ROWS {
+        blocks.push(current);
+    }
+
+    let mut tables = Vec::new();
+    for block in blocks {
+        // Prose in several columns also yields multi-fragment lines, but with long fragments
+        let mut word_counts: Vec<usize> = block.iter()
+            .flat_map(|line| &line.fragments)
+            .map(|f| f.text.split_whitespace().count())
+            .collect();
+        word_counts.sort_unstable();
+        if word_counts[word_counts.len() / 2] > MAX_CELL_WORDS {
+            continue;
+        }
+
+        let columns = layout::column_boundaries(&PageLayout {
+            width: layout.width,
+            height: layout.height,
+            lines: block.iter().map(|l| (*l).clone()).collect(),
+        });
+        if columns.is_empty() {
+            continue;
+        }
+
+        let mut bbox = BoundingBox { left: f32::MAX, bottom: f32::MAX, right: f32::MIN, top: f32::MIN };
+        for fragment in block.iter().flat_map(|line| &line.fragments) {
+            bbox.grow(&BoundingBox { left: fragment.left, bottom: fragment.bottom, right: fragment.right, top: fragment.top });
+        }
+
+        if let Some(table) = fill_grid(&block, bbox, &columns, None) {
+            tables.push(table);
+        }
+    }
+    tables
+}
+
+/// Place the fragments of `lines` into cells. Columns are split at the x positions in `columns`;
+/// rows at the y positions in `rows`, or one row per line when no row edges are given.
+fn fill_grid(lines: &[&TextLine], bbox: BoundingBox, columns: &[f32], rows: Option<&[f32]>) -> Option<DetectedTable> {
+    let column_count = columns.len() + 1;
+    let row_count = rows.map(|r| r.len() + 1).unwrap_or(lines.len());
+    let mut cells = vec![vec![String::new(); column_count]; row_count];
+
+    for (line_index, line) in lines.iter().enumerate() {
+        let row = match rows {
+            Some(rows) => rows.iter().filter(|y| **y > line_center_y(line)).count(),
+            None => line_index,
+        };
+        for fragment in &line.fragments {
+            let center = (fragment.left + fragment.right) / 2.0;
+            let column = columns.iter().filter(|x| **x < center).count();
+            let cell = &mut cells[row][column];
+            if !cell.is_empty() {
+                cell.push(' ');
+            }
+            cell.push_str(&fragment.text);
+        }
+    }
+
+    cells.retain(|row| row.iter().any(|cell| !cell.is_empty()));
+
+    // A table needs at least two rows with content in more than one column
+    let multi_column_rows = cells.iter().filter(|row| row.iter().filter(|c| !c.is_empty()).count() >= 2).count();
+    if cells.len() < 2 || multi_column_rows < 2 {
+        return None;
+    }
+
+    Some(DetectedTable { bbox, rows: cells })
+}
+
+/// Sort positions and merge the ones closer than the ruling tolerance
+fn merge_positions(mut positions: Vec<f32>) -> Vec<f32> {
+    positions.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mut merged: Vec<f32> = Vec::new();
+    for position in positions {
+        match merged.last() {
+            Some(last) if position - last < RULING_TOLERANCE => {},
+            _ => merged.push(position),
+        }
+    }
+    merged
+}
+
+fn line_center_x(line: &TextLine) -> f32 {
+    let left = line.fragments.iter().map(|f| f.left).fold(f32::MAX, f32::min);
+    let right = line.fragments.iter().map(|f| f.right).fold(f32::MIN, f32::max);
+    (left + right) / 2.0
+}
+
+fn line_center_y(line: &TextLine) -> f32 {
+    (line.top + line.bottom) / 2.0
+}
+
+/// Write one CSV file per table into a directory, plus an `index.csv` with page and bounding box.
+/// Files are named after the document stems in `stems`, keyed by record path.
+fn write_csv(records: &[TableRecord], stems: &HashMap<String, String>, output_dir: &Path) -> Result<(), TableError> {
+    fs::create_dir_all(output_dir)?;
+
+    let mut index = csv::Writer::from_path(output_dir.join("index.csv"))?;
+    index.write_record(["path", "page", "table", "left", "bottom", "right", "top", "rows", "columns", "file"])?;
+
+    for record in records {
+        let stem = &stems[&record.path];
+        let file_name = format!("{}_page{}_table{}.csv", stem, record.page, record.table);
+        let table = DetectedTable { bbox: record.bbox, rows: record.rows.clone() };
+
+        fs::write(output_dir.join(&file_name), table.to_delimited(b','))?;
+
+        index.write_record([
+            record.path.clone(),
+            record.page.to_string(),
+            record.table.to_string(),
+            format!("{:.1}", record.bbox.left),
+            format!("{:.1}", record.bbox.bottom),
+            format!("{:.1}", record.bbox.right),
+            format!("{:.1}", record.bbox.top),
+            record.rows.len().to_string(),
+            table.columns().to_string(),
+            file_name,
+        ])?;
+    }
+    index.flush()?;
+    Ok(())
+}
+
+/// Write all tables as a JSON array to a file or stdout
+fn write_json(records: &[TableRecord], output_file: Option<&str>) -> Result<(), TableError> {
+    let json = serde_json::to_string_pretty(records)?;
+    match output_file {
+        Some(path) => fs::write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::TextFragment;
+
+    /// A line with one 10pt fragment per (text, left) pair, 6pt per character
+    fn line(cells: &[(&str, f32)], bottom: f32) -> TextLine {
+        let fragments = cells.iter().map(|(text, left)| TextFragment {
+            text: text.to_string(),
+            left: *left,
+            right: left + text.len() as f32 * 6.0,
+            bottom,
+            top: bottom + 10.0,
+            font_size: 10.0,
+            bold_spans: Vec::new(),
+        }).collect();
+        TextLine { fragments, bottom, top: bottom + 10.0 }
+    }
+
+    #[test]
+    fn merges_close_positions() {
+        assert_eq!(merge_positions(vec![100.0, 10.0, 11.5, 50.0, 101.0]), vec![10.0, 50.0, 100.0]);
+    }
+
+    #[test]
+    fn finds_aligned_text_tables() {
+        let lines = vec![
+            line(&[("Item", 50.0), ("Qty", 200.0), ("Price", 300.0)], 700.0),
+            line(&[("Apples", 50.0), ("3", 200.0), ("1.20", 300.0)], 685.0),
+            line(&[("Pears", 50.0), ("12", 200.0), ("0.80", 300.0)], 670.0),
+        ];
+        let layout = PageLayout { width: 600.0, height: 800.0, lines: lines.clone() };
+        let refs: Vec<&TextLine> = lines.iter().collect();
+
+        let tables = text_tables(&layout, &refs);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].rows, vec![
+            vec!["Item", "Qty", "Price"],
+            vec!["Apples", "3", "1.20"],
+            vec!["Pears", "12", "0.80"],
+        ]);
+        assert_eq!(tables[0].to_delimited(b','), "Item,Qty,Price\nApples,3,1.20\nPears,12,0.80\n");
+    }
+
+    #[test]
+    fn skips_prose_columns() {
+        let prose = "several words of running prose text";
+        let lines: Vec<TextLine> = (0..4)
+            .map(|i| line(&[(prose, 50.0), (prose, 320.0)], 700.0 - i as f32 * 14.0))
+            .collect();
+        let layout = PageLayout { width: 600.0, height: 800.0, lines: lines.clone() };
+        let refs: Vec<&TextLine> = lines.iter().collect();
+        assert!(text_tables(&layout, &refs).is_empty());
+    }
+
+    #[test]
+    fn fills_ruled_grid_cells() {
+        let lines = [
+            line(&[("a", 20.0), ("b", 120.0)], 80.0),
+            line(&[("c", 20.0), ("d", 120.0)], 40.0),
+            line(&[("e", 20.0)], 25.0),
+        ];
+        let refs: Vec<&TextLine> = lines.iter().collect();
+        let bbox = BoundingBox { left: 0.0, bottom: 0.0, right: 200.0, top: 100.0 };
+        let table = fill_grid(&refs, bbox, &[100.0], Some(&[60.0])).unwrap();
+        assert_eq!(table.rows, vec![vec!["a", "b"], vec!["c e", "d"]]);
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use image::RgbaImage;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use pdfium_render::prelude::*;
+use walkdir::WalkDir;
+
+/// Collect all PDF file paths from the provided input paths
+pub fn collect_pdf_paths(input_paths: &[String]) -> Vec<PathBuf> {
+    let mut pdf_paths: Vec<PathBuf> = Vec::new();
+
+    for path in input_paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            for entry in WalkDir::new(&path).into_iter().filter_map(|e| e.ok()) {
+                if entry.path().extension().and_then(|s| s.to_str()) == Some("pdf") {
+                    pdf_paths.push(entry.path().to_path_buf());
+                }
+            }
+        } else if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("pdf") {
+            pdf_paths.push(path);
+        } else {
+            eprintln!("Warning: Skipping invalid path: {}", path.display());
+        }
+    }
+
+    // Remove duplicates
+    pdf_paths.sort();
+    pdf_paths.dedup();
+
+    pdf_paths
+}
+
+/// Write text output to a file or stdout
+pub fn write_output(content: &str, output_file: Option<&str>) -> io::Result<()> {
+    match output_file {
+        Some(path) => fs::write(path, content),
+        None => {
+            println!("{}", content);
+            Ok(())
+        },
+    }
+}
+
+/// A CSV writer for a file or stdout
+pub fn csv_writer(output_file: Option<&str>) -> io::Result<csv::Writer<Box<dyn io::Write>>> {
+    let writer: Box<dyn io::Write> = match output_file {
+        Some(path) => Box::new(fs::File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    Ok(csv::Writer::from_writer(writer))
+}
+
+/// File stems to name outputs after, one per input. Inputs from different directories
+/// that share a name get numbered stems (`report`, `report_2`, ...) so their outputs
+/// don't overwrite each other in one output directory.
+pub fn unique_stems(paths: &[PathBuf]) -> Vec<String> {
+    let mut used = HashSet::new();
+    paths.iter().map(|path| {
+        let stem = path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "document".to_string());
+        // Compare case-insensitively: output directories may be on a case-insensitive file system
+        let mut candidate = stem.clone();
+        let mut counter = 2;
+        while !used.insert(candidate.to_lowercase()) {
+            candidate = format!("{}_{}", stem, counter);
+            counter += 1;
+        }
+        candidate
+    }).collect()
+}
+
+/// Decode a PDF text string (UTF-16BE with BOM, or PDFDocEncoding)
+pub fn decode_pdf_string(bytes: &[u8]) -> String {
+    if bytes.len() >= 2 && bytes[0] == 0xFE && bytes[1] == 0xFF {
+        let units: Vec<u16> = bytes[2..]
+            .chunks(2)
+            .filter(|c| c.len() == 2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        String::from_utf8_lossy(&bytes[3..]).into_owned()
+    } else {
+        // PDFDocEncoding is close enough to Latin-1 for metadata purposes
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+/// Encode a PDF text string: Latin-1 text as is, anything else as UTF-16BE with BOM
+pub fn encode_pdf_string(text: &str) -> Object {
+    if text.chars().all(|c| (c as u32) < 0x80 || (0xA0..0x100).contains(&(c as u32))) {
+        Object::string_literal(text.chars().map(|c| c as u8).collect::<Vec<u8>>())
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+        Object::string_literal(bytes)
+    }
+}
+
+/// Convert a PDF date (`D:YYYYMMDDHHmmSSOHH'mm'`) into `YYYY-MM-DD HH:MM:SS[+HH:MM]`
+pub fn format_pdf_date(raw: &str) -> String {
+    let s = raw.trim().trim_start_matches("D:");
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 4 {
+        return raw.to_string();
+    }
+
+    let part = |start: usize, len: usize, default: &str| -> String {
+        digits.get(start..start + len).unwrap_or(default).to_string()
+    };
+
+    let mut result = format!(
+        "{}-{}-{} {}:{}:{}",
+        part(0, 4, "0000"), part(4, 2, "01"), part(6, 2, "01"),
+        part(8, 2, "00"), part(10, 2, "00"), part(12, 2, "00")
+    );
+
+    // Append the timezone offset if present
+    let tz = &s[digits.len()..];
+    if tz.starts_with('Z') {
+        result.push('Z');
+    } else if tz.starts_with('+') || tz.starts_with('-') {
+        let tz_digits: String = tz[1..].chars().filter(|c| c.is_ascii_digit()).collect();
+        if tz_digits.len() >= 2 {
+            let minutes = tz_digits.get(2..4).unwrap_or("00");
+            result.push_str(&format!("{}{}:{}", &tz[..1], &tz_digits[..2], minutes));
+        }
+    }
+
+    result
+}
+
+/// Look up a string value in a dictionary, following references
+pub fn dict_string(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<String> {
+    let object = dict.get(key).ok()?;
+    let (_, object) = doc.dereference(object).ok()?;
+    match object {
+        Object::String(bytes, _) => Some(decode_pdf_string(bytes)),
+        Object::Name(name) => Some(String::from_utf8_lossy(name).into_owned()),
+        _ => None,
+    }
+}
+
+/// Get the document Info dictionary, if any
+pub fn info_dictionary(doc: &Document) -> Option<&Dictionary> {
+    let info = doc.trailer.get(b"Info").ok()?;
+    let (_, info) = doc.dereference(info).ok()?;
+    info.as_dict().ok()
+}
+
+/// Look up a page attribute, walking up the page tree for inherited values
+pub fn inherited_page_attribute<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut current = doc.get_dictionary(page_id).ok();
+    // Guard against cyclic page trees
+    let mut depth = 0;
+    while let Some(dict) = current {
+        if let Ok(value) = dict.get(key) {
+            return doc.dereference(value).ok().map(|(_, o)| o);
+        }
+        depth += 1;
+        if depth > 32 {
+            break;
+        }
+        current = dict.get(b"Parent")
+            .and_then(|p| p.as_reference())
+            .and_then(|id| doc.get_dictionary(id))
+            .ok();
+    }
+    None
+}
+
+/// Get the media box of a page as `[left, bottom, right, top]` in points
+pub fn page_media_box(doc: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let array = inherited_page_attribute(doc, page_id, b"MediaBox")?.as_array().ok()?;
+    if array.len() != 4 {
+        return None;
+    }
+    let mut rect = [0.0; 4];
+    for (i, value) in array.iter().enumerate() {
+        let (_, value) = doc.dereference(value).ok()?;
+        rect[i] = value.as_float().ok()?;
+    }
+    Some(rect)
+}
+
+/// Get the annotation dictionaries of a page, skipping entries that do not resolve
+pub fn page_annotations(doc: &Document, page_id: ObjectId) -> Vec<&Dictionary> {
+    let annots = doc.get_dictionary(page_id).ok()
+        .and_then(|page| page.get(b"Annots").ok())
+        .and_then(|annots| doc.dereference(annots).ok())
+        .and_then(|(_, annots)| annots.as_array().ok());
+
+    annots.map(|annots| {
+        annots.iter()
+            .filter_map(|annot| doc.dereference(annot).ok())
+            .filter_map(|(_, annot)| annot.as_dict().ok())
+            .collect()
+    }).unwrap_or_default()
+}
+
+/// Turn a title into a short lowercase identifier for file names and anchors
+pub fn slug(title: &str) -> String {
+    let slug: String = title.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let slug: Vec<&str> = slug.split('-').filter(|part| !part.is_empty()).collect();
+    let slug: String = slug.join("-").chars().take(40).collect();
+    if slug.is_empty() { "section".to_string() } else { slug }
+}
+
+/// Find URLs (`http://`, `https://` and bare `www.` addresses) in text, as
+/// (start, end, href) byte ranges; trailing punctuation is not part of the URL
+pub fn find_urls(text: &str) -> Vec<(usize, usize, String)> {
+    let mut found = Vec::new();
+    for scheme in ["https://", "http://", "www."] {
+        let mut offset = 0;
+        while let Some(position) = text[offset..].find(scheme) {
+            let start = offset + position;
+            let length = text[start..].find(char::is_whitespace).unwrap_or(text.len() - start);
+            let url = text[start..start + length].trim_end_matches(['.', ',', ';', ':', ')', ']', '}', '"', '\'']);
+            offset = start + length.max(1);
+            // `www.` inside an `https://www.` URL is already covered
+            if url.len() <= scheme.len() || (scheme == "www." && text[..start].ends_with("//")) {
+                continue;
+            }
+            let href = if scheme == "www." { format!("https://{}", url) } else { url.to_string() };
+            found.push((start, start + url.len(), href));
+        }
+    }
+    found.sort_by_key(|(start, _, _)| *start);
+    found
+}
+
+/// Host name of a URL, lowercased and without `www.`; the mail domain for `mailto:` links
+pub fn url_domain(url: &str) -> Option<String> {
+    let url = url.trim();
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        None => match url.split_once(':') {
+            Some((scheme, address)) if scheme.eq_ignore_ascii_case("mailto") => {
+                return address.split('?').next()?.rsplit_once('@').map(|(_, host)| host.to_lowercase());
+            },
+            _ => url,
+        },
+    };
+    let host = rest.split(['/', '?', '#']).next()?;
+    // Drop credentials and port
+    let host = host.rsplit('@').next()?.split(':').next()?.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
+    if host.is_empty() { None } else { Some(host) }
+}
+
+/// Hex-encode a byte slice
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bind to the system Pdfium library, the same one the GUI viewer renders with
+pub fn bind_pdfium() -> Result<Pdfium, String> {
+    Pdfium::bind_to_system_library()
+        .map(Pdfium::new)
+        .map_err(|e| format!("Failed to initialize Pdfium: {}", e))
+}
+
+/// Rasterize a page at the given resolution into an RGBA image
+pub fn render_page_rgba(page: &PdfPage, dpi: f32) -> Result<RgbaImage, String> {
+    let config = PdfRenderConfig::new()
+        .scale_page_by_factor(dpi / 72.0)
+        .render_form_data(true);
+
+    let bitmap = page.render_with_config(&config)
+        .map_err(|e| format!("Error rendering page: {}", e))?;
+
+    RgbaImage::from_raw(bitmap.width() as u32, bitmap.height() as u32, bitmap.as_rgba_bytes())
+        .ok_or_else(|| "Rendered bitmap has an unexpected size".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_repeated_stems() {
+        let paths: Vec<PathBuf> = ["a/report.pdf", "b/report.pdf", "c/Report.pdf", "report_2.pdf", "x/other.pdf"]
+            .iter().map(PathBuf::from).collect();
+        assert_eq!(unique_stems(&paths), vec!["report", "report_2", "Report_3", "report_2_2", "other"]);
+    }
+
+    #[test]
+    fn decodes_utf16_and_latin1_strings() {
+        assert_eq!(decode_pdf_string(&[0xFE, 0xFF, 0x00, 0x41, 0x04, 0x14]), "AД");
+        assert_eq!(decode_pdf_string(b"Caf\xe9"), "Café");
+        assert_eq!(decode_pdf_string(b"\xEF\xBB\xBFna\xc3\xafve"), "naïve");
+    }
+
+    #[test]
+    fn formats_pdf_dates() {
+        assert_eq!(format_pdf_date("D:20240115093000+01'00'"), "2024-01-15 09:30:00+01:00");
+        assert_eq!(format_pdf_date("D:20240115093000Z"), "2024-01-15 09:30:00Z");
+        assert_eq!(format_pdf_date("D:2024"), "2024-01-01 00:00:00");
+        assert_eq!(format_pdf_date("yesterday"), "yesterday");
+    }
+
+    #[test]
+    fn finds_urls_without_trailing_punctuation() {
+        let text = "See https://www.example.com/a?b=1. Or www.test.org, (http://x.io/y).";
+        let hrefs: Vec<(usize, String)> = find_urls(text).into_iter().map(|(start, _, href)| (start, href)).collect();
+        assert_eq!(hrefs, vec![
+            (4, "https://www.example.com/a?b=1".to_string()),
+            (38, "https://www.test.org".to_string()),
+            (53, "http://x.io/y".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn extracts_url_domains() {
+        assert_eq!(url_domain("https://user:pw@WWW.Example.com:8080/path?q#f").as_deref(), Some("example.com"));
+        assert_eq!(url_domain("mailto:info@Example.org?subject=hi").as_deref(), Some("example.org"));
+        assert_eq!(url_domain("www.test.org/page").as_deref(), Some("test.org"));
+        assert_eq!(url_domain("https://").as_deref(), None);
+    }
+}